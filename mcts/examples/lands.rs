@@ -59,6 +59,23 @@ impl Move {
     }
 }
 
+impl From<usize> for Player {
+    fn from(value: usize) -> Self {
+        // The search keeps a tree for four players, map them onto our two
+        if value % 2 == 0 {
+            Player::One
+        } else {
+            Player::Two
+        }
+    }
+}
+
+impl From<Player> for usize {
+    fn from(value: Player) -> Self {
+        value as usize
+    }
+}
+
 impl Player {
     fn next(self) -> Self {
         match self {
@@ -82,6 +99,7 @@ impl HandKnowledge {
         }
     }
 
+    #[allow(dead_code)]
     pub fn count_known(&self) -> u8 {
         self.enemy_hand
             .values()
@@ -141,7 +159,7 @@ impl CardKnowledge {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 struct LandsGame {
     deck: Vec<Card>,
     in_play: [EnumMap<Card, u8>; 2],
@@ -172,12 +190,13 @@ impl Display for LandsGame {
 impl LandsGame {
     fn new(seed: u64) -> Self {
         let mut rng = StdRng::seed_from_u64(seed);
-        let mut deck = Vec::new();
-        deck.push(vec![Card::White; 15]);
-        deck.push(vec![Card::Black; 15]);
-        deck.push(vec![Card::Red; 15]);
-        deck.push(vec![Card::Blue; 15]);
-        deck.push(vec![Card::Green; 15]);
+        let deck = [
+            vec![Card::White; 15],
+            vec![Card::Black; 15],
+            vec![Card::Red; 15],
+            vec![Card::Blue; 15],
+            vec![Card::Green; 15],
+        ];
         let mut deck = deck.iter().flatten().cloned().collect_vec();
         deck.shuffle(&mut rng);
         assert!(deck.len() == 75);
@@ -247,7 +266,7 @@ impl LandsGame {
             || self.in_play[player as usize].values().all(|v| *v > 0)
     }
 
-    fn randomize_hand(&mut self, player: Player) {
+    fn randomize_hand<R: rand::Rng>(&mut self, player: Player, rng: &mut R) {
         let hand = &mut self.hands[player as usize];
         let count = hand.values().sum::<u8>();
         // Put back cards into deck
@@ -256,13 +275,14 @@ impl LandsGame {
         }
         // Clear hand
         hand.clear();
-        self.deck.shuffle(&mut rand::thread_rng());
+        self.deck.shuffle(rng);
         // Draw new hand
         (0..count).for_each(|_| {
             hand[self.deck.pop().expect("Not empty")] += 1;
         });
     }
 
+    #[allow(dead_code)]
     fn determinize_hand_with_knowledge(&mut self, player: Player) {
         let hand = &mut self.hands[player as usize];
         let count = hand.values().sum::<u8>();
@@ -303,6 +323,7 @@ impl GameState for LandsGame {
     type Move = Move;
     type Player = Player;
     type MoveList = Vec<Self::Move>;
+    type Knowledge = ();
 
     fn current_player(&self) -> Self::Player {
         self.to_move
@@ -458,11 +479,33 @@ impl GameState for LandsGame {
         }
     }
 
-    fn randomize_determination(&mut self, observer: Self::Player) {
-        self.randomize_hand(observer.next());
+    fn randomize_determination<R: rand::Rng>(
+        &mut self,
+        observer: Self::Player,
+        _knowledge: &Self::Knowledge,
+        rng: &mut R,
+    ) {
+        self.randomize_hand(observer.next(), rng);
+    }
+
+    fn update_knowledge(&self, _mv: &Self::Move, _knowledge: &mut Self::Knowledge) {}
+
+    fn new_knowledge(&self, _observer: Self::Player) -> Self::Knowledge {}
+
+    fn knowledge_from_state(&self, _observer: Self::Player) -> Self::Knowledge {}
+
+    fn outcome(&self, player: &Self::Player) -> Option<bool> {
+        if self.won(*player) {
+            Some(true)
+        } else if self.won(player.next()) {
+            Some(false)
+        } else {
+            None
+        }
     }
 }
 
+#[derive(Clone)]
 struct GameEval;
 
 impl Evaluator<AI> for GameEval {
@@ -471,7 +514,7 @@ impl Evaluator<AI> for GameEval {
     fn eval_new(
         &self,
         state: &<AI as MCTS>::State,
-        _handle: Option<search::Handle<AI>>,
+        _handle: Option<search::SearchHandle<AI>>,
     ) -> Self::StateEval {
         let won = state.won(state.to_move) as i64 * 100;
         let devotion = *state.in_play[state.to_move as usize]
@@ -499,20 +542,11 @@ impl Evaluator<AI> for GameEval {
         devotion + domain + card_advantage + won
     }
 
-    fn eval_new(
-        &self,
-        state: &LandsGame,
-        moves: &Vec<Move>,
-        handle: Option<search::Handle<AI>>,
-    ) -> (Vec<MoveEval<AI>>, Self::StateEval) {
-        (vec![(); moves.len()], self.eval_new(&state, handle))
-    }
-
     fn eval_existing(
         &self,
         _state: &LandsGame,
         existing: &Self::StateEval,
-        _handle: search::Handle<AI>,
+        _handle: search::SearchHandle<AI>,
     ) -> Self::StateEval {
         *existing
     }
@@ -525,7 +559,7 @@ impl Evaluator<AI> for GameEval {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 struct AI;
 
 impl MCTS for AI {
@@ -540,13 +574,13 @@ impl MCTS for AI {
 
 fn main() {
     let mut input = String::new();
-    let mut mcts = Manager::new(LandsGame::new(23), AI, UCTPolicy(0.7), GameEval);
+    let mut mcts = Manager::new(LandsGame::new(23), AI, UCTPolicy::new(0.7), GameEval);
     println!("{}", mcts.tree().root_state());
 
     mcts.playout_n_parallel(5_000, 8);
     if let Some(best_move) = mcts.best_move() {
         println!("Make move {:?}", best_move);
-        mcts.advance(best_move);
+        mcts.advance(&best_move);
         mcts.print_root_moves();
         mcts.print_stats();
     }
@@ -554,48 +588,37 @@ fn main() {
     mcts.playout_n_parallel(5_000, 8);
     if let Some(best_move) = mcts.best_move() {
         println!("Make move {:?}", best_move);
-        mcts.advance(best_move);
+        mcts.advance(&best_move);
         mcts.print_root_moves();
         mcts.print_stats();
     }
 
-    return;
     loop {
         if io::stdin().read_line(&mut input).is_ok() {
             if input == "m\n" {
-                mcts.tree().legal_moves();
+                mcts.print_root_legal_moves();
                 println!("{}", mcts.tree().root_state());
             } else if input == "lm\n" {
                 println!("{:?}", mcts.tree().root_state().legal_moves());
             } else if input == "pv\n" {
                 let pv = mcts.pv(500);
                 println!("{:?}", pv);
-            } else if input == "bm\n" {
+            } else if input == "bm\n" || input == "adv\n" {
                 if let Some(best_move) = mcts.best_move() {
                     println!("Make move {:?}", best_move);
-                    mcts = mcts.make_move(best_move);
+                    mcts.advance(&best_move);
                 }
-            } else if input == "adv\n" {
+            } else if input == "pmm\n" {
+                // Returns right away if the move is forced
+                mcts.playout_n_parallel(2_500_000, 8);
                 if let Some(best_move) = mcts.best_move() {
                     println!("Make move {:?}", best_move);
-                    mcts.advance(best_move);
-                }
-            } else if input == "pmm\n" {
-                let legal_moves = mcts.tree().root_state().legal_moves();
-                if legal_moves.len() == 1 {
-                    println!("Make move {:?}", legal_moves[0]);
-                    mcts.advance(legal_moves[0]);
-                } else {
-                    mcts.playout_n_parallel(2_500_000, 8);
-                    if let Some(best_move) = mcts.best_move() {
-                        println!("Make move {:?}", best_move);
-                        mcts.advance(best_move);
-                    }
+                    mcts.advance(&best_move);
                 }
                 println!("{}", mcts.tree().root_state());
             } else if let Ok(number) = input.strip_suffix('\n').unwrap().parse::<usize>() {
                 let mv = mcts.tree().root_state().legal_moves()[number];
-                mcts.advance(mv);
+                mcts.advance(&mv);
             } else if input == "s\n" {
                 println!("{}", mcts.tree().root_state());
             } else if input == "st\n" {
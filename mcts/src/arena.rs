@@ -0,0 +1,314 @@
+use std::sync::{
+    atomic::{AtomicU32, AtomicU64, Ordering},
+    Mutex, OnceLock,
+};
+
+use crate::{node::Node, MCTS};
+
+/// Size of the first chunk, every following chunk is twice as large as the previous one.
+const FIRST_CHUNK: usize = 1 << 10;
+/// Enough chunks to address every `u32` index.
+const NUM_CHUNKS: usize = 23;
+
+/// Index of a node in a [`NodeArena`].
+/// The generation is bumped every time a slot gets freed, so ids of recycled nodes become invalid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+impl NodeId {
+    const NONE: u64 = u64::MAX;
+
+    fn to_bits(self) -> u64 {
+        u64::from(self.index) | u64::from(self.generation) << 32
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn from_bits(bits: u64) -> Option<Self> {
+        (bits != Self::NONE).then_some(Self {
+            index: bits as u32,
+            generation: (bits >> 32) as u32,
+        })
+    }
+}
+
+/// An optional [`NodeId`] that can be shared between threads.
+pub struct AtomicNodeId(AtomicU64);
+
+impl Default for AtomicNodeId {
+    fn default() -> Self {
+        Self(AtomicU64::new(NodeId::NONE))
+    }
+}
+
+impl AtomicNodeId {
+    #[must_use]
+    pub fn load(&self) -> Option<NodeId> {
+        NodeId::from_bits(self.0.load(Ordering::Acquire))
+    }
+
+    /// Removes the id and returns it, the node it pointed to is not freed.
+    pub fn take(&mut self) -> Option<NodeId> {
+        NodeId::from_bits(std::mem::replace(self.0.get_mut(), NodeId::NONE))
+    }
+
+    /// Stores `id` if no node is set yet.
+    /// # Errors
+    /// If a node is already set, the id of that node is returned.
+    pub fn set_if_none(&self, id: NodeId) -> Result<(), NodeId> {
+        self.0
+            .compare_exchange(
+                NodeId::NONE,
+                id.to_bits(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .map(|_| ())
+            .map_err(|bits| NodeId::from_bits(bits).expect("Only fails if a node is set"))
+    }
+}
+
+struct Slot<M: MCTS> {
+    node: OnceLock<Node<M>>,
+    generation: AtomicU32,
+}
+
+impl<M: MCTS> Default for Slot<M> {
+    fn default() -> Self {
+        Self {
+            node: OnceLock::new(),
+            generation: 0.into(),
+        }
+    }
+}
+
+/// Slab allocator for the nodes of a search tree.
+///
+/// Nodes are stored in chunks that never move, so references handed out while searching stay valid.
+/// Allocating only needs a shared reference, freeing needs exclusive access to the arena which
+/// guarantees no references into freed slots exist. Freed slots are reused by later allocations.
+pub struct NodeArena<M: MCTS> {
+    chunks: [OnceLock<Box<[Slot<M>]>>; NUM_CHUNKS],
+    /// Number of slots that have been handed out at least once
+    len: AtomicU32,
+    free: Mutex<Vec<u32>>,
+}
+
+impl<M: MCTS> Default for NodeArena<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the chunk and the offset inside of it of the slot with the given index.
+fn locate(index: u32) -> (usize, usize) {
+    let index = index as usize;
+    let chunk = (index / FIRST_CHUNK + 1).ilog2() as usize;
+    (chunk, index - FIRST_CHUNK * ((1 << chunk) - 1))
+}
+
+impl<M: MCTS> NodeArena<M> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            chunks: core::array::from_fn(|_| OnceLock::new()),
+            len: 0.into(),
+            free: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn slot(&self, index: u32) -> &Slot<M> {
+        let (chunk, offset) = locate(index);
+        let chunk = self.chunks[chunk]
+            .get_or_init(|| (0..FIRST_CHUNK << chunk).map(|_| Slot::default()).collect());
+        &chunk[offset]
+    }
+
+    fn slot_mut(&mut self, index: u32) -> Option<&mut Slot<M>> {
+        let (chunk, offset) = locate(index);
+        self.chunks[chunk].get_mut().map(|chunk| &mut chunk[offset])
+    }
+
+    /// Stores `node` in a free slot, reusing freed slots before growing the arena.
+    pub fn alloc(&self, node: Node<M>) -> NodeId {
+        let index = self
+            .free
+            .lock()
+            .unwrap()
+            .pop()
+            .unwrap_or_else(|| self.len.fetch_add(1, Ordering::Relaxed));
+        let slot = self.slot(index);
+        assert!(slot.node.set(node).is_ok(), "Allocated slot is occupied");
+        NodeId {
+            index,
+            generation: slot.generation.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the node with the given id or `None` if it has been freed.
+    #[must_use]
+    pub fn get(&self, id: NodeId) -> Option<&Node<M>> {
+        if id.index >= self.len.load(Ordering::Relaxed) {
+            return None;
+        }
+        let slot = self.slot(id.index);
+        if slot.generation.load(Ordering::Relaxed) == id.generation {
+            slot.node.get()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the node with the given id or `None` if it has been freed.
+    #[must_use]
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut Node<M>> {
+        let slot = self.slot_mut(id.index)?;
+        if *slot.generation.get_mut() == id.generation {
+            slot.node.get_mut()
+        } else {
+            None
+        }
+    }
+
+    /// Frees the slot of `index` and invalidates all ids pointing to it.
+    fn free_slot(&mut self, index: u32) -> bool {
+        let Some(slot) = self.slot_mut(index) else {
+            return false;
+        };
+        if slot.node.take().is_none() {
+            return false;
+        }
+        *slot.generation.get_mut() = slot.generation.get_mut().wrapping_add(1);
+        true
+    }
+
+    /// Frees every node that can't be reached from `roots` and returns the number of nodes still alive.
+    pub fn retain_reachable(&mut self, roots: &[NodeId]) -> usize {
+        let len = *self.len.get_mut();
+        let mut reachable = vec![false; len as usize];
+        let mut stack = roots.to_vec();
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.get(id) else {
+                continue;
+            };
+            if std::mem::replace(&mut reachable[id.index as usize], true) {
+                continue;
+            }
+            stack.extend(node.moves.iter().filter_map(|info| info.child.load()));
+        }
+        for index in 0..len {
+            if !reachable[index as usize] && self.free_slot(index) {
+                self.free.get_mut().unwrap().push(index);
+            }
+        }
+        reachable.into_iter().filter(|r| *r).count()
+    }
+
+    /// Bytes of the slots allocated so far, freed slots included. Memory allocated by the nodes
+    /// themselves isn't counted, see [`crate::search::Tree::memory_stats`].
+    #[must_use]
+    pub fn allocated_bytes(&self) -> usize {
+        let slots = self
+            .chunks
+            .iter()
+            .filter_map(OnceLock::get)
+            .map(|chunk| chunk.len())
+            .sum::<usize>();
+        std::mem::size_of_val(&self.chunks) + slots * std::mem::size_of::<Slot<M>>()
+    }
+
+    /// Frees all nodes while keeping the allocated memory for later use.
+    pub fn clear(&mut self) {
+        for index in 0..*self.len.get_mut() {
+            self.free_slot(index);
+        }
+        *self.len.get_mut() = 0;
+        self.free.get_mut().unwrap().clear();
+    }
+}
+
+// The tests are kept small so they can also run under `cargo miri test`
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::{
+        node::{Children, MoveInfo},
+        testing::*,
+    };
+
+    fn node() -> Node<TestAI> {
+        Node::new(&RaceEval, &Race::default(), None)
+    }
+
+    #[test]
+    fn racing_appends() {
+        let children = Children::<TestAI>::new();
+        std::thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let children = &children;
+                scope.spawn(move || {
+                    for mv in (0..100u8).cycle().skip(thread * 7).take(100) {
+                        let index = children.push_if_absent(&mv, || MoveInfo::new(mv));
+                        assert_eq!(children[index].mv, mv);
+                        // Readers see every move appended so far, in order
+                        let seen = children.iter().collect::<Vec<_>>();
+                        assert!(seen.len() > index);
+                        assert!(std::ptr::eq(seen[index], &children[index]));
+                    }
+                });
+            }
+        });
+        assert_eq!(children.len(), 100);
+        let mut moves = children.iter().map(|info| info.mv).collect::<Vec<_>>();
+        moves.sort_unstable();
+        assert_eq!(moves, (0..100).collect::<Vec<_>>());
+        assert_eq!(children.get(100).map(|info| info.mv), None);
+    }
+
+    #[test]
+    fn racing_children() {
+        let mut arena = NodeArena::new();
+        let parent = arena.alloc(node());
+        arena.get(parent).unwrap().moves.push(MoveInfo::new(1));
+        let winners = AtomicUsize::new(0);
+        let ids = std::thread::scope(|scope| {
+            let handles = (0..THREADS)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let created = arena.alloc(node());
+                        let moves = &arena.get(parent).unwrap().moves;
+                        match moves[0].child.set_if_none(created) {
+                            Ok(()) => {
+                                winners.fetch_add(1, Ordering::Relaxed);
+                                (created, created)
+                            }
+                            Err(other) => (created, other),
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(winners.into_inner(), 1);
+        let child = arena.get(parent).unwrap().moves[0].child.load().unwrap();
+        // Every thread agrees on the child that got attached
+        assert!(ids.iter().all(|(_, attached)| *attached == child));
+
+        assert_eq!(arena.retain_reachable(&[parent]), 2);
+        for (created, _) in ids {
+            assert_eq!(arena.get(created).is_some(), created == child);
+        }
+        // Freed slots are reused with a new generation
+        let reused = arena.alloc(node());
+        assert!((reused.index as usize) <= THREADS);
+        assert!(arena.get(reused).is_some());
+    }
+}
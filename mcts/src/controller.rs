@@ -0,0 +1,372 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, Weak,
+    },
+    thread::JoinHandle,
+};
+
+use crate::{manager::Manager, node::ComputedStats, search::Tree, Move, ThreadData, MCTS};
+
+/// Remaining playouts of a search without a budget.
+const UNLIMITED: u64 = u64::MAX;
+
+/// State shared between a [`SearchController`], its workers and the manager that spawned them.
+pub(crate) struct Shared<M: MCTS> {
+    paused: AtomicBool,
+    stopped: AtomicBool,
+    /// Set once a playout hit the node limit. The tree can't be pruned while the workers share it.
+    full: AtomicBool,
+    /// Playouts the workers may still start, [`UNLIMITED`] if there is no budget
+    budget: AtomicU64,
+    /// Workers in the middle of a playout
+    running: AtomicUsize,
+    playouts: AtomicU64,
+    /// Whether the tree is in `lock`, cleared while the manager changes the tree
+    attached: AtomicBool,
+    /// The tree the workers search. Workers only hold a reference during a playout, so the
+    /// manager can take the tree away once no playout is running, see [`Shared::detach`].
+    lock: Mutex<Option<Arc<Tree<M>>>>,
+    changed: Condvar,
+}
+
+impl<M: MCTS> Shared<M> {
+    fn can_run(&self) -> bool {
+        self.attached.load(Ordering::SeqCst)
+            && !self.paused.load(Ordering::SeqCst)
+            && !self.full.load(Ordering::SeqCst)
+            && self.budget.load(Ordering::SeqCst) != 0
+    }
+
+    fn is_finished(&self) -> bool {
+        let exhausted = self.full.load(Ordering::SeqCst) || self.budget.load(Ordering::SeqCst) == 0;
+        exhausted && self.running.load(Ordering::SeqCst) == 0
+    }
+
+    /// Reserves a playout of the budget, `false` if the workers have to wait.
+    fn take_playout(&self) -> bool {
+        self.attached.load(Ordering::SeqCst)
+            && !self.paused.load(Ordering::SeqCst)
+            && !self.full.load(Ordering::SeqCst)
+            && self
+                .budget
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |budget| match budget {
+                    0 => None,
+                    UNLIMITED => Some(UNLIMITED),
+                    budget => Some(budget - 1),
+                })
+                .is_ok()
+    }
+
+    /// Returns a reserved playout that couldn't be run.
+    fn give_back(&self) {
+        let _ = self
+            .budget
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |budget| {
+                (budget != UNLIMITED).then_some(budget + 1)
+            });
+    }
+
+    /// Wakes up workers and waiters after the state changed.
+    fn notify(&self) {
+        let _guard = self.lock.lock().unwrap();
+        self.changed.notify_all();
+    }
+
+    /// Takes the tree away from the workers and blocks until no playout is running anymore.
+    /// The workers wait without searching until [`Self::attach`] gives them a tree again.
+    pub(crate) fn detach(&self) {
+        self.attached.store(false, Ordering::SeqCst);
+        let mut tree = self.lock.lock().unwrap();
+        *tree = None;
+        // Playouts that started before hold their own reference until they are done
+        let _tree = self
+            .changed
+            .wait_while(tree, |_| self.running.load(Ordering::SeqCst) > 0)
+            .unwrap();
+    }
+
+    /// Lets the workers continue with `tree`, which may have room for new nodes again.
+    pub(crate) fn attach(&self, tree: &Arc<Tree<M>>) {
+        *self.lock.lock().unwrap() = Some(Arc::clone(tree));
+        self.full.store(false, Ordering::SeqCst);
+        self.attached.store(true, Ordering::SeqCst);
+        self.notify();
+    }
+
+    fn work(&self, mut tld: ThreadData<M>) {
+        while !self.stopped.load(Ordering::SeqCst) {
+            self.running.fetch_add(1, Ordering::SeqCst);
+            if self.take_playout() {
+                let tree = self.lock.lock().unwrap().clone();
+                match tree.map(|tree| tree.playout(&mut tld)) {
+                    Some(true) => {
+                        self.playouts.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Some(false) => {
+                        self.full.store(true, Ordering::SeqCst);
+                        self.give_back();
+                    }
+                    // The manager took the tree after the playout was reserved
+                    None => self.give_back(),
+                }
+                let last = self.running.fetch_sub(1, Ordering::SeqCst) == 1;
+                if self.is_finished() || (last && !self.attached.load(Ordering::SeqCst)) {
+                    self.notify();
+                }
+                continue;
+            }
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            self.notify();
+            // Sleep until there is something to do again
+            let guard = self.lock.lock().unwrap();
+            let _guard = self
+                .changed
+                .wait_while(guard, |_| {
+                    !self.stopped.load(Ordering::SeqCst) && !self.can_run()
+                })
+                .unwrap();
+        }
+    }
+}
+
+/// What a [`SearchController`] has found so far.
+#[derive(Clone, Debug)]
+pub struct SearchSnapshot<M: MCTS> {
+    /// Playouts run by the workers of the controller
+    pub playouts: u64,
+    pub nodes: usize,
+    /// Moves of the player to move with their statistics, most visited first
+    pub moves: Vec<(Move<M>, ComputedStats)>,
+}
+
+impl<M: MCTS> SearchSnapshot<M> {
+    #[must_use]
+    pub fn best_move(&self) -> Option<&Move<M>> {
+        self.moves.first().map(|(mv, _)| mv)
+    }
+}
+
+/// Worker threads searching a tree in the background until they are stopped,
+/// started by [`crate::manager::Manager::spawn_workers`].
+///
+/// Unlike [`crate::manager::Manager::playout_n_parallel`] nothing blocks while searching.
+/// The search can be paused, resumed and given a new budget at any time from any thread.
+/// Workers stop once the tree reaches the node limit, it can only be pruned by the manager.
+///
+/// The manager can advance, clear or load the tree while the workers run. It waits for the
+/// running playouts to finish, the workers continue with the changed tree afterwards.
+pub struct SearchController<M: MCTS> {
+    shared: Arc<Shared<M>>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl<M: MCTS> SearchController<M> {
+    pub(crate) fn spawn(tree: &Arc<Tree<M>>, num_threads: usize, seed: Option<u64>) -> Self
+    where
+        M: 'static,
+        Tree<M>: Send + Sync,
+        ThreadData<M>: Default,
+    {
+        let shared = Arc::new(Shared {
+            paused: false.into(),
+            stopped: false.into(),
+            full: false.into(),
+            budget: UNLIMITED.into(),
+            running: 0.into(),
+            playouts: 0.into(),
+            attached: true.into(),
+            lock: Mutex::new(Some(Arc::clone(tree))),
+            changed: Condvar::new(),
+        });
+        let handles = (0..num_threads)
+            .map(|thread| {
+                let shared = Arc::clone(&shared);
+                std::thread::spawn(move || {
+                    shared.work(Manager::<M>::thread_data(seed, thread));
+                })
+            })
+            .collect();
+        Self { shared, handles }
+    }
+
+    /// State the manager uses to take the tree away from the workers while changing it.
+    pub(crate) fn shared(&self) -> Weak<Shared<M>> {
+        Arc::downgrade(&self.shared)
+    }
+
+    /// Lets the workers finish their current playout and start no new ones until [`Self::resume`].
+    pub fn pause(&self) {
+        self.shared.paused.store(true, Ordering::SeqCst);
+        self.shared.notify();
+    }
+
+    pub fn resume(&self) {
+        self.shared.paused.store(false, Ordering::SeqCst);
+        self.shared.notify();
+    }
+
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.shared.paused.load(Ordering::SeqCst)
+    }
+
+    /// Allows `playouts` more playouts from now on, `None` searches until the controller is stopped.
+    pub fn set_budget(&self, playouts: Option<u64>) {
+        self.shared.budget.store(
+            playouts.map_or(UNLIMITED, |p| p.min(UNLIMITED - 1)),
+            Ordering::SeqCst,
+        );
+        self.shared.notify();
+    }
+
+    /// Returns `true` once the budget is used up or the tree is full and no playout is running.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.shared.is_finished()
+    }
+
+    /// Blocks until [`Self::is_finished`] returns `true`.
+    /// Returns immediately if the workers are paused.
+    pub fn wait(&self) {
+        let guard = self.shared.lock.lock().unwrap();
+        let _guard = self
+            .shared
+            .changed
+            .wait_while(guard, |_| !self.shared.is_finished() && !self.is_paused())
+            .unwrap();
+    }
+
+    #[must_use]
+    pub fn playouts(&self) -> u64 {
+        self.shared.playouts.load(Ordering::SeqCst)
+    }
+
+    /// Statistics of the root moves, taken while the workers keep searching.
+    /// Waits while the manager is changing the tree.
+    /// # Panics
+    /// If a thread panicked while changing the tree.
+    #[must_use]
+    pub fn snapshot_stats(&self) -> SearchSnapshot<M> {
+        let tree = self.shared.lock.lock().unwrap();
+        let tree = self
+            .shared
+            .changed
+            .wait_while(tree, |tree| tree.is_none())
+            .unwrap();
+        let tree = tree.as_ref().expect("Waited for the tree to be attached");
+        SearchSnapshot {
+            playouts: self.playouts(),
+            nodes: tree.num_nodes(),
+            moves: tree.root().move_stats(),
+        }
+    }
+
+    /// Stops the workers and waits for them to finish, the manager can modify the tree afterwards.
+    pub fn stop(self) {}
+
+    fn stop_workers(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        self.shared.notify();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl<M: MCTS> Drop for SearchController<M> {
+    fn drop(&mut self) {
+        self.stop_workers();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{policies::UCTPolicy, testing::*, GameState};
+
+    #[test]
+    fn controlled_search() {
+        let mut manager = Manager::new(
+            Race::default(),
+            TestAI::default(),
+            UCTPolicy::new(0.7),
+            RaceEval,
+        );
+        let controller = manager.spawn_workers(THREADS);
+        controller.set_budget(Some(200));
+        controller.wait();
+        let snapshot = controller.snapshot_stats();
+        assert_eq!(snapshot.playouts, 200);
+        assert!(controller.is_finished());
+        assert_eq!(
+            snapshot.moves.iter().map(|(_, s)| s.visits).sum::<u64>(),
+            200
+        );
+        assert_eq!(
+            snapshot.best_move(),
+            snapshot
+                .moves
+                .iter()
+                .max_by_key(|(_, s)| s.visits)
+                .map(|(mv, _)| mv)
+        );
+
+        // A paused search doesn't use its budget
+        controller.pause();
+        controller.set_budget(Some(100));
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(controller.playouts(), 200);
+        controller.resume();
+        controller.wait();
+        assert_eq!(controller.playouts(), 300);
+
+        controller.set_budget(None);
+        while controller.playouts() < 400 {
+            std::thread::yield_now();
+        }
+        assert!(!controller.is_finished());
+        controller.stop();
+        let visits = manager.stats().iter().map(|s| s.visits).sum::<u64>();
+        assert!(visits >= 400);
+        // The tree belongs to the manager again
+        let mv = manager.best_move().unwrap();
+        manager.advance(&mv);
+    }
+
+    #[test]
+    fn advance_while_workers_search() {
+        let mut manager = Manager::new(
+            Race::default(),
+            TestAI::default(),
+            UCTPolicy::new(0.7),
+            RaceEval,
+        );
+        let controller = manager.spawn_workers(THREADS);
+        let mut state = Race::default();
+        for _ in 0..3 {
+            let playouts = controller.playouts();
+            while controller.playouts() < playouts + 100 {
+                std::thread::yield_now();
+            }
+            let mv = manager.best_move().unwrap();
+            state.make_move(&mv);
+            manager.advance(&mv);
+            // The workers continue with the new root
+            let playouts = controller.playouts();
+            while controller.playouts() < playouts + 100 {
+                std::thread::yield_now();
+            }
+            assert_eq!(
+                controller.snapshot_stats().moves.len(),
+                state.legal_moves().len()
+            );
+        }
+        manager.clear();
+        controller.set_budget(Some(50));
+        controller.wait();
+        controller.stop();
+        let visits = manager.stats().iter().map(|s| s.visits).sum::<u64>();
+        assert!(visits >= 50);
+    }
+}
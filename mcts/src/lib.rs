@@ -1,28 +1,54 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::missing_panics_doc, clippy::cast_lossless)]
-#![feature(mapped_lock_guards)]
 
 use node::MoveInfo;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
 use search::SearchHandle;
 
+pub mod arena;
+pub mod controller;
 pub mod manager;
 pub mod node;
 pub mod policies;
 pub mod search;
+#[cfg(test)]
+mod testing;
 
-pub trait MCTS: Sized + Sync {
-    type State: GameState + Sync + std::fmt::Debug;
+/// Number of players of the game, the search keeps a tree for every one of them.
+#[cfg(not(feature = "six-players"))]
+pub const NUM_PLAYERS: usize = 4;
+/// Number of players of the game, the search keeps a tree for every one of them.
+#[cfg(feature = "six-players")]
+pub const NUM_PLAYERS: usize = 6;
+
+pub trait MCTS: Sized + Sync + Clone {
+    type State: GameState + Send + Sync + std::fmt::Debug;
     type Eval: Evaluator<Self> + Sync;
     type Select: Policy<Self> + Sync;
 
+    /// Evaluation subtracted from every edge a playout passes through until the playout is
+    /// backpropagated. Parallel searches use this to spread threads over different moves,
+    /// `0` lets all threads follow the same path.
     fn virtual_loss(&self) -> i64 {
         0
     }
 
+    /// Evaluations made relative by the evaluator lie between `-value_scale` and `value_scale`.
+    /// Policies divide mean values by this, so their constants work with values between -1 and 1.
+    fn value_scale(&self) -> f64 {
+        1.0
+    }
+
     fn node_limit(&self) -> usize {
         usize::MAX
     }
 
+    /// What the search does once the tree has grown to [`Self::node_limit`] nodes.
+    fn node_limit_strategy(&self) -> NodeLimitStrategy {
+        NodeLimitStrategy::Stop
+    }
+
     fn visits_before_expansion(&self) -> u64 {
         1
     }
@@ -31,7 +57,40 @@ pub trait MCTS: Sized + Sync {
         1_000
     }
 
-    fn select_child_after_search<'a>(&self, children: &'a [MoveInfo<Self>]) -> &'a MoveInfo<Self> {
+    /// Random moves played after a playout left the tree, before the state gets evaluated.
+    /// `None` plays until the game is over.
+    fn rollout_length(&self) -> Option<usize> {
+        Some(4)
+    }
+
+    /// Progressive widening: the number of children of a node that get expanded given
+    /// how often the node was visited. Untried moves are only expanded while fewer
+    /// children than this are available in the current determinization.
+    fn max_children_considered(&self, _visits: u64) -> usize {
+        usize::MAX
+    }
+
+    /// Heuristic score of playing `mv` in `state`, used by the progressive bias of
+    /// [`policies::UCTPolicy`]. Higher scores make a move more attractive while it has few visits.
+    fn move_heuristic(&self, _state: &Self::State, _mv: &Move<Self>) -> f64 {
+        0.0
+    }
+
+    /// Playouts whose sampled determinization is kept for
+    /// [`manager::Manager::last_determinizations`], `0` keeps none.
+    fn determinization_log_size(&self) -> usize {
+        0
+    }
+
+    /// What the evaluation at the end of a playout gets to see, see [`EvalView`].
+    fn eval_view(&self) -> EvalView {
+        EvalView::Determinized
+    }
+
+    /// Picks the move to play from the legal children of a searched node, used for
+    /// [`search::Tree::pv`]. Only children with the best proven result are passed in,
+    /// `children` is never empty. Returns the most visited child by default.
+    fn select_child_after_search<'a>(&self, children: &[&'a MoveInfo<Self>]) -> &'a MoveInfo<Self> {
         children
             .iter()
             .max_by_key(|child| child.visits())
@@ -39,6 +98,29 @@ pub trait MCTS: Sized + Sync {
     }
 }
 
+/// How a search continues after reaching [`MCTS::node_limit`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NodeLimitStrategy {
+    /// Playouts stop until the tree shrinks again, e.g. by advancing it
+    #[default]
+    Stop,
+    /// The least visited subtrees are freed until half of the limit is left, see
+    /// [`search::Tree::prune`]. The statistics of the moves leading to them are kept.
+    PruneLeastVisited,
+}
+
+/// What the evaluation at the end of a playout sees of the hidden information.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvalView {
+    /// The full state of the sampled determinization, see [`Evaluator::eval_new`].
+    /// Values of hidden information the player at the root can't know leak into the search,
+    /// e.g. a strong hand of an opponent that was only sampled
+    #[default]
+    Determinized,
+    /// Only what the player at the root knows, see [`Evaluator::eval_observed`]
+    Observer,
+}
+
 pub type Move<M> = <<M as MCTS>::State as GameState>::Move;
 pub type MoveList<M> = <<M as MCTS>::State as GameState>::MoveList;
 pub type StateEval<M> = <<M as MCTS>::Eval as Evaluator<M>>::StateEval;
@@ -55,13 +137,51 @@ pub trait GameState: Clone {
     fn current_player(&self) -> Self::Player;
     fn legal_moves(&self) -> Self::MoveList;
     fn make_move(&mut self, mv: &Self::Move);
-    fn randomize_determination(&mut self, observer: Self::Player, knowledge: &Self::Knowledge);
+    /// Replaces the hidden information with a sample consistent with what `observer` knows.
+    /// All randomness has to come from `rng` so seeded searches are reproducible.
+    fn randomize_determination<R: rand::Rng>(
+        &mut self,
+        observer: Self::Player,
+        knowledge: &Self::Knowledge,
+        rng: &mut R,
+    );
     fn update_knowledge(&self, mv: &Self::Move, knowledge: &mut Self::Knowledge);
     fn new_knowledge(&self, observer: Self::Player) -> Self::Knowledge;
     fn knowledge_from_state(&self, observer: Self::Player) -> Self::Knowledge;
+
+    /// Returns whether the move leading to this state dealt new hidden information, like the
+    /// cards of a new round. Nothing searched before a deal carries over to the positions after
+    /// it, see [`search::Tree::advance`].
+    fn new_deal(&self) -> bool {
+        false
+    }
+
+    /// Brings the knowledge of `observer` up to date right after a [`Self::new_deal`].
+    /// Starts over with [`Self::knowledge_from_state`] by default.
+    fn update_knowledge_for_deal(&self, observer: Self::Player, knowledge: &mut Self::Knowledge) {
+        *knowledge = self.knowledge_from_state(observer);
+    }
+
+    /// Returns the form of `mv` that [`Self::legal_moves`] uses. Moves which only differ in
+    /// their representation have to be equal afterwards, otherwise their statistics are split.
+    fn canonical_move(&self, mv: &Self::Move) -> Self::Move {
+        mv.clone()
+    }
+
+    /// Returns whether `player` won once the game is over and `None` while it is still ongoing.
+    /// The search uses this to prove wins and losses, see [`node::Proven`].
+    fn outcome(&self, _player: &Self::Player) -> Option<bool> {
+        None
+    }
+
+    /// Picks a random legal move, used to advance the state during rollouts.
+    /// Override this if moves can be sampled without generating the full move list.
+    fn random_move<R: rand::Rng>(&self, rng: &mut R) -> Option<Self::Move> {
+        rand::seq::IteratorRandom::choose(self.legal_moves().into_iter(), rng)
+    }
 }
 
-pub trait Evaluator<M: MCTS>: Sync {
+pub trait Evaluator<M: MCTS>: Sync + Clone {
     type StateEval: Sync + Send;
 
     fn eval_new(&self, state: &M::State, handle: Option<SearchHandle<M>>) -> Self::StateEval;
@@ -72,9 +192,22 @@ pub trait Evaluator<M: MCTS>: Sync {
         handle: SearchHandle<M>,
     ) -> Self::StateEval;
     fn make_relative(&self, eval: &Self::StateEval, player: &Player<M>) -> i64;
+
+    /// Evaluates `state` with the hidden information masked to what `observer` knows about it,
+    /// used with [`EvalView::Observer`]. The hidden information in `state` is only a sample,
+    /// `knowledge` is what `observer` knows after the moves leading to `state`.
+    /// Evaluates the full state by default.
+    fn eval_observed(
+        &self,
+        state: &M::State,
+        _observer: &Player<M>,
+        _knowledge: &Knowledge<M>,
+    ) -> Self::StateEval {
+        self.eval_new(state, None)
+    }
 }
 
-pub trait Policy<M: MCTS<Select = Self>>: Sync + Sized {
+pub trait Policy<M: MCTS<Select = Self>>: Sync + Sized + Clone {
     type MoveSelect: Sync + Send;
     type ThreadLocalData: Default;
 
@@ -85,10 +218,45 @@ pub trait Policy<M: MCTS<Select = Self>>: Sync + Sized {
     ) -> (usize, &'a MoveInfo<M>)
     where
         MoveIter: Iterator<Item = &'a MoveInfo<M>> + Clone;
+
+    /// Whether the search has to collect the all-moves-as-first statistics of
+    /// [`MoveInfo::rave`] for this policy.
+    fn needs_rave(&self) -> bool {
+        false
+    }
+
+    /// Whether moves that haven't been expanded yet are tried in the order of their
+    /// [`Self::prior`] instead of at random.
+    fn orders_by_prior(&self) -> bool {
+        false
+    }
+
+    /// Prior of `mv` before its statistics accumulate, higher is tried first if
+    /// [`Self::orders_by_prior`]. The heuristic score of the move by default, see
+    /// [`MCTS::move_heuristic`].
+    fn prior(&self, mv: &MoveInfo<M>) -> f64 {
+        mv.heuristic()
+    }
 }
 
 pub struct ThreadData<M: MCTS> {
     pub policy_data: TreePolicyThreadData<M>,
+    /// Source of randomness for determinizations, expansions and rollouts
+    pub rng: XorShiftRng,
+}
+
+impl<M: MCTS> ThreadData<M>
+where
+    TreePolicyThreadData<M>: Default,
+{
+    /// Thread data whose random choices are fully determined by `seed`.
+    #[must_use]
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            policy_data: Default::default(),
+            rng: XorShiftRng::seed_from_u64(seed),
+        }
+    }
 }
 
 impl<M: MCTS> Default for ThreadData<M>
@@ -98,6 +266,7 @@ where
     fn default() -> Self {
         Self {
             policy_data: Default::default(),
+            rng: XorShiftRng::from_entropy(),
         }
     }
 }
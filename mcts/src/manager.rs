@@ -1,10 +1,63 @@
-use std::sync::atomic::{AtomicIsize, Ordering};
+use std::{
+    io,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicIsize, Ordering},
+        Arc, Mutex, Weak,
+    },
+    thread::JoinHandle,
+};
 
-use crate::{node::ComputedStats, search::Tree, GameState, Move, ThreadData, MCTS};
+use crate::{
+    controller::{SearchController, Shared},
+    node::ComputedStats,
+    search::{AdvanceReport, Determinization, Tree},
+    GameState, Knowledge, Move, Player, StateEval, ThreadData, MCTS, NUM_PLAYERS,
+};
+
+/// How the playouts of a parallel search are distributed over the worker threads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SearchMode {
+    /// All workers search the same tree
+    #[default]
+    TreeParallel,
+    /// Every worker searches its own tree, the root statistics are merged at the end.
+    /// This avoids contention when expanding nodes at the cost of less deep trees.
+    RootParallel,
+}
+
+/// Background threads searching the tree while the manager is idle.
+struct Ponder {
+    stop: Arc<AtomicBool>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Ponder {
+    fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        for handle in self.handles {
+            let _ = handle.join();
+        }
+    }
+}
 
 pub struct Manager<M: MCTS> {
-    search_tree: Tree<M>,
+    search_tree: Arc<Tree<M>>,
     tld: Option<ThreadData<M>>,
+    mode: SearchMode,
+    ponder: Option<Ponder>,
+    seed: Option<u64>,
+    /// Workers of the controllers spawned by [`Self::spawn_workers`], which have to give back
+    /// the tree before it can be changed
+    workers: Mutex<Vec<Weak<Shared<M>>>>,
+}
+
+impl<M: MCTS> Drop for Manager<M> {
+    fn drop(&mut self) {
+        if let Some(ponder) = self.ponder.take() {
+            ponder.stop();
+        }
+    }
 }
 
 #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
@@ -15,40 +68,180 @@ where
     pub fn new(state: M::State, manager: M, policy: M::Select, eval: M::Eval) -> Self {
         let search_tree = Tree::new(state, manager, policy, eval);
         Self {
-            search_tree,
+            search_tree: Arc::new(search_tree),
             tld: None,
+            mode: SearchMode::default(),
+            ponder: None,
+            seed: None,
+            workers: Mutex::new(Vec::new()),
         }
     }
 
+    /// Makes all random choices of the search depend only on `seed`, so searching the
+    /// same position with the same number of playouts gives the same result.
+    /// Parallel searches are only reproducible per thread, the order in which the threads
+    /// update the tree still varies.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self.tld = None;
+        self
+    }
+
+    /// Thread data for the `thread`th worker of a search.
+    pub(crate) fn thread_data(seed: Option<u64>, thread: usize) -> ThreadData<M> {
+        match seed {
+            Some(seed) => ThreadData::with_seed(seed.wrapping_add(thread as u64)),
+            None => ThreadData::default(),
+        }
+    }
+
+    pub fn set_search_mode(&mut self, mode: SearchMode) {
+        self.mode = mode;
+    }
+
+    pub fn search_mode(&self) -> SearchMode {
+        self.mode
+    }
+
     pub fn playout(&mut self) {
-        if self.tld.is_none() {
-            self.tld = Some(ThreadData::default());
+        let seed = self.seed;
+        let tld = self.tld.get_or_insert_with(|| Self::thread_data(seed, 0));
+        if !self.search_tree.playout(tld) && Self::make_room(&mut self.search_tree) {
+            let _ = self.search_tree.playout(tld);
         }
-        let _ = self.search_tree.playout(self.tld.as_mut().unwrap());
     }
 
+    /// Prunes the tree after the node limit was hit, returns whether searching can continue.
+    /// The tree can't be pruned while other threads are pondering on it.
+    fn make_room(search_tree: &mut Arc<Tree<M>>) -> bool {
+        Arc::get_mut(search_tree).is_some_and(Tree::make_room)
+    }
+
+    /// Runs `n` playouts, none if the move is forced, see [`Self::forced_move`].
     pub fn playout_n(&mut self, n: u64) {
+        if self.forced_move().is_some() {
+            return;
+        }
         (0..n).for_each(|_| self.playout());
     }
 
+    /// Returns the only legal move at the root, `None` if there is a choice or the game is over.
+    /// Searching can't change which move gets played then, so the `playout_n` methods skip it
+    /// and [`Self::best_move`] returns it right away.
+    pub fn forced_move(&self) -> Option<Move<M>> {
+        let mut moves = self.search_tree.root_state().legal_moves().into_iter();
+        let mv = moves.next()?;
+        moves.next().is_none().then_some(mv)
+    }
+
+    /// Runs between `min` and `max` playouts in stages, doubling the playouts with every stage.
+    /// Stops early once the best move is settled: either its share of the root visits changed
+    /// by at most `epsilon` since the last stage without another move taking its place, or no
+    /// other move could reach its visits with the playouts left. Forced and obvious moves
+    /// finish after a few stages, close decisions get the full budget.
+    /// Returns the number of playouts run.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn playout_until_stable(&mut self, min: u64, max: u64, epsilon: f64) -> u64 {
+        if self.forced_move().is_some() {
+            return 0;
+        }
+        let mut done = 0;
+        let mut stage = min.clamp(1, max.max(1));
+        let mut last: Option<(Move<M>, f64)> = None;
+        while done < max {
+            let n = stage.min(max - done);
+            self.playout_n(n);
+            done += n;
+            let Some(best) = self.best_move() else {
+                break;
+            };
+            let stats = self.root_move_stats();
+            let visits = |others: bool| {
+                stats
+                    .iter()
+                    .filter(|(mv, _)| (*mv != best) == others)
+                    .map(|(_, s)| s.visits)
+                    .max()
+                    .unwrap_or(0)
+            };
+            let (best_visits, runner_up) = (visits(false), visits(true));
+            if best_visits >= runner_up + (max - done) {
+                break;
+            }
+            let total = stats.iter().map(|(_, s)| s.visits).sum::<u64>().max(1);
+            let share = best_visits as f64 / total as f64;
+            if last.as_ref().is_some_and(|(mv, last_share)| {
+                *mv == best && (share - last_share).abs() <= epsilon
+            }) {
+                break;
+            }
+            last = Some((best, share));
+            stage = done;
+        }
+        done
+    }
+
+    /// Runs `n` playouts on `num_threads` threads, none if the move is forced, see
+    /// [`Self::forced_move`].
     pub fn playout_n_parallel(&mut self, n: u64, num_threads: usize) {
-        if num_threads == 0 {
+        if num_threads == 0 || self.forced_move().is_some() {
             return;
         }
+        if self.mode == SearchMode::RootParallel {
+            self.playout_n_root_parallel(n, num_threads);
+            return;
+        }
+
+        let counter = &AtomicIsize::new(n as isize);
+        let seed = self.seed;
+        loop {
+            let search_tree: &Tree<M> = &self.search_tree;
+            let _ = crossbeam::scope(|scope| {
+                (0..num_threads).for_each(|thread| {
+                    scope.spawn(move |_| {
+                        let mut tld = Self::thread_data(seed, thread);
+                        loop {
+                            let count = counter.fetch_sub(1, Ordering::SeqCst);
+                            if count <= 0 {
+                                break;
+                            }
+                            if !search_tree.playout(&mut tld) {
+                                // Give the playout back, it runs after the tree was pruned
+                                counter.fetch_add(1, Ordering::SeqCst);
+                                break;
+                            }
+                        }
+                    });
+                });
+            });
+            if counter.load(Ordering::SeqCst) <= 0 || !Self::make_room(&mut self.search_tree) {
+                break;
+            }
+        }
+    }
 
-        let counter = AtomicIsize::new(n as isize);
-        let search_tree = &self.search_tree;
+    fn playout_n_root_parallel(&mut self, n: u64, num_threads: usize) {
+        let counter = &AtomicIsize::new(n as isize);
+        let search_tree: &Tree<M> = &self.search_tree;
+        let seed = self.seed;
         let _ = crossbeam::scope(|scope| {
-            (0..num_threads).for_each(|_| {
-                scope.spawn(|_| {
-                    let mut tld = ThreadData::default();
+            (0..num_threads).for_each(|thread| {
+                scope.spawn(move |_| {
+                    let mut tree = search_tree.fork();
+                    let mut tld = Self::thread_data(seed, thread);
                     loop {
                         let count = counter.fetch_sub(1, Ordering::SeqCst);
                         if count <= 0 {
                             break;
                         }
-                        let _ = search_tree.playout(&mut tld);
+                        let searched =
+                            tree.playout(&mut tld) || (tree.make_room() && tree.playout(&mut tld));
+                        if !searched {
+                            break;
+                        }
                     }
+                    search_tree.merge_roots(&tree);
                 });
             });
         });
@@ -75,12 +268,143 @@ where
         states
     }
 
+    /// Starts `num_threads` threads that keep searching the current root in the background
+    /// until [`Self::stop_pondering`] or [`Self::advance`] is called.
+    pub fn start_pondering(&mut self, num_threads: usize)
+    where
+        M: 'static,
+        Tree<M>: Send + Sync,
+    {
+        self.stop_pondering();
+        let stop = Arc::new(AtomicBool::new(false));
+        let handles = (0..num_threads)
+            .map(|thread| {
+                let search_tree = Arc::clone(&self.search_tree);
+                let stop = Arc::clone(&stop);
+                let seed = self.seed;
+                std::thread::spawn(move || {
+                    let mut tld = Self::thread_data(seed, thread);
+                    while !stop.load(Ordering::Relaxed) {
+                        // Stop once the node limit is reached
+                        if !search_tree.playout(&mut tld) {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect();
+        self.ponder = Some(Ponder { stop, handles });
+    }
+
+    /// Starts `num_threads` workers searching the current root until the returned controller
+    /// is stopped or dropped, see [`SearchController`]. The workers always search the tree
+    /// of the manager, independent of the search mode. Advancing, clearing or loading the tree
+    /// waits for their running playouts, the workers continue with the new root afterwards.
+    /// # Panics
+    /// If a thread panicked while spawning workers.
+    #[must_use]
+    pub fn spawn_workers(&self, num_threads: usize) -> SearchController<M>
+    where
+        M: 'static,
+        Tree<M>: Send + Sync,
+    {
+        let controller = SearchController::spawn(&self.search_tree, num_threads, self.seed);
+        let mut workers = self.workers.lock().unwrap();
+        workers.retain(|shared| shared.strong_count() > 0);
+        workers.push(controller.shared());
+        controller
+    }
+
+    /// Stops the background search and waits for all pondering threads to finish.
+    pub fn stop_pondering(&mut self) {
+        if let Some(ponder) = self.ponder.take() {
+            ponder.stop();
+        }
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.ponder.is_some()
+    }
+
+    /// Changes the tree with `f` once nothing else searches it. Pondering stops, workers of
+    /// [`SearchController`]s pause until `f` returned and then search the changed tree.
+    fn modify_tree<R>(&mut self, f: impl FnOnce(&mut Tree<M>) -> R) -> R {
+        self.stop_pondering();
+        let workers = self.workers.get_mut().unwrap();
+        let detached = workers
+            .iter()
+            .filter_map(Weak::upgrade)
+            .inspect(|shared| shared.detach())
+            .collect::<Vec<_>>();
+        workers.retain(|shared| shared.strong_count() > 0);
+        let result = f(Arc::get_mut(&mut self.search_tree)
+            .expect("No other references to the tree after pondering stopped and workers paused"));
+        for shared in detached {
+            shared.attach(&self.search_tree);
+        }
+        result
+    }
+
+    /// Moves the root to the child reached by `mv`. Safe to call while workers spawned by
+    /// [`Self::spawn_workers`] search, it waits for their running playouts.
     pub fn advance(&mut self, mv: &Move<M>) {
-        self.search_tree.advance(mv);
+        self.modify_tree(|tree| tree.advance(mv));
+    }
+
+    /// Like [`Self::advance`], also reports how much of the search was kept for the new root.
+    pub fn advance_with_report(&mut self, mv: &Move<M>) -> AdvanceReport {
+        self.modify_tree(|tree| tree.advance_with_report(mv))
+    }
+
+    /// Discards the search results, the memory of the tree is reused by the next search.
+    pub fn clear(&mut self) {
+        self.modify_tree(Tree::clear);
+    }
+
+    /// Saves the search results to `path`, see [`Tree::save`].
+    /// # Errors
+    /// If the file can't be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()>
+    where
+        Move<M>: Into<u64> + TryFrom<u64>,
+        StateEval<M>: Clone,
+    {
+        self.search_tree.save(path)
+    }
+
+    /// Continues the search saved to `path` by [`Self::save`] for the same root state.
+    /// Stops pondering like [`Self::advance`].
+    /// # Errors
+    /// If the file can't be read or doesn't contain a saved tree, the search results are kept.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> io::Result<()>
+    where
+        Move<M>: Into<u64> + TryFrom<u64>,
+        StateEval<M>: Clone,
+    {
+        self.modify_tree(|tree| tree.load(path))
+    }
+
+    /// Returns up to `n` of the determinizations the last playouts searched, oldest first.
+    /// Only the last [`MCTS::determinization_log_size`] are kept, advancing or clearing the
+    /// search forgets them.
+    pub fn last_determinizations(&self, n: usize) -> Vec<Determinization<M>> {
+        self.search_tree.last_determinizations(n)
     }
 
     pub fn best_move(&self) -> Option<Move<M>> {
-        self.pv(1).first().cloned()
+        self.forced_move().or_else(|| self.pv(1).first().cloned())
+    }
+
+    /// Samples the move to play from the visits of the root moves instead of always playing
+    /// the best one, see [`Tree::sample_move`]. Uses the random numbers of the search, so
+    /// seeded managers sample reproducibly.
+    pub fn sample_move(&mut self, temperature: f64) -> Option<Move<M>> {
+        if let Some(mv) = self.forced_move() {
+            return Some(mv);
+        }
+        let seed = self.seed;
+        let tld = self.tld.get_or_insert_with(|| Self::thread_data(seed, 0));
+        self.search_tree.sample_move(temperature, &mut tld.rng)
     }
 
     pub fn moves(&self) -> Vec<Move<M>> {
@@ -91,10 +415,32 @@ where
         self.tree().root().stats()
     }
 
+    /// Returns the moves searched for the player to move with their statistics, most visited first.
+    /// This includes moves that were only legal in some determinizations.
+    pub fn root_move_stats(&self) -> Vec<(Move<M>, ComputedStats)> {
+        self.tree().root().move_stats()
+    }
+
+    /// Like [`Self::root_move_stats`] in the tree of `player`, see [`Tree::root_for`].
+    /// For another player than the one to move this shows how `player` expects them to play.
+    pub fn move_stats_for(&self, player: Player<M>) -> Vec<(Move<M>, ComputedStats)> {
+        self.tree().root_for(player).move_stats()
+    }
+
     pub fn print_stats(&self) {
         self.search_tree.print_stats();
     }
 
+    pub fn knowledge(&self) -> &[Knowledge<M>; NUM_PLAYERS] {
+        self.search_tree.knowledge()
+    }
+
+    /// Replaces the knowledge of every player, see [`Tree::set_knowledge`].
+    /// Stops pondering like [`Self::advance`].
+    pub fn set_knowledge(&mut self, knowledge: [Knowledge<M>; NUM_PLAYERS]) {
+        self.modify_tree(|tree| tree.set_knowledge(knowledge));
+    }
+
     pub fn print_knowledge(&self) {
         self.search_tree.print_knowledge();
     }
@@ -107,3 +453,156 @@ where
         self.tree().display_legal_moves();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{policies::UCTPolicy, testing::*};
+
+    #[test]
+    fn parallel_search_and_advance() {
+        for mode in [SearchMode::TreeParallel, SearchMode::RootParallel] {
+            let mut manager = Manager::new(
+                Race::default(),
+                TestAI::default(),
+                UCTPolicy::new(0.7),
+                RaceEval,
+            );
+            manager.set_search_mode(mode);
+            let mut state = Race::default();
+            while state.total < Race::TARGET {
+                manager.playout_n_parallel(200, THREADS);
+                let mv = manager.best_move().unwrap();
+                assert!(state.legal_moves().contains(&mv));
+                state.make_move(&mv);
+                manager.advance(&mv);
+                assert!(manager.tree().num_nodes() >= 4);
+            }
+        }
+    }
+
+    #[test]
+    fn prune_at_node_limit() {
+        const PLAYOUTS: u64 = 500;
+        let ai = TestAI {
+            node_limit: 60,
+            ..TestAI::default()
+        };
+        for (mode, threads) in [
+            (SearchMode::TreeParallel, 1),
+            (SearchMode::TreeParallel, THREADS),
+            (SearchMode::RootParallel, THREADS),
+        ] {
+            let mut manager =
+                Manager::new(Race::default(), ai.clone(), UCTPolicy::new(0.7), RaceEval);
+            manager.set_search_mode(mode);
+            if threads == 1 {
+                manager.playout_n(PLAYOUTS);
+            } else {
+                manager.playout_n_parallel(PLAYOUTS, threads);
+            }
+            // Every playout ran and the statistics of pruned moves were kept
+            let visits = manager.stats().iter().map(|s| s.visits).sum::<u64>();
+            assert_eq!(visits, PLAYOUTS, "{mode:?} {threads}");
+            // Playouts still running may each add a node to the tree of every player
+            assert!(manager.tree().num_nodes() <= ai.node_limit + threads * NUM_PLAYERS);
+        }
+    }
+
+    #[test]
+    fn sample_move() {
+        let mut manager = Manager::new(
+            Race::default(),
+            TestAI::default(),
+            UCTPolicy::new(0.7),
+            RaceEval,
+        )
+        .with_seed(5);
+        assert_eq!(manager.sample_move(1.0), None);
+        // Too few playouts to prove the result of a move
+        manager.playout_n(20);
+        let best = manager.best_move();
+        assert_eq!(manager.sample_move(0.0), best);
+        assert_eq!(manager.sample_move(0.01), best);
+        let sampled = (0..200)
+            .filter_map(|_| manager.sample_move(f64::INFINITY))
+            .collect::<Vec<_>>();
+        assert!(sampled
+            .iter()
+            .all(|mv| Race::default().legal_moves().contains(mv)));
+        // Without a preference every legal move gets played
+        assert!(sampled.iter().any(|mv| Some(*mv) != best));
+    }
+
+    #[test]
+    fn playout_until_stable() {
+        let new = |total| {
+            let race = Race { total, to_move: 0 };
+            Manager::new(race, TestAI::default(), UCTPolicy::new(0.7), RaceEval).with_seed(1)
+        };
+        // Adding two wins right away, so the search settles long before the budget is used
+        let mut manager = new(Race::TARGET - 2);
+        let done = manager.playout_until_stable(20, 10_000, 0.01);
+        assert!((20..10_000).contains(&done), "{done}");
+        assert_eq!(manager.best_move(), Some(2));
+        assert_eq!(manager.stats().iter().map(|s| s.visits).sum::<u64>(), done);
+
+        // Without a tolerance only a decided search stops early
+        let mut manager = new(0);
+        let done = manager.playout_until_stable(20, 200, -1.0);
+        assert!((20..=200).contains(&done), "{done}");
+        assert_eq!(new(0).playout_until_stable(0, 0, 0.0), 0);
+        assert_eq!(new(0).playout_until_stable(50, 30, 0.0), 30);
+    }
+
+    #[test]
+    fn player_trees() {
+        let mut manager = Manager::new(
+            Race::default(),
+            TestAI::default(),
+            UCTPolicy::new(0.7),
+            RaceEval,
+        );
+        manager.playout_n(100);
+        assert_eq!(manager.move_stats_for(0), manager.root_move_stats());
+        // Every playout passes through the root of every tree
+        for player in 0..NUM_PLAYERS {
+            let stats = manager.move_stats_for(player);
+            assert_eq!(stats.iter().map(|(_, s)| s.visits).sum::<u64>(), 100);
+        }
+    }
+
+    #[test]
+    fn logs_determinizations() {
+        let mut manager = Manager::new(
+            Race::default(),
+            TestAI::default(),
+            UCTPolicy::new(1.0),
+            RaceEval,
+        );
+        manager.playout_n(10);
+        assert!(manager.last_determinizations(10).is_empty());
+
+        for mode in [SearchMode::TreeParallel, SearchMode::RootParallel] {
+            let ai = TestAI {
+                log_size: 16,
+                ..TestAI::default()
+            };
+            let mut manager = Manager::new(Race::default(), ai, UCTPolicy::new(1.0), RaceEval);
+            manager.set_search_mode(mode);
+            manager.playout_n(10);
+            assert_eq!(manager.last_determinizations(100).len(), 10);
+            manager.playout_n_parallel(100, THREADS);
+            let logged = manager.last_determinizations(100);
+            assert_eq!(logged.len(), 16);
+            assert_eq!(manager.last_determinizations(4).len(), 4);
+            for determinization in &logged {
+                assert_eq!(determinization.state.total, 0);
+                assert!(matches!(determinization.first_move, Some(1 | 2)));
+                assert_eq!(determinization.eval.abs(), 1);
+            }
+            manager.advance(&1);
+            assert!(manager.last_determinizations(100).is_empty());
+        }
+    }
+}
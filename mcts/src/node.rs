@@ -1,38 +1,45 @@
 use std::sync::{
-    atomic::{AtomicI64, AtomicPtr, AtomicUsize, Ordering},
-    RwLock,
+    atomic::{AtomicI64, AtomicU8, AtomicUsize, Ordering},
+    Mutex, OnceLock,
 };
 
 use itertools::Itertools;
 
-use crate::{search::SearchHandle, Evaluator, Move, StateEval, MCTS};
+use crate::{
+    arena::{AtomicNodeId, NodeId},
+    search::SearchHandle,
+    Evaluator, Move, StateEval, MCTS,
+};
 
 pub struct MoveInfo<M: MCTS> {
     pub mv: Move<M>,
-    pub child: AtomicPtr<Node<M>>,
+    pub child: AtomicNodeId,
     pub stats: Stats,
-}
-
-impl<M: MCTS> Drop for MoveInfo<M> {
-    fn drop(&mut self) {
-        let ptr = self.child.load(Ordering::SeqCst);
-        if !ptr.is_null() {
-            unsafe {
-                let x = Box::from_raw(ptr);
-                x.moves.write().unwrap().clear();
-                drop(x);
-            }
-        }
-    }
+    /// All-moves-as-first statistics, updated by every playout in which the player to move
+    /// played this move later on. Only collected for policies that need them,
+    /// see [`crate::Policy::needs_rave`]
+    pub rave: Stats,
+    /// Players winning the game after this move, `0` while the result isn't proven
+    winners: AtomicU8,
+    /// Heuristic score of the move, see [`MCTS::move_heuristic`]
+    heuristic: f64,
 }
 
 impl<M: MCTS> MoveInfo<M> {
     #[must_use]
     pub fn new(mv: Move<M>) -> Self {
+        Self::with_heuristic(mv, 0.0)
+    }
+
+    #[must_use]
+    pub fn with_heuristic(mv: Move<M>, heuristic: f64) -> Self {
         Self {
             mv,
-            child: AtomicPtr::default(),
+            child: AtomicNodeId::default(),
             stats: Stats::new(),
+            rave: Stats::new(),
+            winners: 0.into(),
+            heuristic,
         }
     }
 
@@ -41,6 +48,11 @@ impl<M: MCTS> MoveInfo<M> {
         &self.mv
     }
 
+    #[must_use]
+    pub fn heuristic(&self) -> f64 {
+        self.heuristic
+    }
+
     #[must_use]
     pub fn visits(&self) -> u64 {
         self.stats.visits.load(Ordering::Relaxed) as u64
@@ -56,6 +68,16 @@ impl<M: MCTS> MoveInfo<M> {
         self.stats.sum_evaluations.load(Ordering::Relaxed)
     }
 
+    #[must_use]
+    pub fn rave_visits(&self) -> u64 {
+        self.rave.visits.load(Ordering::Relaxed) as u64
+    }
+
+    #[must_use]
+    pub fn rave_rewards(&self) -> i64 {
+        self.rave.sum_evaluations.load(Ordering::Relaxed)
+    }
+
     #[allow(clippy::cast_precision_loss)]
     #[must_use]
     pub fn computed_stats(&self) -> ComputedStats {
@@ -68,19 +90,249 @@ impl<M: MCTS> MoveInfo<M> {
         }
     }
 
+    /// Returns the players winning after this move as a bitmask indexed by player,
+    /// or `None` if the result of the move isn't proven.
+    #[must_use]
+    pub fn winners(&self) -> Option<u8> {
+        let winners = self.winners.load(Ordering::Relaxed);
+        (winners != 0).then_some(winners)
+    }
+
+    /// Returns the proven result of this move for the player with index `player`.
+    #[must_use]
+    pub fn proven(&self, player: usize) -> Option<Proven> {
+        self.winners().map(|winners| {
+            if winners & 1 << player == 0 {
+                Proven::Loss
+            } else {
+                Proven::Win
+            }
+        })
+    }
+
+    /// Marks the move as leading to a game won by the players in the `winners` bitmask.
+    pub fn prove(&self, winners: u8) {
+        debug_assert!(winners != 0, "Someone has to win");
+        self.winners.store(winners, Ordering::Relaxed);
+    }
+
+    /// Returns the id of the node reached by this move, see [`crate::search::Tree::node`].
+    #[must_use]
+    pub fn child(&self) -> Option<NodeId> {
+        self.child.load()
+    }
+}
+
+/// Game-theoretic value of a move, proven by reaching the end of the game during search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Proven {
+    Win,
+    Loss,
+}
+
+/// Size of the first chunk of [`Children`], every following chunk is twice as large as the previous one.
+const FIRST_CHILDREN_CHUNK: usize = 4;
+
+struct ChildrenChunk<M: MCTS> {
+    infos: Box<[OnceLock<MoveInfo<M>>]>,
+    next: OnceLock<Box<ChildrenChunk<M>>>,
+}
+
+impl<M: MCTS> ChildrenChunk<M> {
+    fn new(size: usize) -> Self {
+        Self {
+            infos: (0..size).map(|_| OnceLock::new()).collect(),
+            next: OnceLock::new(),
+        }
+    }
+}
+
+/// The moves explored from a node.
+///
+/// Moves are only ever appended and stored in chunks that never move, so reading and
+/// descending needs no lock. Appending is serialized between threads, readers never wait for it.
+pub struct Children<M: MCTS> {
+    first: OnceLock<Box<ChildrenChunk<M>>>,
+    /// Number of moves visible to readers, every slot below it is initialized
+    len: AtomicUsize,
+    append: Mutex<()>,
+}
+
+impl<M: MCTS> Default for Children<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M: MCTS> Children<M> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            first: OnceLock::new(),
+            len: 0.into(),
+            append: Mutex::new(()),
+        }
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the slot with the given index, creating the chunks up to it.
+    fn slot(&self, index: usize) -> &OnceLock<MoveInfo<M>> {
+        let (mut chunk, mut size, mut offset) = (&self.first, FIRST_CHILDREN_CHUNK, index);
+        loop {
+            let current = chunk.get_or_init(|| Box::new(ChildrenChunk::new(size)));
+            if offset < size {
+                return &current.infos[offset];
+            }
+            offset -= size;
+            size *= 2;
+            chunk = &current.next;
+        }
+    }
+
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&MoveInfo<M>> {
+        (index < self.len()).then(|| self.slot(index).get().expect("Published slot is set"))
+    }
+
+    /// Bytes of the chunks allocated for the moves, which don't move once allocated.
+    #[must_use]
+    pub fn allocated_bytes(&self) -> usize {
+        let mut bytes = 0;
+        let mut chunk = self.first.get();
+        while let Some(current) = chunk {
+            bytes += std::mem::size_of::<ChildrenChunk<M>>()
+                + std::mem::size_of_val::<[OnceLock<MoveInfo<M>>]>(&current.infos);
+            chunk = current.next.get();
+        }
+        bytes
+    }
+
+    /// Iterates over the moves appended before the call.
+    pub fn iter(&self) -> ChildrenIter<'_, M> {
+        ChildrenIter {
+            chunk: self.first.get().map(AsRef::as_ref),
+            offset: 0,
+            remaining: self.len(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut MoveInfo<M>> {
+        let mut infos = Vec::with_capacity(*self.len.get_mut());
+        let mut chunk = self.first.get_mut();
+        while let Some(current) = chunk {
+            let ChildrenChunk { infos: slots, next } = &mut **current;
+            infos.extend(slots.iter_mut().filter_map(OnceLock::get_mut));
+            chunk = next.get_mut();
+        }
+        infos.into_iter()
+    }
+
+    /// Returns the index of `mv` and its info, `None` if it hasn't been explored.
     #[must_use]
-    pub fn child(&self) -> Option<NodeHandle<M>> {
-        let ptr = self.child.load(Ordering::Relaxed);
-        if ptr.is_null() {
-            None
-        } else {
-            unsafe { Some(NodeHandle { node: &*ptr }) }
+    pub fn find(&self, mv: &Move<M>) -> Option<(usize, &MoveInfo<M>)> {
+        self.iter().enumerate().find(|(_, info)| info.mv == *mv)
+    }
+
+    /// Appends `info` and returns its index.
+    pub fn push(&self, info: MoveInfo<M>) -> usize {
+        let _append = self.append.lock().unwrap();
+        self.push_locked(info)
+    }
+
+    /// Appends the info created by `info` unless `mv` has already been explored,
+    /// even if another thread appends it at the same time. Returns the index of `mv`.
+    pub fn push_if_absent(&self, mv: &Move<M>, info: impl FnOnce() -> MoveInfo<M>) -> usize {
+        if let Some((index, _)) = self.find(mv) {
+            return index;
+        }
+        let _append = self.append.lock().unwrap();
+        match self.find(mv) {
+            Some((index, _)) => index,
+            None => self.push_locked(info()),
         }
     }
+
+    fn push_locked(&self, info: MoveInfo<M>) -> usize {
+        let index = self.len.load(Ordering::Relaxed);
+        assert!(
+            self.slot(index).set(info).is_ok(),
+            "Appended slot is occupied"
+        );
+        self.len.store(index + 1, Ordering::Release);
+        index
+    }
 }
 
+impl<M: MCTS> std::ops::Index<usize> for Children<M> {
+    type Output = MoveInfo<M>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.get(index).expect("Index out of bounds")
+    }
+}
+
+impl<'a, M: MCTS> IntoIterator for &'a Children<M> {
+    type Item = &'a MoveInfo<M>;
+    type IntoIter = ChildrenIter<'a, M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<M: MCTS> FromIterator<MoveInfo<M>> for Children<M> {
+    fn from_iter<T: IntoIterator<Item = MoveInfo<M>>>(iter: T) -> Self {
+        let children = Self::new();
+        for info in iter {
+            children.push(info);
+        }
+        children
+    }
+}
+
+pub struct ChildrenIter<'a, M: MCTS> {
+    chunk: Option<&'a ChildrenChunk<M>>,
+    offset: usize,
+    remaining: usize,
+}
+
+impl<'a, M: MCTS> Iterator for ChildrenIter<'a, M> {
+    type Item = &'a MoveInfo<M>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let mut chunk = self.chunk?;
+        if self.offset == chunk.infos.len() {
+            chunk = chunk.next.get()?;
+            self.chunk = Some(chunk);
+            self.offset = 0;
+        }
+        let info = chunk.infos[self.offset].get()?;
+        self.offset += 1;
+        self.remaining -= 1;
+        Some(info)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<M: MCTS> ExactSizeIterator for ChildrenIter<'_, M> {}
+
 pub struct Node<M: MCTS> {
-    pub moves: RwLock<Vec<MoveInfo<M>>>,
+    pub moves: Children<M>,
     pub eval: StateEval<M>,
     pub stats: Stats,
 }
@@ -89,11 +341,17 @@ impl<M: MCTS> Node<M> {
     #[must_use]
     pub fn new(eval: &M::Eval, state: &M::State, handle: Option<SearchHandle<M>>) -> Node<M> {
         Self {
-            moves: Vec::new().into(),
+            moves: Children::new(),
             eval: eval.eval_new(state, handle),
             stats: Stats::new(),
         }
     }
+
+    /// Sum of the visits of all moves from this node.
+    #[must_use]
+    pub fn visits(&self) -> u64 {
+        self.moves.iter().map(MoveInfo::visits).sum()
+    }
 }
 
 pub struct Stats {
@@ -133,10 +391,42 @@ impl Stats {
         self.sum_evaluations.fetch_add(delta, Ordering::Relaxed);
     }
 
-    pub fn replace(&self, other: &Stats) {
+    /// Records a visit with the given evaluation, without any virtual loss.
+    pub fn record(&self, eval: i64) {
+        self.visits.fetch_add(1, Ordering::Relaxed);
+        self.sum_evaluations.fetch_add(eval, Ordering::Relaxed);
+    }
+
+    /// Visits, availability count and sum of evaluations, as stored by [`crate::search::Tree::save`].
+    pub(crate) fn counts(&self) -> (u64, u64, i64) {
+        (
+            self.visits.load(Ordering::Relaxed) as u64,
+            self.availability_count.load(Ordering::Relaxed) as u64,
+            self.sum_evaluations.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Inverse of [`Self::counts`], `None` if the counts don't fit into memory.
+    pub(crate) fn from_counts(
+        visits: u64,
+        availability_count: u64,
+        sum_evaluations: i64,
+    ) -> Option<Self> {
+        Some(Self {
+            visits: usize::try_from(visits).ok()?.into(),
+            availability_count: usize::try_from(availability_count).ok()?.into(),
+            sum_evaluations: sum_evaluations.into(),
+        })
+    }
+
+    pub fn add(&self, other: &Stats) {
         self.visits
-            .store(other.visits.load(Ordering::Relaxed), Ordering::Relaxed);
-        self.sum_evaluations.store(
+            .fetch_add(other.visits.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.availability_count.fetch_add(
+            other.availability_count.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        self.sum_evaluations.fetch_add(
             other.sum_evaluations.load(Ordering::Relaxed),
             Ordering::Relaxed,
         );
@@ -149,38 +439,34 @@ pub struct NodeHandle<'a, M: 'a + MCTS> {
     pub node: &'a Node<M>,
 }
 
-#[allow(clippy::cast_precision_loss)]
-impl<'a, M: MCTS> NodeHandle<'a, M> {
+impl<M: MCTS> NodeHandle<'_, M> {
     #[must_use]
     pub fn moves(&self) -> Vec<Move<M>> {
+        self.node.moves.iter().map(|x| x.mv.clone()).collect_vec()
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> Vec<ComputedStats> {
         self.node
             .moves
-            .read()
-            .unwrap()
             .iter()
-            .map(|x| x.mv.clone())
+            .map(MoveInfo::computed_stats)
             .collect_vec()
     }
 
+    /// Returns every child move together with its statistics, most visited first.
     #[must_use]
-    pub fn stats(&self) -> Vec<ComputedStats> {
+    pub fn move_stats(&self) -> Vec<(Move<M>, ComputedStats)> {
         self.node
             .moves
-            .read()
-            .unwrap()
             .iter()
-            .map(|x| ComputedStats {
-                visits: x.visits(),
-                availability_count: x.availability(),
-                sum_evaluations: x.sum_rewards(),
-                mean_action_value: x.sum_rewards() as f64 / x.visits() as f64,
-                availability: ((1.0 + x.availability() as f64).ln() / x.visits() as f64).sqrt(),
-            })
+            .map(|x| (x.mv.clone(), x.computed_stats()))
+            .sorted_by(|(_, a), (_, b)| b.visits.cmp(&a.visits))
             .collect_vec()
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct ComputedStats {
     pub visits: u64,
     pub availability_count: u64,
@@ -188,3 +474,108 @@ pub struct ComputedStats {
     pub mean_action_value: f64,
     pub availability: f64,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{manager::Manager, policies::UCTPolicy, testing::*, ThreadData, NUM_PLAYERS};
+
+    #[test]
+    fn virtual_loss() {
+        let ai = TestAI {
+            virtual_loss: 1,
+            ..TestAI::default()
+        };
+        let mut manager = Manager::new(Race::default(), ai.clone(), UCTPolicy::new(0.7), RaceEval);
+        manager.playout_n_parallel(400, THREADS);
+        // Every virtual loss was reverted, so all values are between a loss and a win
+        let tree = manager.tree();
+        let mut stack = vec![tree.root().node];
+        while let Some(node) = stack.pop() {
+            for info in &node.moves {
+                assert!(info.sum_rewards().unsigned_abs() <= info.visits());
+                stack.extend(info.child().and_then(|id| tree.node(id)).map(|h| h.node));
+            }
+        }
+        // A playout in flight makes the edge look worse to the other threads
+        let moves = &tree.root().node.moves;
+        let info = moves.iter().max_by_key(|info| info.visits()).unwrap();
+        let before = info.computed_stats();
+        info.stats.down(&ai);
+        let during = info.computed_stats();
+        assert_eq!(during.visits, before.visits + 1);
+        assert!(during.mean_action_value < before.mean_action_value);
+        info.stats.up(&ai, 1);
+        assert_eq!(info.sum_rewards(), before.sum_evaluations + 1);
+    }
+
+    #[test]
+    fn virtual_loss_across_threads() {
+        let ai = TestAI {
+            // Outweighs any difference of the mean values, so the effect doesn't depend on them
+            virtual_loss: 1_000,
+            ..TestAI::default()
+        };
+        let mut manager = Manager::new(Race::default(), ai.clone(), UCTPolicy::new(0.7), RaceEval);
+        // Few enough playouts that no root move is proven yet, those would be taken or avoided
+        // regardless of the virtual loss
+        manager.playout_n(100);
+        let tree = manager.tree();
+        assert!(tree
+            .root()
+            .node
+            .moves
+            .iter()
+            .all(|info| info.winners().is_none()));
+        let root_visits = |player: usize| {
+            tree.root_for(player)
+                .node
+                .moves
+                .iter()
+                .map(|info| (info.mv, info.visits()))
+                .collect::<Vec<_>>()
+        };
+        // The first thread descends into the most visited move and hasn't backpropagated yet.
+        // Like a playout, it applies the virtual loss in the tree of every player
+        let first = tree
+            .root()
+            .node
+            .moves
+            .iter()
+            .max_by_key(|info| info.visits())
+            .unwrap()
+            .mv;
+        let in_flight = (0..NUM_PLAYERS)
+            .map(|player| tree.root_for(player).node.moves.find(&first).unwrap().1)
+            .collect::<Vec<_>>();
+        let before = in_flight
+            .iter()
+            .map(|info| info.computed_stats())
+            .collect::<Vec<_>>();
+        for info in &in_flight {
+            info.stats.down(&ai);
+        }
+        for (info, before) in in_flight.iter().zip(&before) {
+            let during = info.computed_stats();
+            assert_eq!(during.visits, before.visits + 1);
+            assert!(during.mean_action_value < before.mean_action_value);
+        }
+        // The second thread sees the pending loss and takes the other move in every tree
+        let visits_before = (0..NUM_PLAYERS).map(root_visits).collect::<Vec<_>>();
+        assert!(tree.playout(&mut ThreadData::with_seed(1)));
+        for (player, visits_before) in visits_before.iter().enumerate() {
+            let visited = root_visits(player)
+                .into_iter()
+                .zip(visits_before)
+                .filter(|((_, after), (_, before))| after > before)
+                .map(|((mv, _), _)| mv)
+                .collect::<Vec<_>>();
+            assert_eq!(visited.len(), 1);
+            assert_ne!(visited[0], first);
+        }
+        // Backpropagating the first thread takes its virtual loss back
+        for (info, before) in in_flight.iter().zip(&before) {
+            info.stats.up(&ai, 1);
+            assert_eq!(info.sum_rewards(), before.sum_evaluations + 1);
+        }
+    }
+}
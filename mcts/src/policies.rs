@@ -27,7 +27,86 @@ impl<M: MCTS<Select = Self>> Policy<M> for UCBPolicy {
 }
 
 #[derive(Debug, Clone)]
-pub struct UCTPolicy(pub f64);
+pub struct UCTPolicy {
+    /// Weight of the exploration term
+    pub exploration: f64,
+    /// First play urgency, the value of children that haven't been visited yet
+    pub fpu: f64,
+    /// Weight of the progressive bias, the heuristic score of a move divided by its visits
+    pub progressive_bias: f64,
+    /// Bias of the all-moves-as-first values, blending them in with UCT-RAVE if set
+    pub rave_bias: Option<f64>,
+    /// Unvisited children are tried in the order of their prior instead of at random
+    pub prior_ordering: bool,
+}
+
+impl UCTPolicy {
+    /// Unvisited children are always tried first and no heuristic bias is applied.
+    #[must_use]
+    pub fn new(exploration: f64) -> Self {
+        Self {
+            exploration,
+            fpu: f64::INFINITY,
+            progressive_bias: 0.0,
+            rave_bias: None,
+            prior_ordering: false,
+        }
+    }
+
+    #[must_use]
+    pub fn with_fpu(self, fpu: f64) -> Self {
+        Self { fpu, ..self }
+    }
+
+    /// Adds `weight * heuristic / (visits + 1)` to the score of every child,
+    /// see [`MCTS::move_heuristic`].
+    #[must_use]
+    pub fn with_progressive_bias(self, weight: f64) -> Self {
+        Self {
+            progressive_bias: weight,
+            ..self
+        }
+    }
+
+    /// Blends the mean value of a move with its all-moves-as-first value. The weight of the
+    /// latter is `r / (n + r + 4 * bias^2 * n * r)` for `n` visits and `r` RAVE visits, so it
+    /// dominates while a move has few visits and fades out faster the larger `bias` is.
+    #[must_use]
+    pub fn with_rave(self, bias: f64) -> Self {
+        Self {
+            rave_bias: Some(bias),
+            ..self
+        }
+    }
+
+    /// Expands and visits the moves with the highest prior first, see [`Policy::prior`].
+    /// Without it they are tried at random, with an infinite first play urgency every
+    /// unvisited child is equally urgent.
+    #[must_use]
+    pub fn with_prior_ordering(self) -> Self {
+        Self {
+            prior_ordering: true,
+            ..self
+        }
+    }
+
+    /// Mean value of `mov` between -1 and 1, blended with its RAVE value if enabled.
+    #[allow(clippy::cast_precision_loss)]
+    fn value<M: MCTS>(&self, mov: &node::MoveInfo<M>, scale: f64) -> f64 {
+        let visits = mov.visits() as f64;
+        let mean = mov.sum_rewards() as f64 / visits / scale;
+        let rave_visits = mov.rave_visits() as f64;
+        match self.rave_bias {
+            Some(bias) if rave_visits > 0.0 => {
+                let rave_mean = mov.rave_rewards() as f64 / rave_visits / scale;
+                let beta =
+                    rave_visits / (visits + rave_visits + 4.0 * bias * bias * visits * rave_visits);
+                (1.0 - beta) * mean + beta * rave_mean
+            }
+            _ => mean,
+        }
+    }
+}
 
 #[allow(clippy::cast_precision_loss)]
 impl<M: MCTS<Select = Self>> Policy<M> for UCTPolicy {
@@ -42,27 +121,43 @@ impl<M: MCTS<Select = Self>> Policy<M> for UCTPolicy {
     where
         MoveIter: Iterator<Item = &'a node::MoveInfo<M>> + Clone,
     {
-        // let total_visits = moves.clone().map(|x| x.visits()).sum::<u64>();
-        // let adjusted_total = (total_visits + 1) as f64;
-        // let ln_adjusted_total = adjusted_total.ln();
-        handle
-            .thread_data()
-            .policy_data
+        let scale = handle.mcts().value_scale();
+        let policy_data = &mut handle.thread_data().policy_data;
+        if self.prior_ordering && self.fpu.is_infinite() {
+            let unvisited = policy_data.select_by_key(moves.clone(), |mov| {
+                if mov.visits() == 0 {
+                    self.prior(mov)
+                } else {
+                    f64::NEG_INFINITY
+                }
+            });
+            if let Some(choice) = unvisited.filter(|(_, mov)| mov.visits() == 0) {
+                return choice;
+            }
+        }
+        policy_data
             .select_by_key(moves, |mov| {
-                let sum_rewards = mov.sum_rewards();
                 let child_visits = mov.visits();
                 let available = mov.availability();
+                let bias = self.progressive_bias * mov.heuristic() / (child_visits + 1) as f64;
                 if child_visits == 0 {
-                    f64::INFINITY
+                    self.fpu + bias
                 } else {
                     let explore_term =
                         2.0 * ((available as f64 + 1.0).ln() / child_visits as f64).sqrt();
-                    let mean_action_value = sum_rewards as f64 / child_visits as f64;
-                    self.0 * explore_term + mean_action_value
+                    self.exploration * explore_term + self.value(mov, scale) + bias
                 }
             })
             .unwrap()
     }
+
+    fn needs_rave(&self) -> bool {
+        self.rave_bias.is_some()
+    }
+
+    fn orders_by_prior(&self) -> bool {
+        self.prior_ordering
+    }
 }
 
 #[derive(Clone)]
@@ -118,3 +213,47 @@ impl Default for PolicyRng {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{manager::Manager, node::MoveInfo, testing::*};
+
+    #[test]
+    fn prior_ordering() {
+        let policy = UCTPolicy::new(0.7).with_prior_ordering();
+        let ai = TestAI {
+            heuristic: true,
+            ..TestAI::default()
+        };
+        let mut manager = Manager::new(Race::default(), ai, policy, RaceEval);
+        // The preferred move is expanded first
+        manager.playout();
+        assert_eq!(manager.moves(), vec![2]);
+        // Once every move has been tried the statistics decide
+        manager.playout_n(100);
+        assert!(manager.stats().iter().all(|s| s.visits > 0));
+    }
+
+    #[test]
+    fn rave_statistics() {
+        for rave in [false, true] {
+            let policy = UCTPolicy::new(0.7);
+            let policy = if rave { policy.with_rave(0.1) } else { policy };
+            let mut manager = Manager::new(Race::default(), TestAI::default(), policy, RaceEval);
+            manager.playout_n(300);
+            let moves = &manager.tree().root().node.moves;
+            let rave_visits = moves.iter().map(MoveInfo::rave_visits).sum::<u64>();
+            if !rave {
+                assert_eq!(rave_visits, 0);
+                continue;
+            }
+            // The player moves again later in most playouts, which counts for the root moves too
+            assert!(rave_visits > moves.iter().map(MoveInfo::visits).sum());
+            for info in moves {
+                assert!(info.rave_visits() >= info.visits());
+                assert!(info.rave_rewards().unsigned_abs() <= info.rave_visits());
+            }
+        }
+    }
+}
@@ -1,35 +1,128 @@
 use std::{
-    ptr::null_mut,
-    sync::atomic::{AtomicUsize, Ordering},
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
 };
 
 use itertools::Itertools;
-use rand::{seq::IteratorRandom, thread_rng};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    seq::IteratorRandom,
+    Rng,
+};
 use smallvec::SmallVec;
 
 use crate::{
-    node::{MoveInfo, Node, NodeHandle},
-    Evaluator, GameState, Knowledge, Move, Player, Policy, StateEval, ThreadData, MCTS,
+    arena::{NodeArena, NodeId},
+    node::{Children, MoveInfo, Node, NodeHandle, Proven, Stats},
+    EvalView, Evaluator, GameState, Knowledge, Move, MoveList, NodeLimitStrategy, Player, Policy,
+    StateEval, ThreadData, MCTS, NUM_PLAYERS,
 };
 
+/// How much of one player tree survived advancing the search by a move.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TreeRetention {
+    pub nodes_before: usize,
+    /// Nodes below the new root, `0` if the move was never expanded in this tree
+    pub nodes_retained: usize,
+    /// Visits of the moves at the old root
+    pub visits_before: u64,
+    /// Visits of the moves at the new root
+    pub visits_retained: u64,
+}
+
+impl TreeRetention {
+    /// Share of the visits that are still useful for the next search.
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn visit_ratio(&self) -> f64 {
+        if self.visits_before == 0 {
+            0.0
+        } else {
+            self.visits_retained as f64 / self.visits_before as f64
+        }
+    }
+}
+
+/// Returned by [`Tree::advance_with_report`], one entry per player tree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AdvanceReport {
+    pub trees: [TreeRetention; NUM_PLAYERS],
+}
+
+/// Memory used by a [`Tree`], see [`Tree::memory_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Nodes of all player trees
+    pub nodes: usize,
+    /// Moves explored from these nodes
+    pub move_infos: usize,
+    /// Estimate of the allocated bytes, including memory kept for reuse by later searches
+    pub bytes: usize,
+}
+
+impl MemoryStats {
+    /// Average bytes per node, useful to pick a [`MCTS::node_limit`] for the available memory.
+    #[must_use]
+    pub fn bytes_per_node(&self) -> usize {
+        self.bytes / self.nodes.max(1)
+    }
+}
+
+impl std::fmt::Display for MemoryStats {
+    #[allow(clippy::cast_precision_loss)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} nodes, {} moves, {:.1} MiB",
+            self.nodes,
+            self.move_infos,
+            self.bytes as f64 / (1 << 20) as f64
+        )
+    }
+}
+
+/// The hidden information sampled for one playout and how the playout ended,
+/// recorded if [`MCTS::determinization_log_size`] is set.
+#[derive(Clone, Debug)]
+pub struct Determinization<M: MCTS> {
+    /// Root state with the sampled hidden information
+    pub state: M::State,
+    /// Move the playout chose at the root, `None` if the root has no legal moves
+    pub first_move: Option<Move<M>>,
+    /// Evaluation at the end of the playout for the player to move at the root
+    pub eval: i64,
+}
+
 pub struct Tree<M: MCTS> {
-    roots: [Node<M>; 4],
+    nodes: NodeArena<M>,
+    roots: [NodeId; NUM_PLAYERS],
     root_state: M::State,
-    knowledge: [Knowledge<M>; 4],
+    knowledge: [Knowledge<M>; NUM_PLAYERS],
     policy: M::Select,
     eval: M::Eval,
     manager: M,
 
     num_nodes: AtomicUsize,
     expansion_contention_events: AtomicUsize,
+    /// The last [`MCTS::determinization_log_size`] determinizations, oldest first
+    determinizations: Mutex<VecDeque<Determinization<M>>>,
 }
 
 impl<M: MCTS> Tree<M> {
     #[must_use]
     pub fn new(state: M::State, manager: M, policy: M::Select, eval: M::Eval) -> Self {
         let knowledge = core::array::from_fn(|i| state.knowledge_from_state(Player::<M>::from(i)));
+        let nodes = NodeArena::new();
+        let roots = core::array::from_fn(|_| nodes.alloc(Node::new(&eval, &state, None)));
         Self {
-            roots: core::array::from_fn(|_| Node::new(&eval, &state, None)),
+            nodes,
+            roots,
             root_state: state,
             knowledge,
             policy,
@@ -37,10 +130,44 @@ impl<M: MCTS> Tree<M> {
             manager,
             num_nodes: 1.into(),
             expansion_contention_events: 0.into(),
+            determinizations: Mutex::default(),
         }
     }
 
     pub fn advance(&mut self, mv: &Move<M>) {
+        let _ = self.advance_roots(mv);
+    }
+
+    /// Like [`Self::advance`], also reports how much of every player tree was kept.
+    pub fn advance_with_report(&mut self, mv: &Move<M>) -> AdvanceReport {
+        let before = self
+            .roots
+            .map(|root| (self.subtree_size(root), self.get(root).visits()));
+        let kept = self.advance_roots(mv);
+        AdvanceReport {
+            trees: core::array::from_fn(|idx| {
+                let root = self.roots[idx];
+                let (nodes_before, visits_before) = before[idx];
+                TreeRetention {
+                    nodes_before,
+                    visits_before,
+                    nodes_retained: if kept[idx] {
+                        self.subtree_size(root)
+                    } else {
+                        0
+                    },
+                    visits_retained: self.get(root).visits(),
+                }
+            }),
+        }
+    }
+
+    /// Moves the roots to the children reached by `mv` and frees the rest of the trees.
+    /// After a [`GameState::new_deal`] the trees start over instead, as their children were
+    /// searched with hands sampled from the old deal, and the knowledge is refreshed from the
+    /// new state. Returns for every player tree whether a child existed and was kept.
+    fn advance_roots(&mut self, mv: &Move<M>) -> [bool; NUM_PLAYERS] {
+        let mv = &self.root_state.canonical_move(mv);
         // advance state
         let mut new_state = self.root_state.clone();
         for k in &mut self.knowledge {
@@ -48,29 +175,175 @@ impl<M: MCTS> Tree<M> {
         }
         new_state.make_move(mv);
         self.root_state = new_state;
+        self.determinizations.get_mut().unwrap().clear();
+        let new_deal = self.root_state.new_deal();
+        if new_deal {
+            for (idx, k) in self.knowledge.iter_mut().enumerate() {
+                self.root_state
+                    .update_knowledge_for_deal(Player::<M>::from(idx), k);
+            }
+        }
 
-        for root in &mut self.roots {
-            let child_idx = {
-                let children = root.moves.read().unwrap();
-                // Find the child corresponding to the move we played
-                let idx = children
-                    .iter()
-                    .enumerate()
-                    .find(|(_, x)| x.mv == *mv)
-                    .map(|(idx, _)| idx)
-                    .unwrap();
-                idx
-            };
-            let new_root = {
-                let mut moves = root.moves.write().unwrap();
-                moves.remove(child_idx)
-            };
-            let new_root_ptr = new_root.child.load(Ordering::SeqCst);
-            let old_root = std::mem::replace(root, unsafe { *Box::from_raw(new_root_ptr) });
-            old_root.moves.write().unwrap().clear();
-            std::mem::forget(new_root);
+        let mut kept = [false; NUM_PLAYERS];
+        for (idx, kept) in kept.iter_mut().enumerate() {
+            let child = (!new_deal)
+                .then(|| self.get(self.roots[idx]).moves.iter().find(|x| x.mv == *mv))
+                .flatten()
+                .and_then(MoveInfo::child);
+            *kept = child.is_some();
+            // The move might not have been expanded in this tree, e.g. after merging trees
+            self.roots[idx] = child.unwrap_or_else(|| {
+                self.nodes
+                    .alloc(Node::new(&self.eval, &self.root_state, None))
+            });
+        }
+        // Recycle the nodes of all subtrees that can't be reached anymore
+        let alive = self.nodes.retain_reachable(&self.roots);
+        self.num_nodes.store(alive, Ordering::SeqCst);
+        kept
+    }
+
+    /// Number of nodes in the subtree below `id`, including the node itself.
+    fn subtree_size(&self, id: NodeId) -> usize {
+        let mut size = 0;
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            size += 1;
+            stack.extend(self.get(id).moves.iter().filter_map(MoveInfo::child));
+        }
+        size
+    }
+
+    /// Frees the least visited subtrees until at most `max_nodes` nodes are left and returns the
+    /// number of freed nodes. Moves leading to a freed subtree keep their statistics, their
+    /// node gets created again once a playout passes through them.
+    pub fn prune(&mut self, max_nodes: usize) -> usize {
+        let before = self.num_nodes.load(Ordering::SeqCst);
+        // A node survives if all moves on the path to it have more visits than the threshold,
+        // so the smallest visit count on that path decides when it gets pruned
+        let mut path_visits = HashMap::new();
+        let mut stack = self.roots.map(|root| (root, u64::MAX)).to_vec();
+        while let Some((id, visits)) = stack.pop() {
+            path_visits.insert(id, visits);
+            let node = self.get(id);
+            for info in &node.moves {
+                if let Some(child) = info.child() {
+                    stack.push((child, visits.min(info.visits())));
+                }
+            }
         }
+        if path_visits.len() <= max_nodes {
+            return 0;
+        }
+        let mut sorted = path_visits.values().copied().collect_vec();
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+        let threshold = sorted[max_nodes];
+        if threshold == u64::MAX {
+            return 0;
+        }
+        for (id, visits) in &path_visits {
+            if *visits <= threshold {
+                continue;
+            }
+            let node = self.nodes.get_mut(*id).expect("Node has been freed");
+            for info in node.moves.iter_mut() {
+                if info.visits() <= threshold {
+                    info.child.take();
+                }
+            }
+        }
+        let alive = self.nodes.retain_reachable(&self.roots);
+        self.num_nodes.store(alive, Ordering::SeqCst);
+        before.saturating_sub(alive)
     }
+
+    /// Called once a playout hit the node limit. Prunes the tree if the
+    /// [`NodeLimitStrategy`] allows it and returns whether playouts can continue.
+    pub fn make_room(&mut self) -> bool {
+        match self.manager.node_limit_strategy() {
+            NodeLimitStrategy::Stop => false,
+            NodeLimitStrategy::PruneLeastVisited => self.prune(self.manager.node_limit() / 2) > 0,
+        }
+    }
+
+    /// Throws away the search results while keeping the memory of the nodes for the next search.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.roots = core::array::from_fn(|_| {
+            self.nodes
+                .alloc(Node::new(&self.eval, &self.root_state, None))
+        });
+        self.num_nodes.store(1, Ordering::SeqCst);
+        self.expansion_contention_events.store(0, Ordering::SeqCst);
+        self.determinizations.get_mut().unwrap().clear();
+    }
+
+    /// Returns a new tree searching from the same root state with the same knowledge.
+    #[must_use]
+    pub fn fork(&self) -> Self {
+        let nodes = NodeArena::new();
+        let roots =
+            core::array::from_fn(|_| nodes.alloc(Node::new(&self.eval, &self.root_state, None)));
+        Self {
+            nodes,
+            roots,
+            root_state: self.root_state.clone(),
+            knowledge: self.knowledge.clone(),
+            policy: self.policy.clone(),
+            eval: self.eval.clone(),
+            manager: self.manager.clone(),
+            num_nodes: 1.into(),
+            expansion_contention_events: 0.into(),
+            determinizations: Mutex::default(),
+        }
+    }
+
+    /// Adds the statistics of the root moves of `other` to the roots of this tree.
+    /// Moves that only exist in `other` are added without children.
+    pub fn merge_roots(&self, other: &Self) {
+        for (&root, &other_root) in self.roots.iter().zip(&other.roots) {
+            let (root, other_root) = (self.get(root), other.get(other_root));
+            root.stats.add(&other_root.stats);
+            for other_info in &other_root.moves {
+                let index = root.moves.push_if_absent(&other_info.mv, || {
+                    MoveInfo::with_heuristic(other_info.mv.clone(), other_info.heuristic())
+                });
+                let info = &root.moves[index];
+                info.stats.add(&other_info.stats);
+                info.rave.add(&other_info.rave);
+                if let Some(winners) = other_info.winners() {
+                    info.prove(winners);
+                }
+            }
+        }
+        let other_log = other.determinizations.lock().unwrap().clone();
+        for determinization in other_log {
+            self.log_determinization(determinization);
+        }
+    }
+
+    fn log_determinization(&self, determinization: Determinization<M>) {
+        let size = self.manager.determinization_log_size();
+        if size == 0 {
+            return;
+        }
+        let mut log = self.determinizations.lock().unwrap();
+        if log.len() >= size {
+            log.pop_front();
+        }
+        log.push_back(determinization);
+    }
+
+    /// Returns up to `n` of the most recently logged determinizations, oldest first.
+    #[must_use]
+    pub fn last_determinizations(&self, n: usize) -> Vec<Determinization<M>> {
+        let log = self.determinizations.lock().unwrap();
+        log.iter()
+            .skip(log.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+
     #[allow(clippy::too_many_lines)]
     #[must_use]
     pub fn playout(&self, tld: &mut ThreadData<M>) -> bool {
@@ -83,13 +356,27 @@ impl<M: MCTS> Tree<M> {
         state.randomize_determination(
             state.current_player(),
             &self.knowledge[state.current_player().into()],
+            &mut tld.rng,
         );
+        let sampled = (self.manager.determinization_log_size() > 0).then(|| state.clone());
+        // What the player at the root knows along the playout, for evaluations restricted to it
+        let observer = state.current_player();
+        let mut observer_knowledge = (self.manager.eval_view() == EvalView::Observer)
+            .then(|| self.knowledge[state.current_player().into()].clone());
 
-        let mut path_indices: [SmallVec<usize, 64>; 4] = [const { SmallVec::new() }; 4];
-        let mut node_path: [SmallVec<(&Node<M>, &Node<M>), 64>; 4] = [const { SmallVec::new() }; 4];
+        let mut path_indices: [SmallVec<usize, 64>; NUM_PLAYERS] =
+            [const { SmallVec::new() }; NUM_PLAYERS];
+        let mut node_path: [SmallVec<(&Node<M>, &Node<M>), 64>; NUM_PLAYERS] =
+            [const { SmallVec::new() }; NUM_PLAYERS];
         let mut players: SmallVec<Player<M>, 64> = SmallVec::new();
-        let mut nodes: [&Node<M>; 4] = core::array::from_fn(|idx| &self.roots[idx]);
-        let mut knowledges: [_; 4] =
+        // Index of the player to move and the legal moves at every step, used to solve nodes
+        let mut steps: SmallVec<(usize, MoveList<M>), 64> = SmallVec::new();
+        // Moves chosen at every step, used for the all-moves-as-first statistics
+        let mut played: SmallVec<Move<M>, 64> = SmallVec::new();
+        let mut terminal = false;
+        let mut nodes: [&Node<M>; NUM_PLAYERS] =
+            core::array::from_fn(|idx| self.get(self.roots[idx]));
+        let mut knowledges: [_; NUM_PLAYERS] =
             core::array::from_fn(|i| state.new_knowledge(Player::<M>::from(i)));
 
         // Select
@@ -102,35 +389,61 @@ impl<M: MCTS> Tree<M> {
             let to_move_idx: usize = to_move.into();
             let target_node: &Node<M> = nodes[to_move_idx];
 
-            let no_legal_moves = legal_moves.clone().into_iter().count() == 0;
-            if no_legal_moves {
+            let num_legal = legal_moves.clone().into_iter().count();
+            if num_legal == 0 {
                 break;
             }
 
             // All moves that are legal now but have never been explored yet
-            let untried = {
-                let node_moves = target_node.moves.read().unwrap();
-                legal_moves
+            let (untried, visits) = {
+                let node_moves = &target_node.moves;
+                let untried = legal_moves
                     .clone()
                     .into_iter()
                     .filter(|lmv| node_moves.is_empty() || !node_moves.iter().any(|c| c.mv == *lmv))
-                    .collect_vec()
+                    .collect_vec();
+                (
+                    untried,
+                    node_moves.iter().map(MoveInfo::visits).sum::<u64>(),
+                )
             };
-            let any_untried = !untried.is_empty();
-            if any_untried {
-                let choice = untried.into_iter().choose(&mut thread_rng()).unwrap();
-                target_node
-                    .moves
-                    .write()
-                    .unwrap()
-                    .push(MoveInfo::new(choice));
-            }
+            // Only expand if we haven't reached the amount of children we want to consider yet.
+            // We always need to expand if none of the legal moves have been expanded before
+            let num_expanded = num_legal - untried.len();
+            let any_untried = !untried.is_empty()
+                && (num_expanded == 0
+                    || num_expanded < self.manager.max_children_considered(visits));
+            let expanded = any_untried.then(|| {
+                let with_heuristic = |mv: Move<M>| {
+                    let heuristic = self.manager.move_heuristic(&state, &mv);
+                    MoveInfo::with_heuristic(mv, heuristic)
+                };
+                let info = if self.policy.orders_by_prior() {
+                    let mut infos = untried.into_iter().map(with_heuristic).collect_vec();
+                    let priors = infos
+                        .iter()
+                        .map(|info| self.policy.prior(info))
+                        .collect_vec();
+                    let best = priors.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                    let idx = (0..infos.len())
+                        .filter(|idx| priors[*idx] >= best)
+                        .choose(&mut tld.rng)
+                        .unwrap_or(0);
+                    infos.swap_remove(idx)
+                } else {
+                    with_heuristic(untried.into_iter().choose(&mut tld.rng).unwrap())
+                };
+                let choice = info.mv.clone();
+                // Another thread might have expanded the same move in the meantime
+                target_node.moves.push_if_absent(&choice, || info);
+                choice
+            });
 
             // Select
             let choice_mv = {
-                let node_moves = target_node.moves.read().unwrap();
-                let choice = if any_untried {
-                    node_moves.last().unwrap()
+                let node_moves = &target_node.moves;
+                let choice = if let Some(expanded) = &expanded {
+                    node_moves.iter().find(|c| c.mv == *expanded).unwrap()
                 } else {
                     // Get the children corresponding to all legal moves
                     let moves = {
@@ -144,39 +457,54 @@ impl<M: MCTS> Tree<M> {
                     // This means all legal moves have been expanded once already
                     debug_assert!(!moves.is_empty());
 
-                    self.policy
-                        .choose(moves.iter().copied(), self.make_handle(target_node, tld))
-                        .1
+                    // Always play a proven win and never a proven loss unless there is no other choice
+                    if let Some(win) = moves
+                        .iter()
+                        .find(|m| m.proven(to_move_idx) == Some(Proven::Win))
+                    {
+                        win
+                    } else {
+                        let unproven = moves
+                            .iter()
+                            .copied()
+                            .filter(|m| m.proven(to_move_idx) != Some(Proven::Loss))
+                            .collect_vec();
+                        let candidates = if unproven.is_empty() {
+                            &moves
+                        } else {
+                            &unproven
+                        };
+                        self.policy
+                            .choose(
+                                candidates.iter().copied(),
+                                self.make_handle(target_node, tld),
+                            )
+                            .1
+                    }
                 };
-                choice.stats.down(&self.manager);
                 choice.mv.clone()
             };
 
             for node in nodes {
-                if !node
-                    .moves
-                    .read()
-                    .unwrap()
-                    .iter()
-                    .any(|mv| choice_mv == mv.mv)
-                {
-                    node.moves
-                        .write()
-                        .unwrap()
-                        .push(MoveInfo::new(choice_mv.clone()));
-                }
+                node.moves.push_if_absent(&choice_mv, || {
+                    let heuristic = self.manager.move_heuristic(&state, &choice_mv);
+                    MoveInfo::with_heuristic(choice_mv.clone(), heuristic)
+                });
             }
 
             players.push(state.current_player());
-            for k in &mut knowledges {
+            played.push(choice_mv.clone());
+            steps.push((to_move_idx, legal_moves.clone()));
+            for k in knowledges.iter_mut().chain(&mut observer_knowledge) {
                 state.update_knowledge(&choice_mv, k);
             }
             state.make_move(&choice_mv);
+            let winners = Self::winners(&state);
             let new_nodes = core::array::from_fn(|idx| {
                 let node = nodes[idx];
                 // Increment availability count for each legal move we have in the current determinization
                 {
-                    let node_moves = node.moves.read().unwrap();
+                    let node_moves = &node.moves;
                     legal_moves
                         .clone()
                         .into_iter()
@@ -185,41 +513,152 @@ impl<M: MCTS> Tree<M> {
                 }
                 // Expand
                 let (new_node, _, choice_idx) = self.descend(&state, &choice_mv, node, tld);
+                {
+                    let node_moves = &node.moves;
+                    // Other threads see the virtual loss on the edge until this playout is
+                    // backpropagated, which steers them towards different moves
+                    node_moves[choice_idx].stats.down(&self.manager);
+                    if let Some(winners) = winners {
+                        node_moves[choice_idx].prove(winners);
+                    }
+                }
                 node_path[idx].push((node, new_node));
                 path_indices[idx].push(choice_idx);
                 new_node.stats.down(&self.manager);
                 new_node
             });
             nodes = new_nodes;
-            if any_untried {
+            terminal = winners.is_some();
+            if any_untried || terminal {
                 break;
             }
         }
 
         // Rollout
-        let rollout_eval = Self::rollout(&mut state, &self.eval, Some(4));
+        let rollout_eval = if terminal {
+            Self::evaluate(&self.eval, &state, &observer, observer_knowledge.as_ref())
+        } else {
+            Self::rollout(
+                &mut state,
+                &self.eval,
+                self.manager.rollout_length(),
+                &mut tld.rng,
+                &observer,
+                observer_knowledge.as_mut(),
+            )
+        };
+        if let Some(sampled) = sampled {
+            let eval = self
+                .eval
+                .make_relative(&rollout_eval, &self.root_state.current_player());
+            self.log_determinization(Determinization {
+                state: sampled,
+                first_move: played.first().cloned(),
+                eval,
+            });
+        }
         // Backprop
         for (idx, _) in nodes.iter().enumerate() {
-            self.backpropagation(&path_indices[idx], &node_path[idx], &players, &rollout_eval);
+            self.backpropagation(
+                &path_indices[idx],
+                &node_path[idx],
+                &players,
+                &steps,
+                &played,
+                &rollout_eval,
+            );
         }
         true
     }
 
+    /// Returns the players that won as a bitmask indexed by player, `None` if the game isn't over.
+    fn winners(state: &M::State) -> Option<u8> {
+        (0..NUM_PLAYERS).try_fold(0, |winners, idx| {
+            state
+                .outcome(&Player::<M>::from(idx))
+                .map(|won| winners | u8::from(won) << idx)
+        })
+    }
+
+    /// MCTS-Solver: the move leading to `node` is proven if the player to move there can play a
+    /// proven win or every legal move is a proven loss. Returns the winners of the proven result.
+    ///
+    /// Legal moves depend on the sampled determinization,
+    /// so proofs assume the hidden information of this playout.
+    fn solve(node: &Node<M>, to_move: usize, legal_moves: &MoveList<M>) -> Option<u8> {
+        let moves = &node.moves;
+        let mut loss = None;
+        let mut all_lost = true;
+        for mv in legal_moves.clone() {
+            match moves
+                .iter()
+                .find(|info| info.mv == mv)
+                .and_then(MoveInfo::winners)
+            {
+                Some(winners) if winners & 1 << to_move != 0 => return Some(winners),
+                Some(winners) => loss = Some(winners),
+                None => all_lost = false,
+            }
+        }
+        loss.filter(|_| all_lost)
+    }
+
     fn backpropagation(
         &self,
         path: &[usize],
         nodes: &[(&Node<M>, &Node<M>)],
         players: &[Player<M>],
+        steps: &[(usize, MoveList<M>)],
+        played: &[Move<M>],
         eval: &StateEval<M>,
     ) {
-        for ((move_info, player), (parent, child)) in
-            path.iter().zip(players.iter()).zip(nodes.iter()).rev()
+        let rave = self.policy.needs_rave();
+        for (step, ((move_info, player), (parent, child))) in path
+            .iter()
+            .zip(players.iter())
+            .zip(nodes.iter())
+            .enumerate()
+            .rev()
         {
             let eval_value = self.eval.make_relative(eval, player);
             child.stats.up(&self.manager, eval_value);
-            parent.moves.read().unwrap()[*move_info]
-                .stats
-                .replace(&child.stats);
+            let parent_moves = &parent.moves;
+            let info = &parent_moves[*move_info];
+            // Reverts the virtual loss applied while selecting
+            info.stats.up(&self.manager, eval_value);
+            if rave {
+                Self::update_rave(parent_moves, step, players, played, eval_value);
+            }
+            // Proofs of deeper moves were already propagated, so the child can be solved now
+            if info.winners().is_none() {
+                if let Some((to_move, legal_moves)) = steps.get(step + 1) {
+                    if let Some(winners) = Self::solve(child, *to_move, legal_moves) {
+                        info.prove(winners);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Credits every move the player to move at `step` played from there on to the
+    /// all-moves-as-first statistics of the matching moves of the node, once per playout.
+    fn update_rave(
+        moves: &Children<M>,
+        step: usize,
+        players: &[Player<M>],
+        played: &[Move<M>],
+        eval: i64,
+    ) {
+        let to_move = &players[step];
+        for (later, mv) in played.iter().enumerate().skip(step) {
+            let repeated =
+                (step..later).any(|prev| players[prev] == *to_move && played[prev] == *mv);
+            if players[later] != *to_move || repeated {
+                continue;
+            }
+            if let Some(info) = moves.iter().find(|info| info.mv == *mv) {
+                info.rave.record(eval);
+            }
         }
     }
 
@@ -228,14 +667,34 @@ impl<M: MCTS> Tree<M> {
         state: &mut M::State,
         eval: &M::Eval,
         rollout_length: Option<usize>,
+        rng: &mut impl Rng,
+        observer: &Player<M>,
+        mut observer_knowledge: Option<&mut Knowledge<M>>,
     ) -> StateEval<M> {
         let rollout_length = rollout_length.unwrap_or(usize::MAX);
         (0..rollout_length).for_each(|_| {
-            if let Some(mv) = state.legal_moves().into_iter().choose(&mut thread_rng()) {
+            if let Some(mv) = state.random_move(rng) {
+                if let Some(knowledge) = observer_knowledge.as_deref_mut() {
+                    state.update_knowledge(&mv, knowledge);
+                }
                 state.make_move(&mv);
             }
         });
-        eval.eval_new(state, None)
+        Self::evaluate(eval, state, observer, observer_knowledge.as_deref())
+    }
+
+    /// Evaluates the end of a playout. With [`EvalView::Observer`] the evaluation is restricted
+    /// to `observer_knowledge`, what `observer` knows.
+    fn evaluate(
+        eval: &M::Eval,
+        state: &M::State,
+        observer: &Player<M>,
+        observer_knowledge: Option<&Knowledge<M>>,
+    ) -> StateEval<M> {
+        match observer_knowledge {
+            Some(knowledge) => eval.eval_observed(state, observer, knowledge),
+            None => eval.eval_new(state, None),
+        }
     }
 
     #[must_use]
@@ -247,36 +706,38 @@ impl<M: MCTS> Tree<M> {
         current_node: &'b Node<M>,
         tld: &'b mut ThreadData<M>,
     ) -> (&'a Node<M>, bool, usize) {
-        let read = &current_node.moves.read().unwrap();
+        let read = &current_node.moves;
         let (choice, idx) = read
             .iter()
             .enumerate()
             .find_map(|(idx, mv_info)| (mv_info.mv == *choice).then_some((mv_info, idx)))
             .expect("Should exist");
-        let child = choice.child.load(Ordering::Relaxed).cast_const();
-        if !child.is_null() {
-            return unsafe { (&*child, false, idx) };
+        if let Some(child) = choice.child() {
+            return (self.get(child), false, idx);
         }
 
         let created = Node::new(&self.eval, state, Some(self.make_handle(current_node, tld)));
-        let created = Box::into_raw(Box::new(created));
-        let other_child = choice.child.compare_exchange(
-            null_mut(),
-            created,
-            Ordering::Relaxed,
-            Ordering::Relaxed,
-        );
-        if let Err(other_child) = other_child {
+        let created = self.nodes.alloc(created);
+        if let Err(other_child) = choice.child.set_if_none(created) {
+            // The node we created is unreachable and gets recycled on the next advance
             self.expansion_contention_events
                 .fetch_add(1, Ordering::Relaxed);
-            unsafe {
-                drop(Box::from_raw(created));
-                return (&*other_child, false, idx);
-            }
+            return (self.get(other_child), false, idx);
         }
 
         self.num_nodes.fetch_add(1, Ordering::Relaxed);
-        unsafe { (&*created, true, idx) }
+        (self.get(created), true, idx)
+    }
+
+    /// Returns the node of a valid id, nodes reachable from a root are never freed while searching.
+    fn get(&self, id: NodeId) -> &Node<M> {
+        self.nodes.get(id).expect("Node has been freed")
+    }
+
+    /// Returns the node with the given id or `None` if it has been recycled.
+    #[must_use]
+    pub fn node(&self, id: NodeId) -> Option<NodeHandle<'_, M>> {
+        self.nodes.get(id).map(|node| NodeHandle { node })
     }
 
     #[must_use]
@@ -292,42 +753,59 @@ impl<M: MCTS> Tree<M> {
         }
     }
 
+    /// Legal moves of `node` with the best proven result for `player`, all legal moves if none is proven.
+    fn best_proven<'a>(
+        node: &'a Node<M>,
+        player: usize,
+        legal: &MoveList<M>,
+    ) -> Vec<&'a MoveInfo<M>> {
+        let rank = |mv: &MoveInfo<M>| match mv.proven(player) {
+            Some(Proven::Win) => 2,
+            None => 1,
+            Some(Proven::Loss) => 0,
+        };
+        let candidates = node
+            .moves
+            .iter()
+            .filter(|mv| legal.clone().into_iter().any(|lmv| mv.mv == lmv))
+            .collect::<Vec<_>>();
+        let best_rank = candidates.iter().map(|mv| rank(mv)).max();
+        candidates
+            .into_iter()
+            .filter(|mv| Some(rank(mv)) == best_rank)
+            .collect()
+    }
+
     #[must_use]
     pub fn pv(&self, num_moves: usize) -> Vec<Move<M>> {
         let mut res = Vec::new();
         let mut curr_player: usize = self.root_state.current_player().into();
-        let mut curr: [&Node<M>; 4] = core::array::from_fn(|i| &self.roots[i]);
+        let mut curr: [&Node<M>; NUM_PLAYERS] = core::array::from_fn(|i| self.get(self.roots[i]));
         let mut curr_state = self.root_state.clone();
 
         while curr_state.legal_moves().into_iter().count() > 0 && res.len() < num_moves {
-            if let Some(choice) = curr[curr_player]
-                .moves
-                .read()
-                .unwrap()
-                .iter()
-                .filter_map(|mv| {
-                    curr_state
-                        .legal_moves()
-                        .into_iter()
-                        .any(|lmv| mv.mv == lmv)
-                        .then_some((mv.mv.clone(), mv.visits()))
-                })
-                .max_by_key(|(_, visits)| *visits)
-                .map(|(mv, _)| mv)
-            {
+            let candidates =
+                Self::best_proven(curr[curr_player], curr_player, &curr_state.legal_moves());
+            if let Some(choice) = (!candidates.is_empty()).then(|| {
+                self.manager
+                    .select_child_after_search(&candidates)
+                    .mv
+                    .clone()
+            }) {
                 res.push(choice.clone());
                 curr_state.make_move(&choice);
                 curr_player = curr_state.current_player().into();
-                let new_nodes: [Option<&Node<M>>; 4] = core::array::from_fn(|idx| {
+                let new_nodes: [Option<&Node<M>>; NUM_PLAYERS] = core::array::from_fn(|idx| {
                     let node = curr[idx];
-                    let read = &node.moves.read().unwrap();
-                    let child = read.iter().find(|m| m.mv == choice);
-                    let ptr = child.map(|child| child.child.load(Ordering::Relaxed));
-                    let next = ptr.map(|ptr| (!ptr.is_null()).then_some(unsafe { &*ptr }));
-                    next.flatten()
+                    let read = &node.moves;
+                    read.iter()
+                        .find(|m| m.mv == choice)
+                        .and_then(MoveInfo::child)
+                        .map(|child| self.get(child))
                 });
                 if new_nodes.iter().all(std::option::Option::is_some) {
-                    let new: [&Node<M>; 4] = core::array::from_fn(|idx| new_nodes[idx].unwrap());
+                    let new: [&Node<M>; NUM_PLAYERS] =
+                        core::array::from_fn(|idx| new_nodes[idx].unwrap());
                     curr = new;
                 } else {
                     break;
@@ -339,9 +817,37 @@ impl<M: MCTS> Tree<M> {
         res
     }
 
+    /// Picks a legal root move with a probability proportional to `visits^(1 / temperature)`.
+    /// Higher temperatures play weaker moves more often, a temperature of `0` or less always
+    /// plays the first move of [`Self::pv`]. Like there, moves with a worse proven result than
+    /// another move are never picked and neither are unvisited moves.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn sample_move<R: Rng>(&self, temperature: f64, rng: &mut R) -> Option<Move<M>> {
+        let player = self.root_state.current_player().into();
+        let candidates =
+            Self::best_proven(self.root().node, player, &self.root_state.legal_moves())
+                .into_iter()
+                .filter(|info| info.visits() > 0)
+                .collect_vec();
+        let most_visits = candidates.iter().map(|info| info.visits()).max();
+        if temperature <= 0.0 || most_visits.is_none() {
+            return self.pv(1).pop();
+        }
+        // Relative to the most visited move, so large visit counts don't overflow
+        let most_visits = most_visits.unwrap_or(1) as f64;
+        let weights = candidates
+            .iter()
+            .map(|info| (info.visits() as f64 / most_visits).powf(temperature.recip()));
+        match WeightedIndex::new(weights) {
+            Ok(dist) => Some(candidates[dist.sample(rng)].mv.clone()),
+            // The most visited move always has a weight of 1, so only a NaN temperature fails
+            Err(_) => self.pv(1).pop(),
+        }
+    }
+
     pub fn display_moves(&self) {
         let player_idx = self.root_state.current_player().into();
-        let inner = self.roots[player_idx].moves.read().unwrap();
+        let inner = &self.get(self.roots[player_idx]).moves;
         let mut moves: Vec<&MoveInfo<M>> = inner.iter().collect();
         moves.sort_by_key(|x| x.visits());
         for mv in moves {
@@ -351,7 +857,7 @@ impl<M: MCTS> Tree<M> {
 
     pub fn display_legal_moves(&self) {
         let player_idx = self.root_state.current_player().into();
-        let inner = self.roots[player_idx].moves.read().unwrap();
+        let inner = &self.get(self.roots[player_idx]).moves;
         let legal = self.root_state.legal_moves();
 
         let mut moves: Vec<&MoveInfo<M>> = inner
@@ -366,8 +872,33 @@ impl<M: MCTS> Tree<M> {
         println!("---------------------------------------------------------");
     }
 
+    /// Counts the nodes and moves of all player trees and estimates the memory they use.
+    /// Moves are counted with their size in the tree, memory they allocate on their own
+    /// isn't included. Can be called while searching.
+    #[must_use]
+    pub fn memory_stats(&self) -> MemoryStats {
+        let mut stats = MemoryStats {
+            bytes: std::mem::size_of::<Self>() + self.nodes.allocated_bytes(),
+            ..MemoryStats::default()
+        };
+        let mut stack = self.roots.to_vec();
+        while let Some(id) = stack.pop() {
+            let Some(node) = self.nodes.get(id) else {
+                continue;
+            };
+            stats.nodes += 1;
+            stats.move_infos += node.moves.len();
+            stats.bytes += node.moves.allocated_bytes();
+            stack.extend(node.moves.iter().filter_map(MoveInfo::child));
+        }
+        let determinizations = self.determinizations.lock().unwrap();
+        stats.bytes += determinizations.capacity() * std::mem::size_of::<Determinization<M>>();
+        stats
+    }
+
     pub fn print_stats(&self) {
         println!("{} nodes", self.num_nodes.load(Ordering::Relaxed));
+        println!("{}", self.memory_stats());
         println!(
             "{} e/c events",
             self.expansion_contention_events.load(Ordering::Relaxed)
@@ -394,12 +925,31 @@ impl<M: MCTS> Tree<M> {
     }
 
     #[must_use]
-    pub fn root(&self) -> NodeHandle<M> {
+    pub fn root(&self) -> NodeHandle<'_, M> {
+        self.root_for(self.root_state.current_player())
+    }
+
+    /// Returns the root of the tree searched from the view of `player`. Every player has
+    /// their own tree whose nodes are the positions as `player` can tell them apart, so the
+    /// moves of the others are only split by what `player` can observe.
+    #[must_use]
+    pub fn root_for(&self, player: Player<M>) -> NodeHandle<'_, M> {
         NodeHandle {
-            node: &self.roots[self.root_state.current_player().into()],
+            node: self.get(self.roots[player.into()]),
         }
     }
 
+    /// What each player knows about the hidden information, indexed by player.
+    pub fn knowledge(&self) -> &[Knowledge<M>; NUM_PLAYERS] {
+        &self.knowledge
+    }
+
+    /// Replaces the knowledge of every player, e.g. with one saved by an earlier session.
+    /// Determinizations of later playouts follow it, the search results so far are kept.
+    pub fn set_knowledge(&mut self, knowledge: [Knowledge<M>; NUM_PLAYERS]) {
+        self.knowledge = knowledge;
+    }
+
     pub fn print_knowledge(&self) {
         for k in &self.knowledge {
             println!("{k:?}");
@@ -407,6 +957,184 @@ impl<M: MCTS> Tree<M> {
     }
 }
 
+/// Start of every file written by [`Tree::save`], followed by the version of the format.
+const SAVE_MAGIC: &[u8; 4] = b"MCTS";
+const SAVE_VERSION: u8 = 1;
+/// Child index of moves that were never expanded.
+const NO_CHILD: u32 = u32::MAX;
+
+/// Saving and resuming a search, for moves that can be stored as a `u64`.
+impl<M: MCTS> Tree<M>
+where
+    Move<M>: Into<u64> + TryFrom<u64>,
+    StateEval<M>: Clone,
+{
+    /// Saves the statistics of all nodes to `path`. The root state isn't saved,
+    /// [`Self::load`] has to be called on a tree with the same root state to resume the search.
+    /// # Errors
+    /// If the file can't be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+
+    /// Replaces the search results with the ones saved by [`Self::save`].
+    /// # Errors
+    /// If the file can't be read or doesn't contain a saved tree, the tree is left unchanged.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the trees in the format read by [`Self::read_from`], all numbers little endian:
+    /// - [`SAVE_MAGIC`] and [`SAVE_VERSION`]
+    /// - every node starting with the roots of every player, followed by the nodes below them in breadth-first order.
+    ///   A node is its [`Stats`] and the number of moves as `u32` followed by the moves.
+    /// - every move: the move as `u64`, the heuristic as `f64`, the proven winners as `u8`,
+    ///   [`MoveInfo::stats`], [`MoveInfo::rave`] and the index of its child as `u32`.
+    ///   Children are numbered in the order they are written, [`NO_CHILD`] if there is none.
+    ///
+    /// [`Stats`] are the visits, the availability count and the sum of evaluations as `u64`, `u64` and `i64`.
+    /// # Errors
+    /// If writing to `out` fails.
+    pub fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_all(SAVE_MAGIC)?;
+        out.write_all(&[SAVE_VERSION])?;
+        let mut queue = self.roots.to_vec();
+        let mut idx = 0;
+        while let Some(&id) = queue.get(idx) {
+            idx += 1;
+            let node = self.get(id);
+            write_stats(&mut out, &node.stats)?;
+            let moves = &node.moves;
+            out.write_all(&u32::try_from(moves.len()).unwrap().to_le_bytes())?;
+            for info in moves {
+                out.write_all(&info.mv.clone().into().to_le_bytes())?;
+                out.write_all(&info.heuristic().to_le_bytes())?;
+                out.write_all(&[info.winners().unwrap_or(0)])?;
+                write_stats(&mut out, &info.stats)?;
+                write_stats(&mut out, &info.rave)?;
+                // Children are only read from this snapshot of the moves,
+                // so the numbering stays consistent while other threads keep searching
+                let child = match info.child() {
+                    Some(child) => {
+                        queue.push(child);
+                        u32::try_from(queue.len() - 1).unwrap()
+                    }
+                    None => NO_CHILD,
+                };
+                out.write_all(&child.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the search results with the trees written by [`Self::write_to`].
+    /// # Errors
+    /// If reading fails or the input isn't a saved tree, the tree is left unchanged.
+    pub fn read_from(&mut self, mut input: impl Read) -> io::Result<()> {
+        let mut header = [0; 5];
+        input.read_exact(&mut header)?;
+        if header[..4] != *SAVE_MAGIC || header[4] != SAVE_VERSION {
+            return Err(invalid_data("Not a saved search tree"));
+        }
+        let mut nodes = Vec::new();
+        // Every child refers to the next node that hasn't been referred to yet
+        let mut num_nodes = self.roots.len();
+        while nodes.len() < num_nodes {
+            let stats = read_stats(&mut input)?;
+            let num_moves = read_u32(&mut input)?;
+            let mut moves = Vec::new();
+            for _ in 0..num_moves {
+                let mv = Move::<M>::try_from(read_u64(&mut input)?)
+                    .map_err(|_| invalid_data("Invalid move"))?;
+                let mut info = MoveInfo::with_heuristic(mv, f64::from_bits(read_u64(&mut input)?));
+                let winners = read_u8(&mut input)?;
+                if winners != 0 {
+                    info.prove(winners);
+                }
+                info.stats = read_stats(&mut input)?;
+                info.rave = read_stats(&mut input)?;
+                let child = match read_u32(&mut input)? {
+                    NO_CHILD => None,
+                    child if child as usize == num_nodes => {
+                        num_nodes += 1;
+                        Some(child as usize)
+                    }
+                    _ => return Err(invalid_data("Children have to be numbered in order")),
+                };
+                moves.push((info, child));
+            }
+            nodes.push((stats, moves));
+        }
+
+        // Nodes don't correspond to a single state, all of them get the evaluation of the root
+        let eval = self.eval.eval_new(&self.root_state, None);
+        self.nodes.clear();
+        let (stats, moves): (Vec<_>, Vec<_>) = nodes.into_iter().unzip();
+        let ids = stats
+            .into_iter()
+            .map(|stats| {
+                self.nodes.alloc(Node {
+                    moves: Children::new(),
+                    eval: eval.clone(),
+                    stats,
+                })
+            })
+            .collect_vec();
+        // Children are attached once all nodes exist
+        for (&id, moves) in ids.iter().zip(moves) {
+            for (info, child) in moves {
+                if let Some(child) = child {
+                    let _ = info.child.set_if_none(ids[child]);
+                }
+                self.get(id).moves.push(info);
+            }
+        }
+        self.roots = core::array::from_fn(|idx| ids[idx]);
+        self.num_nodes.store(ids.len(), Ordering::SeqCst);
+        self.expansion_contention_events.store(0, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_stats(out: &mut impl Write, stats: &Stats) -> io::Result<()> {
+    let (visits, availability_count, sum_evaluations) = stats.counts();
+    out.write_all(&visits.to_le_bytes())?;
+    out.write_all(&availability_count.to_le_bytes())?;
+    out.write_all(&sum_evaluations.to_le_bytes())
+}
+
+fn read_stats(input: &mut impl Read) -> io::Result<Stats> {
+    let visits = read_u64(input)?;
+    let availability_count = read_u64(input)?;
+    let sum_evaluations = i64::from_le_bytes(read_bytes(input)?);
+    Stats::from_counts(visits, availability_count, sum_evaluations)
+        .ok_or_else(|| invalid_data("Too many visits"))
+}
+
+fn read_bytes<const N: usize>(input: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+    read_bytes::<1>(input).map(|[byte]| byte)
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    read_bytes(input).map(u32::from_le_bytes)
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    read_bytes(input).map(u64::from_le_bytes)
+}
+
 #[allow(clippy::module_name_repetitions)]
 pub struct SearchHandle<'a, M: 'a + MCTS> {
     node: &'a Node<M>,
@@ -443,8 +1171,153 @@ impl<'a> IncreaseSentinel<'a> {
     }
 }
 
-impl<'a> Drop for IncreaseSentinel<'a> {
+impl Drop for IncreaseSentinel<'_> {
     fn drop(&mut self) {
         self.x.fetch_sub(1, Ordering::Relaxed);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{manager::Manager, policies::UCTPolicy, testing::*};
+
+    #[test]
+    fn memory_stats() {
+        let mut manager = Manager::new(
+            Race::default(),
+            TestAI::default(),
+            UCTPolicy::new(0.7),
+            RaceEval,
+        );
+        let empty = manager.tree().memory_stats();
+        assert_eq!((empty.nodes, empty.move_infos), (NUM_PLAYERS, 0));
+        manager.playout_n(300);
+        let searched = manager.tree().memory_stats();
+        assert!(searched.nodes > empty.nodes);
+        assert!(searched.move_infos >= searched.nodes - NUM_PLAYERS);
+        assert!(searched.bytes > empty.bytes);
+        assert!(searched.bytes_per_node() > std::mem::size_of::<Node<TestAI>>());
+
+        let mv = manager.best_move().unwrap();
+        manager.advance(&mv);
+        let advanced = manager.tree().memory_stats();
+        assert_eq!(advanced.nodes, manager.tree().num_nodes());
+        assert!(advanced.move_infos < searched.move_infos);
+        // Freed nodes keep their slots for the next search
+        manager.clear();
+        assert!(manager.tree().memory_stats().bytes > empty.bytes);
+    }
+
+    #[test]
+    fn observed_eval() {
+        let search = |view| {
+            let ai = TestAI {
+                rollout_length: Some(0),
+                eval_view: view,
+                ..TestAI::default()
+            };
+            let mut manager = Manager::new(Race::default(), ai, UCTPolicy::new(0.7), RaceEval);
+            manager.playout_n(20);
+            manager.stats()
+        };
+        // The next player is to move after the first move, so the full state is a loss
+        assert!(search(EvalView::Determinized)
+            .iter()
+            .all(|s| s.mean_action_value < 1.0));
+        assert!(search(EvalView::Observer)
+            .iter()
+            .all(|s| s.visits > 0 && (s.mean_action_value - 1.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn advance_report() {
+        let mut manager = Manager::new(
+            Race::default(),
+            TestAI::default(),
+            UCTPolicy::new(0.7),
+            RaceEval,
+        );
+        manager.playout_n(300);
+        let nodes = manager.tree().num_nodes();
+        let mv = manager.best_move().unwrap();
+        let report = manager.advance_with_report(&mv);
+        let mover = &report.trees[0];
+        assert!(mover.nodes_retained > 0);
+        assert!(mover.visits_retained > 0);
+        assert!(mover.visit_ratio() < 1.0);
+        for tree in report.trees {
+            assert!(tree.nodes_retained < tree.nodes_before);
+            assert!(tree.visits_retained <= tree.visits_before);
+        }
+        assert!(report.trees.iter().map(|t| t.nodes_before).sum::<usize>() >= nodes);
+        assert!(
+            report.trees.iter().map(|t| t.nodes_retained).sum::<usize>()
+                <= manager.tree().num_nodes()
+        );
+    }
+
+    #[test]
+    fn save_and_resume() {
+        let new_tree = || {
+            Tree::new(
+                Race::default(),
+                TestAI::default(),
+                UCTPolicy::new(0.7),
+                RaceEval,
+            )
+        };
+        let mut tld = ThreadData::with_seed(3);
+        let tree = new_tree();
+        (0..300).for_each(|_| assert!(tree.playout(&mut tld)));
+        let mut saved = Vec::new();
+        tree.write_to(&mut saved).unwrap();
+
+        let mut loaded = new_tree();
+        loaded.read_from(saved.as_slice()).unwrap();
+        // A new tree counts the roots of all players as one node
+        assert_eq!(loaded.num_nodes(), tree.num_nodes() + NUM_PLAYERS - 1);
+        assert_eq!(loaded.pv(10), tree.pv(10));
+        for player in 0..NUM_PLAYERS {
+            let (moves, loaded_moves) = (tree.root_for(player), loaded.root_for(player));
+            assert_eq!(loaded_moves.move_stats(), moves.move_stats());
+            let moves = &moves.node.moves;
+            for (info, loaded) in moves.iter().zip(loaded_moves.node.moves.iter()) {
+                assert_eq!(loaded.winners(), info.winners());
+                assert_eq!(loaded.rave_visits(), info.rave_visits());
+            }
+        }
+        let mut resaved = Vec::new();
+        loaded.write_to(&mut resaved).unwrap();
+        assert_eq!(resaved, saved);
+
+        // The search goes on where it stopped
+        (0..100).for_each(|_| assert!(loaded.playout(&mut tld)));
+        assert_eq!(loaded.root().node.visits(), 400);
+
+        // Broken input leaves the tree as it was
+        for broken in [&saved[..saved.len() - 1], b"TREE\x01", &[]] {
+            assert!(loaded.read_from(broken).is_err());
+            assert_eq!(loaded.root().node.visits(), 400);
+        }
+
+        let path = std::env::temp_dir().join(format!("mcts-save-{}", std::process::id()));
+        let mut manager = Manager::new(
+            Race::default(),
+            TestAI::default(),
+            UCTPolicy::new(0.7),
+            RaceEval,
+        );
+        manager.playout_n(200);
+        manager.save(&path).unwrap();
+        let mut resumed = Manager::new(
+            Race::default(),
+            TestAI::default(),
+            UCTPolicy::new(0.7),
+            RaceEval,
+        );
+        resumed.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resumed.root_move_stats(), manager.root_move_stats());
+    }
+}
@@ -0,0 +1,158 @@
+//! A small game and search configurations shared by the tests of the search.
+
+use crate::{
+    policies::UCTPolicy, search::SearchHandle, EvalView, Evaluator, GameState, NodeLimitStrategy,
+    MCTS, NUM_PLAYERS,
+};
+
+/// Threads of the parallel searches.
+pub const THREADS: usize = 4;
+
+/// Players take turns adding one or two, whoever reaches the target wins.
+#[derive(Clone, Debug, Default)]
+pub struct Race {
+    pub total: u8,
+    pub to_move: usize,
+}
+
+impl Race {
+    pub const TARGET: u8 = 12;
+}
+
+impl GameState for Race {
+    type Move = u8;
+    type Player = usize;
+    type MoveList = Vec<u8>;
+    type Knowledge = ();
+
+    fn current_player(&self) -> usize {
+        self.to_move
+    }
+
+    fn legal_moves(&self) -> Vec<u8> {
+        if self.total >= Self::TARGET {
+            Vec::new()
+        } else {
+            vec![1, 2]
+        }
+    }
+
+    fn make_move(&mut self, mv: &u8) {
+        self.total += mv;
+        if self.total < Self::TARGET {
+            self.to_move = (self.to_move + 1) % NUM_PLAYERS;
+        }
+    }
+
+    fn randomize_determination<R: rand::Rng>(
+        &mut self,
+        _observer: usize,
+        _knowledge: &(),
+        _rng: &mut R,
+    ) {
+    }
+
+    fn update_knowledge(&self, _mv: &u8, _knowledge: &mut ()) {}
+
+    fn new_knowledge(&self, _observer: usize) {}
+
+    fn knowledge_from_state(&self, _observer: usize) {}
+
+    fn outcome(&self, player: &usize) -> Option<bool> {
+        (self.total >= Self::TARGET).then_some(*player == self.to_move)
+    }
+}
+
+/// Whoever is to move at the end of a playout wins. Evaluated from their own view, everybody
+/// believes they win.
+#[derive(Clone)]
+pub struct RaceEval;
+
+impl<M: MCTS<State = Race>> Evaluator<M> for RaceEval {
+    type StateEval = usize;
+
+    fn eval_new(&self, state: &Race, _handle: Option<SearchHandle<M>>) -> usize {
+        state.to_move
+    }
+
+    fn eval_existing(&self, _state: &Race, existing: &usize, _handle: SearchHandle<M>) -> usize {
+        *existing
+    }
+
+    fn make_relative(&self, eval: &usize, player: &usize) -> i64 {
+        if eval == player {
+            1
+        } else {
+            -1
+        }
+    }
+
+    fn eval_observed(&self, _state: &Race, observer: &usize, _knowledge: &()) -> usize {
+        *observer
+    }
+}
+
+/// Searches the race, every field overrides the [`MCTS`] method of the same name.
+#[derive(Clone)]
+pub struct TestAI {
+    /// Prunes the least visited subtrees once the tree has this many nodes
+    pub node_limit: usize,
+    pub virtual_loss: i64,
+    pub rollout_length: Option<usize>,
+    /// Keeps the determinizations of this many playouts
+    pub log_size: usize,
+    /// Prefers adding two
+    pub heuristic: bool,
+    pub eval_view: EvalView,
+}
+
+impl Default for TestAI {
+    fn default() -> Self {
+        Self {
+            node_limit: usize::MAX,
+            virtual_loss: 0,
+            rollout_length: Some(4),
+            log_size: 0,
+            heuristic: false,
+            eval_view: EvalView::Determinized,
+        }
+    }
+}
+
+impl MCTS for TestAI {
+    type State = Race;
+    type Eval = RaceEval;
+    type Select = UCTPolicy;
+
+    fn virtual_loss(&self) -> i64 {
+        self.virtual_loss
+    }
+
+    fn node_limit(&self) -> usize {
+        self.node_limit
+    }
+
+    fn node_limit_strategy(&self) -> NodeLimitStrategy {
+        NodeLimitStrategy::PruneLeastVisited
+    }
+
+    fn rollout_length(&self) -> Option<usize> {
+        self.rollout_length
+    }
+
+    fn move_heuristic(&self, _state: &Race, mv: &u8) -> f64 {
+        if self.heuristic {
+            f64::from(*mv)
+        } else {
+            0.0
+        }
+    }
+
+    fn determinization_log_size(&self) -> usize {
+        self.log_size
+    }
+
+    fn eval_view(&self) -> EvalView {
+        self.eval_view
+    }
+}
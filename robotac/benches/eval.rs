@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use robotac::{board::Board, eval::EvalParams, rules::Ruleset};
+
+const STATES: u64 = 64;
+
+pub fn criterion_benchmark(criterion: &mut Criterion) {
+    // States from every phase of a game, reached by random legal moves
+    let boards = (0..STATES)
+        .map(|seed| Board::new_random_state(seed, Ruleset::default(), 0..=400))
+        .collect::<Vec<_>>();
+    let params = EvalParams::default();
+    criterion.bench_function("eval random states", |b| {
+        b.iter(|| {
+            for board in black_box(&boards) {
+                black_box(board.eval_with(&params));
+            }
+        });
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(100).measurement_time(Duration::from_secs(10));
+    targets = criterion_benchmark
+}
+criterion_main!(benches);
@@ -1,18 +1,21 @@
 use std::time::Duration;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use robotac::board::Board;
-use tac_types::{ALL_COLORS, CARDS};
+use mcts::GameState;
+use robotac::{board::Board, rules::Ruleset};
+use tac_types::{Distance, HomeSquare, PackedTacMove, ALL_COLORS, CARDS};
+
+const STATES: u64 = 64;
 
 pub fn criterion_benchmark(criterion: &mut Criterion) {
     let mut board = Board::new_with_seed(0);
     for color in ALL_COLORS {
-        board.put_ball_in_play(color);
-        board.move_ball(color.home(), color.home().add(4), color);
-        board.put_ball_in_play(color);
-        board.move_ball(color.home(), color.home().sub(4), color);
-        board.put_ball_in_play(color);
-        board.move_ball_to_goal(color.home(), 2, color);
+        let _ = board.put_ball_in_play(color);
+        let _ = board.move_ball(color.home(), color.home().add(Distance::new(4)), color);
+        let _ = board.put_ball_in_play(color);
+        let _ = board.move_ball(color.home(), color.home().sub(Distance::new(4)), color);
+        let _ = board.put_ball_in_play(color);
+        board.move_ball_to_goal(color.home(), HomeSquare::new(2), color);
     }
     criterion.bench_function("gen moves", |b| {
         b.iter(|| {
@@ -23,6 +26,31 @@ pub fn criterion_benchmark(criterion: &mut Criterion) {
             }
         });
     });
+    // The search generates its moves into a buffer that is reused, instead of collecting
+    // the unpacked moves first
+    let boards = (0..STATES)
+        .map(|seed| Board::new_random_state(seed, Ruleset::default(), 0..=400))
+        .collect::<Vec<_>>();
+    criterion.bench_function("legal moves", |b| {
+        b.iter(|| {
+            for board in black_box(&boards) {
+                black_box(board.legal_moves());
+            }
+        });
+    });
+    criterion.bench_function("legal moves collected", |b| {
+        b.iter(|| {
+            for board in black_box(&boards) {
+                black_box(
+                    board
+                        .get_moves(board.current_player())
+                        .iter()
+                        .map(PackedTacMove::pack)
+                        .collect::<Vec<_>>(),
+                );
+            }
+        });
+    });
 }
 
 criterion_group! {
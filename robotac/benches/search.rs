@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use robotac::{arena::AgentConfig, board::Board, rules::Ruleset};
+
+const PLAYOUTS: u64 = 2000;
+
+pub fn criterion_benchmark(criterion: &mut Criterion) {
+    // A position with balls in play, the first moves of a game are mostly trades
+    let board = Board::new_random_state(0, Ruleset::default(), 24..=24);
+    let agent = AgentConfig::new("bench");
+    let mut group = criterion.benchmark_group("search");
+    group.throughput(Throughput::Elements(PLAYOUTS));
+    for threads in [1, 4] {
+        group.bench_function(format!("playouts {threads} threads"), |b| {
+            b.iter(|| {
+                let mut mcts = agent.manager(&board);
+                mcts.playout_n_parallel(PLAYOUTS, threads);
+                mcts
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20).measurement_time(Duration::from_secs(20));
+    targets = criterion_benchmark
+}
+criterion_main!(benches);
@@ -4,7 +4,12 @@ use mcts::{manager::Manager, policies::UCTPolicy};
 use robotac::{board::Board, TacAI, TacEval};
 
 fn main() {
-    let mut mcts = Manager::new(Board::new_with_seed(1), TacAI, UCTPolicy(35.0), TacEval);
+    let mut mcts = Manager::new(
+        Board::new_with_seed(1),
+        TacAI::default(),
+        UCTPolicy::new(35.0),
+        TacEval::default(),
+    );
     println!("{:?}", mcts.tree().root_state());
 
     let before = Instant::now();
@@ -0,0 +1,962 @@
+use std::sync::Arc;
+
+use mcts::{manager::Manager, policies::UCTPolicy, EvalView};
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tac_types::{Color, PackedTacMove, TacMove, ALL_COLORS, NUM_TEAMS};
+
+use crate::{
+    board::Board,
+    book::OpeningBook,
+    eval::EvalParams,
+    history::{unix_time, Annotation, History, PlayerInfo, SearchStats},
+    knowledge::Knowledge,
+    safety, solver, EvalKind, FinalSelection, TacAI, TacEval,
+};
+
+/// Most positions the solver may visit per determinization when looking for a forced win
+const SOLVER_NODES: u64 = 20_000;
+/// Determinizations a forced win has to hold in
+const SOLVER_SAMPLES: usize = 8;
+/// The first stage of a search with [`AgentConfig::stable_stop`] runs one in this many playouts
+const STABLE_STAGES: u64 = 8;
+
+/// Configuration of one player in a self-play match.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AgentConfig {
+    pub name: String,
+    pub uct_constant: f64,
+    /// First play urgency of the UCT policy
+    pub fpu: f64,
+    /// Weight of the heuristic move score in the UCT policy
+    pub progressive_bias: f64,
+    /// Blends all-moves-as-first values into the UCT policy with this bias, see
+    /// [`UCTPolicy::with_rave`]
+    #[serde(default)]
+    pub rave_bias: Option<f64>,
+    /// Expands moves in the order of their heuristic score instead of at random, see
+    /// [`UCTPolicy::with_prior_ordering`]
+    #[serde(default)]
+    pub prior_ordering: bool,
+    /// Depth of the endgame [`solver`], used for leaf evaluations and to find forced wins
+    /// before searching. `0` disables it
+    #[serde(default)]
+    pub solver_depth: usize,
+    pub eval: EvalKind,
+    pub params: EvalParams,
+    /// Number of playouts per move
+    pub playouts: u64,
+    /// Stops searching a move early once the share of visits of the best move changes by at
+    /// most this much, see [`Manager::playout_until_stable`]. Only single threaded searches
+    /// stop early
+    #[serde(default)]
+    pub stable_stop: Option<f64>,
+    pub threads: usize,
+    /// Random moves at the end of every playout, see [`TacAI::rollout_length`]
+    #[serde(default = "default_rollout_length")]
+    pub rollout_length: usize,
+    /// Chance of playing a random legal move instead of the searched one
+    #[serde(default)]
+    pub blunder_rate: f64,
+    /// How the move is picked from the searched ones
+    #[serde(default)]
+    pub final_selection: FinalSelection,
+    /// Playouts whose determinization is kept to inspect the search afterwards,
+    /// see [`mcts::manager::Manager::last_determinizations`]
+    #[serde(default)]
+    pub determinization_log: usize,
+    /// Evaluates playouts only with what the searching player knows about the hands of the
+    /// others instead of the sampled hands, see [`mcts::EvalView::Observer`]
+    #[serde(default)]
+    pub observed_eval: bool,
+    /// Tries the immediate replies of the next opponent to the searched move and plays a
+    /// similarly visited move instead if that loses less to them, see [`safety`]
+    #[serde(default)]
+    pub loss_check: bool,
+    /// Seeds the search and the random choices around it, so the agent plays the same moves in
+    /// the same positions. `None` seeds from entropy
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Moves for the start of the game that are played without searching
+    #[serde(skip)]
+    pub book: Option<Arc<OpeningBook>>,
+}
+
+fn default_rollout_length() -> usize {
+    TacAI::DEFAULT_ROLLOUT_LENGTH
+}
+
+/// Presets of [`AgentConfig`] for casual games.
+/// Easier levels search less, explore more and now and then play a random move.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Self; 3] = [Self::Easy, Self::Medium, Self::Hard];
+
+    #[must_use]
+    pub fn agent(self) -> AgentConfig {
+        let agent = AgentConfig::new(format!("{self:?}"));
+        match self {
+            Difficulty::Easy => AgentConfig {
+                uct_constant: 1.4,
+                solver_depth: 0,
+                playouts: 200,
+                rollout_length: 1,
+                blunder_rate: 0.15,
+                ..agent
+            },
+            Difficulty::Medium => AgentConfig {
+                playouts: 2_000,
+                threads: 2,
+                blunder_rate: 0.03,
+                ..agent
+            },
+            Difficulty::Hard => AgentConfig {
+                playouts: 20_000,
+                threads: 4,
+                ..agent
+            },
+        }
+    }
+
+    /// The next harder level, wrapping around to [`Difficulty::Easy`].
+    #[must_use]
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|d| *d == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+}
+
+impl AgentConfig {
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            uct_constant: 0.7,
+            fpu: f64::INFINITY,
+            progressive_bias: 0.2,
+            rave_bias: None,
+            prior_ordering: false,
+            solver_depth: 2,
+            eval: EvalKind::default(),
+            params: EvalParams::default(),
+            playouts: 1_000,
+            stable_stop: None,
+            threads: 1,
+            rollout_length: TacAI::DEFAULT_ROLLOUT_LENGTH,
+            blunder_rate: 0.0,
+            final_selection: FinalSelection::default(),
+            determinization_log: 0,
+            observed_eval: false,
+            loss_check: false,
+            seed: None,
+            book: None,
+        }
+    }
+
+    /// Creates a search rooted at `board` with the policy and evaluation of this agent.
+    #[must_use]
+    pub fn manager(&self, board: &Board) -> Manager<TacAI> {
+        let mut policy = UCTPolicy::new(self.uct_constant)
+            .with_fpu(self.fpu)
+            .with_progressive_bias(self.progressive_bias);
+        if let Some(bias) = self.rave_bias {
+            policy = policy.with_rave(bias);
+        }
+        if self.prior_ordering {
+            policy = policy.with_prior_ordering();
+        }
+        let manager = Manager::new(
+            board.clone(),
+            TacAI {
+                rollout_length: self.rollout_length,
+                final_selection: self.final_selection,
+                determinization_log: self.determinization_log,
+                eval_view: if self.observed_eval {
+                    EvalView::Observer
+                } else {
+                    EvalView::Determinized
+                },
+            },
+            policy,
+            TacEval {
+                kind: self.eval,
+                params: self.params,
+                solver_depth: self.solver_depth,
+            },
+        );
+        match self.seed {
+            Some(seed) => manager.with_seed(seed),
+            None => manager,
+        }
+    }
+
+    /// Source of the random choices of this agent outside of the search, see [`Self::seed`].
+    #[must_use]
+    pub fn rng(&self) -> StdRng {
+        self.seed
+            .map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)
+    }
+
+    /// Returns a move that wins the endgame within [`Self::solver_depth`] moves no matter which
+    /// cards the others hold, as far as `knowledge` can tell.
+    #[must_use]
+    pub fn forced_win(
+        &self,
+        board: &Board,
+        knowledge: &Knowledge,
+        rng: &mut impl Rng,
+    ) -> Option<TacMove> {
+        if self.solver_depth == 0 || !solver::is_endgame(board) {
+            return None;
+        }
+        solver::winning_move(
+            board,
+            knowledge,
+            self.solver_depth,
+            SOLVER_NODES,
+            SOLVER_SAMPLES,
+            rng,
+        )
+    }
+
+    /// Returns the move of the opening book for the position, if there is one.
+    #[must_use]
+    pub fn book_move(&self, board: &Board) -> Option<TacMove> {
+        self.book.as_ref()?.lookup(board)
+    }
+
+    /// Replaces `mv` by a random legal move with the probability [`Self::blunder_rate`].
+    pub fn blunder(&self, board: &Board, mv: TacMove, rng: &mut impl Rng) -> TacMove {
+        if self.blunder_rate <= 0.0 || !rng.gen_bool(self.blunder_rate.min(1.0)) {
+            return mv;
+        }
+        board
+            .get_moves(board.current_player())
+            .into_iter()
+            .choose(rng)
+            .unwrap_or(mv)
+    }
+
+    /// With [`Self::loss_check`], replaces the searched move `best` by a similarly visited move
+    /// that the next opponent can punish less, see [`safety::safest_move`]. The replies are tried
+    /// in [`safety::SAMPLES`] determinizations of what `knowledge` allows.
+    #[must_use]
+    pub fn checked_move(
+        &self,
+        mcts: &Manager<TacAI>,
+        board: &Board,
+        knowledge: &Knowledge,
+        best: TacMove,
+        rng: &mut impl Rng,
+    ) -> TacMove {
+        if !self.loss_check {
+            return best;
+        }
+        let candidates = safety::similar_moves(mcts, board, &best);
+        if candidates.len() < 2 {
+            return best;
+        }
+        let determinizations = safety::determinizations(board, knowledge, safety::SAMPLES, rng);
+        safety::safest_move(&determinizations, &candidates).unwrap_or(best)
+    }
+
+    /// Returns the move this agent would play in the given position.
+    #[must_use]
+    pub fn choose_move(&self, board: &Board) -> Option<TacMove> {
+        self.search(board).map(|(mv, _)| mv)
+    }
+
+    /// Like [`Self::choose_move`], also returns the statistics of the move if there was a choice.
+    #[must_use]
+    pub fn search(&self, board: &Board) -> Option<(TacMove, Option<SearchStats>)> {
+        let mut mcts = self.manager(board);
+        if let Some(mv) = mcts.forced_move() {
+            return Some((mv.unpack(), None));
+        }
+        if board.is_terminal() {
+            return None;
+        }
+        if let Some(mv) = self.book_move(board) {
+            return Some((mv, None));
+        }
+        let mut rng = self.rng();
+        let knowledge = Knowledge::new_from_board(board.current_player(), board);
+        if let Some(mv) = self.forced_win(board, &knowledge, &mut rng) {
+            return Some((mv, None));
+        }
+        if self.threads > 1 {
+            mcts.playout_n_parallel(self.playouts, self.threads);
+        } else if let Some(epsilon) = self.stable_stop {
+            mcts.playout_until_stable(self.playouts / STABLE_STAGES, self.playouts, epsilon);
+        } else {
+            mcts.playout_n(self.playouts);
+        }
+        let best = mcts.best_move()?;
+        let best = self.checked_move(&mcts, board, &knowledge, best.unpack(), &mut rng);
+        let mv = self.blunder(board, best, &mut rng);
+        let stats = SearchStats::of(&mcts, PackedTacMove::pack(&mv));
+        Some((mv, stats))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameOutcome {
+    Win,
+    Loss,
+    Draw,
+}
+
+/// Wins, losses and draws from the point of view of the first agent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+/// Converts a score between 0 and 1 into an elo difference.
+fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Converts an elo difference into the expected score.
+fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Outcome of a sequential probability ratio test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SprtStatus {
+    /// The elo difference is likely at most `elo0`
+    AcceptH0,
+    /// The elo difference is likely at least `elo1`
+    AcceptH1,
+    Continue,
+}
+
+/// Hypotheses and error rates of a sequential probability ratio test.
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Self {
+            elo0: 0.0,
+            elo1: 10.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+impl Sprt {
+    /// Returns the lower and upper bound for the log likelihood ratio.
+    #[must_use]
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    #[must_use]
+    pub fn status(&self, result: &MatchResult) -> SprtStatus {
+        let llr = result.llr(self.elo0, self.elo1);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            SprtStatus::AcceptH0
+        } else if llr >= upper {
+            SprtStatus::AcceptH1
+        } else {
+            SprtStatus::Continue
+        }
+    }
+}
+
+#[allow(clippy::cast_lossless)]
+impl MatchResult {
+    pub fn add(&mut self, outcome: GameOutcome) {
+        match outcome {
+            GameOutcome::Win => self.wins += 1,
+            GameOutcome::Loss => self.losses += 1,
+            GameOutcome::Draw => self.draws += 1,
+        }
+    }
+
+    #[must_use]
+    pub fn games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    #[must_use]
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.games().max(1) as f64
+    }
+
+    #[must_use]
+    pub fn draw_rate(&self) -> f64 {
+        self.draws as f64 / self.games().max(1) as f64
+    }
+
+    /// Returns the average score, counting a draw as half a win.
+    #[must_use]
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games().max(1) as f64
+    }
+
+    /// Variance of the score of a single game.
+    fn variance(&self) -> f64 {
+        let n = self.games().max(1) as f64;
+        let s = self.score();
+        (self.wins as f64 * (1.0 - s).powi(2)
+            + self.draws as f64 * (0.5 - s).powi(2)
+            + self.losses as f64 * s.powi(2))
+            / n
+    }
+
+    /// Returns the estimated elo difference and its 95% error margin,
+    /// or `None` if one side won or lost every game.
+    #[must_use]
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        let dev = (self.variance() / self.games() as f64).sqrt();
+        let lower = score_to_elo((score - 1.96 * dev).max(f64::EPSILON));
+        let upper = score_to_elo((score + 1.96 * dev).min(1.0 - f64::EPSILON));
+        Some((score_to_elo(score), (upper - lower) / 2.0))
+    }
+
+    /// Returns the log likelihood ratio of H1 (`elo1`) against H0 (`elo0`),
+    /// using the normal approximation of the trinomial model.
+    #[must_use]
+    pub fn llr(&self, elo0: f64, elo1: f64) -> f64 {
+        let variance = self.variance();
+        if self.games() == 0 || variance <= 0.0 {
+            return 0.0;
+        }
+        let s0 = elo_to_score(elo0);
+        let s1 = elo_to_score(elo1);
+        self.games() as f64 * (s1 - s0) * (2.0 * self.score() - s0 - s1) / (2.0 * variance)
+    }
+}
+
+/// Seed of game number `game` of a match starting at `seed` and the team of the first agent.
+/// Consecutive games use the same deal until the first agent played it from every team.
+#[allow(clippy::cast_possible_truncation)]
+fn seating(seed: u64, game: u64) -> (u64, Color) {
+    let teams = NUM_TEAMS as u64;
+    (seed + game / teams, ALL_COLORS[(game % teams) as usize])
+}
+
+fn outcome_for(board: &Board, first_team: Color) -> GameOutcome {
+    match board.game_result() {
+        Some(result) if result.is_win_for(first_team) => GameOutcome::Win,
+        Some(_) => GameOutcome::Loss,
+        None => GameOutcome::Draw,
+    }
+}
+
+/// Plays games between two agents, alternating which team each agent controls.
+pub struct Arena {
+    pub first: AgentConfig,
+    pub second: AgentConfig,
+    /// Games still running after this many moves are counted as draws
+    pub max_plies: usize,
+    pub seed: u64,
+}
+
+impl Arena {
+    #[must_use]
+    pub fn new(first: AgentConfig, second: AgentConfig) -> Self {
+        Self {
+            first,
+            second,
+            max_plies: 2_000,
+            seed: 0,
+        }
+    }
+
+    /// Plays game number `game` and returns the outcome for the first agent.
+    /// Consecutive pairs of games use the same deal with the seats swapped.
+    #[must_use]
+    pub fn play_game(&self, game: u64) -> GameOutcome {
+        self.record_game(game).0
+    }
+
+    /// Like [`Self::play_game`], also returns the history of the game.
+    #[must_use]
+    pub fn record_game(&self, game: u64) -> (GameOutcome, History) {
+        let (seed, first_team) = seating(self.seed, game);
+        let mut board = Board::new_with_seed(seed);
+        let on_team = |c: Color| c == first_team || c == first_team.partner();
+        let agent_of = |c: Color| {
+            if on_team(c) {
+                &self.first
+            } else {
+                &self.second
+            }
+        };
+        let mut history = History::new(seed);
+        history.players = ALL_COLORS.map(|c| PlayerInfo {
+            name: agent_of(c).name.clone(),
+            agent: Some(agent_of(c).clone()),
+        });
+        for _ in 0..self.max_plies {
+            let Some((mv, search)) = agent_of(board.current_player()).search(&board) else {
+                break;
+            };
+            board.play(&mv);
+            history.push(
+                mv,
+                Annotation {
+                    time: unix_time(),
+                    search,
+                    comment: None,
+                },
+            );
+        }
+        (outcome_for(&board, first_team), history)
+    }
+
+    /// Plays up to `games` games, calling `on_game` with the history after each one.
+    /// Stops early once the given SPRT reaches a decision.
+    pub fn run(
+        &self,
+        games: u64,
+        sprt: Option<Sprt>,
+        mut on_game: impl FnMut(u64, GameOutcome, &MatchResult, &History),
+    ) -> MatchResult {
+        let mut result = MatchResult::default();
+        for game in 0..games {
+            let (outcome, history) = self.record_game(game);
+            result.add(outcome);
+            on_game(game, outcome, &result, &history);
+            if sprt.is_some_and(|s| s.status(&result) != SprtStatus::Continue) {
+                break;
+            }
+        }
+        result
+    }
+}
+
+/// Plays games between two sets of evaluation weights, each side playing
+/// [`Board::greedy_move`] without searching. Orders of magnitude faster than an [`Arena`],
+/// as an inner loop for tuning weights before confirming them in full matches.
+pub struct GreedyArena {
+    pub first: EvalParams,
+    pub second: EvalParams,
+    /// Games still running after this many moves are counted as draws
+    pub max_plies: usize,
+    pub seed: u64,
+}
+
+impl GreedyArena {
+    #[must_use]
+    pub fn new(first: EvalParams, second: EvalParams) -> Self {
+        Self {
+            first,
+            second,
+            max_plies: 2_000,
+            seed: 0,
+        }
+    }
+
+    /// Plays game number `game` and returns the outcome for the first weights.
+    /// Games are deterministic, consecutive pairs use the same deal with the seats swapped.
+    #[must_use]
+    pub fn play_game(&self, game: u64) -> GameOutcome {
+        let (seed, first_team) = seating(self.seed, game);
+        let mut board = Board::new_with_seed(seed);
+        for _ in 0..self.max_plies {
+            let player = board.current_player();
+            let params = if player == first_team || player == first_team.partner() {
+                &self.first
+            } else {
+                &self.second
+            };
+            let Some(mv) = board.greedy_move(params) else {
+                break;
+            };
+            board.play(&mv);
+        }
+        outcome_for(&board, first_team)
+    }
+
+    /// Plays `games` games, calling `on_game` after each one.
+    pub fn run(
+        &self,
+        games: u64,
+        mut on_game: impl FnMut(u64, GameOutcome, &MatchResult),
+    ) -> MatchResult {
+        let mut result = MatchResult::default();
+        for game in 0..games {
+            let outcome = self.play_game(game);
+            result.add(outcome);
+            on_game(game, outcome, &result);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mcts::node::ComputedStats;
+    use rand::seq::IteratorRandom;
+    use tac_types::{Card, ALL_COLORS};
+
+    use super::*;
+
+    #[test]
+    fn difficulties() {
+        let agents = Difficulty::ALL.map(Difficulty::agent);
+        for pair in agents.windows(2) {
+            assert!(pair[0].playouts < pair[1].playouts);
+            assert!(pair[0].blunder_rate > pair[1].blunder_rate);
+        }
+        assert_eq!(Difficulty::Hard.next(), Difficulty::Easy);
+        // Configurations saved before difficulties existed still load
+        let mut saved = ron::to_string(&AgentConfig::new("old")).unwrap();
+        saved = saved
+            .replace(",rollout_length:4", "")
+            .replace(",blunder_rate:0.0", "");
+        assert!(!saved.contains("blunder_rate"));
+        assert_eq!(
+            ron::from_str::<AgentConfig>(&saved).unwrap(),
+            AgentConfig::new("old")
+        );
+    }
+
+    #[test]
+    fn blunders() {
+        let board = Board::new_with_seed(1);
+        let mut rng = StdRng::seed_from_u64(1);
+        let moves = board.get_moves(board.current_player());
+        let best = moves[0].clone();
+        let mut agent = AgentConfig::new("blunder");
+        assert_eq!(agent.blunder(&board, best.clone(), &mut rng), best);
+        agent.blunder_rate = 1.0;
+        let played = (0..50)
+            .map(|_| agent.blunder(&board, best.clone(), &mut rng))
+            .collect::<Vec<_>>();
+        assert!(played.iter().all(|mv| moves.contains(mv)));
+        assert!(played.iter().any(|mv| *mv != best));
+
+        // Seeded agents blunder the same way every time
+        agent.blunder_rate = 0.5;
+        agent.playouts = 20;
+        agent.seed = Some(1);
+        let searched = (0..4)
+            .map(|_| agent.choose_move(&board))
+            .collect::<Vec<_>>();
+        assert!(searched.iter().all(|mv| *mv == searched[0]));
+    }
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    fn final_selections() {
+        let mut board = Board::new_with_seed(5);
+        let mut rng = StdRng::seed_from_u64(5);
+        // Some balls in play, so there are several moves to choose from
+        let mut plies = 0;
+        while plies < 40 || board.get_moves(board.current_player()).len() < 4 {
+            let mv = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+                .unwrap();
+            board.play(&mv);
+            plies += 1;
+        }
+        let legal = board.get_moves(board.current_player());
+        for selection in [
+            FinalSelection::MostVisited,
+            FinalSelection::BestMean,
+            FinalSelection::AvailabilityWeighted,
+        ] {
+            let agent = AgentConfig {
+                final_selection: selection,
+                ..AgentConfig::new("select")
+            };
+            let mut mcts = agent.manager(&board);
+            mcts.playout_n(300);
+            let best = mcts.best_move().unwrap();
+            assert!(legal.contains(&best.unpack()));
+            let stats = mcts
+                .root_move_stats()
+                .into_iter()
+                .filter(|(mv, _)| legal.contains(&mv.unpack()))
+                .collect::<Vec<_>>();
+            let most_visits = stats.iter().map(|(_, s)| s.visits).max().unwrap();
+            let chosen = stats.iter().find(|(mv, _)| *mv == best).unwrap().1;
+            match selection {
+                FinalSelection::MostVisited => assert_eq!(chosen.visits, most_visits),
+                FinalSelection::BestMean => {
+                    assert!(2 * chosen.visits >= most_visits);
+                    assert!(stats
+                        .iter()
+                        .filter(|(_, s)| 2 * s.visits >= most_visits)
+                        .all(|(_, s)| s.mean_action_value <= chosen.mean_action_value));
+                }
+                FinalSelection::AvailabilityWeighted => {
+                    let per_availability =
+                        |s: &ComputedStats| s.visits as f64 / s.availability_count.max(1) as f64;
+                    assert!(2 * chosen.visits >= most_visits);
+                    assert!(stats
+                        .iter()
+                        .filter(|(_, s)| 2 * s.visits >= most_visits)
+                        .all(|(_, s)| per_availability(s) <= per_availability(&chosen)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn logged_determinizations() {
+        let board = Board::new_with_seed(3);
+        let player = board.current_player();
+        let agent = AgentConfig {
+            determinization_log: 20,
+            ..AgentConfig::new("log")
+        };
+        let mut mcts = agent.manager(&board);
+        mcts.playout_n(50);
+        let logged = mcts.last_determinizations(usize::MAX);
+        assert_eq!(logged.len(), 20);
+        for determinization in logged {
+            // Only the hands of the others are sampled
+            let world = &determinization.state;
+            assert_eq!(world.hand(player), board.hand(player));
+            for color in ALL_COLORS {
+                assert_eq!(world.hand(color).amount(), board.hand(color).amount());
+            }
+            assert!(determinization.eval.abs() <= TacEval::SCALE);
+            let first = determinization.first_move.unwrap().unpack();
+            assert!(board.get_moves(player).contains(&first));
+        }
+    }
+
+    #[test]
+    fn plays_winning_move() {
+        let agent = AgentConfig {
+            playouts: 300,
+            ..AgentConfig::new("solver")
+        };
+        let mut tested = 0;
+        for seed in 0..20 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            loop {
+                let player = board.current_player();
+                let moves = board.get_moves(player);
+                let wins = moves
+                    .iter()
+                    .filter(|mv| {
+                        let mut after = board.clone();
+                        after.play(mv);
+                        // After a devil the move could also win the game for the opponents
+                        after.is_terminal() && after.won(player)
+                    })
+                    .count();
+                // A tac in a sampled hand could take the win back, so only test positions
+                // where the win is certain in every determinization
+                let tac_hidden = board.deck().remaining(Card::Tac) > 0
+                    || ALL_COLORS
+                        .iter()
+                        .any(|c| *c != player && board.hand(*c).contains(Card::Tac));
+                if wins > 0 && wins < moves.len() && !tac_hidden {
+                    let mv = agent.choose_move(&board).unwrap();
+                    board.play(&mv);
+                    assert!(board.won(player), "{seed} {mv}");
+                    tested += 1;
+                    break;
+                }
+                let Some(mv) = moves.into_iter().choose(&mut rng) else {
+                    break;
+                };
+                board.play(&mv);
+            }
+        }
+        assert!(tested > 0);
+    }
+
+    #[test]
+    fn forced_moves() {
+        let agent = AgentConfig::new("forced");
+        let mut board = Board::new_with_seed(8);
+        let mut rng = StdRng::seed_from_u64(8);
+        let mut forced = 0;
+        while forced < 5 && !board.is_terminal() {
+            let moves = board.get_moves(board.current_player());
+            let mut mcts = agent.manager(&board);
+            if let [only] = moves.as_slice() {
+                assert_eq!(mcts.forced_move().unwrap().unpack(), *only);
+                // Nothing gets searched
+                mcts.playout_n(100);
+                assert_eq!(mcts.playout_until_stable(10, 100, 0.1), 0);
+                assert!(mcts.root_move_stats().is_empty());
+                assert_eq!(mcts.best_move().unwrap().unpack(), *only);
+                assert_eq!(agent.search(&board), Some((only.clone(), None)));
+                forced += 1;
+            } else {
+                assert!(mcts.forced_move().is_none());
+            }
+            let mv = moves.into_iter().choose(&mut rng).unwrap();
+            board.play(&mv);
+        }
+        assert_eq!(forced, 5);
+    }
+
+    #[test]
+    fn new_deals() {
+        let agent = AgentConfig::new("deals");
+        let mut board = Board::new_with_seed(3);
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut mcts = agent.manager(&board);
+        let mut knowledge = ALL_COLORS.map(|c| Knowledge::new_from_board(c, &board));
+        let mut deals = 0;
+        while deals < 2 && !board.is_terminal() {
+            let mv = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+                .unwrap();
+            for k in &mut knowledge {
+                k.update_with_move(&mv, &board);
+            }
+            board.play(&mv);
+            if board.just_started() {
+                mcts.playout_n(50);
+                let report = mcts.advance_with_report(&PackedTacMove::pack(&mv));
+                // Nothing searched with the old hands is kept
+                assert!(report.trees.iter().all(|t| t.nodes_retained == 0));
+                assert!(mcts.tree().root_state().just_started());
+                for (tree, k) in mcts.knowledge().iter().zip(&knowledge) {
+                    let mut dealt = *k;
+                    dealt.start_deal(&board);
+                    assert_eq!(*tree, dealt);
+                }
+                deals += 1;
+            } else {
+                mcts.advance(&PackedTacMove::pack(&mv));
+                // The first move of a deal doesn't take in the new hands a second time
+                assert_eq!(mcts.knowledge(), &knowledge);
+            }
+        }
+        assert_eq!(deals, 2);
+    }
+
+    #[test]
+    fn seeded_search() {
+        let agent = AgentConfig::new("seeded");
+        let mut board = Board::new_with_seed(6);
+        let mut rng = StdRng::seed_from_u64(6);
+        for _ in 0..12 {
+            let mv = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+                .unwrap();
+            board.play(&mv);
+        }
+        let search = |seed| {
+            let mut mcts = agent.manager(&board).with_seed(seed);
+            mcts.playout_n(500);
+            mcts.root_move_stats()
+                .into_iter()
+                .map(|(mv, stats)| (mv, stats.visits))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(search(1), search(1));
+        assert_ne!(search(1), search(2));
+    }
+
+    #[test]
+    fn greedy_games() {
+        let board = Board::new_with_seed(2);
+        let params = EvalParams::default();
+        let mv = board.greedy_move(&params).unwrap();
+        assert!(board.get_moves(board.current_player()).contains(&mv));
+
+        // With the same weights all seatings of a deal are the same game
+        let arena = GreedyArena::new(params, params);
+        let result = arena.run(6, |_, _, _| {});
+        assert_eq!(
+            result.wins as usize * (NUM_TEAMS - 1),
+            result.losses as usize
+        );
+        assert_eq!(result.games(), 6);
+        assert_eq!(arena.play_game(3), arena.play_game(3));
+
+        // Weights that don't value getting balls home play much worse
+        let careless = EvalParams {
+            in_home: 0,
+            near_goal: 0,
+            ..params
+        };
+        let result = GreedyArena::new(params, careless).run(10, |_, _, _| {});
+        assert!(result.wins > result.losses, "{result:?}");
+    }
+
+    #[test]
+    fn elo_estimate() {
+        let even = MatchResult {
+            wins: 10,
+            losses: 10,
+            draws: 5,
+        };
+        let (elo, margin) = even.elo().unwrap();
+        assert!(elo.abs() < 1e-9);
+        assert!(margin > 0.0);
+
+        let strong = MatchResult {
+            wins: 75,
+            losses: 25,
+            draws: 0,
+        };
+        let (elo, _) = strong.elo().unwrap();
+        assert!((elo - 190.85).abs() < 0.01);
+        assert!(MatchResult {
+            wins: 3,
+            ..Default::default()
+        }
+        .elo()
+        .is_none());
+    }
+
+    #[test]
+    fn sprt() {
+        let sprt = Sprt::default();
+        let winning = MatchResult {
+            wins: 600,
+            losses: 400,
+            draws: 0,
+        };
+        assert_eq!(sprt.status(&winning), SprtStatus::AcceptH1);
+        let losing = MatchResult {
+            wins: 400,
+            losses: 600,
+            draws: 0,
+        };
+        assert_eq!(sprt.status(&losing), SprtStatus::AcceptH0);
+        let few = MatchResult {
+            wins: 3,
+            losses: 2,
+            draws: 1,
+        };
+        assert_eq!(sprt.status(&few), SprtStatus::Continue);
+    }
+}
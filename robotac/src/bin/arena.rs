@@ -0,0 +1,284 @@
+use std::path::PathBuf;
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use robotac::{
+    arena::{AgentConfig, Arena, GameOutcome, Sprt, SprtStatus},
+    EvalKind, FinalSelection,
+};
+
+/// Names of the command line arguments configuring one agent.
+struct AgentArgs {
+    name: &'static str,
+    uct: &'static str,
+    fpu: &'static str,
+    bias: &'static str,
+    rave: &'static str,
+    eval: &'static str,
+    params: &'static str,
+    playouts: &'static str,
+    select: &'static str,
+    observed: &'static str,
+    stable: &'static str,
+    loss_check: &'static str,
+}
+
+const AGENT_A: AgentArgs = AgentArgs {
+    name: "a",
+    uct: "a-uct",
+    fpu: "a-fpu",
+    bias: "a-bias",
+    rave: "a-rave",
+    eval: "a-eval",
+    params: "a-params",
+    playouts: "a-playouts",
+    select: "a-select",
+    observed: "a-observed",
+    stable: "a-stable",
+    loss_check: "a-loss-check",
+};
+
+const AGENT_B: AgentArgs = AgentArgs {
+    name: "b",
+    uct: "b-uct",
+    fpu: "b-fpu",
+    bias: "b-bias",
+    rave: "b-rave",
+    eval: "b-eval",
+    params: "b-params",
+    playouts: "b-playouts",
+    select: "b-select",
+    observed: "b-observed",
+    stable: "b-stable",
+    loss_check: "b-loss-check",
+};
+
+fn agent_args(cmd: Command, args: &AgentArgs) -> Command {
+    cmd.arg(
+        Arg::new(args.uct)
+            .long(args.uct)
+            .value_parser(value_parser!(f64))
+            .default_value("0.7")
+            .help("Exploration constant of the UCT policy"),
+    )
+    .arg(
+        Arg::new(args.fpu)
+            .long(args.fpu)
+            .value_parser(value_parser!(f64))
+            .default_value("inf")
+            .help("Value of moves that haven't been visited yet"),
+    )
+    .arg(
+        Arg::new(args.bias)
+            .long(args.bias)
+            .value_parser(value_parser!(f64))
+            .default_value("0.2")
+            .help("Weight of the heuristic move score, fading with the visits of a move"),
+    )
+    .arg(
+        Arg::new(args.rave)
+            .long(args.rave)
+            .value_parser(value_parser!(f64))
+            .help("Blend all-moves-as-first values into the UCT policy with this bias"),
+    )
+    .arg(
+        Arg::new(args.eval)
+            .long(args.eval)
+            .value_parser(["basic", "full"])
+            .default_value("full")
+            .help("Evaluation function used for leaf nodes"),
+    )
+    .arg(
+        Arg::new(args.params)
+            .long(args.params)
+            .value_parser(value_parser!(PathBuf))
+            .help("File with evaluation weights in ron format"),
+    )
+    .arg(
+        Arg::new(args.playouts)
+            .long(args.playouts)
+            .value_parser(value_parser!(u64))
+            .default_value("1000")
+            .help("Playouts per move"),
+    )
+    .arg(
+        Arg::new(args.select)
+            .long(args.select)
+            .value_parser(["visits", "mean", "availability"])
+            .default_value("visits")
+            .help("How the move is picked after searching"),
+    )
+    .arg(
+        Arg::new(args.observed)
+            .long(args.observed)
+            .action(ArgAction::SetTrue)
+            .help("Evaluate playouts with the known cards instead of the sampled hands"),
+    )
+    .arg(
+        Arg::new(args.stable)
+            .long(args.stable)
+            .value_parser(value_parser!(f64))
+            .help(
+                "Stop searching once the visit share of the best move changes by at most this much",
+            ),
+    )
+    .arg(
+        Arg::new(args.loss_check)
+            .long(args.loss_check)
+            .action(ArgAction::SetTrue)
+            .help("Avoid moves the next opponent can answer by capturing a ball or winning"),
+    )
+}
+
+fn agent_config(matches: &ArgMatches, args: &AgentArgs, threads: usize) -> AgentConfig {
+    let mut agent = AgentConfig::new(args.name);
+    agent.uct_constant = *matches.get_one(args.uct).unwrap();
+    agent.fpu = *matches.get_one(args.fpu).unwrap();
+    agent.progressive_bias = *matches.get_one(args.bias).unwrap();
+    agent.rave_bias = matches.get_one(args.rave).copied();
+    agent.playouts = *matches.get_one(args.playouts).unwrap();
+    agent.eval = match matches.get_one::<String>(args.eval).unwrap().as_str() {
+        "basic" => EvalKind::Basic,
+        _ => EvalKind::Full,
+    };
+    agent.final_selection = match matches.get_one::<String>(args.select).unwrap().as_str() {
+        "mean" => FinalSelection::BestMean,
+        "availability" => FinalSelection::AvailabilityWeighted,
+        _ => FinalSelection::MostVisited,
+    };
+    if let Some(path) = matches.get_one::<PathBuf>(args.params) {
+        let params = std::fs::read_to_string(path).expect("Failed to read evaluation weights");
+        agent.params = ron::from_str(&params).expect("Failed to parse evaluation weights");
+    }
+    agent.observed_eval = matches.get_flag(args.observed);
+    agent.stable_stop = matches.get_one(args.stable).copied();
+    agent.loss_check = matches.get_flag(args.loss_check);
+    agent.threads = threads;
+    agent
+}
+
+fn main() {
+    let cmd = Command::new("robotac-arena")
+        .about("Plays games between two agents and reports their relative strength")
+        .arg(
+            Arg::new("games")
+                .long("games")
+                .short('n')
+                .value_parser(value_parser!(u64))
+                .default_value("100"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u64))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .value_parser(value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("max-plies")
+                .long("max-plies")
+                .value_parser(value_parser!(usize))
+                .default_value("2000"),
+        )
+        .arg(
+            Arg::new("sprt")
+                .long("sprt")
+                .action(ArgAction::SetTrue)
+                .help("Stop as soon as the SPRT reaches a decision"),
+        )
+        .arg(
+            Arg::new("elo0")
+                .long("elo0")
+                .value_parser(value_parser!(f64))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("elo1")
+                .long("elo1")
+                .value_parser(value_parser!(f64))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("histories")
+                .long("histories")
+                .value_parser(value_parser!(PathBuf))
+                .help("Directory to write the history of every game to"),
+        );
+    let matches = agent_args(agent_args(cmd, &AGENT_A), &AGENT_B).get_matches();
+
+    let threads = *matches.get_one("threads").unwrap();
+    let mut arena = Arena::new(
+        agent_config(&matches, &AGENT_A, threads),
+        agent_config(&matches, &AGENT_B, threads),
+    );
+    arena.seed = *matches.get_one("seed").unwrap();
+    arena.max_plies = *matches.get_one("max-plies").unwrap();
+    let sprt = Sprt {
+        elo0: *matches.get_one("elo0").unwrap(),
+        elo1: *matches.get_one("elo1").unwrap(),
+        ..Default::default()
+    };
+    let games = *matches.get_one("games").unwrap();
+    let histories = matches.get_one::<PathBuf>("histories");
+    if let Some(dir) = histories {
+        std::fs::create_dir_all(dir).expect("Failed to create history directory");
+    }
+
+    println!("{:?}\nvs\n{:?}", arena.first, arena.second);
+    let result = arena.run(
+        games,
+        matches.get_flag("sprt").then_some(sprt),
+        |game, outcome, result, history| {
+            if let Some(dir) = histories {
+                let path = dir.join(format!("game-{}.hist", game + 1));
+                std::fs::write(path, history.to_ron()).expect("Failed to write history");
+            }
+            let outcome = match outcome {
+                GameOutcome::Win => "win",
+                GameOutcome::Loss => "loss",
+                GameOutcome::Draw => "draw",
+            };
+            println!(
+                "game {}: {outcome} ({}-{}-{})",
+                game + 1,
+                result.wins,
+                result.losses,
+                result.draws
+            );
+        },
+    );
+
+    println!(
+        "\n{} games, +{} -{} ={}",
+        result.games(),
+        result.wins,
+        result.losses,
+        result.draws
+    );
+    println!(
+        "win rate {:.1}%, draw rate {:.1}%, score {:.3}",
+        result.win_rate() * 100.0,
+        result.draw_rate() * 100.0,
+        result.score()
+    );
+    match result.elo() {
+        Some((elo, margin)) => println!("elo {elo:+.1} +/- {margin:.1}"),
+        None => println!("elo not available"),
+    }
+    let (lower, upper) = sprt.bounds();
+    let status = match sprt.status(&result) {
+        SprtStatus::AcceptH0 => "H0 accepted",
+        SprtStatus::AcceptH1 => "H1 accepted",
+        SprtStatus::Continue => "inconclusive",
+    };
+    println!(
+        "sprt [{}, {}]: llr {:.2} ({lower:.2}, {upper:.2}) {status}",
+        sprt.elo0,
+        sprt.elo1,
+        result.llr(sprt.elo0, sprt.elo1)
+    );
+}
@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+use clap::{value_parser, Arg, Command};
+use robotac::{arena::AgentConfig, book::OpeningBook};
+
+fn main() {
+    let matches = Command::new("robotac-book")
+        .about("Searches the first deal of many games and writes the results as an opening book")
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .value_parser(value_parser!(PathBuf))
+                .default_value("book.ron")
+                .help("Book file, an existing book is extended"),
+        )
+        .arg(
+            Arg::new("games")
+                .long("games")
+                .short('n')
+                .value_parser(value_parser!(u64))
+                .default_value("100"),
+        )
+        .arg(
+            Arg::new("start")
+                .long("start")
+                .value_parser(value_parser!(u64))
+                .default_value("0")
+                .help("Seed of the first game"),
+        )
+        .arg(
+            Arg::new("playouts")
+                .long("playouts")
+                .value_parser(value_parser!(u64))
+                .default_value("20000")
+                .help("Playouts per position"),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .value_parser(value_parser!(usize))
+                .default_value("1"),
+        )
+        .get_matches();
+
+    let path = matches.get_one::<PathBuf>("output").unwrap();
+    let mut book = match std::fs::read_to_string(path) {
+        Ok(s) => match OpeningBook::from_ron(&s) {
+            Ok(book) => book,
+            Err(e) => {
+                eprintln!("failed to parse {}: {e}", path.display());
+                std::process::exit(1);
+            }
+        },
+        Err(_) => OpeningBook::default(),
+    };
+    let mut agent = AgentConfig::new("book");
+    agent.playouts = *matches.get_one("playouts").unwrap();
+    agent.threads = *matches.get_one("threads").unwrap();
+
+    let start = *matches.get_one::<u64>("start").unwrap();
+    let games = *matches.get_one::<u64>("games").unwrap();
+    for seed in start..start + games {
+        let searched = book.extend_with_game(seed, &agent);
+        println!(
+            "seed {seed}: searched {searched} positions, {} entries",
+            book.len()
+        );
+    }
+    if let Err(e) = std::fs::write(path, book.to_ron()) {
+        eprintln!("failed to write {}: {e}", path.display());
+        std::process::exit(1);
+    }
+    println!("wrote {} entries to {}", book.len(), path.display());
+}
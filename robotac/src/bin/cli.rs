@@ -0,0 +1,207 @@
+use std::{error::Error, path::PathBuf};
+
+use clap::{value_parser, Arg, ArgMatches, Command};
+use robotac::{
+    arena::AgentConfig,
+    board::Board,
+    history::{unix_time, Annotation, History},
+};
+use tac_types::{HomeSquare, TacMove, ALL_COLORS};
+
+/// Plays the moves of `history` on a new board, checking that every move is legal.
+fn replay(history: &History) -> Result<Board, Box<dyn Error>> {
+    let mut board = Board::new_with_seed(history.seed);
+    for (idx, mv) in history.moves.iter().enumerate() {
+        board
+            .try_play(mv)
+            .map_err(|e| format!("move {} `{mv}`: {e}", idx + 1))?;
+    }
+    Ok(board)
+}
+
+/// Reads a history written as RON or as a game record, see [`History::to_text`], and replays
+/// it to the position it leads to.
+fn read_history(path: &PathBuf) -> Result<(History, Board), Box<dyn Error>> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("can't read {}: {e}", path.display()))?;
+    let history = History::from_ron(&content).or_else(|_| History::from_text(&content))?;
+    let board = replay(&history)?;
+    Ok((history, board))
+}
+
+fn write_history(path: &PathBuf, history: &History) -> Result<(), Box<dyn Error>> {
+    std::fs::write(path, history.to_ron())
+        .map_err(|e| format!("can't write {}: {e}", path.display()))?;
+    Ok(())
+}
+
+/// Describes the position as plain text, every hand is shown.
+fn show(board: &Board) -> String {
+    let mut text = match board.game_result() {
+        Some(result) => format!("Move {}, won by {result:?}\n", board.move_count),
+        None => format!(
+            "Move {}, {:?} to move\n",
+            board.move_count,
+            board.current_player()
+        ),
+    };
+    if board.hand_to_play() != board.current_player() {
+        text += &format!("Plays the hand of {:?}\n", board.hand_to_play());
+    }
+    for color in ALL_COLORS {
+        let ring = board
+            .balls_with(color)
+            .iter()
+            .map(|sq| sq.0.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let home = HomeSquare::ALL
+            .iter()
+            .map(|pos| {
+                if board.home(color).is_free(*pos) {
+                    '.'
+                } else {
+                    'o'
+                }
+            })
+            .collect::<String>();
+        text += &format!(
+            "{color:?}: base {}, ring [{ring}], home {home}, hand {:?}\n",
+            board.num_base(color),
+            board.hand(color)
+        );
+    }
+    let flags = [
+        ("trade", board.need_trade()),
+        ("announce", board.need_announce()),
+        ("discard", board.force_discard()),
+        ("jester", board.jester_flag()),
+        ("devil", board.devil_flag()),
+    ]
+    .iter()
+    .filter_map(|(name, set)| set.then_some(*name))
+    .collect::<Vec<_>>();
+    if !flags.is_empty() {
+        text += &format!("Pending: {}\n", flags.join(", "));
+    }
+    text += &format!("Discarded: {:?}", board.discarded());
+    text
+}
+
+fn run(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let state = matches.get_one::<PathBuf>("state").unwrap();
+    let (command, args) = matches.subcommand().expect("A subcommand is required");
+    if command == "newgame" {
+        let seed = *args.get_one::<u64>("seed").unwrap();
+        return write_history(state, &History::new(seed));
+    }
+    let (mut history, mut board) = read_history(state)?;
+    match command {
+        "show" => println!("{}", show(&board)),
+        "moves" => {
+            for mv in board.get_moves(board.current_player()) {
+                println!("{mv}");
+            }
+        }
+        "play" => {
+            let mv = args.get_one::<String>("move").unwrap().parse::<TacMove>()?;
+            board.try_play(&mv)?;
+            history.push(
+                mv.canonical(),
+                Annotation {
+                    time: unix_time(),
+                    ..Annotation::default()
+                },
+            );
+            write_history(state, &history)?;
+        }
+        "ai" => {
+            let mut agent = AgentConfig::new("robotac-cli");
+            agent.playouts = *args.get_one("playouts").unwrap();
+            agent.threads = *args.get_one("threads").unwrap();
+            let (mv, search) = agent.search(&board).ok_or("There is no move to play")?;
+            println!("{mv}");
+            history.push(
+                mv,
+                Annotation {
+                    time: unix_time(),
+                    search,
+                    ..Annotation::default()
+                },
+            );
+            write_history(state, &history)?;
+        }
+        "save" => write_history(args.get_one::<PathBuf>("file").unwrap(), &history)?,
+        "load" => {
+            let (loaded, _) = read_history(args.get_one::<PathBuf>("file").unwrap())?;
+            write_history(state, &loaded)?;
+        }
+        _ => unreachable!("Unknown subcommand {command}"),
+    }
+    Ok(())
+}
+
+fn main() {
+    let file = || {
+        Arg::new("file")
+            .required(true)
+            .value_parser(value_parser!(PathBuf))
+    };
+    let matches = Command::new("robotac-cli")
+        .about("Plays a game one command at a time, the game is kept in a state file in between")
+        .arg(
+            Arg::new("state")
+                .long("state")
+                .global(true)
+                .value_parser(value_parser!(PathBuf))
+                .default_value("robotac-state.ron")
+                .help("History of the game that is being played"),
+        )
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("newgame").about("Starts a new game").arg(
+                Arg::new("seed")
+                    .long("seed")
+                    .value_parser(value_parser!(u64))
+                    .default_value("0"),
+            ),
+        )
+        .subcommand(Command::new("show").about("Prints the position with every hand"))
+        .subcommand(Command::new("moves").about("Prints the legal moves in move notation"))
+        .subcommand(
+            Command::new("play")
+                .about("Plays a move in move notation, e.g. 1:ENTER@K")
+                .arg(Arg::new("move").required(true)),
+        )
+        .subcommand(
+            Command::new("ai")
+                .about("Searches the position, plays the best move and prints it")
+                .arg(
+                    Arg::new("playouts")
+                        .long("playouts")
+                        .value_parser(value_parser!(u64))
+                        .default_value("1000"),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .long("threads")
+                        .value_parser(value_parser!(usize))
+                        .default_value("1"),
+                ),
+        )
+        .subcommand(
+            Command::new("save")
+                .about("Copies the game to a history file")
+                .arg(file()),
+        )
+        .subcommand(
+            Command::new("load")
+                .about("Continues the game of a history file or game record")
+                .arg(file()),
+        )
+        .get_matches();
+    if let Err(e) = run(&matches) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
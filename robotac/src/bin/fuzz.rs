@@ -0,0 +1,80 @@
+use clap::{value_parser, Arg, ArgAction, Command};
+use robotac::{
+    fuzz::{check_game, FuzzConfig},
+    rules::Ruleset,
+};
+
+fn main() {
+    let matches = Command::new("robotac-fuzz")
+        .about("Plays random games and checks the knowledge of every player after each move")
+        .arg(
+            Arg::new("start")
+                .long("start")
+                .value_parser(value_parser!(u64))
+                .default_value("0")
+                .help("First seed to play"),
+        )
+        .arg(
+            Arg::new("games")
+                .long("games")
+                .short('n')
+                .value_parser(value_parser!(u64))
+                .default_value("10000"),
+        )
+        .arg(
+            Arg::new("max-moves")
+                .long("max-moves")
+                .value_parser(value_parser!(usize))
+                .default_value("10000"),
+        )
+        .arg(
+            Arg::new("redetermine-every")
+                .long("redetermine-every")
+                .value_parser(value_parser!(usize))
+                .default_value("8")
+                .help("Moves between redeterminations, 0 to never redetermine"),
+        )
+        .arg(
+            Arg::new("rules")
+                .long("rules")
+                .help("Ruleset as RON, e.g. \"(seven_captures_own: false)\""),
+        )
+        .arg(
+            Arg::new("keep-going")
+                .long("keep-going")
+                .action(ArgAction::SetTrue)
+                .help("Continue with the next seed after a failure"),
+        )
+        .get_matches();
+
+    let start = *matches.get_one::<u64>("start").unwrap();
+    let games = *matches.get_one::<u64>("games").unwrap();
+    let redetermine_every = *matches.get_one::<usize>("redetermine-every").unwrap();
+    let rules = matches
+        .get_one::<String>("rules")
+        .map_or_else(Ruleset::default, |rules| {
+            ron::from_str(rules).expect("Rules should be valid RON")
+        });
+    let config = FuzzConfig {
+        max_moves: *matches.get_one("max-moves").unwrap(),
+        redetermine_every: (redetermine_every > 0).then_some(redetermine_every),
+        rules,
+    };
+    let keep_going = matches.get_flag("keep-going");
+    let mut failures = 0;
+    for seed in start..start + games {
+        if let Err(failure) = check_game(seed, &config) {
+            failures += 1;
+            println!("{failure}\n");
+            if !keep_going {
+                break;
+            }
+        }
+        if (seed - start + 1) % 100 == 0 {
+            println!("{} games, {failures} failures", seed - start + 1);
+        }
+    }
+    if failures > 0 {
+        std::process::exit(1);
+    }
+}
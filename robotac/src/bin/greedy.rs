@@ -0,0 +1,114 @@
+use std::{cmp::Ordering, path::PathBuf, time::Instant};
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use robotac::{
+    arena::{GameOutcome, GreedyArena},
+    eval::EvalParams,
+};
+
+fn params(matches: &ArgMatches, name: &str) -> EvalParams {
+    matches
+        .get_one::<PathBuf>(name)
+        .map_or_else(EvalParams::default, |path| {
+            let params = std::fs::read_to_string(path).expect("Failed to read evaluation weights");
+            ron::from_str(&params).expect("Failed to parse evaluation weights")
+        })
+}
+
+fn main() {
+    let matches = Command::new("robotac-greedy")
+        .about(
+            "Plays deterministic games between two sets of evaluation weights without searching, \
+             to compare weights quickly before running full matches",
+        )
+        .arg(
+            Arg::new("a-params")
+                .long("a-params")
+                .value_parser(value_parser!(PathBuf))
+                .help("File with evaluation weights in ron format, the defaults if not given"),
+        )
+        .arg(
+            Arg::new("b-params")
+                .long("b-params")
+                .value_parser(value_parser!(PathBuf))
+                .help("File with evaluation weights in ron format, the defaults if not given"),
+        )
+        .arg(
+            Arg::new("games")
+                .long("games")
+                .short('n')
+                .value_parser(value_parser!(u64))
+                .default_value("1000")
+                .help("Number of games, every deal is played twice with the seats swapped"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u64))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("max-plies")
+                .long("max-plies")
+                .value_parser(value_parser!(usize))
+                .default_value("2000"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .action(ArgAction::SetTrue)
+                .help("Print the outcome of every game"),
+        )
+        .get_matches();
+
+    let mut arena = GreedyArena::new(params(&matches, "a-params"), params(&matches, "b-params"));
+    arena.seed = *matches.get_one("seed").unwrap();
+    arena.max_plies = *matches.get_one("max-plies").unwrap();
+    let games = *matches.get_one("games").unwrap();
+    let verbose = matches.get_flag("verbose");
+
+    println!("{:?}\nvs\n{:?}", arena.first, arena.second);
+    let start = Instant::now();
+    let result = arena.run(games, |game, outcome, result| {
+        if verbose {
+            let outcome = match outcome {
+                GameOutcome::Win => "win",
+                GameOutcome::Loss => "loss",
+                GameOutcome::Draw => "draw",
+            };
+            println!(
+                "game {}: {outcome} ({}-{}-{})",
+                game + 1,
+                result.wins,
+                result.losses,
+                result.draws
+            );
+        }
+    });
+
+    println!(
+        "\n{} games in {:.1?}, +{} -{} ={}",
+        result.games(),
+        start.elapsed(),
+        result.wins,
+        result.losses,
+        result.draws
+    );
+    println!(
+        "win rate {:.1}%, draw rate {:.1}%, score {:.3}",
+        result.win_rate() * 100.0,
+        result.draw_rate() * 100.0,
+        result.score()
+    );
+    match result.elo() {
+        Some((elo, margin)) => println!("elo {elo:+.1} +/- {margin:.1}"),
+        None => println!("elo not available"),
+    }
+    let winner = match result.wins.cmp(&result.losses) {
+        Ordering::Greater => "a",
+        Ordering::Less => "b",
+        Ordering::Equal => "neither",
+    };
+    println!("better weights: {winner}");
+}
@@ -0,0 +1,86 @@
+use std::time::Instant;
+
+use clap::{value_parser, Arg, ArgAction, Command};
+use robotac::{board::Board, perft::POSITIONS};
+
+fn run(name: &str, board: &Board, depth: usize, expected: &[u64], divide: bool) -> bool {
+    if divide {
+        for (mv, count) in board.perft_divide(depth) {
+            println!("{mv}: {count}");
+        }
+    }
+    let mut ok = true;
+    for depth in 1..=depth {
+        let start = Instant::now();
+        let count = board.perft(depth);
+        let elapsed = start.elapsed();
+        let status = match expected.get(depth - 1) {
+            Some(expected) if *expected == count => "ok".to_string(),
+            Some(expected) => {
+                ok = false;
+                format!("MISMATCH, expected {expected}")
+            }
+            None => "no baseline".to_string(),
+        };
+        println!("{name} depth {depth}: {count} in {elapsed:.2?} ({status})");
+    }
+    ok
+}
+
+fn main() {
+    let matches = Command::new("robotac-perft")
+        .about("Counts move paths of known positions to verify move generation")
+        .arg(
+            Arg::new("depth")
+                .long("depth")
+                .short('d')
+                .value_parser(value_parser!(usize))
+                .default_value("4"),
+        )
+        .arg(
+            Arg::new("position")
+                .long("position")
+                .short('p')
+                .value_parser(POSITIONS.iter().map(|p| p.name).collect::<Vec<_>>())
+                .help("Only count this position, all known positions by default"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u64))
+                .conflicts_with("position")
+                .help("Count the start of the game dealt with this seed instead"),
+        )
+        .arg(
+            Arg::new("divide")
+                .long("divide")
+                .action(ArgAction::SetTrue)
+                .help("Print the count after every first move"),
+        )
+        .get_matches();
+
+    let depth = *matches.get_one("depth").unwrap();
+    let divide = matches.get_flag("divide");
+    let ok = if let Some(seed) = matches.get_one::<u64>("seed") {
+        run(
+            &format!("seed {seed}"),
+            &Board::new_with_seed(*seed),
+            depth,
+            &[],
+            divide,
+        )
+    } else {
+        let position = matches.get_one::<String>("position");
+        let mut ok = true;
+        // Every position is counted even if an earlier one fails
+        for p in POSITIONS {
+            if position.is_none_or(|name| name == p.name) {
+                ok &= run(p.name, &p.board(), depth, p.counts, divide);
+            }
+        }
+        ok
+    };
+    if !ok {
+        std::process::exit(1);
+    }
+}
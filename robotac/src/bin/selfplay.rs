@@ -0,0 +1,66 @@
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use clap::{value_parser, Arg, Command};
+use robotac::{arena::AgentConfig, selfplay::SelfPlay};
+
+fn main() {
+    let matches = Command::new("robotac-selfplay")
+        .about("Plays games of the AI against itself and writes the positions as JSON lines")
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .value_parser(value_parser!(PathBuf))
+                .required(true),
+        )
+        .arg(
+            Arg::new("games")
+                .long("games")
+                .short('n')
+                .value_parser(value_parser!(u64))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u64))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("playouts")
+                .long("playouts")
+                .value_parser(value_parser!(u64))
+                .default_value("1000")
+                .help("Playouts per move"),
+        )
+        .arg(
+            Arg::new("threads")
+                .long("threads")
+                .value_parser(value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("max-plies")
+                .long("max-plies")
+                .value_parser(value_parser!(usize))
+                .default_value("2000"),
+        )
+        .get_matches();
+
+    let mut agent = AgentConfig::new("selfplay");
+    agent.playouts = *matches.get_one("playouts").unwrap();
+    agent.threads = *matches.get_one("threads").unwrap();
+    let mut selfplay = SelfPlay::new(agent);
+    selfplay.max_plies = *matches.get_one("max-plies").unwrap();
+
+    let path = matches.get_one::<PathBuf>("output").unwrap();
+    let file = File::create(path).expect("Failed to create output file");
+    let written = selfplay
+        .write_games(
+            *matches.get_one("seed").unwrap(),
+            *matches.get_one("games").unwrap(),
+            BufWriter::new(file),
+        )
+        .expect("Failed to write samples");
+    println!("wrote {written} samples to {}", path.display());
+}
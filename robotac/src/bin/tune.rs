@@ -0,0 +1,139 @@
+use std::path::PathBuf;
+
+use clap::{value_parser, Arg, Command};
+use robotac::{
+    arena::{AgentConfig, Arena, GreedyArena},
+    eval::EvalParams,
+    tune::{Spsa, SpsaConfig, TUNABLE},
+};
+
+fn main() {
+    let matches = Command::new("robotac-tune")
+        .about("Tunes the evaluation weights with SPSA, writing the current weights after every iteration")
+        .arg(
+            Arg::new("start")
+                .long("start")
+                .value_parser(value_parser!(PathBuf))
+                .help("File with the weights to start from in ron format, the defaults if not given"),
+        )
+        .arg(
+            Arg::new("checkpoint")
+                .long("checkpoint")
+                .value_parser(value_parser!(PathBuf))
+                .default_value("tuned.ron")
+                .help("File the tuned weights are written to"),
+        )
+        .arg(
+            Arg::new("iterations")
+                .long("iterations")
+                .short('i')
+                .value_parser(value_parser!(u64))
+                .default_value("100"),
+        )
+        .arg(
+            Arg::new("resume")
+                .long("resume")
+                .value_parser(value_parser!(u64))
+                .default_value("0")
+                .help("Number of iterations already done, continues with the step sizes after them"),
+        )
+        .arg(
+            Arg::new("games")
+                .long("games")
+                .short('n')
+                .value_parser(value_parser!(u64))
+                .default_value("200")
+                .help("Games per iteration, every deal is played twice with the seats swapped"),
+        )
+        .arg(
+            Arg::new("playouts")
+                .long("playouts")
+                .value_parser(value_parser!(u64))
+                .default_value("0")
+                .help("Playouts per move, 0 plays the best rated move without searching"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u64))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("learning-rate")
+                .long("learning-rate")
+                .value_parser(value_parser!(f64))
+                .default_value("2.0"),
+        )
+        .arg(
+            Arg::new("perturbation")
+                .long("perturbation")
+                .value_parser(value_parser!(f64))
+                .default_value("0.2")
+                .help("How far weights are moved for a match, relative to their starting value"),
+        )
+        .get_matches();
+
+    let start = matches
+        .get_one::<PathBuf>("start")
+        .map_or_else(EvalParams::default, |path| {
+            let params = std::fs::read_to_string(path).expect("Failed to read evaluation weights");
+            ron::from_str(&params).expect("Failed to parse evaluation weights")
+        });
+    let seed: u64 = *matches.get_one("seed").unwrap();
+    let config = SpsaConfig {
+        learning_rate: *matches.get_one("learning-rate").unwrap(),
+        perturbation: *matches.get_one("perturbation").unwrap(),
+        seed,
+        ..SpsaConfig::default()
+    };
+    let mut spsa = Spsa::new(start, config);
+    spsa.iteration = *matches.get_one("resume").unwrap();
+    let games: u64 = *matches.get_one("games").unwrap();
+    let playouts: u64 = *matches.get_one("playouts").unwrap();
+    let checkpoint = matches.get_one::<PathBuf>("checkpoint").unwrap();
+
+    // Every iteration plays new deals
+    let play = |iteration: u64, plus: &EvalParams, minus: &EvalParams| {
+        let seed = seed + iteration * games.div_ceil(2);
+        if playouts == 0 {
+            let mut arena = GreedyArena::new(*plus, *minus);
+            arena.seed = seed;
+            arena.run(games, |_, _, _| {})
+        } else {
+            let agent = |name: &str, params: &EvalParams| AgentConfig {
+                params: *params,
+                playouts,
+                ..AgentConfig::new(name)
+            };
+            let mut arena = Arena::new(agent("plus", plus), agent("minus", minus));
+            arena.seed = seed;
+            arena.run(games, None, |_, _, _, _| {})
+        }
+    };
+    let tuned = spsa
+        .run(
+            *matches.get_one("iterations").unwrap(),
+            play,
+            Some(checkpoint),
+            |iteration| {
+                let result = iteration.result;
+                println!(
+                    "iteration {}: +{} -{} ={} {:?}",
+                    iteration.iteration,
+                    result.wins,
+                    result.losses,
+                    result.draws,
+                    iteration.params.tunable()
+                );
+            },
+        )
+        .expect("Failed to write checkpoint");
+
+    println!();
+    for (name, (start, tuned)) in TUNABLE
+        .iter()
+        .zip(start.tunable().into_iter().zip(tuned.tunable()))
+    {
+        println!("{name}: {start} -> {tuned}");
+    }
+}
@@ -1,31 +1,133 @@
-use std::{
-    cmp::Ordering,
-    ops::{BitOr, BitXor},
-    option::Option,
-};
+use std::{ops::RangeInclusive, option::Option};
 
 use arraydeque::{ArrayDeque, Wrapping};
 use itertools::Itertools;
-use rand::{rngs::StdRng, SeedableRng};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::{IteratorRandom, SliceRandom},
+    Rng, SeedableRng,
+};
+use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use tac_types::{
-    BitBoard, Card, Color, Deck, Hand, Home, Square, TacAction, TacMove, TacMoveResult, ALL_COLORS,
+    BitBoard, Card, Color, ColorPermutation, Deck, Distance, Hand, Home, HomeSquare, PackedTacMove,
+    PackedTacMoveResult, Square, TacAction, TacMove, TacMoveResult, ALL_COLORS, CARDS, NUM_COLORS,
+    NUM_TEAMS,
 };
 
-use crate::knowledge::Knowledge;
+use crate::{
+    eval::ColorEval,
+    knowledge::Knowledge,
+    rules::{OpeningAnnouncement, Ruleset},
+    trade,
+};
 
 // This is is choosen because the situation which needs the most lookup into past is:
 // Card - Jester - Tac - Tac - Tac - Tac - Tac
 // These are seven cards but we up it to eight so it's a power of two. The performance impact of this decision has not been measured
 const PAST_MOVES_LEN: usize = 8;
 
-#[derive(Clone)]
+/// Why a move can't be played, see [`Board::try_play`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlayError {
+    /// The game is already over
+    GameOver,
+    /// The move isn't played from the hand of the player whose turn it is
+    WrongPlayer { expected: Color, played_by: Color },
+    /// The player doesn't hold the card
+    IllegalCard(Card),
+    /// The card can't be played like this in the current position
+    IllegalAction(TacMove),
+}
+
+impl std::fmt::Display for PlayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::GameOver => write!(f, "The game is over"),
+            Self::WrongPlayer {
+                expected,
+                played_by,
+            } => write!(f, "It's the turn of {expected:?}, not {played_by:?}"),
+            Self::IllegalCard(card) => write!(f, "{card:?} isn't in the hand"),
+            Self::IllegalAction(mv) => write!(f, "Illegal move {mv}"),
+        }
+    }
+}
+
+impl std::error::Error for PlayError {}
+
+/// The team that won a finished game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameResult {
+    #[cfg(not(feature = "six-players"))]
+    BlackGreen,
+    #[cfg(not(feature = "six-players"))]
+    BlueRed,
+    #[cfg(feature = "six-players")]
+    BlackRed,
+    #[cfg(feature = "six-players")]
+    BlueYellow,
+    #[cfg(feature = "six-players")]
+    GreenPurple,
+}
+
+/// The results of all teams, indexed by [`Color::team`].
+#[cfg(not(feature = "six-players"))]
+pub const ALL_RESULTS: [GameResult; NUM_TEAMS] = [GameResult::BlackGreen, GameResult::BlueRed];
+#[cfg(feature = "six-players")]
+pub const ALL_RESULTS: [GameResult; NUM_TEAMS] = [
+    GameResult::BlackRed,
+    GameResult::BlueYellow,
+    GameResult::GreenPurple,
+];
+
+impl GameResult {
+    #[must_use]
+    pub fn from_winner(player: Color) -> Self {
+        ALL_RESULTS[player.team()]
+    }
+
+    /// Returns `true` if `player` is in the winning team.
+    #[must_use]
+    pub fn is_win_for(self, player: Color) -> bool {
+        self == Self::from_winner(player)
+    }
+}
+
+/// (De)serializes the past moves as a plain list, since `ArrayDeque` has no serde support.
+mod past_moves_serde {
+    use arraydeque::{ArrayDeque, Wrapping};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use tac_types::{TacMove, TacMoveResult};
+
+    use super::PAST_MOVES_LEN;
+
+    type PastMoves = ArrayDeque<(TacMove, Option<TacMoveResult>), PAST_MOVES_LEN, Wrapping>;
+
+    pub fn serialize<S: Serializer>(moves: &PastMoves, serializer: S) -> Result<S::Ok, S::Error> {
+        moves.iter().collect::<Vec<_>>().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PastMoves, D::Error> {
+        let moves = Vec::<(TacMove, Option<TacMoveResult>)>::deserialize(deserializer)?;
+        let mut past_moves = PastMoves::new();
+        for mv in moves {
+            past_moves.push_back(mv);
+        }
+        Ok(past_moves)
+    }
+}
+
+/// The complete game state. Serializing it captures everything needed to continue the game,
+/// including hands, deck and flags, independent of how the cards were dealt.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Board {
-    balls: [BitBoard; 4],
+    balls: [BitBoard; NUM_COLORS],
     player_to_move: Color,
-    homes: [Home; 4],
-    base: [u8; 4],
-    fresh: [bool; 4],
+    homes: [Home; NUM_COLORS],
+    base: [u8; NUM_COLORS],
+    fresh: [bool; NUM_COLORS],
     discard_flag: bool,
     jester_flag: bool,
     devil_flag: bool,
@@ -34,62 +136,222 @@ pub struct Board {
     deck_fresh_flag: bool,
     deck: Deck,
     discarded: Vec<Card>,
+    #[serde(with = "past_moves_serde")]
     past_moves: ArrayDeque<(TacMove, Option<TacMoveResult>), PAST_MOVES_LEN, Wrapping>,
-    hands: [Hand; 4],
-    traded: [Option<Card>; 4],
-    one_or_thirteen: [bool; 4],
+    hands: [Hand; NUM_COLORS],
+    traded: [Option<Card>; NUM_COLORS],
+    one_or_thirteen: [bool; NUM_COLORS],
+    /// Players that made their announcement of this deal, see [`OpeningAnnouncement::Declared`]
+    #[serde(default)]
+    announced: [bool; NUM_COLORS],
     pub move_count: u32,
     seed: u64,
     started: Color,
+    #[serde(default)]
+    rules: Ruleset,
+    /// Evaluation terms of each color with the balls and home they were computed for
+    #[serde(skip)]
+    pub(crate) eval_cache: [Option<(BitBoard, Home, ColorEval)>; NUM_COLORS],
+}
+
+/// Most cards a player holds, right after the first deal of a deck
+const MAX_HAND_SIZE: usize = 6;
+
+/// A state [`Board::check_invariants`] rejects because no game can reach it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// `color` doesn't have four balls between base, ring and home
+    Balls { color: Color, amount: usize },
+    /// Balls of two colors share a square
+    SharedSquare { square: Square },
+    /// More copies of `card` than there are in the deck
+    Cards { card: Card, amount: usize },
+    /// `player` holds more cards than a deal hands out
+    HandSize { player: Color, amount: usize },
+    /// Hands, trades and played cards don't add up to the cards of the last deal
+    Dealt { amount: usize, dealt: usize },
+    /// A flag is set although the move it belongs to wasn't played
+    Flag { flag: &'static str },
+    /// The announcement of `player` doesn't match the cards they were dealt
+    Announcement { player: Color },
+    /// A card is waiting for `player` outside of the trade phase
+    Trade { player: Color },
+    /// The ball of `color` is marked as moved but doesn't sit on its home square
+    Fresh { color: Color },
 }
 
-#[allow(dead_code)]
+impl std::fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Balls { color, amount } => write!(f, "{color:?} has {amount} balls"),
+            Self::SharedSquare { square } => write!(f, "several balls on {}", square.0),
+            Self::Cards { card, amount } => {
+                write!(f, "{amount} {card:?} but there are only {}", card.amount())
+            }
+            Self::HandSize { player, amount } => write!(f, "{player:?} holds {amount} cards"),
+            Self::Dealt { amount, dealt } => {
+                write!(f, "{amount} cards in this deal but {dealt} were dealt")
+            }
+            Self::Flag { flag } => write!(f, "{flag} flag set without its move"),
+            Self::Announcement { player } => {
+                write!(
+                    f,
+                    "{player:?} announced something else than they were dealt"
+                )
+            }
+            Self::Trade { player } => write!(f, "{player:?} gets a trade outside of trading"),
+            Self::Fresh { color } => write!(f, "{color:?} has no fresh ball on its home square"),
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
+
+/// How often [`Board::redetermine`] samples hands before accepting one that breaks the inferred limits.
+const REDETERMINE_ATTEMPTS: usize = 16;
+
+/// What an observer knows about the hand of another player, used by [`Board::redetermine`].
+struct HandConstraint {
+    player: Color,
+    /// Number of cards in hand
+    amount: usize,
+    /// Cards known to be in hand
+    required: Vec<Card>,
+    /// Upper bounds for the cards drawn randomly
+    limits: Vec<(Card, u8)>,
+}
+
+/// Compact representation of a [`Board`], used to store the states of an [`UndoStack`].
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct PackedBoard {
-    balls: [BitBoard; 4],
-    // homes: [Home; 4],
+    balls: [BitBoard; NUM_COLORS],
     // 4 bits per home
-    homes: u16,
-    // base: [u8; 4],
+    homes: u32,
     // 3 bits per base (max is four -> 100)
-    // 4 unused bits
-    base: u16,
-    // fresh: [bool; 4],
-    // + 4 bits
-    // discard_flag: bool,
-    // jester_flag: bool,
-    // devil_flag: bool,
-    // trade_flag: bool,
-    // + 1 bit each
-    // one_or_thirteen: [bool; 4],
-    // + 4 bits
-    // player_to_move: Color,
-    // + 2 bits
-    // -> 14 bits
-    flags: u16,
+    base: u32,
+    // fresh: 1 bit per color
+    // one_or_thirteen: 1 bit per color
+    // discard, jester, devil, trade, started and deck fresh flag: 1 bit each
+    // player_to_move, started: COLOR_BITS each
+    // announced: 1 bit per color
+    // -> 22 bits with four colors, 30 bits with six
+    flags: u32,
     // Could be improved?
     // Maybe enum map with u8 for each card which should be 18 * (u8 + u8) -> 18 * 2 bytes
-    // top_idx, times_dealt -> u8 each -> 2 bytes
-    // Total: 18 * 2 + 2 = 38 bytes, down from
     deck: Deck,
     // 24 u8, could be smallvec
     discarded: Vec<Card>,
-    past_moves: ArrayDeque<
-        (
-            tac_types::PackedTacMove,
-            Option<tac_types::PackedTacMoveResult>,
-        ),
-        PAST_MOVES_LEN,
-        Wrapping,
-    >,
-    // 1 card -> u8, 6 cards in hand -> 48 bits -> u64
-    // 1 card could be 5 bits so 6 cards -> 30 bits -> u32
-    // would be 96 bytes -> 16 (u32) or 32 (u64) bytes
-    hands: [u32; 4],
+    past_moves: ArrayDeque<(PackedTacMove, Option<PackedTacMoveResult>), PAST_MOVES_LEN, Wrapping>,
+    // 1 card -> 5 bits, 6 cards in hand -> 30 bits -> u32
+    // Empty slots have all bits set
+    hands: [u32; NUM_COLORS],
     // Can't be improved I think
-    traded: [Option<Card>; 4],
+    traded: [Option<Card>; NUM_COLORS],
     // This doesn't belong here
-    pub move_count: u32,
+    move_count: u32,
     seed: u64,
+    rules: Ruleset,
+}
+
+const EMPTY_CARD_SLOT: u32 = 0b1_1111;
+
+// Offsets of the packed flags
+#[allow(clippy::cast_possible_truncation)]
+const COLOR_BITS: u32 = usize::BITS - (NUM_COLORS - 1).leading_zeros();
+const ONE_OR_THIRTEEN_SHIFT: usize = NUM_COLORS;
+const FLAGS_SHIFT: usize = 2 * NUM_COLORS;
+#[allow(clippy::cast_possible_truncation)]
+const TO_MOVE_SHIFT: u32 = FLAGS_SHIFT as u32 + 6;
+const STARTED_SHIFT: u32 = TO_MOVE_SHIFT + COLOR_BITS;
+const ANNOUNCED_SHIFT: u32 = STARTED_SHIFT + COLOR_BITS;
+
+impl PackedBoard {
+    #[must_use]
+    pub fn unpack(&self) -> Board {
+        let flag = |bit: usize| self.flags >> bit & 1 == 1;
+        let colors =
+            |shift: u32| Color::from((self.flags >> shift & ((1 << COLOR_BITS) - 1)) as usize);
+        Board {
+            balls: self.balls,
+            player_to_move: colors(TO_MOVE_SHIFT),
+            homes: core::array::from_fn(|i| Home((self.homes >> (4 * i) & 0b1111) as u8)),
+            base: core::array::from_fn(|i| (self.base >> (3 * i) & 0b111) as u8),
+            fresh: core::array::from_fn(flag),
+            discard_flag: flag(FLAGS_SHIFT),
+            jester_flag: flag(FLAGS_SHIFT + 1),
+            devil_flag: flag(FLAGS_SHIFT + 2),
+            trade_flag: flag(FLAGS_SHIFT + 3),
+            started_flag: flag(FLAGS_SHIFT + 4),
+            deck_fresh_flag: flag(FLAGS_SHIFT + 5),
+            deck: self.deck.clone(),
+            discarded: self.discarded.clone(),
+            past_moves: self
+                .past_moves
+                .iter()
+                .map(|(mv, res)| (mv.unpack(), res.map(PackedTacMoveResult::unpack)))
+                .collect(),
+            hands: self.hands.map(|bits| {
+                (0..6)
+                    .map(|i| bits >> (5 * i) & EMPTY_CARD_SLOT)
+                    .take_while(|card| *card != EMPTY_CARD_SLOT)
+                    .map(|card| CARDS[card as usize])
+                    .collect()
+            }),
+            traded: self.traded,
+            one_or_thirteen: core::array::from_fn(|i| flag(ONE_OR_THIRTEEN_SHIFT + i)),
+            announced: core::array::from_fn(|i| flag(ANNOUNCED_SHIFT as usize + i)),
+            move_count: self.move_count,
+            seed: self.seed,
+            started: colors(STARTED_SHIFT),
+            rules: self.rules,
+            eval_cache: [None; NUM_COLORS],
+        }
+    }
+}
+
+/// Earlier states of a board that moves can be taken back to. It's kept apart from the
+/// [`Board`], so copies of the board for searching don't carry the states along.
+#[derive(Clone, Default)]
+pub struct UndoStack {
+    states: Vec<PackedBoard>,
+}
+
+impl UndoStack {
+    /// Save the state of `board` so it can be restored with `undo`.
+    pub fn push(&mut self, board: &Board) {
+        self.states.push(board.pack());
+    }
+
+    /// Restore the state saved by the last call to `push` into `board`.
+    /// Returns `false` if there is no state to restore.
+    pub fn undo(&mut self, board: &mut Board) -> bool {
+        let Some(previous) = self.states.pop() else {
+            return false;
+        };
+        *board = previous.unpack();
+        true
+    }
+
+    /// Drops the states saved after the first `len`.
+    pub fn truncate(&mut self, len: usize) {
+        self.states.truncate(len);
+    }
+
+    /// Drops every saved state.
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+
+    /// Returns the amount of states that can be restored with `undo`.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
 }
 
 impl Default for Board {
@@ -104,13 +366,20 @@ impl Board {
         Self::new_with_seed(0)
     }
 
+    #[must_use]
     pub fn new_with_seed(seed: u64) -> Self {
+        Self::new_with_rules(seed, Ruleset::default())
+    }
+
+    /// Creates a board which plays by the given house rules.
+    #[must_use]
+    pub fn new_with_rules(seed: u64, rules: Ruleset) -> Self {
         let mut s = Self {
-            balls: [BitBoard::EMPTY; 4],
+            balls: [BitBoard::EMPTY; NUM_COLORS],
             player_to_move: Color::Black,
-            homes: [Home::EMPTY; 4],
-            base: [4; 4],
-            fresh: [true; 4],
+            homes: [Home::EMPTY; NUM_COLORS],
+            base: [4; NUM_COLORS],
+            fresh: [true; NUM_COLORS],
             discard_flag: false,
             jester_flag: false,
             devil_flag: false,
@@ -120,17 +389,149 @@ impl Board {
             deck: Deck::default(),
             discarded: Vec::new(),
             past_moves: ArrayDeque::new(),
-            hands: [const { Vec::new() }; 4].map(Hand::new),
-            traded: [None; 4],
-            one_or_thirteen: [false; 4],
+            hands: [const { Vec::new() }; NUM_COLORS].map(Hand::new),
+            traded: [None; NUM_COLORS],
+            one_or_thirteen: [false; NUM_COLORS],
+            announced: [false; NUM_COLORS],
             move_count: 0,
             seed,
             started: Color::Black,
+            rules,
+            eval_cache: [None; NUM_COLORS],
         };
 
         s.deal_new();
+        s.update_eval_cache();
         s
     }
+    /// Plays random legal moves from a new game with `seed` until a random depth in `depth` is
+    /// reached, so the state is one a real game can get to. Stops early instead of ending the game.
+    #[must_use]
+    pub fn new_random_state(seed: u64, rules: Ruleset, depth: RangeInclusive<usize>) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let depth = rng.gen_range(depth);
+        let mut board = Self::new_with_rules(seed, rules);
+        for _ in 0..depth {
+            let moves = board.get_moves(board.current_player());
+            let Some(mv) = moves.into_iter().choose(&mut rng) else {
+                break;
+            };
+            let mut next = board.clone();
+            next.play(&mv);
+            if next.is_terminal() {
+                break;
+            }
+            board = next;
+        }
+        board
+    }
+
+    /// Checks properties every state reached by playing legal moves has.
+    /// # Errors
+    /// The first property the state breaks.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        for color in ALL_COLORS {
+            let amount = self.balls_with(color).len()
+                + self.home(color).amount() as usize
+                + self.num_base(color) as usize;
+            if amount != 4 {
+                return Err(InvariantViolation::Balls { color, amount });
+            }
+            let on_home = self.balls_with(color).has(color.home());
+            if !self.fresh(color) && !on_home {
+                return Err(InvariantViolation::Fresh { color });
+            }
+        }
+        for (first, second) in ALL_COLORS.into_iter().tuple_combinations() {
+            let shared = self.balls_with(first) & self.balls_with(second);
+            if let Some(square) = shared.try_next_square() {
+                return Err(InvariantViolation::SharedSquare { square });
+            }
+        }
+        for card in CARDS {
+            let amount = self
+                .hands
+                .iter()
+                .map(|hand| usize::from(hand.count(card)))
+                .sum::<usize>()
+                + self.traded.iter().filter(|c| **c == Some(card)).count()
+                + self.discarded.iter().filter(|c| **c == card).count()
+                + usize::from(self.deck.remaining(card));
+            if amount > usize::from(card.amount()) {
+                return Err(InvariantViolation::Cards { card, amount });
+            }
+        }
+        let amount = self.hands.iter().map(Hand::amount).sum::<usize>()
+            + self.traded.iter().flatten().count()
+            + self.discarded.len();
+        let dealt = self.deck.last_deal_size();
+        if amount != dealt {
+            return Err(InvariantViolation::Dealt { amount, dealt });
+        }
+        self.check_flags()?;
+        for player in ALL_COLORS {
+            let amount = self.hand(player).amount();
+            if amount > MAX_HAND_SIZE {
+                return Err(InvariantViolation::HandSize { player, amount });
+            }
+            if self.traded[player as usize].is_some() && !self.trade_flag {
+                return Err(InvariantViolation::Trade { player });
+            }
+            // Until the traded cards are handed out, hands only hold what was dealt
+            let holds_opening = self
+                .hand(player)
+                .iter()
+                .any(|c| matches!(c, Card::One | Card::Thirteen));
+            let must_announce = match self.rules.opening_announcement {
+                OpeningAnnouncement::Dealt => self.trade_flag && holds_opening,
+                OpeningAnnouncement::Silent | OpeningAnnouncement::Declared => false,
+            };
+            if must_announce && !self.one_or_thirteen[player as usize] {
+                return Err(InvariantViolation::Announcement { player });
+            }
+            if self.announced[player as usize]
+                && self.rules.opening_announcement != OpeningAnnouncement::Declared
+            {
+                return Err(InvariantViolation::Announcement { player });
+            }
+        }
+        Ok(())
+    }
+
+    /// Flags are only set by the move played last, trading happens before any card is played.
+    /// A suspended player that plays a Tac as a jester still has to discard afterwards.
+    fn check_flags(&self) -> Result<(), InvariantViolation> {
+        let last = self.past_moves.back().map(|(mv, _)| &mv.action);
+        let suspended = self
+            .past_moves
+            .iter()
+            .rev()
+            .find(|(mv, _)| !matches!(mv.action, TacAction::Jester))
+            .is_some_and(|(mv, _)| matches!(mv.action, TacAction::Suspend));
+        let flags = [
+            ("discard", self.discard_flag, suspended),
+            (
+                "jester",
+                self.jester_flag,
+                matches!(last, Some(TacAction::Jester)),
+            ),
+            (
+                "devil",
+                self.devil_flag,
+                matches!(last, Some(TacAction::Devil)),
+            ),
+            (
+                "trade",
+                self.trade_flag,
+                self.past_moves.is_empty() && self.discarded.is_empty(),
+            ),
+        ];
+        match flags.into_iter().find(|(_, set, allowed)| *set && !allowed) {
+            Some((flag, ..)) => Err(InvariantViolation::Flag { flag }),
+            None => Ok(()),
+        }
+    }
+
     /// Put ball from given player onto the board.
     /// Captures any ball that was on the starting position.
     #[must_use]
@@ -155,13 +556,13 @@ impl Board {
     }
 
     /// Move ball from `start` to `goal_pos`.
-    pub fn move_ball_to_goal(&mut self, start: Square, goal_pos: u8, color: Color) {
+    pub fn move_ball_to_goal(&mut self, start: Square, goal_pos: HomeSquare, color: Color) {
         self.unset(start, color);
         self.homes[color as usize].set(goal_pos);
     }
 
     /// Move ball that is in it's home from `start` to `end`.
-    pub fn move_ball_in_goal(&mut self, start: u8, end: u8, color: Color) {
+    pub fn move_ball_in_goal(&mut self, start: HomeSquare, end: HomeSquare, color: Color) {
         self.homes[color as usize].unset(start);
         self.homes[color as usize].set(end);
     }
@@ -271,6 +672,12 @@ impl Board {
         &self.hands[color as usize]
     }
 
+    /// Cards played or discarded since the current deal started, visible to every player.
+    #[must_use]
+    pub fn discarded(&self) -> &[Card] {
+        &self.discarded
+    }
+
     /// Returns `true` if the current player is forced to discard a card.
     #[must_use]
     pub fn force_discard(&self) -> bool {
@@ -287,11 +694,11 @@ impl Board {
         // Last move discard and move before suspend
         self.past_moves
             .back()
-            .map_or(false, |(mv, _)| matches!(mv.action, TacAction::Discard))
+            .is_some_and(|(mv, _)| matches!(mv.action, TacAction::Discard))
             && self
                 .past_moves
                 .get(len - 2)
-                .map_or(false, |(mv, _)| matches!(mv.action, TacAction::Suspend))
+                .is_some_and(|(mv, _)| matches!(mv.action, TacAction::Suspend))
     }
 
     /// Returns `true` if new round start.
@@ -312,14 +719,37 @@ impl Board {
         self.jester_flag
     }
 
+    /// Returns `true` if current player played devil and chooses the next move of the player
+    /// after them.
+    #[must_use]
+    pub fn devil_flag(&self) -> bool {
+        self.devil_flag
+    }
+
+    /// Returns the player whose hand the next move is played from.
+    /// After a devil the player to move chooses a card of the next player and how it is played.
+    #[must_use]
+    pub fn hand_to_play(&self) -> Color {
+        if self.devil_flag {
+            self.player_to_move.next()
+        } else {
+            self.player_to_move
+        }
+    }
+
     /// Checks if a ball at a given position can reach its home with a given amount.
     /// Returns the position in the goal if able to.
     #[must_use]
-    pub fn position_in_home(&self, start: Square, amount: u8, color: Color) -> Option<u8> {
-        let min_needed = start.distance_to_home(color) + 1;
+    pub fn position_in_home(
+        &self,
+        start: Square,
+        amount: Distance,
+        color: Color,
+    ) -> Option<HomeSquare> {
+        let min_needed = start.distance_to_home(color).steps() + 1;
         let home_free = self.homes[color as usize].free();
-        if (min_needed..min_needed + home_free).contains(&amount) {
-            Some(amount - min_needed)
+        if (min_needed..min_needed + home_free).contains(&amount.steps()) {
+            Some(HomeSquare::new(amount.steps() - min_needed))
         } else {
             None
         }
@@ -344,29 +774,8 @@ impl Board {
     /// Requires that `start` != `goal`
     #[must_use]
     pub fn can_move(&self, start: Square, goal: Square) -> bool {
-        if start == goal {
-            return false;
-        }
-        // TODO investigate if it is worth splitting the computation into two cases
-        // Case 1: start < goal
-        // Easy case where we just need to check bits between start and goal
-        // Case 2: start > goal
-        // Case we currently always do. Requires rotating bitboard instead of simply shifting
-
-        // Get the distance of the start to the zero square
-        let offset = start.0;
-        // Convert to bitboard representation
-        let start = start.bitboard();
-        let goal = goal.bitboard();
-
-        self.all_balls() // Need to check all balls for potential blockers
-            .bitor(goal) // Set goal bit
-            .bitxor(start) // Remove start bit
-            .rotate_right(offset) // Rotate by distance start bit has to the 0th bit
-            .next_square() // Get the next set bit. If we can_move this should be the goal bit
-            .bitboard() // Convert back to bitboard
-            .rotate_left(offset) // Rotate back
-            .eq(&goal) // Bit has same position as goal bit
+        // No ball may be in the way, the goal itself can be occupied
+        start != goal && self.all_balls().is_disjoint(BitBoard::between(start, goal))
     }
 
     /// Returns true if square is occupied
@@ -377,20 +786,23 @@ impl Board {
 
     /// Apply a `TacMove` to the current state
     pub fn play(&mut self, mv: &TacMove) {
+        // Tests set up positions no game reaches, only states that were valid have to stay valid
+        let valid_before = cfg!(debug_assertions) && self.check_invariants().is_ok();
+        // The move chosen after a devil is played from the hand of the next player
+        self.player_to_move = self.hand_to_play();
         self.jester_flag = false;
         self.devil_flag = false;
         self.started_flag = false;
         self.deck_fresh_flag = false;
         let player = self.player_to_move;
-        if matches!(mv.card, Card::Tac)
-            && !matches!(
-                mv.action,
-                TacAction::Discard | TacAction::Trade | TacAction::Jester
-            )
-        {
+        if replaced_move(mv) {
             self.tac_undo();
         }
-        if matches!(mv.action, TacAction::Trade) {
+        if let TacAction::Announce { opening } = mv.action {
+            self.one_or_thirteen[player as usize] = opening;
+            self.announced[player as usize] = true;
+            self.next_player();
+        } else if matches!(mv.action, TacAction::Trade) {
             self.trade(mv.card, player);
             if self.traded.iter().all(Option::is_some) {
                 self.take_traded();
@@ -412,32 +824,58 @@ impl Board {
 
             if self.hands.iter().all(Hand::is_empty) {
                 debug_assert!(!self.discard_flag);
+                // A jester played as the last card has no hands left to pass on
+                self.jester_flag = false;
                 self.deal_new();
                 self.past_moves.clear();
                 self.discarded.clear();
                 self.player_to_move = self.started.next();
                 self.started = self.player_to_move;
-            } else if !self.jester_flag {
+            } else if !(self.jester_flag || self.devil_flag) {
                 self.next_player();
             }
         }
         self.move_count += 1;
-        for c in ALL_COLORS {
-            debug_assert_eq!(
-                self.balls_with(c).len()
-                    + self.home(c).amount() as usize
-                    + self.num_base(c) as usize,
-                4,
-                "{:?} {:?} {:?} {:?} {:?}\n",
-                c,
-                self.balls_with(c).len(),
-                self.home(c).amount(),
-                self.num_base(c),
-                self
-            );
+        self.update_eval_cache();
+        if valid_before {
+            if let Err(violation) = self.check_invariants() {
+                panic!("{violation} after {mv}\n{self:?}");
+            }
         }
     }
 
+    /// Checks that `mv` can be played in the current position without playing it.
+    /// # Errors
+    /// The first rule `mv` breaks.
+    pub fn check_move(&self, mv: &TacMove) -> Result<(), PlayError> {
+        if self.is_terminal() {
+            return Err(PlayError::GameOver);
+        }
+        let expected = self.hand_to_play();
+        if mv.played_by != expected {
+            return Err(PlayError::WrongPlayer {
+                expected,
+                played_by: mv.played_by,
+            });
+        }
+        if !self.hand(expected).contains(mv.card) {
+            return Err(PlayError::IllegalCard(mv.card));
+        }
+        if !self.is_legal(mv) {
+            return Err(PlayError::IllegalAction(mv.clone().canonical()));
+        }
+        Ok(())
+    }
+
+    /// Plays `mv` like [`Board::play`] if it is legal.
+    /// # Errors
+    /// If `mv` can't be played, the board is left unchanged.
+    pub fn try_play(&mut self, mv: &TacMove) -> Result<(), PlayError> {
+        self.check_move(mv)?;
+        self.play(&mv.clone().canonical());
+        Ok(())
+    }
+
     pub fn apply_action(&mut self, action: TacAction, player: Color) -> Option<TacMoveResult> {
         match action {
             TacAction::Step { from, to } => {
@@ -456,15 +894,20 @@ impl Board {
             TacAction::Discard => self.discard_flag = false,
             TacAction::SevenSteps { steps } => {
                 // Move in home first, this is because `StepInHome` moves rely on this
-                // due to move generation
+                // due to move generation. Balls in home can move in both directions,
+                // so they are all lifted before any is put down again
                 for s in &steps {
-                    if let TacAction::StepHome { from, to } = s {
-                        self.move_ball_in_goal(*from, *to, player);
+                    if let TacAction::StepHome { from, .. } = s {
+                        self.homes[player as usize].unset(*from);
+                    };
+                }
+                for s in &steps {
+                    if let TacAction::StepHome { to, .. } = s {
+                        self.homes[player as usize].set(*to);
                     };
                 }
 
-                // Order steps to prevent capturing of balls that have to move
-                let mut board_steps = steps
+                let board_steps = steps
                     .iter()
                     .filter_map(|s| match s {
                         TacAction::Step { from, to } => Some((*from, *from, *to, None, false)),
@@ -473,37 +916,58 @@ impl Board {
                         }
                         _ => None,
                     })
-                    .sorted_by(|(_, s1, _, _, _), (_, s2, _, _, _)| {
-                        if s1.is_min() && s2.is_max() {
-                            Ordering::Less
-                        } else if s1.is_max() && s2.is_min() {
-                            Ordering::Greater
-                        } else {
-                            Ord::cmp(s1, s2).reverse()
-                        }
-                    })
                     .collect_vec();
+                // Order steps to prevent capturing of balls that have to move.
+                // Measured from a square no ball passes, balls further ahead move first
+                let covered = board_steps
+                    .iter()
+                    .fold(BitBoard::EMPTY, |acc, (s, _, e, _, _)| {
+                        acc | BitBoard::ray_from(*s, s.distance_to(*e).steps() + 1)
+                    });
+                let anchor = (!covered)
+                    .try_next_square()
+                    .expect("Seven steps can't cover the whole ring");
+                let mut board_steps = board_steps
+                    .into_iter()
+                    .sorted_by_key(|(s, _, _, _, _)| std::cmp::Reverse(anchor.distance_to(*s)))
+                    .collect_vec();
+                if !self.rules.seven_captures_own {
+                    return self.apply_seven_jumping_own(&board_steps, player);
+                }
                 let mut res: SmallVec<(Square, Color), 7> = SmallVec::new();
                 let mut change = true;
                 while change {
                     change = false;
-                    for (orig, s, e, g, _) in &mut board_steps {
+                    for idx in 0..board_steps.len() {
+                        let (_, s, e, g, _) = board_steps[idx];
                         // We are at the end
                         if s == e {
                             // Step in home
                             if let Some(goal) = g {
-                                self.move_ball_to_goal(*e, *goal, player);
-                                *g = None;
+                                self.move_ball_to_goal(e, goal, player);
+                                board_steps[idx].3 = None;
                                 change = true;
                             }
                         } else {
                             // Step one square forwards
-                            let next = s.add(1);
-                            if let Some(cap) = self.move_ball(*s, next, player) {
+                            let next = s.add(Distance::ONE);
+                            if let Some(cap) = self.move_ball(s, next, player) {
+                                // If we captured one of our own balls that already finished its steps,
+                                // store where it started so undoing can put it back there
+                                let square = if cap == player {
+                                    board_steps
+                                        .iter()
+                                        .find_map(|(orig, s, e, _, in_home)| {
+                                            (!in_home && *s == next && s == e).then_some(*orig)
+                                        })
+                                        .unwrap_or(next)
+                                } else {
+                                    next
+                                };
                                 // Store position and color if we captured
-                                res.push((next, cap));
+                                res.push((square, cap));
                             }
-                            *s = next;
+                            board_steps[idx].1 = next;
                             change = true;
                         }
                     }
@@ -512,28 +976,7 @@ impl Board {
                 if res.is_empty() {
                     return None;
                 }
-                let res = res
-                    .iter()
-                    .map(|(sq, c)| {
-                        (
-                            // If captured ball is same color as moving player,
-                            // adjust it in case it moved before being captured
-                            // TODO Maybe move this above because this doesnt handle, balls that didnt move
-                            if *c == player {
-                                board_steps
-                                    .iter()
-                                    .find_map(|(orig, s, _, _, in_home)| {
-                                        (!in_home && *sq == *s).then_some(*orig)
-                                    })
-                                    .unwrap_or(*sq)
-                            } else {
-                                *sq
-                            },
-                            *c,
-                        )
-                    })
-                    .collect_vec();
-                return Some(TacMoveResult::SevenCaptures(res.into()));
+                return Some(TacMoveResult::SevenCaptures(res));
             }
             TacAction::Warrior { from, to } => {
                 if from == to {
@@ -541,11 +984,55 @@ impl Board {
                 }
                 return self.move_ball(from, to, player).map(TacMoveResult::Capture);
             }
-            TacAction::Trade => {}
+            TacAction::Trade | TacAction::Announce { .. } => {}
         }
         None
     }
 
+    /// Moves the balls of a seven one after another, starting with the one furthest ahead.
+    /// Our own balls are only captured on the last square of a step, so the order of the
+    /// steps only matters for balls that already finished.
+    fn apply_seven_jumping_own(
+        &mut self,
+        board_steps: &[(Square, Square, Square, Option<HomeSquare>, bool)],
+        player: Color,
+    ) -> Option<TacMoveResult> {
+        let mut res: SmallVec<(Square, Color), 7> = SmallVec::new();
+        for (idx, (from, _, to, goal, _)) in board_steps.iter().enumerate() {
+            self.unset(*from, player);
+            let mut square = *from;
+            while square != *to {
+                square = square.add(Distance::ONE);
+                let passing = square != *to || goal.is_some();
+                match self.color_on(square) {
+                    Some(color) if color == player && passing => {}
+                    Some(color) => {
+                        // Put balls that already finished back to where they started when undoing
+                        let captured_at = if color == player {
+                            board_steps[..idx]
+                                .iter()
+                                .find_map(|(orig, _, e, _, in_home)| {
+                                    (!in_home && *e == square).then_some(*orig)
+                                })
+                                .unwrap_or(square)
+                        } else {
+                            square
+                        };
+                        let _ = self.capture(square);
+                        res.push((captured_at, color));
+                    }
+                    None => {}
+                }
+            }
+            if let Some(goal) = goal {
+                self.homes[player as usize].set(*goal);
+            } else {
+                self.set(*to, player);
+            }
+        }
+        (!res.is_empty()).then_some(TacMoveResult::SevenCaptures(res))
+    }
+
     pub fn undo_action(
         &mut self,
         action: TacAction,
@@ -613,13 +1100,9 @@ impl Board {
                 }
 
                 let mut moved = Vec::new();
-                for s in steps.into_iter() {
+                for s in steps {
                     match s {
-                        TacAction::Step { from, .. } => {
-                            self.set(from, player);
-                            moved.push(from);
-                        }
-                        TacAction::StepInHome { from, .. } => {
+                        TacAction::Step { from, .. } | TacAction::StepInHome { from, .. } => {
                             self.set(from, player);
                             moved.push(from);
                         }
@@ -631,15 +1114,14 @@ impl Board {
                     for (square, color) in captures {
                         // color == player already handled above
                         self.base[color as usize] -= 1;
-                        if color != player {
-                            self.set(square, color);
-                        } else if !moved.contains(&square) {
+                        if color != player || !moved.contains(&square) {
                             self.set(square, color);
                         }
                     }
                 }
             }
             TacAction::Trade => unreachable!("Can't undo trading"),
+            TacAction::Announce { .. } => unreachable!("Can't undo announcing"),
         }
     }
 
@@ -660,11 +1142,9 @@ impl Board {
         // TODO handle play for here
         let (mv, captured) = stored.last().unwrap();
         self.undo_action(mv.action.clone(), mv.played_for, captured.clone());
-        if matches!(mv.card, Card::Tac) {
-            self.tac_undo_recursive(
-                (!matches!(mv.action, TacAction::Discard)).then_some(true),
-                self.player_to_move.prev(),
-            );
+        // A discarded Tac didn't replace anything
+        if replaced_move(mv) {
+            self.tac_undo_recursive(true, self.player_to_move.prev());
         }
         // Push back when we are done
         for e in stored {
@@ -672,36 +1152,181 @@ impl Board {
         }
     }
 
-    fn tac_undo_recursive(&mut self, redo: Option<bool>, player: Color) {
+    fn tac_undo_recursive(&mut self, redo: bool, player: Color) {
         let (mv, captured) = self
             .past_moves
             .pop_back() // Pop here so recursive tac works
             .expect("Undo only ever called with past_moves non-empty");
-        if let Some(redo) = redo {
-            if redo {
-                self.apply_action(mv.action.clone(), mv.played_for);
-            } else {
-                self.undo_action(mv.action.clone(), mv.played_for, captured.clone());
-            }
+        // Jesters are skipped by a Tac, the move before them is the one that was replaced
+        if matches!(mv.action, TacAction::Jester) {
+            self.tac_undo_recursive(redo, player);
+            self.past_moves.push_back((mv, captured));
+            return;
         }
-
-        if matches!(mv.card, Card::Tac) {
-            if let Some(redo) = redo {
-                self.tac_undo_recursive(Some(!redo), player.prev());
-            } else {
-                self.tac_undo_recursive(None, player.prev());
+        if redo {
+            // A tac was played on the state before the move it replaced,
+            // so that move has to be undone before we can redo the tac
+            if replaced_move(&mv) {
+                self.tac_undo_recursive(false, player.prev());
+            }
+            self.apply_action(mv.action.clone(), mv.played_for);
+            // The suspended player already had their turn, nobody has to discard again
+            if matches!(mv.action, TacAction::Suspend) {
+                self.discard_flag = false;
+            }
+        } else {
+            // After undoing a tac the move it replaced is valid again
+            self.undo_action(mv.action.clone(), mv.played_for, captured.clone());
+            if replaced_move(&mv) {
+                self.tac_undo_recursive(true, player.prev());
             }
         }
         // Push back when we are done
         self.past_moves.push_back((mv, captured));
     }
 
-    /// Set card to be traded
+    /// Returns a compact copy of this board without the undo stack.
+    #[must_use]
+    pub fn pack(&self) -> PackedBoard {
+        let bits = |values: [bool; NUM_COLORS]| {
+            values
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, v)| acc | u32::from(*v) << i)
+        };
+        let flags = bits(self.fresh)
+            | bits(self.one_or_thirteen) << ONE_OR_THIRTEEN_SHIFT
+            | u32::from(self.discard_flag) << FLAGS_SHIFT
+            | u32::from(self.jester_flag) << (FLAGS_SHIFT + 1)
+            | u32::from(self.devil_flag) << (FLAGS_SHIFT + 2)
+            | u32::from(self.trade_flag) << (FLAGS_SHIFT + 3)
+            | u32::from(self.started_flag) << (FLAGS_SHIFT + 4)
+            | u32::from(self.deck_fresh_flag) << (FLAGS_SHIFT + 5)
+            | (self.player_to_move as u32) << TO_MOVE_SHIFT
+            | (self.started as u32) << STARTED_SHIFT
+            | bits(self.announced) << ANNOUNCED_SHIFT;
+        PackedBoard {
+            balls: self.balls,
+            homes: self
+                .homes
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, h)| acc | u32::from(h.0) << (4 * i)),
+            base: self
+                .base
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, b)| acc | u32::from(*b) << (3 * i)),
+            flags,
+            deck: self.deck.clone(),
+            discarded: self.discarded.clone(),
+            past_moves: self
+                .past_moves
+                .iter()
+                .map(|(mv, res)| {
+                    (
+                        PackedTacMove::pack(mv),
+                        res.as_ref().map(PackedTacMoveResult::pack),
+                    )
+                })
+                .collect(),
+            hands: self.hands.each_ref().map(|hand| {
+                let mut cards = hand.iter();
+                (0..6).fold(0, |acc, i| {
+                    let card = cards.next().map_or(EMPTY_CARD_SLOT, |c| *c as u32);
+                    acc | card << (5 * i)
+                })
+            }),
+            traded: self.traded,
+            move_count: self.move_count,
+            seed: self.seed,
+            rules: self.rules,
+        }
+    }
+
+    /// Returns the board with every color rotated by `perm`.
+    /// The seed stays the same, so later deals can hand out different cards than on this board.
+    #[must_use]
+    pub fn permuted(&self, perm: ColorPermutation) -> Self {
+        Self {
+            balls: perm.array(self.balls.map(|balls| perm.bitboard(balls))),
+            player_to_move: perm.color(self.player_to_move),
+            homes: perm.array(self.homes),
+            base: perm.array(self.base),
+            fresh: perm.array(self.fresh),
+            discard_flag: self.discard_flag,
+            jester_flag: self.jester_flag,
+            devil_flag: self.devil_flag,
+            trade_flag: self.trade_flag,
+            started_flag: self.started_flag,
+            deck_fresh_flag: self.deck_fresh_flag,
+            deck: self.deck.clone(),
+            discarded: self.discarded.clone(),
+            past_moves: self
+                .past_moves
+                .iter()
+                .map(|(mv, res)| (mv.permuted(perm), res.as_ref().map(|r| r.permuted(perm))))
+                .collect(),
+            hands: perm.array(self.hands.clone()),
+            traded: perm.array(self.traded),
+            one_or_thirteen: perm.array(self.one_or_thirteen),
+            announced: perm.array(self.announced),
+            move_count: self.move_count,
+            seed: self.seed,
+            started: perm.color(self.started),
+            rules: self.rules,
+            eval_cache: [None; NUM_COLORS],
+        }
+    }
+
+    /// Rotates the colors so that Black is to move. Boards that only differ by rotating
+    /// all colors have the same canonical board. The permutation turns colors of this board
+    /// into colors of the canonical board, its inverse turns them back.
+    #[must_use]
+    pub fn canonicalize(&self) -> (Board, ColorPermutation) {
+        let perm = ColorPermutation::between(self.player_to_move, Color::Black);
+        (self.permuted(perm), perm)
+    }
+
+    /// Hash of the canonical board, equal for boards that only differ by rotating all colors.
+    #[must_use]
+    pub fn canonical_hash(&self) -> u64 {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+        let mut hasher = DefaultHasher::new();
+        self.canonicalize().0.pack().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Commits `card` for trading. The others only learn about it once every player
+    /// committed a card and the cards are handed out at the same time, see [`Self::take_traded`].
     pub fn trade(&mut self, card: Card, player: Color) {
         self.hands[player as usize].remove(card);
         self.traded[player.partner() as usize] = Some(card);
     }
 
+    /// Returns true if `player` committed the card they give to their partner.
+    #[must_use]
+    pub fn trade_committed(&self, player: Color) -> bool {
+        self.traded[player.partner() as usize].is_some()
+    }
+
+    /// Returns true if the next trade completes the trade phase and hands out the traded cards.
+    #[must_use]
+    pub fn last_trade(&self) -> bool {
+        self.need_trade()
+            && ALL_COLORS
+                .into_iter()
+                .filter(|player| !self.trade_committed(*player))
+                .count()
+                == 1
+    }
+
+    /// Card `player` receives once every player traded
+    #[must_use]
+    pub fn incoming_trade(&self, player: Color) -> Option<Card> {
+        self.traded[player as usize]
+    }
+
     /// Put each traded card into the hand they belong to
     pub fn take_traded(&mut self) {
         self.trade_flag = false;
@@ -717,7 +1342,22 @@ impl Board {
     /// Returns true if we are in trade phase
     #[must_use]
     pub fn need_trade(&self) -> bool {
-        self.trade_flag && self.traded[self.player_to_move.partner() as usize].is_none()
+        self.trade_flag && !self.need_announce() && !self.trade_committed(self.player_to_move)
+    }
+
+    /// Returns true if the player to move still has to announce whether they can open.
+    /// Only happens with [`OpeningAnnouncement::Declared`], before trading.
+    #[must_use]
+    pub fn need_announce(&self) -> bool {
+        self.rules.opening_announcement == OpeningAnnouncement::Declared
+            && self.trade_flag
+            && !self.announced[self.player_to_move as usize]
+    }
+
+    /// Returns true if `player` made their announcement of the current deal.
+    #[must_use]
+    pub fn announced(&self, player: Color) -> bool {
+        self.announced[player as usize]
     }
 
     /// Begin trade phase
@@ -731,15 +1371,20 @@ impl Board {
         let mut rng = StdRng::seed_from_u64(self.seed);
         let dealt_cards = self.deck.deal(&mut rng);
         self.deck_fresh_flag = self.deck.fresh();
-        for set in dealt_cards.chunks_exact(4) {
+        for set in dealt_cards.chunks_exact(NUM_COLORS) {
             for (cidx, card) in set.iter().enumerate() {
                 self.hands[cidx].push(*card);
             }
         }
-        self.one_or_thirteen = self
-            .hands
-            .clone()
-            .map(|h| h.iter().any(|c| matches!(c, Card::One | Card::Thirteen)));
+        // Declared announcements are only known once they are made
+        self.one_or_thirteen = match self.rules.opening_announcement {
+            OpeningAnnouncement::Declared => [false; NUM_COLORS],
+            _ => self
+                .hands
+                .clone()
+                .map(|h| h.iter().any(|c| matches!(c, Card::One | Card::Thirteen))),
+        };
+        self.announced = [false; NUM_COLORS];
         self.started_flag = true;
         self.begin_trade();
     }
@@ -758,75 +1403,226 @@ impl Board {
         }
     }
 
-    pub fn redetermine(&mut self, observer: Color, knowledge: &Knowledge) {
-        // let mut rng = StdRng::seed_from_u64(self.seed);
-        let mut rng = rand::thread_rng();
-        let observer_hand = self.hand(observer).clone();
-        // Store hand count first
-        let amounts = ALL_COLORS
+    /// The hand `observer` sees besides its own, which is the hand it chooses a card from
+    /// after playing a devil.
+    fn revealed_hand(&self, observer: Color) -> Option<Color> {
+        (self.devil_flag && observer == self.player_to_move).then(|| self.hand_to_play())
+    }
+
+    /// Cards each player except `observer` and the hand it sees after a devil has to hold and limits for the cards drawn randomly.
+    fn hand_constraints(&self, observer: Color, knowledge: &Knowledge) -> Vec<HandConstraint> {
+        let traded = knowledge
+            .traded_away()
+            .map(|card| (knowledge.traded_card_holder(), card));
+        let revealed = self.revealed_hand(observer);
+        ALL_COLORS
             .into_iter()
-            .filter_map(|player| {
-                let exact_sum = knowledge
-                    .known_cards(player)
-                    .into_iter()
-                    .fold(0, |acc, (_, amount, exact)| {
-                        acc + if exact { amount } else { 0 }
-                    });
-                debug_assert!(self.hand(player).amount() >= exact_sum as usize);
-                (player != observer)
-                    .then_some((player, self.hand(player).amount() - exact_sum as usize))
+            .filter(|player| *player != observer && Some(*player) != revealed)
+            .map(|player| {
+                let mut required: Vec<Card> = Vec::new();
+                let mut limits = Vec::new();
+                for (card, amount, exact) in knowledge.known_cards(player) {
+                    if exact {
+                        required.extend(std::iter::repeat_n(card, amount as usize));
+                        limits.push((card, 0));
+                    } else {
+                        limits.push((card, amount));
+                    }
+                }
+                // The card we traded away stays with its holder until it gets played
+                if let Some((_, card)) = traded.filter(|(holder, _)| *holder == player) {
+                    let in_hand = self.traded[player as usize].is_none();
+                    let known = required.iter().position(|c| *c == card);
+                    match (in_hand, known) {
+                        // Still waiting for the other trades, knowledge already counts it
+                        (false, Some(idx)) => {
+                            required.swap_remove(idx);
+                        }
+                        (true, None) => required.push(card),
+                        _ => {}
+                    }
+                }
+                // Traded cards are only handed out after everyone traded,
+                // until then players that were dealt no opening can't hold one
+                if self.trade_flag
+                    && self.rules.opening_announcement == OpeningAnnouncement::Dealt
+                    && !self.openings()[player as usize]
+                {
+                    for card in [Card::One, Card::Thirteen] {
+                        limits.retain(|(c, _)| *c != card);
+                        limits.push((card, 0));
+                    }
+                }
+                let amount = self.hand(player).amount();
+                debug_assert!(required.len() <= amount, "{player:?} {required:?}");
+                required.truncate(amount);
+                HandConstraint {
+                    player,
+                    amount,
+                    required,
+                    limits,
+                }
             })
+            .collect_vec()
+    }
+
+    /// Cards the others committed for trading are hidden from `observer`. They are put back
+    /// into the hand of the player who committed them, so they are drawn together with it.
+    /// Returns the players whose trades were taken back.
+    fn take_back_hidden_trades(&mut self, observer: Color) -> Vec<Color> {
+        let committed = ALL_COLORS
+            .into_iter()
+            .filter(|player| *player != observer && self.trade_committed(*player))
             .collect_vec();
+        for player in &committed {
+            let card = self.traded[player.partner() as usize]
+                .take()
+                .expect("The trade was committed");
+            self.hands[*player as usize].push(card);
+        }
+        committed
+    }
+
+    /// Commits the card each of `players` most likely gives away.
+    fn commit_likely_trades(&mut self, players: &[Color]) {
+        for player in players {
+            let knowledge = trade::announced_knowledge(self, *player);
+            let (card, _) = self.best_trades(*player, &knowledge)[0];
+            self.trade(card, *player);
+        }
+    }
+
+    /// Resamples the hands of every player except `observer`.
+    /// Cards known to be in a hand are kept, the rest is drawn from the unseen cards
+    /// without exceeding the upper bounds the observer inferred. Cards are handed out in
+    /// proportion to [`Knowledge::hand_distribution`].
+    #[allow(clippy::cast_precision_loss)]
+    pub fn redetermine<R: Rng>(&mut self, observer: Color, knowledge: &Knowledge, rng: &mut R) {
+        let observer_hand = self.hand(observer).clone();
+        let committed = self.take_back_hidden_trades(observer);
+        // Knowledge only learns about a new deal with the next move, until then the
+        // announcements are all that is known about the new hands
+        let fresh;
+        let knowledge = if self.just_started() {
+            fresh = Knowledge::new_from_board(observer, self);
+            &fresh
+        } else {
+            knowledge
+        };
+        let revealed = self.revealed_hand(observer);
+        let constraints = self.hand_constraints(observer, knowledge);
 
         // Put back cards in hand back into deck
         for player in ALL_COLORS {
-            if player == observer {
+            if player == observer || Some(player) == revealed {
                 continue;
             }
-            for card in self.hands[player as usize].0.drain(..) {
-                self.deck.put_back(card);
+            for card in self.hands[player as usize].iter() {
+                self.deck.put_back(*card);
             }
+            self.hands[player as usize].clear();
             debug_assert!(self.hands[player as usize].is_empty());
         }
 
-        // Draw cards equal to the amount put back
-        for (player, amount) in amounts {
-            debug_assert!(player != observer);
-            let hand = &mut self.hands[player as usize];
-            let mut known = knowledge.known_cards(player);
-            for (card, amnt, is_exact) in &mut known {
-                if *is_exact {
-                    (0..*amnt).for_each(|_| {
-                        self.deck.take(*card);
-                        hand.push(*card);
-                    });
-                    *amnt = 0;
-                }
+        // Hand out all known cards first so random draws can't take them away
+        for constraint in &constraints {
+            for card in &constraint.required {
+                self.deck.take(*card);
+                self.hands[constraint.player as usize].push(*card);
             }
-            (0..amount).for_each(|_| {
-                let mut drawn = self.deck.draw_one(&mut rng);
-                while known.iter().any(|(c, a, _)| *c == drawn && *a == 0) {
-                    self.deck.put_back(drawn);
-                    drawn = self.deck.draw_one(&mut rng);
+        }
+        // Deal the remaining cards one by one, cards which fewest players can hold go first so
+        // tight limits can still be met. Cards nobody gets stay in the deck.
+        let mut pool = self.deck.cards().collect_vec();
+        let free_slots = constraints
+            .iter()
+            .map(|c| c.amount - c.required.len())
+            .collect_vec();
+        let factors = constraints
+            .iter()
+            .map(|c| CARDS.map(|card| knowledge.evidence_factor(c.player, card)))
+            .collect_vec();
+        let allows = |limits: &[(Card, u8)], card: Card| {
+            limits.iter().all(|(c, amount)| *c != card || *amount > 0)
+        };
+        let mut dealt = vec![Vec::new(); constraints.len()];
+        for _ in 0..REDETERMINE_ATTEMPTS {
+            pool.shuffle(rng);
+            pool.sort_by_cached_key(|card| {
+                constraints
+                    .iter()
+                    .filter(|c| allows(&c.limits, *card))
+                    .count()
+            });
+            let mut limits = constraints.iter().map(|c| c.limits.clone()).collect_vec();
+            let mut slots = free_slots.clone();
+            let mut in_deck = pool.len() - slots.iter().sum::<usize>();
+            dealt.iter_mut().for_each(Vec::clear);
+            let mut consistent = true;
+            for card in &pool {
+                let mut weights = (0..slots.len())
+                    .map(|i| {
+                        if allows(&limits[i], *card) {
+                            slots[i] as f64 * factors[i][*card as usize]
+                        } else {
+                            0.0
+                        }
+                    })
+                    .chain([in_deck as f64])
+                    .collect_vec();
+                // Contradicting knowledge shouldn't stop the search, ignore the limits then
+                if weights.iter().all(|w| *w <= 0.0) {
+                    consistent = false;
+                    weights = slots
+                        .iter()
+                        .chain([&in_deck])
+                        .map(|w| *w as f64)
+                        .collect_vec();
                 }
-                if let Some((_, a, _)) = known.iter_mut().find(|(c, _, _)| *c == drawn) {
-                    debug_assert!(*a > 0);
-                    *a -= 1;
+                let choice = WeightedIndex::new(&weights)
+                    .expect("Every card has a place")
+                    .sample(rng);
+                if choice == slots.len() {
+                    in_deck -= 1;
+                    continue;
                 }
-
-                hand.push(drawn);
-            });
-            // debug_assert!(hand.amount() == amount + knowledge.known_cards(player).len());
+                slots[choice] -= 1;
+                if let Some((_, amount)) = limits[choice].iter_mut().find(|(c, _)| c == card) {
+                    *amount = amount.saturating_sub(1);
+                }
+                dealt[choice].push(*card);
+            }
+            if consistent {
+                break;
+            }
         }
+        for (constraint, cards) in constraints.iter().zip(dealt) {
+            for card in cards {
+                self.deck.take(card);
+                self.hands[constraint.player as usize].push(card);
+            }
+        }
+        self.commit_likely_trades(&committed);
         debug_assert!(self
             .hand(observer)
             .iter()
             .all(|c| { observer_hand.iter().any(|c2| c2 == c) }));
     }
 
+    /// Which players announced to hold an opening card after the last deal.
+    /// Without announcements every player might have one.
+    /// Declared announcements are `false` until they are made and might not be true.
+    #[must_use]
+    pub fn openings(&self) -> [bool; NUM_COLORS] {
+        match self.rules.opening_announcement {
+            OpeningAnnouncement::Dealt | OpeningAnnouncement::Declared => self.one_or_thirteen,
+            OpeningAnnouncement::Silent => [true; NUM_COLORS],
+        }
+    }
+
     #[must_use]
-    pub fn openings(&self) -> [bool; 4] {
-        self.one_or_thirteen
+    pub fn rules(&self) -> &Ruleset {
+        &self.rules
     }
 
     #[must_use]
@@ -839,13 +1635,43 @@ impl Board {
         self.home(player).is_full() && self.home(player.partner()).is_full()
     }
 
+    /// Returns the result of the game or `None` if it is still ongoing.
+    /// A winning move only ends the game if the next player can't take it back with a tac.
+    #[must_use]
+    pub fn game_result(&self) -> Option<GameResult> {
+        if self.need_announce() || self.need_trade() {
+            // The previous round was finished without the win getting undone
+            return ALL_COLORS[..NUM_TEAMS]
+                .iter()
+                .copied()
+                .find(|c| self.won(*c))
+                .map(GameResult::from_winner);
+        }
+        let player = self.current_player();
+        if !self.won(player.prev()) {
+            return None;
+        }
+        let can_undo_win =
+            self.hand(player).contains(Card::Tac) && !self.tac_moves(player).is_empty();
+        (!can_undo_win).then(|| GameResult::from_winner(player.prev()))
+    }
+
+    #[must_use]
+    pub fn is_terminal(&self) -> bool {
+        self.game_result().is_some()
+    }
+
     #[cfg(test)]
     pub fn set_player(&mut self, player: Color) {
         self.player_to_move = player;
     }
     #[cfg(test)]
     pub fn add_hand(&mut self, player: Color, card: Card) {
-        self.hands[player as usize].0.push(card);
+        self.hands[player as usize].push(card);
+    }
+    #[cfg(test)]
+    pub fn clear_hand(&mut self, player: Color) {
+        self.hands[player as usize] = Hand::new(Vec::new());
     }
 }
 
@@ -860,7 +1686,7 @@ impl std::fmt::Debug for Board {
         }
         write!(f, "\nhands:\n")?;
         for hand in &self.hands {
-            writeln!(f, "{:?}, ", hand.0)?;
+            writeln!(f, "{hand:?}, ")?;
         }
         write!(f, "deck: ")?;
         write!(f, "{:?}, ", self.deck)?;
@@ -876,6 +1702,10 @@ impl std::fmt::Debug for Board {
         for one_or_thirteen in self.one_or_thirteen {
             write!(f, "{one_or_thirteen}, ")?;
         }
+        write!(f, "\nannounced: ")?;
+        for announced in self.announced {
+            write!(f, "{announced}, ")?;
+        }
         write!(f, "\ntraded: ")?;
         for traded in self.traded {
             write!(f, "{traded:?}, ")?;
@@ -906,22 +1736,510 @@ impl std::fmt::Debug for Board {
     }
 }
 
+/// Returns `true` if `mv` is a Tac which replaced the move before it.
+pub(crate) fn replaced_move(mv: &TacMove) -> bool {
+    matches!(mv.card, Card::Tac)
+        && !matches!(
+            mv.action,
+            TacAction::Discard | TacAction::Jester | TacAction::Trade
+        )
+}
+
 #[cfg(test)]
 mod tests {
+    use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+
     use super::*;
+    #[test]
+    fn try_play() {
+        let mut board = Board::new_with_seed(5);
+        let player = board.current_player();
+        let card = *board.hand(player).iter().next().unwrap();
+        let missing = CARDS
+            .into_iter()
+            .find(|c| !board.hand(player).contains(*c))
+            .unwrap();
+        let trade = TacMove::new(card, TacAction::Trade, player, player);
+        assert_eq!(
+            board.try_play(&TacMove::new(card, TacAction::Trade, player, player.next())),
+            Err(PlayError::WrongPlayer {
+                expected: player,
+                played_by: player.next()
+            })
+        );
+        assert_eq!(
+            board.try_play(&TacMove::new(missing, TacAction::Trade, player, player)),
+            Err(PlayError::IllegalCard(missing))
+        );
+        // No balls are in play at the start
+        let step = TacMove::new(
+            card,
+            TacAction::Step {
+                from: Square(5),
+                to: Square(9),
+            },
+            player,
+            player,
+        );
+        assert_eq!(board.try_play(&step), Err(PlayError::IllegalAction(step)));
+        let amount = board.hand(player).amount();
+        assert_eq!(board.try_play(&trade), Ok(()));
+        assert_eq!(board.hand(player).amount(), amount - 1);
+    }
+
     #[test]
     fn can_move() {
         let mut board = Board::new();
         board.xor(Square(10), Color::Black);
         for i in 1..64u8 {
-            assert!(board.can_move(Square(10), Square(10).add(i)));
+            assert!(board.can_move(Square(10), Square(10).add(Distance::new(i))));
         }
         board.xor(Square(12), Color::Blue);
         for i in 1..3u8 {
-            assert!(board.can_move(Square(10), Square(10).add(i)));
+            assert!(board.can_move(Square(10), Square(10).add(Distance::new(i))));
         }
         for i in 3..13u8 {
-            assert!(!board.can_move(Square(10), Square(10).add(i)));
+            assert!(!board.can_move(Square(10), Square(10).add(Distance::new(i))));
+        }
+    }
+
+    #[test]
+    fn undo_move() {
+        let mut board = Board::new_with_seed(3);
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut undo = UndoStack::default();
+        let mut states = Vec::new();
+        for _ in 0..200 {
+            let Some(mv) = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+            else {
+                break;
+            };
+            states.push(format!("{board:?}"));
+            undo.push(&board);
+            board.play(&mv);
+        }
+        assert_eq!(undo.len(), states.len());
+        while let Some(state) = states.pop() {
+            assert!(undo.undo(&mut board));
+            assert_eq!(format!("{board:?}"), state);
+        }
+        assert!(!undo.undo(&mut board));
+        assert!(undo.is_empty());
+    }
+
+    #[test]
+    #[allow(unused_must_use)]
+    fn seven_jumping_own() {
+        let steps = TacAction::SevenSteps {
+            steps: vec![TacAction::Step {
+                from: Square(1),
+                to: Square(8),
+            }],
+        };
+        for captures_own in [true, false] {
+            let rules = Ruleset {
+                seven_captures_own: captures_own,
+                ..Ruleset::default()
+            };
+            let mut board = Board::new_with_rules(0, rules);
+            board.put_ball_in_play(Color::Black);
+            board.move_ball(Square(0), Square(4), Color::Black);
+            board.put_ball_in_play(Color::Black);
+            board.move_ball(Square(0), Square(1), Color::Black);
+            board.put_ball_in_play(Color::Blue);
+            board.move_ball(Color::Blue.home(), Square(6), Color::Blue);
+            let before = board.clone();
+
+            let captured = board.apply_action(steps.clone(), Color::Black);
+            assert_eq!(board.color_on(Square(6)), None);
+            assert_eq!(board.color_on(Square(8)), Some(Color::Black));
+            assert_eq!(
+                board.color_on(Square(4)) == Some(Color::Black),
+                !captures_own
+            );
+            assert_eq!(board.num_base(Color::Black) == 2, !captures_own);
+
+            board.undo_action(steps.clone(), Color::Black, captured);
+            assert!(board.balls == before.balls);
+            assert_eq!(board.base, before.base);
+        }
+    }
+
+    #[test]
+    fn game_result() {
+        for seed in 0..50 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            while let Some(mv) = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+            {
+                assert!(!board.is_terminal());
+                board.play(&mv);
+            }
+            let result = board.game_result().unwrap();
+            let player = board.current_player();
+            assert!(result.is_win_for(player.prev()) || board.need_trade());
+            assert!(!result.is_win_for(player) || board.need_trade());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "six-players")]
+    fn six_player_results() {
+        assert_eq!(GameResult::from_winner(Color::Black), GameResult::BlackRed);
+        assert_eq!(
+            GameResult::from_winner(Color::Yellow),
+            GameResult::BlueYellow
+        );
+        assert_eq!(
+            GameResult::from_winner(Color::Purple),
+            GameResult::GreenPurple
+        );
+        for color in ALL_COLORS {
+            let result = GameResult::from_winner(color);
+            assert_eq!(ALL_RESULTS[color.team()], result);
+            let winners = ALL_COLORS
+                .into_iter()
+                .filter(|c| result.is_win_for(*c))
+                .collect_vec();
+            assert_eq!(winners.len(), 2);
+            assert!(winners.contains(&color) && winners.contains(&color.partner()));
+        }
+        // A team only wins once both partners are home
+        let mut board = Board::new();
+        for color in [Color::Green, Color::Purple] {
+            for goal in HomeSquare::ALL {
+                let _ = board.put_ball_in_play(color);
+                board.move_ball_to_goal(color.home(), goal, color);
+            }
+            assert_eq!(board.won(Color::Green), color == Color::Purple);
+        }
+        assert!(board.won(Color::Purple));
+        assert!(ALL_COLORS
+            .into_iter()
+            .filter(|c| board.won(*c))
+            .all(|c| GameResult::from_winner(c) == GameResult::GreenPurple));
+    }
+
+    #[test]
+    #[cfg(feature = "six-players")]
+    fn six_player_game() {
+        for seed in 0..20 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut moved = [false; NUM_COLORS];
+            while let Some(mv) = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+            {
+                moved[mv.played_by as usize] = true;
+                assert_eq!(PackedTacMove::pack(&mv).unpack(), mv);
+                board.play(&mv);
+            }
+            assert_eq!(moved, [true; NUM_COLORS]);
+            // Both partners of the winning team are home, nobody else won
+            let result = board.game_result().unwrap();
+            let winners = ALL_COLORS
+                .into_iter()
+                .filter(|c| result.is_win_for(*c))
+                .collect_vec();
+            assert_eq!(winners.len(), 2);
+            assert!(winners.iter().all(|c| board.won(*c)));
+            assert_eq!(winners[1], winners[0].partner());
+        }
+    }
+
+    #[test]
+    fn declared_announcements() {
+        let rules = Ruleset {
+            opening_announcement: OpeningAnnouncement::Declared,
+            ..Ruleset::default()
+        };
+        let mut board = Board::new_with_rules(3, rules);
+        assert!(board.need_announce() && !board.need_trade());
+        // Everyone claims to hold an opening, whatever they were dealt
+        for _ in ALL_COLORS {
+            let player = board.current_player();
+            let moves = board.get_moves(player);
+            assert_eq!(
+                moves,
+                [
+                    TacMove::announce(true, player),
+                    TacMove::announce(false, player)
+                ]
+            );
+            assert!(moves.iter().all(|mv| board.is_legal(mv)));
+            board.play(&moves[0]);
+            assert!(board.announced(player));
+            assert!(!board.is_legal(&TacMove::announce(true, player.next().next())));
+        }
+        assert_eq!(board.openings(), [true; NUM_COLORS]);
+        assert!(!board.need_announce() && board.need_trade());
+        assert_eq!(board.pack().unpack().openings(), [true; NUM_COLORS]);
+        let player = board.current_player();
+        assert!(!board.is_legal(&TacMove::announce(false, player)));
+        for _ in ALL_COLORS {
+            let mv = board.get_moves(board.current_player())[0].clone();
+            assert!(matches!(mv.action, TacAction::Trade));
+            board.play(&mv);
+        }
+        assert!(!board.need_trade());
+    }
+
+    #[test]
+    fn random_states() {
+        for rules in [
+            Ruleset::default(),
+            Ruleset {
+                opening_announcement: OpeningAnnouncement::Declared,
+                ..Ruleset::default()
+            },
+        ] {
+            let mut depths = Vec::new();
+            for seed in 0..50 {
+                let board = Board::new_random_state(seed, rules, 0..=400);
+                assert_eq!(board.check_invariants(), Ok(()), "{seed}");
+                assert!(!board.is_terminal());
+                assert!(board.pack() == Board::new_random_state(seed, rules, 0..=400).pack());
+                depths.push(board.move_count);
+            }
+            assert!(depths.iter().any(|d| *d > 100));
+        }
+        let board = Board::new_random_state(3, Ruleset::default(), 0..=0);
+        assert_eq!(board.move_count, 0);
+    }
+
+    #[test]
+    fn invariant_violations() {
+        let board = Board::new_with_seed(1);
+        let mut broken = board.clone();
+        broken.set(Square(10), Color::Red);
+        assert_eq!(
+            broken.check_invariants(),
+            Err(InvariantViolation::Balls {
+                color: Color::Red,
+                amount: 5
+            })
+        );
+        let mut broken = board.clone();
+        broken.add_hand(Color::Blue, Card::Tac);
+        assert!(matches!(
+            broken.check_invariants(),
+            Err(InvariantViolation::Cards { .. } | InvariantViolation::HandSize { .. })
+        ));
+        let mut broken = board.clone();
+        let player = ALL_COLORS
+            .into_iter()
+            .find(|p| board.openings()[*p as usize])
+            .unwrap();
+        broken.one_or_thirteen[player as usize] = false;
+        assert_eq!(
+            broken.check_invariants(),
+            Err(InvariantViolation::Announcement { player })
+        );
+        let mut broken = board.clone();
+        broken.devil_flag = true;
+        assert_eq!(
+            broken.check_invariants(),
+            Err(InvariantViolation::Flag { flag: "devil" })
+        );
+        let mut broken = board.clone();
+        broken.discarded.push(Card::Two);
+        assert!(matches!(
+            broken.check_invariants(),
+            Err(InvariantViolation::Dealt { .. } | InvariantViolation::Cards { .. })
+        ));
+        let mut broken = board.clone();
+        broken.trade_flag = false;
+        let card = *board.hand(Color::Green).iter().next().unwrap();
+        broken.hands[Color::Green as usize].remove(card);
+        broken.traded[Color::Black as usize] = Some(card);
+        assert_eq!(
+            broken.check_invariants(),
+            Err(InvariantViolation::Trade {
+                player: Color::Black
+            })
+        );
+    }
+
+    #[test]
+    fn serialize_roundtrip() {
+        let mut board = Board::new_with_seed(11);
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..300 {
+            let restored: Board = ron::from_str(&ron::to_string(&board).unwrap()).unwrap();
+            assert_eq!(format!("{restored:?}"), format!("{board:?}"));
+            let moves = board.get_moves(board.current_player());
+            assert_eq!(restored.get_moves(restored.current_player()), moves);
+            let Some(mv) = moves.into_iter().choose(&mut rng) else {
+                break;
+            };
+            board.play(&mv);
+        }
+    }
+
+    #[test]
+    fn canonicalize() {
+        let mut board = Board::new_with_seed(9);
+        let mut rng = StdRng::seed_from_u64(9);
+        loop {
+            let (canonical, perm) = board.canonicalize();
+            assert_eq!(canonical.current_player(), Color::Black);
+            assert_eq!(perm.color(board.current_player()), Color::Black);
+            // Move generation keeps only the first of the swaps of two balls of the same color,
+            // which depends on where the colors sit on the ring
+            let same_swap = |board: &Board, mv: &TacMove| match mv.action {
+                TacAction::Trickster { target1, target2 } => {
+                    board.color_on(target1) == board.color_on(target2)
+                }
+                _ => false,
+            };
+            let moves = board.get_moves(board.current_player());
+            let mut expected = moves
+                .iter()
+                .filter(|mv| !same_swap(&board, mv))
+                .map(|mv| format!("{:?}", mv.permuted(perm)))
+                .collect::<Vec<_>>();
+            let mut actual = canonical
+                .get_moves(Color::Black)
+                .iter()
+                .filter(|mv| !same_swap(&canonical, mv))
+                .map(|mv| format!("{mv:?}"))
+                .collect::<Vec<_>>();
+            expected.sort();
+            actual.sort();
+            assert_eq!(actual, expected);
+            for seats in 1..4 {
+                let rotated = board.permuted(ColorPermutation::rotation(seats));
+                assert!(rotated.canonicalize().0.pack() == canonical.pack());
+                assert_eq!(rotated.canonical_hash(), board.canonical_hash());
+            }
+            let Some(mv) = moves.into_iter().choose(&mut rng) else {
+                break;
+            };
+            board.play(&mv);
+        }
+    }
+
+    #[test]
+    fn packed_board_roundtrip() {
+        let mut board = Board::new_with_seed(5);
+        let mut rng = StdRng::seed_from_u64(5);
+        loop {
+            let unpacked = board.pack().unpack();
+            assert_eq!(format!("{unpacked:?}"), format!("{board:?}"));
+            let moves = board.get_moves(board.current_player());
+            assert_eq!(unpacked.get_moves(unpacked.current_player()), moves);
+            for mv in &moves {
+                assert_eq!(&PackedTacMove::pack(mv).unpack(), mv);
+            }
+            let Some(mv) = moves.into_iter().choose(&mut rng) else {
+                break;
+            };
+            board.play(&mv);
+        }
+    }
+    #[test]
+    fn tac_chain() {
+        let (black, blue, green, red) = (Color::Black, Color::Blue, Color::Green, Color::Red);
+        let mut board = Board::new();
+        for color in ALL_COLORS {
+            let _ = board.put_ball_in_play(color);
+        }
+        let _ = board.move_ball(blue.home(), black.home().sub(Distance::new(5)), blue);
+        board.add_hand(black, Card::Five);
+        for color in [blue, green, red] {
+            board.add_hand(color, Card::Tac);
+        }
+        board.set_player(black);
+        let step = |from: Square, to: Square| TacAction::Step { from, to };
+        board.play(&TacMove::new(
+            Card::Five,
+            step(black.home(), black.home().add(Distance::new(5))),
+            black,
+            black,
+        ));
+        // Blue tacs the five and captures the ball on its original square
+        board.play(&TacMove::new(
+            Card::Tac,
+            step(black.home().sub(Distance::new(5)), black.home()),
+            blue,
+            blue,
+        ));
+        assert_eq!(board.num_base(black), 4);
+        // Tacing the tac brings the five back
+        board.play(&TacMove::new(
+            Card::Tac,
+            step(green.home(), green.home().add(Distance::new(5))),
+            green,
+            green,
+        ));
+        assert_eq!(
+            board.color_on(black.home().add(Distance::new(5))),
+            Some(black)
+        );
+        assert_eq!(
+            board.color_on(black.home().sub(Distance::new(5))),
+            Some(blue)
+        );
+        // Redoing the tac of blue needs the five undone first, otherwise both balls would
+        // end up on the same square
+        board.play(&TacMove::new(
+            Card::Tac,
+            step(red.home(), red.home().add(Distance::new(5))),
+            red,
+            red,
+        ));
+        assert_eq!(board.num_base(black), 4);
+        assert!(board.balls_with(black).is_empty());
+        assert_eq!(board.color_on(black.home()), Some(blue));
+        assert_eq!(board.color_on(green.home()), Some(green));
+        assert_eq!(board.color_on(red.home().add(Distance::new(5))), Some(red));
+    }
+
+    #[test]
+    fn seven_captures_own_ball() {
+        let black = Color::Black;
+        let home = black.home();
+        let mut board = Board::new();
+        for offset in [5, 10] {
+            let _ = board.put_ball_in_play(black);
+            let _ = board.move_ball(home, home.add(Distance::new(offset)), black);
+        }
+        let _ = board.put_ball_in_play(black);
+        let before = board.clone();
+        // The first step ends on a ball that doesn't move
+        let seven = TacAction::SevenSteps {
+            steps: vec![
+                TacAction::Step {
+                    from: home,
+                    to: home.add(Distance::new(5)),
+                },
+                TacAction::Step {
+                    from: home.add(Distance::new(10)),
+                    to: home.add(Distance::new(12)),
+                },
+            ],
+        };
+        let captured = board.apply_action(seven.clone(), black);
+        assert_eq!(
+            captured,
+            Some(TacMoveResult::SevenCaptures(
+                [(home.add(Distance::new(5)), black)].into_iter().collect()
+            ))
+        );
+        assert_eq!(board.num_base(black), before.num_base(black) + 1);
+        board.undo_action(seven, black, captured);
+        assert_eq!(board.balls_with(black).len(), 3);
+        for offset in [0, 5, 10] {
+            assert_eq!(board.color_on(home.add(Distance::new(offset))), Some(black));
         }
+        assert_eq!(board.num_base(black), before.num_base(black));
     }
 }
@@ -0,0 +1,238 @@
+//! Precomputed moves for the start of a game, where every game looks alike and searching
+//! costs the same time over and over. Positions are identified by what the player to move
+//! knows about them: their hand and the opening announcements of the others, independent of
+//! the color they play, see [`Board::canonicalize`].
+
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tac_types::{Card, Color, TacAction, TacMove, NUM_COLORS};
+
+use crate::{arena::AgentConfig, board::Board};
+
+/// Situations covered by the book, all of them during the first deal of a game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum BookPhase {
+    /// Choosing the card given to the partner
+    Trade,
+    /// Playing a card while no ball has entered the ring yet
+    FirstMove,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct BookKey {
+    pub phase: BookPhase,
+    /// Cards of the player to move in ascending order
+    pub hand: Vec<Card>,
+    /// Announced openings of the other players in seat order, starting with the next player
+    pub openings: [bool; NUM_COLORS - 1],
+}
+
+impl BookKey {
+    /// Returns the key of the position, `None` if the book doesn't cover it.
+    #[must_use]
+    pub fn of(board: &Board) -> Option<Self> {
+        if !board.deck().fresh()
+            || board.is_terminal()
+            || board.need_announce()
+            || board.hand_to_play() != board.current_player()
+        {
+            return None;
+        }
+        // Seats matter, colors don't. Black is to move on the canonical board.
+        let (board, _) = board.canonicalize();
+        let phase = if board.need_trade() {
+            BookPhase::Trade
+        } else if board.all_balls().is_empty() {
+            BookPhase::FirstMove
+        } else {
+            return None;
+        };
+        let openings = board.openings();
+        Some(Self {
+            phase,
+            hand: board.hand(Color::Black).iter().copied().collect(),
+            openings: core::array::from_fn(|i| openings[i + 1]),
+        })
+    }
+}
+
+/// Visits the searches of all positions with the same key gave to each card.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BookEntry {
+    pub visits: BTreeMap<Card, u64>,
+    /// Number of searches that contributed to the entry
+    pub searches: u32,
+}
+
+impl BookEntry {
+    /// The most visited card.
+    #[must_use]
+    pub fn best(&self) -> Option<Card> {
+        self.visits
+            .iter()
+            .max_by_key(|(_, visits)| **visits)
+            .map(|(card, _)| *card)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OpeningBook {
+    entries: BTreeMap<BookKey, BookEntry>,
+}
+
+impl OpeningBook {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[must_use]
+    pub fn get(&self, key: &BookKey) -> Option<&BookEntry> {
+        self.entries.get(key)
+    }
+
+    /// Returns the book move for the position, `None` if the position isn't in the book
+    /// or the card of the entry can be played in several ways.
+    #[must_use]
+    pub fn lookup(&self, board: &Board) -> Option<TacMove> {
+        let card = self.get(&BookKey::of(board)?)?.best()?;
+        board
+            .get_moves(board.current_player())
+            .into_iter()
+            .filter(|mv| mv.card == card && !matches!(mv.action, TacAction::Discard))
+            .exactly_one()
+            .ok()
+    }
+
+    /// Adds the visits every card got in a search of a position with `key`.
+    pub fn record(&mut self, key: BookKey, visits: impl IntoIterator<Item = (Card, u64)>) {
+        let entry = self.entries.entry(key).or_default();
+        for (card, visits) in visits {
+            *entry.visits.entry(card).or_default() += visits;
+        }
+        entry.searches += 1;
+    }
+
+    /// Searches every book position reached in the game with `seed` with `agent` and records
+    /// the results. The game follows the book, so later positions depend on the earlier entries.
+    /// Returns the number of positions searched.
+    pub fn extend_with_game(&mut self, seed: u64, agent: &AgentConfig) -> usize {
+        let mut board = Board::new_with_seed(seed);
+        let mut searched = 0;
+        while let Some(key) = BookKey::of(&board) {
+            let moves = board.get_moves(board.current_player());
+            if moves.len() > 1 {
+                let mut mcts = agent.manager(&board);
+                if agent.threads > 1 {
+                    mcts.playout_n_parallel(agent.playouts, agent.threads);
+                } else {
+                    mcts.playout_n(agent.playouts);
+                }
+                let visits = mcts
+                    .root_move_stats()
+                    .into_iter()
+                    .map(|(mv, stats)| (mv.unpack(), stats.visits))
+                    .filter(|(mv, _)| moves.contains(mv))
+                    .map(|(mv, visits)| (mv.card, visits));
+                self.record(key, visits);
+                searched += 1;
+            }
+            let Some(mv) = self.lookup(&board).or_else(|| moves.into_iter().next()) else {
+                break;
+            };
+            board.play(&mv);
+        }
+        searched
+    }
+
+    /// # Errors
+    /// If `s` isn't a book in ron format.
+    pub fn from_ron(s: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(s)
+    }
+
+    #[must_use]
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("Books can be serialized")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+    use tac_types::ColorPermutation;
+
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let mut board = Board::new_with_seed(4);
+        let mut rng = StdRng::seed_from_u64(4);
+        let key = BookKey::of(&board).unwrap();
+        assert_eq!(key.phase, BookPhase::Trade);
+        assert!(key.hand.is_sorted());
+        let mut first_moves = 0;
+        while let Some(mv) = board
+            .get_moves(board.current_player())
+            .into_iter()
+            .choose(&mut rng)
+        {
+            if let Some(key) = BookKey::of(&board) {
+                assert!(board.deck().fresh());
+                let rotated = board.permuted(ColorPermutation::rotation(1));
+                assert_eq!(BookKey::of(&rotated), Some(key.clone()));
+                if key.phase == BookPhase::FirstMove {
+                    assert!(board.all_balls().is_empty());
+                    first_moves += 1;
+                }
+            }
+            board.play(&mv);
+        }
+        assert!(first_moves > 0);
+        assert!(BookKey::of(&board).is_none());
+    }
+
+    #[test]
+    fn generate_and_lookup() {
+        let agent = AgentConfig {
+            playouts: 50,
+            ..AgentConfig::new("book")
+        };
+        let mut book = OpeningBook::default();
+        let searched = (0..3)
+            .map(|seed| book.extend_with_game(seed, &agent))
+            .sum::<usize>();
+        assert!(searched >= 4);
+        assert!(!book.is_empty());
+        assert_eq!(OpeningBook::from_ron(&book.to_ron()).unwrap(), book);
+
+        let board = Board::new_with_seed(0);
+        let mv = book.lookup(&board).unwrap();
+        assert!(board.get_moves(board.current_player()).contains(&mv));
+        assert!(matches!(mv.action, TacAction::Trade));
+    }
+
+    #[test]
+    fn agent_follows_book() {
+        let board = Board::new_with_seed(7);
+        let key = BookKey::of(&board).unwrap();
+        // The agent trades whatever the book says without searching
+        let card = *key.hand.last().unwrap();
+        let mut book = OpeningBook::default();
+        book.record(key, [(card, 1)]);
+        let agent = AgentConfig {
+            book: Some(book.into()),
+            playouts: 10,
+            ..AgentConfig::new("book")
+        };
+        assert_eq!(agent.choose_move(&board).unwrap().card, card);
+    }
+}
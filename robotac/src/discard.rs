@@ -0,0 +1,118 @@
+use itertools::Itertools;
+use tac_types::{Card, Color};
+
+use crate::{
+    board::Board,
+    knowledge::Knowledge,
+    trade::{card_value, is_opening},
+};
+
+/// Penalty for showing the last copy of a card the others haven't seen yet
+const REVEAL_PENALTY: f64 = 1.0;
+
+/// Scores discarding `card` from the hand of `player`, higher is better.
+/// Weak cards and second copies go first, openings are kept while balls wait in the base.
+///
+/// A discarded card is shown to everyone. If the copies in our hand are the last ones
+/// `knowledge` hasn't seen anywhere else, discarding one tells the others that nobody
+/// else can hold it, e.g. that no Tac is left to punish them.
+#[must_use]
+pub fn discard_score(board: &Board, knowledge: &Knowledge, player: Color, card: Card) -> f64 {
+    let hand = board.hand(player);
+    let mut score = -card_value(card);
+    // A second copy of a card is worth less to us
+    if hand.count(card) > 1 {
+        score += 0.5;
+    }
+    if is_opening(card) && board.num_base(board.play_for(player)) > 0 {
+        score -= 1.0;
+    }
+    if knowledge.observer() == player && knowledge.unseen(card) == 0 {
+        score -= REVEAL_PENALTY;
+    }
+    score
+}
+
+/// Knowledge of `player` that contains its hand and the cards played in the current deal,
+/// which is all a position tells without following the game.
+#[must_use]
+pub fn seen_knowledge(board: &Board, player: Color) -> Knowledge {
+    let mut knowledge = Knowledge::new(player);
+    knowledge.update_with_hand(board.hand(player), player);
+    for card in board.discarded() {
+        knowledge.history[*card] = (knowledge.history[*card] + 1).min(card.amount());
+    }
+    knowledge
+}
+
+impl Board {
+    /// Returns the cards `player` can discard together with their score, best first.
+    #[must_use]
+    pub fn best_discards(&self, player: Color, knowledge: &Knowledge) -> Vec<(Card, f64)> {
+        self.hand(player)
+            .iter()
+            .unique()
+            .map(|card| (*card, discard_score(self, knowledge, player, *card)))
+            .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mcts::MCTS;
+    use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+    use tac_types::{PackedTacMove, TacAction, TacMove};
+
+    use super::*;
+    use crate::TacAI;
+
+    #[test]
+    fn best_discards() {
+        let mut discards = 0;
+        for seed in 0..50 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            while let Some(mv) = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+            {
+                if matches!(mv.action, TacAction::Discard) {
+                    let player = mv.played_by;
+                    let knowledge = seen_knowledge(&board, player);
+                    let ranked = board.best_discards(player, &knowledge);
+                    assert_eq!(
+                        ranked.len(),
+                        board.hand(player).iter().unique().count(),
+                        "Every card is a candidate"
+                    );
+                    assert!(ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+                    // The search prefers the same cards
+                    let heuristic = |card: Card| {
+                        let discard = TacMove::new(card, TacAction::Discard, player, player);
+                        TacAI::default().move_heuristic(&board, &PackedTacMove::pack(&discard))
+                    };
+                    for (card, score) in &ranked {
+                        assert!((heuristic(*card) - score).abs() < 1e-9);
+                    }
+                    discards += 1;
+                }
+                board.play(&mv);
+            }
+        }
+        assert!(discards > 0);
+    }
+
+    #[test]
+    fn last_unseen_copy() {
+        let board = Board::new_with_seed(2);
+        let player = board.current_player();
+        let card = *board.hand(player).iter().next().unwrap();
+        let mut knowledge = seen_knowledge(&board, player);
+        let hidden = discard_score(&board, &knowledge, player, card);
+        // Every other copy was played already, discarding ours shows that nobody holds one
+        knowledge.history[card] = card.amount();
+        assert!(discard_score(&board, &knowledge, player, card) < hidden);
+    }
+}
@@ -0,0 +1,253 @@
+use std::fmt::Display;
+
+use mcts::manager::Manager;
+use rand::rngs::StdRng;
+use tac_types::{Color, Home, PackedTacMove, Square, TacMove, ALL_COLORS, NUM_COLORS};
+
+use crate::{
+    arena::{AgentConfig, Difficulty},
+    board::{Board, GameResult},
+    explain::{self, Candidate},
+    knowledge::{self, Knowledge},
+    TacAI,
+};
+
+/// A move that is not legal in the current position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IllegalMove(pub TacMove);
+
+impl Display for IllegalMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Illegal move {}", self.0)
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
+/// Snapshot of the public state of a game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateSummary {
+    pub to_move: Color,
+    pub move_count: u32,
+    pub result: Option<GameResult>,
+    /// Balls on the ring of each color
+    pub balls: [Vec<Square>; NUM_COLORS],
+    pub homes: [Home; NUM_COLORS],
+    pub base: [u8; NUM_COLORS],
+    /// Evaluation for Black and Green between -1 and 1, see [`Board::eval_normalized`]
+    pub eval: f64,
+}
+
+/// Plays a game of Tac and searches moves for it.
+///
+/// Keeps the board, the knowledge of every player and the search tree in sync,
+/// so embedding the engine only requires feeding it the moves that were played.
+pub struct Engine {
+    board: Board,
+    knowledge: [Knowledge; NUM_COLORS],
+    config: AgentConfig,
+    ai: Manager<TacAI>,
+    /// Random choices around the search, seeded like the search
+    rng: StdRng,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new(AgentConfig::new("engine"))
+    }
+}
+
+impl Engine {
+    /// Creates an engine which searches with the policy and evaluation of `config`.
+    /// The first game is dealt with seed 0.
+    #[must_use]
+    pub fn new(config: AgentConfig) -> Self {
+        let board = Board::new_with_seed(0);
+        Self {
+            knowledge: core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board)),
+            ai: config.manager(&board),
+            rng: config.rng(),
+            board,
+            config,
+        }
+    }
+
+    /// Creates an engine playing at one of the preset levels, see [`Difficulty::agent`].
+    #[must_use]
+    pub fn with_difficulty(difficulty: Difficulty) -> Self {
+        Self::new(difficulty.agent())
+    }
+
+    #[must_use]
+    pub fn config(&self) -> &AgentConfig {
+        &self.config
+    }
+
+    /// Searches with `config` from now on, the search of the current position starts over.
+    pub fn set_config(&mut self, config: AgentConfig) {
+        self.ai = config.manager(&self.board);
+        self.config = config;
+    }
+
+    /// Discards the current game and deals a new one.
+    pub fn new_game(&mut self, seed: u64) {
+        self.board = Board::new_with_seed(seed);
+        self.knowledge =
+            core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &self.board));
+        self.ai = self.config.manager(&self.board);
+        self.rng = self.config.rng();
+    }
+
+    #[must_use]
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// What `player` knows about the hands of the others.
+    #[must_use]
+    pub fn knowledge(&self, player: Color) -> &Knowledge {
+        &self.knowledge[player as usize]
+    }
+
+    #[must_use]
+    pub fn legal_moves(&self) -> Vec<TacMove> {
+        self.board.get_moves(self.board.current_player())
+    }
+
+    /// Plays `mv` for the player to move.
+    /// # Errors
+    /// If `mv` isn't legal in the current position, the game is left unchanged.
+    pub fn apply_move(&mut self, mv: &TacMove) -> Result<(), IllegalMove> {
+        let mv = &mv.clone().canonical();
+        if self.board.check_move(mv).is_err() {
+            return Err(IllegalMove(mv.clone()));
+        }
+        for k in &mut self.knowledge {
+            k.update_with_move(mv, &self.board);
+        }
+        self.board.play(mv);
+        debug_assert_eq!(knowledge::validate(&self.board, &self.knowledge), Ok(()));
+        self.ai.advance(&PackedTacMove::pack(mv));
+        Ok(())
+    }
+
+    /// Searches the current position with `playouts` playouts and returns the best move
+    /// without playing it. Returns `None` once the game is over.
+    pub fn suggest_move(&mut self, playouts: u64) -> Option<TacMove> {
+        if let Some(mv) = self.ai.forced_move() {
+            return Some(mv.unpack());
+        }
+        if self.board.is_terminal() {
+            return None;
+        }
+        if let Some(mv) = self.config.book_move(&self.board) {
+            return Some(mv);
+        }
+        let player = self.board.current_player();
+        let knowledge = &self.knowledge[player as usize];
+        if let Some(mv) = self
+            .config
+            .forced_win(&self.board, knowledge, &mut self.rng)
+        {
+            return Some(mv);
+        }
+        if self.config.threads > 1 {
+            self.ai.playout_n_parallel(playouts, self.config.threads);
+        } else {
+            self.ai.playout_n(playouts);
+        }
+        let best = self.ai.best_move()?.unpack();
+        Some(
+            self.config
+                .checked_move(&self.ai, &self.board, knowledge, best, &mut self.rng),
+        )
+    }
+
+    /// Returns the move the engine plays in the current position, searched with the playouts of
+    /// its configuration. Like a human opponent it sometimes blunders, see [`AgentConfig::blunder`].
+    pub fn choose_move(&mut self) -> Option<TacMove> {
+        let mv = self.suggest_move(self.config.playouts)?;
+        Some(self.config.blunder(&self.board, mv, &mut self.rng))
+    }
+
+    /// Returns the three moves the last search liked best with what they do on the board.
+    /// Empty if nothing was searched in the current position yet.
+    #[must_use]
+    pub fn explain_best_move(&self) -> Vec<Candidate> {
+        explain::candidates(&self.board, &self.ai, 3)
+    }
+
+    #[must_use]
+    pub fn state_summary(&self) -> StateSummary {
+        let board = &self.board;
+        StateSummary {
+            to_move: board.current_player(),
+            move_count: board.move_count,
+            result: board.game_result(),
+            balls: ALL_COLORS.map(|c| board.balls_with(c).iter().collect()),
+            homes: ALL_COLORS.map(|c| *board.home(c)),
+            base: ALL_COLORS.map(|c| board.num_base(c)),
+            eval: board.eval_normalized(&self.config.params),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tac_types::{Card, TacAction};
+
+    use super::*;
+
+    #[test]
+    fn play_game() {
+        let mut engine = Engine::default();
+        engine.new_game(3);
+        for _ in 0..40 {
+            let Some(mv) = engine.suggest_move(50) else {
+                break;
+            };
+            assert!(engine.legal_moves().contains(&mv));
+            let before = engine.state_summary();
+            assert_eq!(engine.apply_move(&mv), Ok(()));
+            assert_eq!(engine.state_summary().move_count, before.move_count + 1);
+        }
+        let summary = engine.state_summary();
+        assert!((-1.0..=1.0).contains(&summary.eval));
+        if engine.suggest_move(50).is_some() && engine.legal_moves().len() > 1 {
+            let candidates = engine.explain_best_move();
+            assert!(!candidates.is_empty() && candidates.len() <= 3);
+            for candidate in candidates {
+                assert!(engine.legal_moves().contains(&candidate.mv));
+                assert!(!candidate.description.is_empty());
+            }
+        }
+        assert_eq!(summary.to_move, engine.board().current_player());
+    }
+
+    #[test]
+    fn plays_at_difficulty() {
+        let mut engine = Engine::with_difficulty(Difficulty::Easy);
+        engine.set_config(AgentConfig {
+            playouts: 20,
+            blunder_rate: 1.0,
+            ..engine.config().clone()
+        });
+        for _ in 0..20 {
+            let Some(mv) = engine.choose_move() else {
+                break;
+            };
+            assert_eq!(engine.apply_move(&mv), Ok(()));
+        }
+        assert_eq!(engine.state_summary().move_count, 20);
+    }
+
+    #[test]
+    fn rejects_illegal_moves() {
+        let mut engine = Engine::default();
+        let player = engine.board().current_player();
+        let mv = TacMove::new(Card::Seven, TacAction::Jester, player, player);
+        let before = engine.state_summary();
+        assert_eq!(engine.apply_move(&mv), Err(IllegalMove(mv)));
+        assert_eq!(engine.state_summary(), before);
+    }
+}
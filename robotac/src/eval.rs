@@ -1,6 +1,173 @@
-use tac_types::{BitBoard, Color, Square};
+use serde::{Deserialize, Serialize};
+use tac_types::{
+    BitBoard, Card, Color, Distance, Square, TacAction, TacMove, TacMoveResult, ALL_COLORS,
+    HOME_SIZE, NUM_TEAMS, RING_SIZE,
+};
 
-use crate::board::Board;
+use crate::{
+    board::{replaced_move, Board},
+    knowledge::Knowledge,
+};
+
+/// Weights used by [`Board::eval_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalParams {
+    /// Score of a won position
+    pub win: i64,
+    /// Per ball in goal
+    pub in_home: i64,
+    /// Per home that can still be entered
+    pub home_free: i64,
+    /// Per home without balls blocking each other
+    pub home_clean: i64,
+    /// Per ball less than 13 squares away from goal
+    pub near_goal: i64,
+    /// Per ball in range of a seven
+    pub seven_range: i64,
+    /// Per ball that can reach goal by moving backwards with a four
+    pub four_range: i64,
+    /// Per player with a ball in play
+    pub in_play: i64,
+    /// Per Tac, Angel or Seven in hand
+    pub premium_card: i64,
+    /// Per One or Thirteen in hand while balls are waiting in base
+    pub opening_card: i64,
+    /// Per card in a hand that will have to be discarded because no ball can move
+    pub discard_liability: i64,
+    /// Per player with a full home, every card they play moves the balls of their partner
+    pub playing_for_partner: i64,
+    /// If one partner is done and the other only has balls close to goal left
+    pub finish_soon: i64,
+    /// Per ball on the home square of an opponent who still has balls on the ring
+    pub blocking: i64,
+    /// Per ball less than 13 squares away from goal that can't enter home because a ball is in the way
+    pub blocked: i64,
+    /// Per ball in range of a seven while the previous player can suspend the next turn with an Eight
+    pub suspend_threat: i64,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            win: 1000,
+            in_home: 100,
+            home_free: 2,
+            home_clean: 2,
+            near_goal: 7,
+            seven_range: 10,
+            four_range: 3,
+            in_play: 3,
+            premium_card: 6,
+            opening_card: 8,
+            discard_liability: 4,
+            playing_for_partner: 30,
+            finish_soon: 20,
+            blocking: 4,
+            blocked: 5,
+            suspend_threat: 3,
+        }
+    }
+}
+
+/// Cards worth keeping in every phase of the game, see [`Board::hand_eval`].
+const PREMIUM_CARDS: [Card; 3] = [Card::Tac, Card::Angel, Card::Seven];
+const OPENING_CARDS: [Card; 2] = [Card::One, Card::Thirteen];
+
+/// Upper bound of normalized evaluations for games that aren't decided yet.
+const MAX_ONGOING: f64 = 0.999;
+
+/// Scores of [`Board::score_move_heuristic`], every square a ball gets closer to home is worth 1.
+const HEURISTIC_IN_HOME: i32 = 100;
+const HEURISTIC_ENTER: i32 = 50;
+const HEURISTIC_CAPTURE: i32 = 40;
+/// Capturing a ball that could have captured a ball of the team next
+const HEURISTIC_RESCUE: i32 = 30;
+const HEURISTIC_TEAM_CAPTURE: i32 = -80;
+
+/// Evaluation terms of a single color, see [`Board::color_eval`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ColorEval {
+    in_home: u8,
+    free: bool,
+    clean: bool,
+    near_goal: u8,
+    seven_range: u8,
+    four_range: u8,
+    in_play: bool,
+}
+
+impl ColorEval {
+    fn score(self, params: &EvalParams) -> i64 {
+        // Balls close to goal are worth more if the home can still be entered
+        let proximity = i64::from(self.free) + 1;
+        i64::from(self.in_home) * params.in_home
+            + i64::from(self.free) * params.home_free
+            + i64::from(self.clean) * params.home_clean
+            + (i64::from(self.near_goal) * params.near_goal
+                + i64::from(self.seven_range) * params.seven_range
+                + i64::from(self.four_range) * params.four_range)
+                * proximity
+            + i64::from(self.in_play) * params.in_play
+    }
+}
+
+/// Evaluation terms of the hand of a single color, see [`Board::hand_eval`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HandEval {
+    premium: u8,
+    openings: u8,
+    liability: u8,
+}
+
+impl HandEval {
+    fn score(self, params: &EvalParams) -> i64 {
+        i64::from(self.premium) * params.premium_card
+            + i64::from(self.openings) * params.opening_card
+            - i64::from(self.liability) * params.discard_liability
+    }
+}
+
+/// Evaluation terms of a team that depend on both partners, see [`Board::team_eval`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TeamEval {
+    playing_for_partner: u8,
+    finish_soon: bool,
+}
+
+impl TeamEval {
+    fn score(self, params: &EvalParams) -> i64 {
+        i64::from(self.playing_for_partner) * params.playing_for_partner
+            + i64::from(self.finish_soon) * params.finish_soon
+    }
+}
+
+/// Blocking terms of a single color, they depend on the balls of every color,
+/// see [`Board::block_eval`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BlockEval {
+    blocking: u8,
+    blocked: u8,
+}
+
+impl BlockEval {
+    fn score(self, params: &EvalParams) -> i64 {
+        i64::from(self.blocking) * params.blocking - i64::from(self.blocked) * params.blocked
+    }
+}
+
+/// Evaluation terms of a single color that depend on the hand of the previous player,
+/// see [`Board::threat_eval`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ThreatEval {
+    suspendable: u8,
+}
+
+impl ThreatEval {
+    fn score(self, params: &EvalParams) -> i64 {
+        -i64::from(self.suspendable) * params.suspend_threat
+    }
+}
 
 #[allow(clippy::cast_possible_truncation)]
 #[allow(clippy::cast_lossless)]
@@ -17,55 +184,386 @@ impl Board {
 
     #[must_use]
     pub fn eval2(&self) -> i64 {
-        let mut eval = 0;
-        let p = self.current_player();
-        let e = self.current_player().next();
-        let p_p = self.current_player().partner();
-        let e_p = self.current_player().next().partner();
-        if self.won(p) {
-            return 1000;
-        } else if self.won(e) {
-            return -1000;
-        }
-
-        // How many more balls do we have in goal
-        let goal_cnt = self.balls_in_home(p) as i64 - self.balls_in_home(e) as i64;
-        eval += goal_cnt * 100;
-
-        // Is our goal free to enter
-        let free = self.home_free(p) as u8;
-        let p_free = self.home_free(p_p) as u8;
-        let e_free = self.home_free(e) as u8;
-        let ep_free = self.home_free(e_p) as u8;
-
-        eval += ((free + p_free) as i64 - (e_free + ep_free) as i64) * 2;
-
-        // Is our goal clean
-        let clean = self.home_clean(p) as u8;
-        let p_clean = self.home_clean(p_p) as u8;
-        let e_clean = self.home_clean(e) as u8;
-        let ep_clean = self.home_clean(e_p) as u8;
-
-        eval += ((clean + p_clean) as i64 - (e_clean + ep_clean) as i64) * 2;
-
-        // How many balls do we have that are near the goal
-        let (fwd, seven, four) = self.near_goal(p);
-        let (p_fwd, p_seven, p_four) = self.near_goal(p_p);
-        let (e_fwd, e_seven, e_four) = self.near_goal(e);
-        let (ep_fwd, ep_seven, ep_four) = self.near_goal(e_p);
-        let goal_proximity =
-            |f, s, b, fr| (f * (fr + 1) * 7 + s * (fr + 1) * 10 + b * (fr + 1) * 3) as i64;
-        let our =
-            goal_proximity(fwd, seven, four, free) + goal_proximity(p_fwd, p_seven, p_four, p_free);
-        let theirs = goal_proximity(e_fwd, e_seven, e_four, e_free)
-            + goal_proximity(ep_fwd, ep_seven, ep_four, ep_free);
-        eval += our - theirs;
-
-        // Do we have balls in play
-        eval += ((self.ball_in_play(p) as i64 + self.ball_in_play(p_p) as i64)
-            - (self.ball_in_play(e) as i64 + self.ball_in_play(e_p) as i64))
-            * 3;
-        eval
+        self.eval_with(&EvalParams::default())
+    }
+
+    #[must_use]
+    pub fn eval_with(&self, params: &EvalParams) -> i64 {
+        self.eval_for(self.current_player(), params)
+    }
+
+    /// Evaluates the position from the view of `player`.
+    /// Uses the per color terms kept up to date by [`Board::play`].
+    #[must_use]
+    pub fn eval_for(&self, player: Color, params: &EvalParams) -> i64 {
+        self.eval_for_with(
+            player,
+            params,
+            |c| self.hand_eval(c),
+            |c| self.threat_eval(c),
+        )
+    }
+
+    /// Like [`Board::eval_for`], but the hands of everyone except the observer of `knowledge`
+    /// only count as far as the observer knows them, see [`Board::observed_hand_eval`].
+    /// Sampled hands in a determinization don't change the evaluation this way.
+    #[must_use]
+    pub fn eval_observed_for(
+        &self,
+        player: Color,
+        params: &EvalParams,
+        knowledge: &Knowledge,
+    ) -> i64 {
+        self.eval_for_with(
+            player,
+            params,
+            |c| self.observed_hand_eval(c, knowledge),
+            |c| self.observed_threat_eval(c, knowledge),
+        )
+    }
+
+    #[allow(clippy::cast_possible_wrap)]
+    fn eval_for_with(
+        &self,
+        player: Color,
+        params: &EvalParams,
+        hand_eval: impl Fn(Color) -> HandEval,
+        threat_eval: impl Fn(Color) -> ThreatEval,
+    ) -> i64 {
+        let opponents = ALL_COLORS[..NUM_TEAMS]
+            .iter()
+            .copied()
+            .filter(|c| c.team() != player.team());
+        if self.won(player) {
+            return params.win;
+        } else if opponents.clone().any(|c| self.won(c)) {
+            return -params.win;
+        }
+        let color = |c: Color| {
+            self.cached_color_eval(c).score(params)
+                + hand_eval(c).score(params)
+                + self.block_eval(c).score(params)
+                + threat_eval(c).score(params)
+        };
+        let team = |c: Color| color(c) + color(c.partner()) + self.team_eval(c).score(params);
+        // Compared against the average opponent team
+        team(player) - opponents.map(team).sum::<i64>() / (NUM_TEAMS - 1) as i64
+    }
+
+    /// Returns the legal move after which `params` rate the position best for the player to
+    /// move, without searching. Looks at the hands of every player, so it is only fair against
+    /// agents doing the same. Ties go to the earlier move, `None` if there is no move.
+    #[must_use]
+    pub fn greedy_move(&self, params: &EvalParams) -> Option<TacMove> {
+        let player = self.current_player();
+        let mut best: Option<(i64, TacMove)> = None;
+        for mv in self.get_moves(player) {
+            let mut after = self.clone();
+            after.play(&mv);
+            let score = after.eval_for(player, params);
+            if best.as_ref().is_none_or(|(best, _)| score > *best) {
+                best = Some((score, mv));
+            }
+        }
+        best.map(|(_, mv)| mv)
+    }
+
+    /// Evaluation of the position for Black and Green between -1 and 1.
+    /// Finished games saturate at ±1, ongoing ones are squashed into the open interval
+    /// so the values keep the same range in every phase of the game.
+    #[must_use]
+    pub fn eval_normalized(&self, params: &EvalParams) -> f64 {
+        self.eval_normalized_for(Color::Black, params)
+    }
+
+    /// Like [`Board::eval_normalized`] for the team of `player`.
+    #[must_use]
+    pub fn eval_normalized_for(&self, player: Color, params: &EvalParams) -> f64 {
+        self.result_value(player)
+            .unwrap_or_else(|| Self::squash(self.eval_for(player, params), params))
+    }
+
+    /// Like [`Board::eval_normalized`] with the hands restricted to what the observer of
+    /// `knowledge` knows, see [`Board::eval_observed_for`].
+    #[must_use]
+    pub fn eval_observed_normalized(&self, params: &EvalParams, knowledge: &Knowledge) -> f64 {
+        self.eval_observed_normalized_for(Color::Black, params, knowledge)
+    }
+
+    /// Like [`Board::eval_observed_normalized`] for the team of `player`.
+    #[must_use]
+    pub fn eval_observed_normalized_for(
+        &self,
+        player: Color,
+        params: &EvalParams,
+        knowledge: &Knowledge,
+    ) -> f64 {
+        self.result_value(player).unwrap_or_else(|| {
+            Self::squash(self.eval_observed_for(player, params, knowledge), params)
+        })
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn squash(eval: i64, params: &EvalParams) -> f64 {
+        (eval as f64 / params.win as f64)
+            .tanh()
+            .clamp(-MAX_ONGOING, MAX_ONGOING)
+    }
+
+    /// Like [`Board::eval_normalized`] but only compares the balls in goal of the teams.
+    #[must_use]
+    pub fn eval_basic_normalized(&self) -> f64 {
+        self.eval_basic_normalized_for(Color::Black)
+    }
+
+    /// Like [`Board::eval_basic_normalized`] for the team of `player`.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn eval_basic_normalized_for(&self, player: Color) -> f64 {
+        self.result_value(player).unwrap_or_else(|| {
+            let in_home =
+                |c: Color| f64::from(self.home(c).amount() + self.home(c.partner()).amount());
+            let opponents = ALL_COLORS[..NUM_TEAMS]
+                .iter()
+                .filter(|c| c.team() != player.team())
+                .map(|c| in_home(*c))
+                .sum::<f64>()
+                / (NUM_TEAMS - 1) as f64;
+            ((in_home(player) - opponents) / f64::from(2 * HOME_SIZE))
+                .tanh()
+                .clamp(-MAX_ONGOING, MAX_ONGOING)
+        })
+    }
+
+    fn result_value(&self, player: Color) -> Option<f64> {
+        self.game_result()
+            .map(|result| if result.is_win_for(player) { 1.0 } else { -1.0 })
+    }
+
+    /// Returns by how much playing `mv` changes [`Board::eval2`] for the player making the move.
+    #[must_use]
+    pub fn eval_delta(&self, mv: &TacMove) -> i64 {
+        self.eval_delta_with(mv, &EvalParams::default())
+    }
+
+    /// Returns by how much playing `mv` changes [`Board::eval_with`] for the player making the move.
+    /// Only the colors affected by the move get evaluated again.
+    #[must_use]
+    pub fn eval_delta_with(&self, mv: &TacMove, params: &EvalParams) -> i64 {
+        let player = self.current_player();
+        let mut after = self.clone();
+        after.play(mv);
+        after.eval_for(player, params) - self.eval_for(player, params)
+    }
+
+    /// Scores `mv` before it is searched, higher is better. Rewards balls entering the ring or
+    /// the home, progress towards home and captures of opponents, especially of balls right
+    /// behind a ball of the team. Capturing a ball of the team is penalized.
+    /// Moves that don't move balls score `0`, a Tac scores what it changes compared to the
+    /// move it replaces.
+    #[must_use]
+    pub fn score_move_heuristic(&self, mv: &TacMove) -> i32 {
+        let player = mv.played_for;
+        let team = |c: Color| c == player || c == player.partner();
+        // Balls in base still have to pass the whole ring
+        let remaining = |board: &Board| {
+            board
+                .balls_with(player)
+                .iter()
+                .map(|ball| i32::from(ball.distance_to_home(player).steps()))
+                .sum::<i32>()
+                + i32::from(board.num_base(player)) * i32::from(RING_SIZE)
+        };
+        let mut after = self.clone();
+        // A Tac is played on the position before the move it replaces
+        if replaced_move(mv) {
+            after.tac_undo();
+        }
+        let captures = match after.apply_action(mv.action.clone(), player) {
+            Some(TacMoveResult::Capture(color)) => match mv.action {
+                TacAction::Step { to, .. } | TacAction::Warrior { to, .. } => vec![(to, color)],
+                _ => vec![(player.home(), color)],
+            },
+            Some(TacMoveResult::SevenCaptures(captures)) => captures.to_vec(),
+            None => Vec::new(),
+        };
+        let mut score = remaining(self) - remaining(&after);
+        score += (i32::from(after.home(player).amount()) - i32::from(self.home(player).amount()))
+            * HEURISTIC_IN_HOME;
+        if matches!(mv.action, TacAction::Enter) {
+            score += HEURISTIC_ENTER;
+        }
+        for (square, color) in captures {
+            if team(color) {
+                score += HEURISTIC_TEAM_CAPTURE;
+                continue;
+            }
+            score += HEURISTIC_CAPTURE;
+            let threatened = ALL_COLORS.into_iter().filter(|c| team(*c)).any(|c| {
+                after
+                    .balls_with(c)
+                    .iter()
+                    .any(|ball| (1..13).contains(&square.distance_to(ball).steps()))
+            });
+            if threatened {
+                score += HEURISTIC_RESCUE;
+            }
+        }
+        score
+    }
+
+    /// Computes the evaluation terms of `color`, they only depend on its balls on the ring and in home.
+    #[must_use]
+    pub fn color_eval(&self, color: Color) -> ColorEval {
+        let (near_goal, seven_range, four_range) = self.near_goal(color);
+        ColorEval {
+            in_home: self.home(color).amount(),
+            free: self.home_free(color),
+            clean: self.home_clean(color),
+            near_goal,
+            seven_range,
+            four_range,
+            in_play: self.ball_in_play(color),
+        }
+    }
+
+    /// Computes the evaluation terms of the hand of `color`. Premium cards are worth keeping in
+    /// every phase of the game, opening cards only while balls are in base. Without a ball to
+    /// move or a card to open with, the whole hand is going to be discarded.
+    #[must_use]
+    pub fn hand_eval(&self, color: Color) -> HandEval {
+        let hand = self.hand(color);
+        let count = |cards: &[Card]| hand.iter().filter(|card| cards.contains(card)).count() as u8;
+        self.hand_eval_with(color, count(&PREMIUM_CARDS), count(&OPENING_CARDS))
+    }
+
+    /// Like [`Board::hand_eval`] from the view of the observer of `knowledge`. Cards of the
+    /// other players count with the number the observer expects, rounded, see
+    /// [`Knowledge::expected_cards`].
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub fn observed_hand_eval(&self, color: Color, knowledge: &Knowledge) -> HandEval {
+        if color == knowledge.observer() {
+            return self.hand_eval(color);
+        }
+        let expected = |cards: &[Card]| knowledge.expected_cards(self, color, cards).round() as u8;
+        self.hand_eval_with(color, expected(&PREMIUM_CARDS), expected(&OPENING_CARDS))
+    }
+
+    fn hand_eval_with(&self, color: Color, premium: u8, openings: u8) -> HandEval {
+        let openings = if self.num_base(color) > 0 {
+            openings
+        } else {
+            0
+        };
+        let stuck = self.num_base(color) > 0 && !self.ball_in_play(color) && openings == 0;
+        HandEval {
+            premium,
+            openings,
+            liability: if stuck {
+                self.hand(color).amount() as u8
+            } else {
+                0
+            },
+        }
+    }
+
+    /// Computes the tempo terms of the team of `color`. A player with a full home moves the
+    /// balls of their partner with every card, so the team gets twice the moves towards the
+    /// last home. The end is near once the partner only has balls close to goal left.
+    #[must_use]
+    pub fn team_eval(&self, color: Color) -> TeamEval {
+        let evals = [color, color.partner()].map(|c| self.cached_color_eval(c));
+        let full = |eval: &ColorEval| eval.in_home == HOME_SIZE;
+        let finish_soon =
+            [(evals[0], evals[1]), (evals[1], evals[0])]
+                .iter()
+                .any(|(done, other)| {
+                    full(done) && !full(other) && other.in_home + other.near_goal == HOME_SIZE
+                });
+        TeamEval {
+            playing_for_partner: evals.iter().filter(|eval| full(eval)).count() as u8,
+            finish_soon,
+        }
+    }
+
+    /// Computes the blocking terms of `color`. No ball can be passed, so a ball on the home
+    /// square of an opponent keeps their balls out of their home until it moves on or gets
+    /// captured. Balls close to goal behind such a blocker, or behind a fresh ball of their
+    /// own color, are stuck until the way is clear again.
+    #[must_use]
+    pub fn block_eval(&self, color: Color) -> BlockEval {
+        let balls = self.balls_with(color);
+        let blocking = [color.next(), color.prev()]
+            .iter()
+            .filter(|opponent| {
+                balls.has(opponent.home()) && !self.balls_with(**opponent).is_empty()
+            })
+            .count() as u8;
+        let blocked = balls
+            .iter()
+            .filter(|ball| {
+                ball.distance_to_home(color).steps() < 13
+                    && !self.can_move(*ball, color.home().add(Distance::ONE))
+            })
+            .count() as u8;
+        BlockEval { blocking, blocked }
+    }
+
+    /// Computes the threat terms of `color`. The previous player is an opponent, with an Eight
+    /// they can suspend the turn of `color`, which hurts most while balls wait for a seven
+    /// to bring them home. During a search the hands are sampled from what the searching
+    /// player knows, so over many playouts this is weighed by how likely the Eight is held.
+    #[must_use]
+    pub fn threat_eval(&self, color: Color) -> ThreatEval {
+        self.threat_eval_with(color, self.hand(color.prev()).contains(Card::Eight))
+    }
+
+    /// Like [`Board::threat_eval`] from the view of the observer of `knowledge`, who counts
+    /// an Eight of another player as held if that is more likely than not.
+    #[must_use]
+    pub fn observed_threat_eval(&self, color: Color, knowledge: &Knowledge) -> ThreatEval {
+        let opponent = color.prev();
+        let holds_eight = if opponent == knowledge.observer() {
+            self.hand(opponent).contains(Card::Eight)
+        } else {
+            knowledge.card_probability(self, opponent, Card::Eight) > 0.5
+        };
+        self.threat_eval_with(color, holds_eight)
+    }
+
+    fn threat_eval_with(&self, color: Color, holds_eight: bool) -> ThreatEval {
+        let opponent = color.prev();
+        let can_suspend =
+            holds_eight && self.can_play(self.play_for(opponent)) && !self.hand(color).is_empty();
+        ThreatEval {
+            suspendable: if can_suspend {
+                self.cached_color_eval(color).seven_range
+            } else {
+                0
+            },
+        }
+    }
+
+    fn cached_color_eval(&self, color: Color) -> ColorEval {
+        match self.eval_cache[color as usize] {
+            Some((balls, home, eval))
+                if balls == self.balls_with(color) && home == *self.home(color) =>
+            {
+                eval
+            }
+            _ => self.color_eval(color),
+        }
+    }
+
+    /// Evaluates every color whose balls changed since the last update.
+    pub(crate) fn update_eval_cache(&mut self) {
+        for color in ALL_COLORS {
+            let key = (self.balls_with(color), *self.home(color));
+            if self.eval_cache[color as usize].is_none_or(|(balls, home, _)| (balls, home) != key) {
+                self.eval_cache[color as usize] = Some((key.0, key.1, self.color_eval(color)));
+            }
+        }
     }
 
     fn ball_in_play(&self, player: Color) -> bool {
@@ -85,25 +583,22 @@ impl Board {
         self.home(player).amount() - self.home(player).get_all_unlocked().len() as u8 == 1
     }
 
-    fn balls_in_home(&self, player: Color) -> u8 {
-        self.home(player).amount() + self.home(player.partner()).amount()
-    }
-
     fn near_goal(&self, player: Color) -> (u8, u8, u8) {
         let mine = self.balls_with(player);
 
         let in_fwd_proximity =
-            |start: Square, player: Color| -> bool { start.distance_to_home(player) < 13 };
+            |start: Square, player: Color| -> bool { start.distance_to_home(player).steps() < 13 };
 
         let in_seven_proximity =
-            |start: Square, player: Color| -> bool { start.distance_to_home(player) < 7 };
+            |start: Square, player: Color| -> bool { start.distance_to_home(player).steps() < 7 };
 
-        let in_four_proximity =
-            |start: Square, player: Color| -> bool { start.distance_to_home(player) > 60 };
+        let in_four_proximity = |start: Square, player: Color| -> bool {
+            start.distance_to_home(player).steps() > RING_SIZE - 4
+        };
 
         let count = |bb: BitBoard, color: Color| -> (u8, u8, u8) {
             // Cast is valid in all cases because iterating bitboard
-            // can return square with value at most 64
+            // can return square with value at most the ring size
             (
                 bb.iter()
                     .filter(|ball| in_fwd_proximity(*ball, color))
@@ -119,3 +614,406 @@ impl Board {
         count(mine, player)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mcts::Evaluator;
+    use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+
+    use tac_types::HomeSquare;
+
+    use super::*;
+    use crate::TacEval;
+
+    #[test]
+    fn incremental_eval() {
+        let params = EvalParams::default();
+        for seed in 0..5 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            while let Some(mv) = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+            {
+                let player = board.current_player();
+                let before = board.eval_for(player, &params);
+                let delta = board.eval_delta(&mv);
+                board.play(&mv);
+                assert_eq!(before + delta, board.eval_for(player, &params));
+                for color in ALL_COLORS {
+                    let cached = board.eval_cache[color as usize].unwrap();
+                    assert_eq!(cached.2, board.color_eval(color), "{board:?}");
+                }
+                // Without a cache everything is computed from scratch
+                let unpacked = board.pack().unpack();
+                assert_eq!(unpacked.eval2(), board.eval2());
+            }
+        }
+    }
+
+    #[test]
+    fn normalized_eval() {
+        let eval = TacEval::default();
+        for seed in 0..5 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            loop {
+                for value in [
+                    board.eval_normalized(&EvalParams::default()),
+                    board.eval_basic_normalized(),
+                ] {
+                    assert!(value.abs() < 1.0 || board.is_terminal());
+                }
+                let value = eval.eval_new(&board, None);
+                for color in ALL_COLORS {
+                    assert_eq!(
+                        eval.make_relative(&value, &color),
+                        eval.make_relative(&value, &color.partner())
+                    );
+                }
+                if NUM_TEAMS == 2 {
+                    assert_eq!(
+                        eval.make_relative(&value, &Color::Green),
+                        -eval.make_relative(&value, &Color::Red)
+                    );
+                }
+                let Some(mv) = board
+                    .get_moves(board.current_player())
+                    .into_iter()
+                    .choose(&mut rng)
+                else {
+                    break;
+                };
+                board.play(&mv);
+            }
+            let result = board.game_result().unwrap();
+            let value = eval.eval_new(&board, None);
+            for color in ALL_COLORS {
+                let value = eval.make_relative(&value, &color);
+                assert_eq!(value.abs(), TacEval::SCALE);
+                assert_eq!(result.is_win_for(color), value > 0);
+            }
+        }
+    }
+
+    #[test]
+    fn eval_params() {
+        let params: EvalParams = ron::from_str("(in_home: 50)").unwrap();
+        assert_eq!(params.in_home, 50);
+        assert_eq!(params.win, EvalParams::default().win);
+        let roundtrip: EvalParams = ron::from_str(&ron::to_string(&params).unwrap()).unwrap();
+        assert_eq!(roundtrip, params);
+
+        let board = Board::new_with_seed(0);
+        assert_eq!(board.eval_with(&EvalParams::default()), board.eval2());
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_wrap)]
+    fn team_terms() {
+        let params = EvalParams::default();
+        let without_team = EvalParams {
+            playing_for_partner: 0,
+            finish_soon: 0,
+            ..params
+        };
+        let (black, partner, opponent) =
+            (Color::Black, Color::Black.partner(), Color::Black.next());
+        let mut board = Board::new_with_seed(0);
+        for (color, balls) in [(black, 4), (partner, 3), (opponent, 2)] {
+            for pos in &HomeSquare::ALL[..balls] {
+                let _ = board.put_ball_in_play(color);
+                board.move_ball_to_goal(color.home(), *pos, color);
+            }
+        }
+        let playing_for_partner = TeamEval {
+            playing_for_partner: 1,
+            finish_soon: false,
+        };
+        assert_eq!(board.team_eval(black), playing_for_partner);
+        assert_eq!(board.team_eval(partner), playing_for_partner);
+        assert_eq!(board.team_eval(opponent), TeamEval::default());
+        let team_score =
+            |board: &Board| board.eval_for(black, &params) - board.eval_for(black, &without_team);
+        assert_eq!(team_score(&board), params.playing_for_partner);
+        assert_eq!(
+            board.eval_for(partner, &params),
+            board.eval_for(black, &params)
+        );
+        // Every team is compared against the others, so one player per team sums up to zero
+        let teams: i64 = ALL_COLORS[..NUM_TEAMS]
+            .iter()
+            .map(|c| board.eval_for(*c, &params))
+            .sum();
+        assert!(teams.abs() < NUM_TEAMS as i64, "{teams}");
+
+        // The last ball of the partner comes close to goal
+        let _ = board.put_ball_in_play(partner);
+        let near = partner.home().sub(Distance::new(5));
+        let _ = board.move_ball(partner.home(), near, partner);
+        assert!(board.team_eval(partner).finish_soon);
+        assert_eq!(
+            team_score(&board),
+            params.playing_for_partner + params.finish_soon
+        );
+
+        // Moving it away from goal again takes the bonus back
+        let _ = board.move_ball(near, near.add(Distance::new(10)), partner);
+        assert_eq!(board.team_eval(black), playing_for_partner);
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_wrap)]
+    fn block_terms() {
+        let params = EvalParams::default();
+        let without_blocks = EvalParams {
+            blocking: 0,
+            blocked: 0,
+            ..params
+        };
+        // Terms of an opponent count against the average opponent team
+        let blocked = params.blocked / (NUM_TEAMS - 1) as i64;
+        let assert_block_score = |board: &Board, expected: i64| {
+            let score = board.eval_for(Color::Black, &params)
+                - board.eval_for(Color::Black, &without_blocks);
+            // Averaging over two or more opponent teams rounds
+            let rounding = NUM_TEAMS as i64 - 2;
+            assert!((score - expected).abs() <= rounding, "{score} {expected}");
+        };
+        let (black, prev) = (Color::Black, Color::Black.prev());
+        let mut board = Board::new();
+        let near = prev.home().sub(Distance::new(5));
+        board.set(near, prev);
+        assert_eq!(board.block_eval(prev), BlockEval::default());
+
+        // A black ball on the home square of the previous player keeps their ball out of its home
+        board.set(prev.home(), black);
+        assert_eq!(
+            board.block_eval(black),
+            BlockEval {
+                blocking: 1,
+                blocked: 0
+            }
+        );
+        assert_eq!(
+            board.block_eval(prev),
+            BlockEval {
+                blocking: 0,
+                blocked: 1
+            }
+        );
+        assert_block_score(&board, params.blocking + blocked);
+
+        // A fresh ball of its own traps the other one just the same, without Black getting credit
+        board.unset(prev.home(), black);
+        board.set(prev.home(), prev);
+        assert_eq!(board.block_eval(black), BlockEval::default());
+        assert_block_score(&board, blocked);
+
+        // Balls far from goal aren't counted, there is still time to clear the way
+        board.unset(near, prev);
+        board.set(prev.home().sub(Distance::new(20)), prev);
+        assert_eq!(board.block_eval(prev), BlockEval::default());
+    }
+
+    #[test]
+    fn threat_terms() {
+        let params = EvalParams::default();
+        let without_threats = EvalParams {
+            suspend_threat: 0,
+            ..params
+        };
+        let (black, prev) = (Color::Black, Color::Black.prev());
+        let mut threatened = [false, false];
+        for seed in 0..20 {
+            let mut board = Board::new_with_seed(seed);
+            let _ = board.put_ball_in_play(prev);
+            let _ = board.put_ball_in_play(black);
+            let near = black.home().sub(Distance::new(3));
+            let _ = board.move_ball(black.home(), near, black);
+            let eight = board.hand(prev).contains(Card::Eight);
+            threatened[usize::from(eight)] = true;
+            let expected = ThreatEval {
+                suspendable: u8::from(eight),
+            };
+            assert_eq!(board.threat_eval(black), expected);
+            assert_eq!(
+                board.eval_for(black, &params) - board.eval_for(black, &without_threats),
+                -i64::from(eight) * params.suspend_threat
+            );
+            // Without balls close to goal a suspend doesn't cost much
+            let _ = board.move_ball(near, near.add(Distance::new(20)), black);
+            assert_eq!(board.threat_eval(black), ThreatEval::default());
+        }
+        assert_eq!(threatened, [true, true]);
+    }
+
+    #[test]
+    fn observed_eval() {
+        let params = EvalParams::default();
+        let mut differing = 0;
+        for seed in 0..10 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut knowledge = ALL_COLORS.map(|c| Knowledge::new_from_board(c, &board));
+            for _ in 0..40 {
+                let mv = board
+                    .get_moves(board.current_player())
+                    .into_iter()
+                    .choose(&mut rng)
+                    .unwrap();
+                for k in &mut knowledge {
+                    k.update_with_move(&mv, &board);
+                }
+                board.play(&mv);
+            }
+            for (observer, knowledge) in ALL_COLORS.into_iter().zip(&knowledge) {
+                assert_eq!(
+                    board.observed_hand_eval(observer, knowledge),
+                    board.hand_eval(observer)
+                );
+                // Sampling the hidden hands doesn't change what the observer expects
+                let observed = board.eval_observed_for(Color::Black, &params, knowledge);
+                for _ in 0..5 {
+                    let mut sampled = board.clone();
+                    sampled.redetermine(observer, knowledge, &mut rng);
+                    differing += usize::from(
+                        sampled.eval_for(Color::Black, &params)
+                            != board.eval_for(Color::Black, &params),
+                    );
+                    assert_eq!(
+                        sampled.eval_observed_for(Color::Black, &params, knowledge),
+                        observed
+                    );
+                }
+            }
+        }
+        // The full evaluation does see the sampled hands
+        assert!(differing > 0);
+    }
+
+    #[test]
+    fn move_heuristic() {
+        let (black, partner, blue) = (Color::Black, Color::Black.partner(), Color::Blue);
+        let step = |from: u8, to: u8| {
+            TacMove::new(
+                Card::Four,
+                TacAction::Step {
+                    from: Square(from),
+                    to: Square(to),
+                },
+                black,
+                black,
+            )
+        };
+        let mut board = Board::new();
+        let enter = TacMove::new(Card::One, TacAction::Enter, black, black);
+        assert_eq!(board.score_move_heuristic(&enter), HEURISTIC_ENTER);
+        board.set(Square(10), black);
+        assert_eq!(board.score_move_heuristic(&step(10, 14)), 4);
+
+        board.set(Square(14), blue);
+        assert_eq!(
+            board.score_move_heuristic(&step(10, 14)),
+            4 + HEURISTIC_CAPTURE
+        );
+        // The blue ball could have captured the one of the partner with its next move
+        board.set(Square(20), partner);
+        assert_eq!(
+            board.score_move_heuristic(&step(10, 14)),
+            4 + HEURISTIC_CAPTURE + HEURISTIC_RESCUE
+        );
+        board.unset(Square(14), blue);
+        board.set(Square(14), partner);
+        assert_eq!(
+            board.score_move_heuristic(&step(10, 14)),
+            4 + HEURISTIC_TEAM_CAPTURE
+        );
+
+        // Reaching home is worth more than any step on the ring
+        let mut board = Board::new();
+        let before_home = black.home().sub(Distance::new(2));
+        board.set(before_home, black);
+        let into_home = board
+            .moves_for_card_squares(before_home.bitboard(), black, black, Card::Three)
+            .into_iter()
+            .find(|mv| matches!(mv.action, TacAction::StepInHome { .. }))
+            .unwrap();
+        assert_eq!(
+            board.score_move_heuristic(&into_home),
+            2 + HEURISTIC_IN_HOME
+        );
+        let discard = TacMove::new(Card::Three, TacAction::Discard, black, black);
+        assert_eq!(board.score_move_heuristic(&discard), 0);
+
+        // Every legal move can be scored, including Tacs replacing the move before
+        for seed in 0..5 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            while let Some(mv) = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .inspect(|mv| {
+                    let _ = board.score_move_heuristic(mv);
+                })
+                .choose(&mut rng)
+            {
+                board.play(&mv);
+            }
+        }
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_wrap)]
+    fn hand_terms() {
+        let only_hands = EvalParams {
+            win: 1000,
+            in_home: 0,
+            home_free: 0,
+            home_clean: 0,
+            near_goal: 0,
+            seven_range: 0,
+            four_range: 0,
+            in_play: 0,
+            ..Default::default()
+        };
+        for seed in 0..10 {
+            let board = Board::new_with_seed(seed);
+            let count = |color: Color, cards: &[Card]| {
+                board
+                    .hand(color)
+                    .iter()
+                    .filter(|card| cards.contains(card))
+                    .count() as i64
+            };
+            for color in ALL_COLORS {
+                // No ball is in play yet, so a hand without an opening card is lost
+                let openings = count(color, &[Card::One, Card::Thirteen]);
+                let premium = count(color, &[Card::Tac, Card::Angel, Card::Seven]);
+                let liability = if openings == 0 {
+                    board.hand(color).amount() as i64
+                } else {
+                    0
+                };
+                assert_eq!(
+                    board.hand_eval(color).score(&only_hands),
+                    premium * only_hands.premium_card + openings * only_hands.opening_card
+                        - liability * only_hands.discard_liability
+                );
+            }
+            let team = |c: Color| {
+                board.hand_eval(c).score(&only_hands)
+                    + board.hand_eval(c.partner()).score(&only_hands)
+            };
+            let opponents = ALL_COLORS[..NUM_TEAMS]
+                .iter()
+                .filter(|c| c.team() != Color::Black.team());
+            assert_eq!(
+                board.eval_for(Color::Black, &only_hands),
+                team(Color::Black)
+                    - opponents.map(|c| team(*c)).sum::<i64>() / (NUM_TEAMS - 1) as i64
+            );
+        }
+    }
+}
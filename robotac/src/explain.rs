@@ -0,0 +1,143 @@
+//! Descriptions of moves and the candidates a search considered, for showing why a move was chosen.
+
+use mcts::manager::Manager;
+use tac_types::{Card, PackedTacMove, Square, TacAction, TacMove, TacMoveResult};
+
+use crate::{board::Board, TacAI, TacEval};
+
+/// A move the search considered at the root.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub mv: TacMove,
+    pub visits: u64,
+    /// Mean value of the move for the player to move between -1 and 1
+    pub mean_value: f64,
+    /// What the move does, see [`Board::describe_move`]
+    pub description: String,
+}
+
+/// Returns the `count` most visited moves at the root of `ai` which searches `board`.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn candidates(board: &Board, ai: &Manager<TacAI>, count: usize) -> Vec<Candidate> {
+    let legal = board.get_moves(board.current_player());
+    ai.root_move_stats()
+        .into_iter()
+        .map(|(mv, stats)| (PackedTacMove::unpack(mv), stats))
+        // Moves only legal in some determinizations can't be played here
+        .filter(|(mv, _)| legal.contains(mv))
+        .take(count)
+        .map(|(mv, stats)| Candidate {
+            description: board.describe_move(&mv),
+            visits: stats.visits,
+            mean_value: stats.mean_action_value / TacEval::SCALE as f64,
+            mv,
+        })
+        .collect()
+}
+
+impl Board {
+    /// Describes what playing `mv` does on the rules level, e.g. "captures Red on 35, enters home".
+    #[must_use]
+    pub fn describe_move(&self, mv: &TacMove) -> String {
+        let mut after = self.clone();
+        after.play(mv);
+        let mut parts = Vec::new();
+        if mv.card == Card::Tac && !matches!(mv.action, TacAction::Discard | TacAction::Trade) {
+            parts.push("replaces the last move with tac".to_string());
+        }
+        if self.force_discard() && !matches!(mv.action, TacAction::Discard) {
+            parts.push("escapes the suspend".to_string());
+        }
+        match &mv.action {
+            TacAction::Enter => {
+                parts.push(format!("brings a ball of {:?} into play", mv.played_for));
+            }
+            TacAction::Suspend => parts.push(format!("suspends {:?}", mv.played_by.next())),
+            TacAction::Jester => parts.push("passes the hands on".to_string()),
+            TacAction::Devil => {
+                parts.push(format!("chooses the move of {:?}", mv.played_by.next()));
+            }
+            TacAction::Discard => parts.push(format!("discards {:?}", mv.card)),
+            TacAction::Trade => parts.push(format!("trades {:?} to partner", mv.card)),
+            TacAction::Announce { opening } => {
+                parts.push(format!(
+                    "announces {}",
+                    if *opening { "an opening" } else { "no opening" }
+                ));
+            }
+            TacAction::Trickster { target1, target2 } => {
+                parts.push(format!("switches {} and {}", target1.0, target2.0));
+            }
+            _ => {}
+        }
+        for (square, color) in captures(mv, &after) {
+            parts.push(format!("captures {color:?} on {}", square.0));
+        }
+        if after.home(mv.played_for).amount() > self.home(mv.played_for).amount() {
+            parts.push("enters home".to_string());
+        }
+        if after.won(mv.played_by) {
+            parts.push("wins the game".to_string());
+        }
+        if parts.is_empty() {
+            parts.push(format!("moves with {:?}", mv.card));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Balls captured by `mv`, read from the last move played on `after`.
+fn captures(mv: &TacMove, after: &Board) -> Vec<(Square, tac_types::Color)> {
+    let Some((played, Some(result))) = after.past_moves().back() else {
+        return Vec::new();
+    };
+    if played != mv {
+        return Vec::new();
+    }
+    match result {
+        TacMoveResult::SevenCaptures(captures) => captures.to_vec(),
+        TacMoveResult::Capture(color) => {
+            let square = match &mv.action {
+                TacAction::Step { to, .. } | TacAction::Warrior { to, .. } => *to,
+                _ => mv.played_for.home(),
+            };
+            vec![(square, *color)]
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(unused_must_use)]
+mod tests {
+    use tac_types::Color;
+
+    use super::*;
+
+    #[test]
+    fn descriptions() {
+        let (black, red) = (Color::Black, Color::Red);
+        let mut board = Board::new();
+        board.add_hand(black, Card::One);
+        board.add_hand(black, Card::Two);
+        board.add_hand(red, Card::Three);
+        let enter = TacMove::new(Card::One, TacAction::Enter, black, black);
+        assert_eq!(
+            board.describe_move(&enter),
+            "brings a ball of Black into play"
+        );
+        board.put_ball_in_play(red);
+        board.move_ball(red.home(), Square(2), red);
+        board.put_ball_in_play(black);
+        let step = TacMove::new(
+            Card::Two,
+            TacAction::Step {
+                from: Square(0),
+                to: Square(2),
+            },
+            black,
+            black,
+        );
+        assert_eq!(board.describe_move(&step), "captures Red on 2");
+    }
+}
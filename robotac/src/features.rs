@@ -0,0 +1,208 @@
+//! Encoding of a [`Board`] as a vector of numbers for machine learning models.
+//!
+//! Everything is encoded from the view of an observer. Colors are ordered by seat starting with
+//! the observer, with four players that is the next player, the partner and the previous player.
+//! Squares are rotated so the home square of the observer comes first.
+//!
+//! | Offset       | Length                         | Content                                           |
+//! |--------------|--------------------------------|---------------------------------------------------|
+//! | [`BALLS`]    | [`NUM_COLORS`] * [`RING_SIZE`] | one plane per color, 1 if a ball is on the square |
+//! | [`HOMES`]    | [`NUM_COLORS`] * 4             | 1 for every occupied home square of each color    |
+//! | [`BASE`]     | [`NUM_COLORS`]                 | balls in base of each color divided by four       |
+//! | [`HAND`]     | 6 * [`NUM_CARDS`]              | one-hot card of each slot of the sorted hand      |
+//! | [`TO_MOVE`]  | [`NUM_COLORS`]                 | one-hot color of the player to move               |
+//! | [`FLAGS`]    | 3                              | forced discard, jester played, trade phase        |
+//! | [`OPENINGS`] | [`NUM_COLORS`]                 | 1 if the color announced an opening card          |
+//!
+//! New values are only ever appended, so existing offsets stay valid.
+
+use tac_types::{Card, Color, Home, HomeSquare, Square, CARDS, NUM_CARDS, NUM_COLORS, RING_SIZE};
+
+use crate::board::Board;
+
+const RING: usize = RING_SIZE as usize;
+/// Most cards a hand can hold
+const HAND_SLOTS: usize = 6;
+
+pub const BALLS: usize = 0;
+pub const HOMES: usize = BALLS + NUM_COLORS * RING;
+pub const BASE: usize = HOMES + NUM_COLORS * 4;
+pub const HAND: usize = BASE + NUM_COLORS;
+pub const TO_MOVE: usize = HAND + HAND_SLOTS * NUM_CARDS;
+pub const FLAGS: usize = TO_MOVE + NUM_COLORS;
+pub const OPENINGS: usize = FLAGS + 3;
+/// Number of values returned by [`Board::to_features`].
+pub const NUM_FEATURES: usize = OPENINGS + NUM_COLORS;
+
+/// The colors in the order they are encoded for `observer`.
+fn relative_colors(observer: Color) -> [Color; NUM_COLORS] {
+    core::array::from_fn(|seats| Color::from((observer as usize + seats) % NUM_COLORS))
+}
+
+fn flag(value: bool) -> f32 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+impl Board {
+    /// Encodes the board from the view of `observer`, see the [module docs](self) for the layout.
+    /// Only the hand of the observer is included.
+    #[must_use]
+    pub fn to_features(&self, observer: Color) -> [f32; NUM_FEATURES] {
+        let mut features = [0.0; NUM_FEATURES];
+        let colors = relative_colors(observer);
+        let offset = observer.home();
+        for (i, color) in colors.iter().enumerate() {
+            for ball in self.balls_with(*color) {
+                let square = ball.sub(Square(0).distance_to(offset));
+                features[BALLS + i * RING + square.0 as usize] = 1.0;
+            }
+            let home = self.home(*color);
+            for pos in HomeSquare::ALL {
+                features[HOMES + i * 4 + pos.index() as usize] = flag(!home.is_free(pos));
+            }
+            features[BASE + i] = f32::from(self.num_base(*color)) / 4.0;
+            features[TO_MOVE + i] = flag(self.current_player() == *color);
+            features[OPENINGS + i] = flag(self.openings()[*color as usize]);
+        }
+        let mut hand = self.hand(observer).iter().copied().collect::<Vec<_>>();
+        hand.sort();
+        for (slot, card) in hand.iter().take(HAND_SLOTS).enumerate() {
+            features[HAND + slot * NUM_CARDS + *card as usize] = 1.0;
+        }
+        features[FLAGS] = flag(self.force_discard());
+        features[FLAGS + 1] = flag(self.jester_flag());
+        features[FLAGS + 2] = flag(self.need_trade());
+        features
+    }
+}
+
+/// The information stored in a feature vector, with colors and squares as they are on the board.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedFeatures {
+    /// Balls on the ring of each color
+    pub balls: [Vec<Square>; NUM_COLORS],
+    pub homes: [Home; NUM_COLORS],
+    pub base: [u8; NUM_COLORS],
+    /// Sorted hand of the observer
+    pub hand: Vec<Card>,
+    pub to_move: Option<Color>,
+    pub force_discard: bool,
+    pub jester: bool,
+    pub trade: bool,
+    pub openings: [bool; NUM_COLORS],
+}
+
+impl DecodedFeatures {
+    /// Reads back the features [`Board::to_features`] produced for `observer`.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn decode(features: &[f32; NUM_FEATURES], observer: Color) -> Self {
+        let set = |idx: usize| features[idx] > 0.5;
+        let mut decoded = Self {
+            balls: Default::default(),
+            homes: [Home::EMPTY; NUM_COLORS],
+            base: [0; NUM_COLORS],
+            hand: Vec::new(),
+            to_move: None,
+            force_discard: set(FLAGS),
+            jester: set(FLAGS + 1),
+            trade: set(FLAGS + 2),
+            openings: [false; NUM_COLORS],
+        };
+        let offset = observer.home();
+        for (i, color) in relative_colors(observer).into_iter().enumerate() {
+            let c = color as usize;
+            for sq in 0..RING_SIZE {
+                if set(BALLS + i * RING + sq as usize) {
+                    decoded.balls[c].push(Square(sq).add(Square(0).distance_to(offset)));
+                }
+            }
+            decoded.balls[c].sort();
+            for pos in HomeSquare::ALL {
+                if set(HOMES + i * 4 + pos.index() as usize) {
+                    decoded.homes[c].set(pos);
+                }
+            }
+            decoded.base[c] = (features[BASE + i] * 4.0).round() as u8;
+            if set(TO_MOVE + i) {
+                decoded.to_move = Some(color);
+            }
+            decoded.openings[c] = set(OPENINGS + i);
+        }
+        for slot in 0..HAND_SLOTS {
+            if let Some(card) = CARDS
+                .iter()
+                .find(|card| set(HAND + slot * NUM_CARDS + **card as usize))
+            {
+                decoded.hand.push(*card);
+            }
+        }
+        decoded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+    use tac_types::ALL_COLORS;
+
+    use super::*;
+
+    fn expected(board: &Board, observer: Color) -> DecodedFeatures {
+        let mut hand = board.hand(observer).iter().copied().collect::<Vec<_>>();
+        hand.sort();
+        DecodedFeatures {
+            balls: ALL_COLORS.map(|c| board.balls_with(c).iter().collect()),
+            homes: ALL_COLORS.map(|c| *board.home(c)),
+            base: ALL_COLORS.map(|c| board.num_base(c)),
+            hand,
+            to_move: Some(board.current_player()),
+            force_discard: board.force_discard(),
+            jester: board.jester_flag(),
+            trade: board.need_trade(),
+            openings: board.openings(),
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for seed in 0..10 {
+            let mut board = Board::new_with_seed(seed);
+            for _ in 0..300 {
+                for observer in ALL_COLORS {
+                    let features = board.to_features(observer);
+                    assert_eq!(
+                        DecodedFeatures::decode(&features, observer),
+                        expected(&board, observer)
+                    );
+                }
+                let Some(mv) = board
+                    .get_moves(board.current_player())
+                    .into_iter()
+                    .choose(&mut rng)
+                else {
+                    break;
+                };
+                board.play(&mv);
+            }
+        }
+    }
+
+    #[test]
+    fn relative_to_observer() {
+        let mut board = Board::new();
+        let _ = board.put_ball_in_play(Color::Blue);
+        let features = board.to_features(Color::Blue);
+        // The ball on the home square of the observer is the first square of the first plane
+        assert!(features[BALLS] > 0.5);
+        let features = board.to_features(Color::Black);
+        // For Black it is the home square of the next player
+        assert!(features[BALLS + RING + Color::Blue.home().0 as usize] > 0.5);
+        assert!(features[TO_MOVE] > 0.5);
+    }
+}
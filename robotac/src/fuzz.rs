@@ -0,0 +1,258 @@
+//! Random games checking that the knowledge of every player stays consistent with the board.
+//! A failing game is shrunk to fewer moves before it is reported, see [`check_game`].
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use itertools::Itertools;
+use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+use tac_types::{Color, TacMove, ALL_COLORS, NUM_COLORS};
+
+use crate::{
+    board::Board,
+    knowledge::{validate, Knowledge},
+    rules::Ruleset,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct FuzzConfig {
+    /// Games are stopped after this many moves
+    pub max_moves: usize,
+    /// Every player redetermines the board after this many moves, never if `None`
+    pub redetermine_every: Option<usize>,
+    pub rules: Ruleset,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        Self {
+            max_moves: 10000,
+            redetermine_every: Some(8),
+            rules: Ruleset::default(),
+        }
+    }
+}
+
+/// A game which broke an invariant.
+#[derive(Clone, Debug)]
+pub struct Failure {
+    pub seed: u64,
+    /// Moves played from the start of the game, the last one broke the invariant
+    pub moves: Vec<TacMove>,
+    pub reason: String,
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "seed {} after {} moves: {}",
+            self.seed,
+            self.moves.len(),
+            self.reason
+        )?;
+        write!(f, "{}", self.moves.iter().join(", "))
+    }
+}
+
+/// Plays a random game with `seed` and checks every player's knowledge after each move.
+/// # Errors
+/// The shrunk game if an invariant was broken or something panicked.
+pub fn check_game(seed: u64, config: &FuzzConfig) -> Result<(), Failure> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut moves = Vec::new();
+    let mut board = Board::new_with_rules(seed, config.rules);
+    while moves.len() < config.max_moves {
+        let Some(mv) = board
+            .get_moves(board.current_player())
+            .into_iter()
+            .choose(&mut rng)
+        else {
+            break;
+        };
+        board.play(&mv);
+        moves.push(mv);
+    }
+    match replay(seed, &moves, config) {
+        Ok(()) => Ok(()),
+        Err(failure) => Err(shrink(failure, config)),
+    }
+}
+
+/// Plays `moves` on a new game with `seed`, skipping moves that aren't legal anymore,
+/// and checks the invariants after every move.
+/// # Errors
+/// The moves played until the first broken invariant.
+pub fn replay(seed: u64, moves: &[TacMove], config: &FuzzConfig) -> Result<(), Failure> {
+    let mut played = Vec::new();
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut board = Board::new_with_rules(seed, config.rules);
+        let mut know: [_; NUM_COLORS] =
+            core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board));
+        for mv in moves {
+            if !board.get_moves(board.current_player()).contains(mv) {
+                continue;
+            }
+            played.push(mv.clone());
+            for k in &mut know {
+                k.update_with_move(mv, &board);
+            }
+            board.play(mv);
+            let redetermine = config
+                .redetermine_every
+                .is_some_and(|every| played.len() % every == 0);
+            check(&board, &know, redetermine, seed ^ played.len() as u64)?;
+        }
+        Ok(())
+    }));
+    let reason = match result {
+        Ok(Ok(())) => return Ok(()),
+        Ok(Err(reason)) => reason,
+        Err(panic) => panic
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| panic.downcast_ref::<&str>().map(ToString::to_string))
+            .map_or_else(|| "panicked".to_string(), |msg| format!("panicked: {msg}")),
+    };
+    Err(Failure {
+        seed,
+        moves: played,
+        reason,
+    })
+}
+
+/// Invariants which have to hold after every move.
+fn check(
+    board: &Board,
+    know: &[Knowledge; NUM_COLORS],
+    redetermine: bool,
+    seed: u64,
+) -> Result<(), String> {
+    board.check_invariants().map_err(|e| e.to_string())?;
+    validate(board, know).map_err(|e| e.to_string())?;
+    for k in know {
+        for player in ALL_COLORS.into_iter().filter(|p| *p != k.observer()) {
+            let distribution = k.hand_distribution(player);
+            if let Some((card, weight)) = distribution.iter().find(|(_, w)| w.is_nan() || **w < 0.0)
+            {
+                return Err(format!(
+                    "{:?} expects {weight} {card:?} for {player:?}",
+                    k.observer()
+                ));
+            }
+        }
+    }
+    if !redetermine {
+        return Ok(());
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    for k in know {
+        let mut determined = board.clone();
+        determined.redetermine(k.observer(), k, &mut rng);
+        if determined.hand(k.observer()) != board.hand(k.observer()) {
+            return Err(format!("{:?} redetermined their own hand", k.observer()));
+        }
+        if let Err(violation) = determined.check_invariants() {
+            return Err(format!("{:?} redetermined {violation}", k.observer()));
+        }
+        for player in ALL_COLORS {
+            if determined.hand(player).amount() != board.hand(player).amount() {
+                return Err(format!(
+                    "{:?} redetermined {} cards for {player:?} who holds {}",
+                    k.observer(),
+                    determined.hand(player).amount(),
+                    board.hand(player).amount()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Drops moves of the failing game as long as it keeps failing.
+#[must_use]
+pub fn shrink(failure: Failure, config: &FuzzConfig) -> Failure {
+    let seed = failure.seed;
+    let mut best = failure;
+    let moves = shrink_moves(best.moves.clone(), |moves| {
+        replay(seed, moves, config).err().map(|failure| {
+            let played = failure.moves.clone();
+            best = failure;
+            played
+        })
+    });
+    debug_assert_eq!(moves, best.moves);
+    best
+}
+
+/// Greedily removes single moves, starting with the last ones, while `fails` still fails.
+/// `fails` returns the moves that were actually played until the failure.
+fn shrink_moves(
+    mut moves: Vec<TacMove>,
+    mut fails: impl FnMut(&[TacMove]) -> Option<Vec<TacMove>>,
+) -> Vec<TacMove> {
+    let mut idx = moves.len();
+    while idx > 0 {
+        idx -= 1;
+        if idx >= moves.len() {
+            continue;
+        }
+        let mut candidate = moves.clone();
+        candidate.remove(idx);
+        if let Some(played) = fails(&candidate) {
+            moves = played;
+        }
+    }
+    moves
+}
+
+#[cfg(test)]
+mod tests {
+    use tac_types::Card;
+
+    use super::*;
+
+    #[test]
+    fn knowledge_stays_consistent() {
+        for seed in 0..20 {
+            if let Err(failure) = check_game(seed, &FuzzConfig::default()) {
+                panic!("{failure}");
+            }
+        }
+    }
+
+    #[test]
+    fn shrinks_to_fewer_moves() {
+        let seed = 3;
+        let mut board = Board::new_with_seed(seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut moves = Vec::new();
+        while let Some(mv) = board
+            .get_moves(board.current_player())
+            .into_iter()
+            .choose(&mut rng)
+        {
+            board.play(&mv);
+            moves.push(mv);
+        }
+        // Pretend the game breaks as soon as a seven is played
+        let fails = |moves: &[TacMove]| {
+            let mut board = Board::new_with_seed(seed);
+            let mut played = Vec::new();
+            for mv in moves {
+                if board.get_moves(board.current_player()).contains(mv) {
+                    board.play(mv);
+                    played.push(mv.clone());
+                    if mv.card == Card::Seven && mv.action != tac_types::TacAction::Discard {
+                        return Some(played);
+                    }
+                }
+            }
+            None
+        };
+        let failing = fails(&moves).expect("A seven is played in the game");
+        let shrunk = shrink_moves(failing.clone(), fails);
+        assert!(shrunk.len() <= failing.len());
+        assert_eq!(fails(&shrunk), Some(shrunk.clone()));
+        assert_eq!(shrunk.last().unwrap().card, Card::Seven);
+    }
+}
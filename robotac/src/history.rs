@@ -1,20 +1,341 @@
-use crate::board::Board;
+use std::{
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    arena::AgentConfig,
+    board::{Board, GameResult, PackedBoard, ALL_RESULTS},
+    knowledge::Knowledge,
+    TacAI, TacEval,
+};
+use mcts::manager::Manager;
 use serde::{Deserialize, Serialize};
-use tac_types::TacMove;
+use tac_types::{PackedTacMove, TacMove, ALL_COLORS, NUM_COLORS};
+
+/// Version of the format written by [`History::to_ron`].
+/// Files without a version only contain the seed and the moves and count as version 1.
+pub const HISTORY_VERSION: u32 = 2;
+
+fn first_version() -> u32 {
+    1
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Seconds since the unix epoch, `None` if the clock is set before it.
+#[must_use]
+pub fn unix_time() -> Option<u64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+fn result_token(result: Option<GameResult>) -> &'static str {
+    match result {
+        #[cfg(not(feature = "six-players"))]
+        Some(GameResult::BlackGreen) => "BlackGreen",
+        #[cfg(not(feature = "six-players"))]
+        Some(GameResult::BlueRed) => "BlueRed",
+        #[cfg(feature = "six-players")]
+        Some(GameResult::BlackRed) => "BlackRed",
+        #[cfg(feature = "six-players")]
+        Some(GameResult::BlueYellow) => "BlueYellow",
+        #[cfg(feature = "six-players")]
+        Some(GameResult::GreenPurple) => "GreenPurple",
+        None => "*",
+    }
+}
+
+/// Who controlled a color during a game.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerInfo {
+    pub name: String,
+    /// Settings of the AI, `None` for humans
+    pub agent: Option<AgentConfig>,
+}
+
+/// What the search thought of a move when it was played.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SearchStats {
+    /// Visits of the move at the root
+    pub visits: u64,
+    /// Mean evaluation of the move between -1 and 1 for the player who played it
+    pub eval: f64,
+}
+
+impl SearchStats {
+    /// Statistics of `mv` at the root of `mcts`, `None` if it wasn't searched.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn of(mcts: &Manager<TacAI>, mv: PackedTacMove) -> Option<Self> {
+        mcts.root_move_stats()
+            .into_iter()
+            .find(|(searched, _)| *searched == mv)
+            .map(|(_, stats)| Self {
+                visits: stats.visits,
+                eval: stats.mean_action_value / TacEval::SCALE as f64,
+            })
+    }
+}
+
+/// Additional information about a single move.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Annotation {
+    /// Seconds since the unix epoch when the move was played
+    pub time: Option<u64>,
+    pub search: Option<SearchStats>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct History {
+    #[serde(default = "first_version")]
+    pub version: u32,
     pub seed: u64,
     pub moves: Vec<TacMove>,
+    #[serde(default)]
+    pub players: [PlayerInfo; NUM_COLORS],
+    /// Seconds since the unix epoch when the game started
+    #[serde(default)]
+    pub started: Option<u64>,
+    /// Annotation of the move with the same index, can be shorter than the moves
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+    /// What every player knew after the last move, so analysis resumes with the same
+    /// information states. Changing the moves discards it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub knowledge: Option<[Knowledge; NUM_COLORS]>,
+}
+
+/// A history file that couldn't be read.
+#[derive(Debug)]
+pub enum HistoryError {
+    Parse(ron::error::SpannedError),
+    /// The file was written by a newer version
+    UnsupportedVersion(u32),
+    /// A game record from [`History::to_text`] that couldn't be read
+    Text(String),
+}
+
+impl Display for HistoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Parse(err) => write!(f, "Invalid history: {err}"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "History version {version} isn't supported")
+            }
+            Self::Text(err) => write!(f, "Invalid game record: {err}"),
+        }
+    }
 }
 
+impl std::error::Error for HistoryError {}
+
 impl History {
     #[must_use]
     pub fn new(seed: u64) -> Self {
         Self {
+            version: HISTORY_VERSION,
             seed,
             moves: Vec::new(),
+            players: Default::default(),
+            started: unix_time(),
+            annotations: Vec::new(),
+            knowledge: None,
+        }
+    }
+
+    /// Reads a history in any supported version.
+    /// # Errors
+    /// If `s` isn't a history or was written by a newer version.
+    pub fn from_ron(s: &str) -> Result<Self, HistoryError> {
+        let mut history: Self = ron::from_str(s).map_err(HistoryError::Parse)?;
+        if history.version > HISTORY_VERSION {
+            return Err(HistoryError::UnsupportedVersion(history.version));
+        }
+        history.version = HISTORY_VERSION;
+        Ok(history)
+    }
+
+    /// # Panics
+    /// If the history can't be serialized, which doesn't happen for valid histories.
+    #[must_use]
+    pub fn to_ron(&self) -> String {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .expect("Histories can be serialized")
+    }
+
+    /// Appends a move with its annotation.
+    pub fn push(&mut self, mv: TacMove, annotation: Annotation) {
+        self.annotations
+            .resize_with(self.moves.len(), Annotation::default);
+        self.knowledge = None;
+        self.moves.push(mv);
+        self.annotations.push(annotation);
+    }
+
+    /// Removes the last move and its annotation.
+    pub fn pop(&mut self) -> Option<TacMove> {
+        let mv = self.moves.pop()?;
+        self.knowledge = None;
+        self.annotations.truncate(self.moves.len());
+        Some(mv)
+    }
+
+    /// Keeps the first `len` moves and their annotations.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.moves.len() {
+            self.knowledge = None;
+        }
+        self.moves.truncate(len);
+        self.annotations.truncate(len);
+    }
+
+    #[must_use]
+    pub fn annotation(&self, idx: usize) -> Option<&Annotation> {
+        self.annotations.get(idx)
+    }
+
+    /// Writes the game as a human readable record, similar to PGN in chess.
+    ///
+    /// The record starts with tags for the seed, the players, the start time and the result,
+    /// followed by one numbered move per line in the notation of [`tac_types::notation`].
+    /// Comments of moves follow them in braces and the result of the game ends the record.
+    ///
+    /// ```text
+    /// [Seed "4"]
+    /// [Black "robotac"]
+    /// [Result "*"]
+    ///
+    /// 1. 4:TRADE@K
+    /// 2. 12:TRADE@U {Keeps the seven}
+    /// *
+    /// ```
+    #[must_use]
+    pub fn to_text(&self) -> String {
+        let tag = |value: &str| value.replace('"', "'");
+        let mut text = format!("[Seed \"{}\"]\n", self.seed);
+        for (color, player) in ALL_COLORS.iter().zip(&self.players) {
+            if !player.name.is_empty() {
+                text += &format!("[{color:?} \"{}\"]\n", tag(&player.name));
+            }
+        }
+        if let Some(started) = self.started {
+            text += &format!("[Started \"{started}\"]\n");
+        }
+        let result = result_token(self.board_with_history().game_result());
+        text += &format!("[Result \"{result}\"]\n\n");
+        for (idx, mv) in self.moves.iter().enumerate() {
+            text += &format!("{}. {mv}", idx + 1);
+            if let Some(comment) = self.annotation(idx).and_then(|a| a.comment.as_ref()) {
+                text += &format!(" {{{}}}", comment.replace('}', ")"));
+            }
+            text += "\n";
+        }
+        text += result;
+        text += "\n";
+        text
+    }
+
+    /// Reads a record written by [`Self::to_text`]. Only the seed tag is required.
+    /// # Errors
+    /// If the record can't be parsed or contains a move that isn't legal.
+    pub fn from_text(text: &str) -> Result<Self, HistoryError> {
+        let mut seed = None;
+        let mut history = History::new(0);
+        history.started = None;
+        let mut lines = text.lines().map(str::trim).peekable();
+        while let Some(line) = lines.next_if(|l| l.starts_with('[') || l.is_empty()) {
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .strip_prefix('[')
+                .and_then(|l| l.strip_suffix("\"]"))
+                .and_then(|l| l.split_once(" \""))
+                .ok_or_else(|| HistoryError::Text(format!("invalid tag `{line}`")))?;
+            match key {
+                "Seed" => {
+                    seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| HistoryError::Text(format!("invalid seed `{value}`")))?,
+                    );
+                }
+                "Started" => history.started = value.parse().ok(),
+                _ => {
+                    if let Some(color) = ALL_COLORS.iter().find(|c| format!("{c:?}") == key) {
+                        history.players[*color as usize].name = value.to_string();
+                    }
+                }
+            }
+        }
+        history.seed = seed.ok_or_else(|| HistoryError::Text("missing seed".to_string()))?;
+        let mut board = Board::new_with_seed(history.seed);
+        let body = lines.collect::<Vec<_>>().join("\n");
+        let mut rest = body.as_str();
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            if let Some(comment) = rest.strip_prefix('{') {
+                let (comment, after) = comment
+                    .split_once('}')
+                    .ok_or_else(|| HistoryError::Text("unclosed comment".to_string()))?;
+                let annotation = history.annotations.last_mut().ok_or_else(|| {
+                    HistoryError::Text("comment before the first move".to_string())
+                })?;
+                annotation.comment = Some(comment.to_string());
+                rest = after;
+                continue;
+            }
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '{')
+                .unwrap_or(rest.len());
+            let (token, after) = rest.split_at(end);
+            rest = after;
+            let is_number = token
+                .strip_suffix('.')
+                .is_some_and(|n| n.parse::<usize>().is_ok());
+            let is_result = token == result_token(None)
+                || ALL_RESULTS.iter().any(|r| token == result_token(Some(*r)));
+            if is_number || is_result {
+                continue;
+            }
+            let mv = token.parse::<TacMove>().map_err(|e| {
+                HistoryError::Text(format!("move {}: {e}", history.moves.len() + 1))
+            })?;
+            if !board.get_moves(board.current_player()).contains(&mv) {
+                return Err(HistoryError::Text(format!(
+                    "move {} `{mv}` isn't legal",
+                    history.moves.len() + 1
+                )));
+            }
+            board.play(&mv);
+            history.push(mv, Annotation::default());
+        }
+        Ok(history)
+    }
+
+    /// Knowledge of every player after the moves, the saved [`Self::knowledge`] if there is one.
+    #[must_use]
+    pub fn knowledge_after_moves(&self) -> [Knowledge; NUM_COLORS] {
+        if let Some(knowledge) = self.knowledge {
+            return knowledge;
+        }
+        let mut board = Board::new_with_seed(self.seed);
+        let mut knowledge =
+            core::array::from_fn(|i| Knowledge::new_from_board(ALL_COLORS[i], &board));
+        for mv in &self.moves {
+            for k in &mut knowledge {
+                k.update_with_move(mv, &board);
+            }
+            board.play(mv);
         }
+        knowledge
     }
 
     #[must_use]
@@ -26,3 +347,270 @@ impl History {
         board
     }
 }
+
+/// Steps through the moves of a [`History`].
+/// Every visited position is cached, so going back or jumping to an earlier move doesn't replay the game.
+pub struct Replay {
+    history: History,
+    positions: Vec<PackedBoard>,
+    board: Board,
+    position: usize,
+}
+
+impl Replay {
+    #[must_use]
+    pub fn new(history: History) -> Self {
+        let board = Board::new_with_seed(history.seed);
+        Self {
+            positions: vec![board.pack()],
+            history,
+            board,
+            position: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Returns the board after the first [`Self::position`] moves have been played.
+    #[must_use]
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Number of moves played to reach the current board.
+    #[must_use]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Total number of moves in the history.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.history.moves.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.history.moves.is_empty()
+    }
+
+    /// Shows the board after `position` moves, clamped to the length of the history.
+    pub fn goto(&mut self, position: usize) {
+        let position = position.min(self.len());
+        if position == self.position {
+            return;
+        }
+        if let Some(packed) = self.positions.get(position) {
+            self.board = packed.unpack();
+        } else {
+            let mut board = self
+                .positions
+                .last()
+                .expect("Start is always cached")
+                .unpack();
+            for mv in &self.history.moves[self.positions.len() - 1..position] {
+                board.play(mv);
+                self.positions.push(board.pack());
+            }
+            self.board = board;
+        }
+        self.position = position;
+    }
+
+    /// Returns `false` if the end of the history was already reached.
+    pub fn step_forward(&mut self) -> bool {
+        let old = self.position;
+        self.goto(old + 1);
+        self.position != old
+    }
+
+    /// Returns `false` if the start of the history was already reached.
+    pub fn step_backward(&mut self) -> bool {
+        let Some(position) = self.position.checked_sub(1) else {
+            return false;
+        };
+        self.goto(position);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn replay() {
+        let mut history = History::new(3);
+        let mut board = Board::new_with_seed(history.seed);
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..200 {
+            let Some(mv) = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+            else {
+                break;
+            };
+            board.play(&mv);
+            history.moves.push(mv);
+        }
+        let board_at = |n: usize| {
+            let mut history = history.clone();
+            history.truncate(n);
+            history.board_with_history()
+        };
+
+        let mut replay = Replay::new(history.clone());
+        assert!(!replay.step_backward());
+        for position in [10, 5, 150, 149, 0, 200, 1000, 42] {
+            replay.goto(position);
+            let position = position.min(replay.len());
+            assert_eq!(replay.position(), position);
+            assert_eq!(
+                format!("{:?}", replay.board()),
+                format!("{:?}", board_at(position))
+            );
+        }
+        while replay.step_forward() {}
+        assert_eq!(replay.position(), replay.len());
+        assert_eq!(format!("{:?}", replay.board()), format!("{board:?}"));
+        assert!(replay.step_backward());
+        assert_eq!(
+            format!("{:?}", replay.board()),
+            format!("{:?}", board_at(replay.len() - 1))
+        );
+    }
+
+    #[test]
+    fn load_versions() {
+        // Histories from before players and annotations were recorded
+        let v1 = "(
+            seed: 4,
+            moves: [
+                (card: Four, action: Trade, played_for: Black, played_by: Black),
+            ],
+        )";
+        let history = History::from_ron(v1).unwrap();
+        assert_eq!(history.version, HISTORY_VERSION);
+        assert_eq!(history.seed, 4);
+        assert_eq!(history.moves.len(), 1);
+        assert_eq!(history.players, <[PlayerInfo; NUM_COLORS]>::default());
+        assert!(history.annotation(0).is_none());
+
+        let mut history = History::new(7);
+        history.players[1] = PlayerInfo {
+            name: "robotac".to_string(),
+            agent: Some(AgentConfig::new("robotac")),
+        };
+        let board = Board::new_with_seed(7);
+        let mut moves = board.get_moves(board.current_player()).into_iter();
+        history.moves.push(moves.next().unwrap());
+        history.push(
+            moves.next().unwrap(),
+            Annotation {
+                time: Some(1_700_000_000),
+                search: Some(SearchStats {
+                    visits: 420,
+                    eval: -0.25,
+                }),
+                comment: Some("Only move".to_string()),
+            },
+        );
+        assert_eq!(history.annotation(0), Some(&Annotation::default()));
+        assert_eq!(History::from_ron(&history.to_ron()).unwrap(), history);
+
+        let newer = history.to_ron().replacen(
+            &format!("version: {HISTORY_VERSION}"),
+            &format!("version: {}", HISTORY_VERSION + 1),
+            1,
+        );
+        assert!(matches!(
+            History::from_ron(&newer),
+            Err(HistoryError::UnsupportedVersion(_))
+        ));
+
+        assert!(history.pop().is_some());
+        assert_eq!(history.annotations.len(), 1);
+        history.truncate(0);
+        assert!(history.annotations.is_empty());
+    }
+
+    #[test]
+    fn saved_knowledge() {
+        let mut history = History::new(11);
+        let mut board = Board::new_with_seed(history.seed);
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..60 {
+            let mv = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+                .unwrap();
+            board.play(&mv);
+            history.push(mv, Annotation::default());
+        }
+        let replayed = history.knowledge_after_moves();
+        assert!(!history.to_ron().contains("knowledge"));
+
+        history.knowledge = Some(replayed);
+        let loaded = History::from_ron(&history.to_ron()).unwrap();
+        assert_eq!(loaded, history);
+        assert_eq!(loaded.knowledge_after_moves(), replayed);
+
+        let mut mcts = AgentConfig::new("robotac").manager(&board);
+        mcts.set_knowledge(replayed);
+        assert_eq!(mcts.knowledge(), &replayed);
+        mcts.playout_n(50);
+
+        history.truncate(history.moves.len());
+        assert!(history.knowledge.is_some());
+        history.truncate(30);
+        assert!(history.knowledge.is_none());
+    }
+
+    #[test]
+    fn text_record() {
+        let mut history = History::new(9);
+        history.players[0].name = "robotac \"v2\"".to_string();
+        let mut board = Board::new_with_seed(history.seed);
+        let mut rng = StdRng::seed_from_u64(9);
+        for ply in 0..300 {
+            let Some(mv) = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+            else {
+                break;
+            };
+            board.play(&mv);
+            let comment = (ply % 7 == 0).then(|| format!("ply {ply} {{braces}}"));
+            history.push(
+                mv,
+                Annotation {
+                    comment,
+                    ..Annotation::default()
+                },
+            );
+        }
+        let text = history.to_text();
+        let parsed = History::from_text(&text).unwrap();
+        assert_eq!(parsed.seed, history.seed);
+        assert_eq!(parsed.moves, history.moves);
+        assert_eq!(parsed.started, history.started);
+        assert_eq!(parsed.players[0].name, "robotac 'v2'");
+        assert_eq!(
+            parsed.annotation(7).unwrap().comment.as_deref(),
+            Some("ply 7 {braces)")
+        );
+        assert_eq!(parsed.to_text(), text);
+
+        assert!(History::from_text("1. 4:TRADE@K").is_err());
+        assert!(History::from_text("[Seed \"9\"]\n\n1. 7:ENTER@K").is_err());
+        assert!(History::from_text("[Seed \"9\"]\n\n1. 7:FLY@K").is_err());
+    }
+}
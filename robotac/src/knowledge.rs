@@ -1,28 +1,62 @@
 use enum_map::EnumMap;
-use tac_types::{Card, Color, Hand, TacAction, TacMove, CARDS};
+use serde::{Deserialize, Serialize};
+use tac_types::{Card, Color, Hand, TacAction, TacMove, ALL_COLORS, CARDS, NUM_COLORS, NUM_TEAMS};
 
-use crate::board::Board;
+use crate::{board::Board, rules::OpeningAnnouncement};
 
-#[derive(Clone, Copy)]
+/// Evidence for the opening cards of a player that announced to hold one
+const OPENING_EVIDENCE: f32 = 2.0;
+/// Evidence moved to the partner of a player with an opening that didn't announce one,
+/// as players usually trade them an opening
+const TRADE_EVIDENCE: f32 = 1.0;
+/// Evidence for a card left after a player plays or discards it
+const PLAYED_DECAY: f32 = 0.5;
+/// Evidence removed from the openings of a player that helped the next player with an angel
+/// while they could have used an opening to enter a ball themselves
+const ANGEL_EVIDENCE: f32 = 1.0;
+/// Evidence removed from the tac of a suspended player that discarded although a tac could have
+/// answered the suspend. Keeping the tac for later is still possible, so it isn't ruled out
+const SUSPEND_TAC_EVIDENCE: f32 = 1.0;
+/// Index of the next player in the arrays about the other players, see [`Knowledge::idx`]
+const NEXT: usize = 0;
+/// Index of the partner in the arrays about the other players
+const PARTNER: usize = NUM_TEAMS - 1;
+/// Index of the previous player in the arrays about the other players
+const PREV: usize = NUM_COLORS - 2;
+
+/// What one player knows about the hands of the others. Can be saved next to a
+/// [`crate::history::History`] to resume with the same information state.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Knowledge {
     // Owner
     observer: Color,
     // Hand information state for each other player
-    hands: [EnumMap<Card, CardKnowledgeKind>; 3],
+    hands: [EnumMap<Card, CardKnowledgeKind>; NUM_COLORS - 1],
     // Announcement information for each other player
-    has_opening: [bool; 3],
+    has_opening: [bool; NUM_COLORS - 1],
+    // Soft evidence for each card in the hands of the other players, used as additional
+    // pseudo counts on top of the unseen cards. Negative if a card became less likely
+    evidence: [EnumMap<Card, f32>; NUM_COLORS - 1],
     // How many of each card type seen already
     pub history: EnumMap<Card, u8>,
     // Card we traded away. This holds a value until the card is played
     traded_away: Option<Card>,
     // Card we got. This holds a value until the card is played
     got_traded: Option<Card>,
-    // Did jester get played this round
-    played_jester: bool,
+    // How often jester got played this round
+    jesters_played: u8,
+    // Player that has to discard because of a suspend, or answer it with tac
+    suspended: Option<Color>,
+    // The current deal was taken into account by `start_deal` before its first move
+    #[serde(default)]
+    deal_started: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Default, Ord, Hash)]
-enum CardKnowledgeKind {
+/// What an observer knows about how many cards of one type another player holds.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Default, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum CardKnowledgeKind {
     #[default]
     Unknown,
     Atmost(u8),
@@ -34,112 +68,182 @@ impl Knowledge {
     pub fn new(observer: Color) -> Self {
         Self {
             observer,
-            hands: [EnumMap::default(); 3],
-            has_opening: [false; 3],
+            hands: [EnumMap::default(); NUM_COLORS - 1],
+            has_opening: [false; NUM_COLORS - 1],
+            evidence: [EnumMap::default(); NUM_COLORS - 1],
             history: EnumMap::default(),
             traded_away: None,
             got_traded: None,
-            played_jester: false,
+            jesters_played: 0,
+            suspended: None,
+            deal_started: false,
+        }
+    }
+
+    /// Forgets what was known about the hands of the last deal and takes in the new hand of the
+    /// observer. `board` has to be right after the deal, see [`Board::just_started`]. Otherwise
+    /// this happens with the first move of the deal in [`Self::update_with_move`].
+    pub fn start_deal(&mut self, board: &Board) {
+        for (card, v) in self.history {
+            debug_assert!(v <= card.amount());
+        }
+        // Full reset knowledge if new deck is played
+        if board.deck_fresh() {
+            self.history = EnumMap::default();
         }
+        // Reset knowledge about hands
+        self.hands = [EnumMap::default(); NUM_COLORS - 1];
+        self.evidence = [EnumMap::default(); NUM_COLORS - 1];
+        // Update with our own hand
+        self.update_with_hand(board.hand(self.observer), self.observer);
+        // Update with announce
+        self.set_openings_from_board(board);
+        self.jesters_played = 0;
+        self.suspended = None;
+        self.deal_started = true;
     }
 
     #[must_use]
     pub fn new_from_board(observer: Color, board: &Board) -> Self {
         let mut res = Self::new(observer);
-        if board.just_started() {
-            let openings = board.openings();
-            let others_openings = [
-                openings[observer.next() as usize],
-                openings[observer.partner() as usize],
-                openings[observer.prev() as usize],
-            ];
-            res.set_openings(others_openings);
+        if board.just_started() || Self::declared(board) {
+            res.set_openings_from_board(board);
         }
         res.update_with_hand(board.hand(observer), observer);
         res.sync();
         res
     }
 
-    pub fn set_openings(&mut self, openings: [bool; 3]) {
+    fn declared(board: &Board) -> bool {
+        board.rules().opening_announcement == OpeningAnnouncement::Declared
+    }
+
+    /// Takes the announcements of the current deal from `board`.
+    /// Declared announcements can be false, so they only count as evidence.
+    pub fn set_openings_from_board(&mut self, board: &Board) {
+        let openings = board.openings();
+        let others: [Color; NUM_COLORS - 1] =
+            core::array::from_fn(|i| Color::from((self.observer as usize + i + 1) % NUM_COLORS));
+        if Self::declared(board) {
+            self.has_opening = [false; NUM_COLORS - 1];
+            for player in others.into_iter().filter(|p| board.announced(*p)) {
+                self.announce(player, openings[player as usize]);
+            }
+        } else {
+            self.set_openings(others.map(|p| openings[p as usize]));
+        }
+    }
+
+    /// Records the declared announcement of `player`, without ruling anything out.
+    fn announce(&mut self, player: Color, opening: bool) {
+        let idx = self.idx(player);
+        self.has_opening[idx] = opening;
+        let amount = if opening { OPENING_EVIDENCE } else { 0.0 };
+        self.evidence[idx][Card::One] = amount;
+        self.evidence[idx][Card::Thirteen] = amount;
+    }
+
+    pub fn set_openings(&mut self, openings: [bool; NUM_COLORS - 1]) {
         self.has_opening = openings;
-        let [next, partner, prev] = openings;
-        if !partner {
-            self.hands[1][Card::One] = CardKnowledgeKind::Exact(0);
-            self.hands[1][Card::Thirteen] = CardKnowledgeKind::Exact(0);
+        for (evidence, has_opening) in self.evidence.iter_mut().zip(openings) {
+            let amount = if has_opening { OPENING_EVIDENCE } else { 0.0 };
+            evidence[Card::One] = amount;
+            evidence[Card::Thirteen] = amount;
+        }
+        if !openings[PARTNER] {
+            self.hands[PARTNER][Card::One] = CardKnowledgeKind::Exact(0);
+            self.hands[PARTNER][Card::Thirteen] = CardKnowledgeKind::Exact(0);
         }
-        // If both enemies have no openings we know for sure both can't have any
+        // If both enemies of a team have no openings we know for sure both can't have any
         // If only one of them has no openings, we know they can have at most one (traded from partner)
         // TODO use this information to know when the enemy with no openings played one, we know he can't have any more
-        if !next && !prev {
-            self.hands[0][Card::One] = CardKnowledgeKind::Exact(0);
-            self.hands[0][Card::Thirteen] = CardKnowledgeKind::Exact(0);
-            self.hands[2][Card::One] = CardKnowledgeKind::Exact(0);
-            self.hands[2][Card::Thirteen] = CardKnowledgeKind::Exact(0);
-        } else if !next {
-            self.hands[0][Card::One] = CardKnowledgeKind::Atmost(1);
-            self.hands[0][Card::Thirteen] = CardKnowledgeKind::Atmost(1);
-        } else if !prev {
-            self.hands[2][Card::One] = CardKnowledgeKind::Atmost(1);
-            self.hands[2][Card::Thirteen] = CardKnowledgeKind::Atmost(1);
+        for opponent in ALL_COLORS
+            .into_iter()
+            .filter(|c| c.team() != self.observer.team())
+        {
+            let idx = self.idx(opponent);
+            if openings[idx] {
+                continue;
+            }
+            let kind = if openings[self.idx(opponent.partner())] {
+                CardKnowledgeKind::Atmost(1)
+            } else {
+                CardKnowledgeKind::Exact(0)
+            };
+            self.hands[idx][Card::One] = kind;
+            self.hands[idx][Card::Thirteen] = kind;
         }
     }
 
+    #[allow(clippy::too_many_lines)]
     pub fn update_with_move(&mut self, mv: &TacMove, board: &Board) {
-        assert_eq!(mv.played_by, board.current_player(), "{}", mv);
+        assert_eq!(mv.played_by, board.hand_to_play(), "{mv}");
         let player = mv.played_by;
-        let play_for = mv.played_for;
         // Account for when jester was played this hand
-        let has_traded_card = if self.played_jester {
-            self.observer.partner().prev()
-        } else {
-            self.observer.partner()
-        };
+        let has_traded_card = self.traded_card_holder();
         // New hand
-        if board.just_started() {
-            for (card, v) in self.history {
-                debug_assert!(v <= card.amount());
-            }
-            // Full reset knowledge if new deck is played
-            if board.deck_fresh() {
-                self.history = EnumMap::default();
-            }
-            // Reset knowledge about hands
-            self.hands = [EnumMap::default(); 3];
-            // Update with our own hand
-            self.update_with_hand(board.hand(self.observer), self.observer);
-            // Update with announce
-            let announce = board.openings();
-            let announce_without_observer = [
-                announce[self.observer.next() as usize],
-                announce[self.observer.partner() as usize],
-                announce[self.observer.prev() as usize],
-            ];
-            self.set_openings(announce_without_observer);
-            self.played_jester = false;
+        if board.just_started() && !self.deal_started {
+            self.start_deal(board);
         }
+        self.deal_started = false;
         for (card, v) in self.history {
             debug_assert!(v <= card.amount(), "{v:?} {card:?} {:?}", card.amount());
         }
+        if let TacAction::Announce { opening } = mv.action {
+            if player != self.observer {
+                self.announce(player, opening);
+            }
+            self.sync();
+            return;
+        }
         // Update knowledge after trade
         if matches!(mv.action, TacAction::Trade) {
-            // Card we got
-            if player == self.observer.partner() {
-                self.got_traded = Some(mv.card);
-                self.history[mv.card] += 1;
+            // Trades are hidden until the last one hands out every card at once
+            if board.last_trade() {
+                let got = if player == self.observer.partner() {
+                    mv.card
+                } else {
+                    board
+                        .incoming_trade(self.observer)
+                        .expect("Our partner committed a card")
+                };
+                self.got_traded = Some(got);
+                self.history[got] += 1;
+            }
             // Card we traded
-            } else if player == self.observer {
+            if player == self.observer {
                 self.traded_away = Some(mv.card);
-                if let CardKnowledgeKind::Exact(x) = self.hands[1][mv.card] {
-                    self.hands[1][mv.card] = CardKnowledgeKind::Exact(x + 1);
+                self.hands[PARTNER][mv.card] = match self.hands[PARTNER][mv.card] {
+                    CardKnowledgeKind::Exact(x) => CardKnowledgeKind::Exact(x + 1),
+                    CardKnowledgeKind::Atmost(x) => CardKnowledgeKind::Atmost(x + 1),
+                    CardKnowledgeKind::Unknown => CardKnowledgeKind::Unknown,
+                };
+            // An opponent with an opening probably helps out their partner without one
+            } else if player != self.observer.partner()
+                && self.has_opening(player)
+                && !self.has_opening(player.partner())
+            {
+                for card in [Card::One, Card::Thirteen] {
+                    let given = &mut self.evidence[self.idx(player)][card];
+                    *given = (*given - TRADE_EVIDENCE).max(0.0);
+                    self.evidence[self.idx(player.partner())][card] += TRADE_EVIDENCE;
                 }
             }
             self.sync();
             return;
         }
+        // Only the card itself is taken from the hand, for a Tac that's the Tac and not the card it replays.
         // If partner plays card we traded away, don't update history because it is already accounted for
         if let Some(traded) = self.traded_away {
             if traded == mv.card && has_traded_card == player {
                 self.traded_away.take();
+                // Only the history already contains the card, it is gone from their hand
+                if player != self.observer {
+                    if let CardKnowledgeKind::Exact(x @ 1..) = self.hands[self.idx(player)][mv.card]
+                    {
+                        self.set_exact(mv.card, player, x - 1);
+                    }
+                }
             } else {
                 // Update history with card played
                 if player != self.observer {
@@ -152,26 +256,50 @@ impl Knowledge {
                 self.update_with_card(mv.card, player);
             }
         }
+        if player != self.observer {
+            self.decay_evidence(mv.card, player);
+        }
+        // A forced discard only tells something if the suspend could have been answered with tac
+        if matches!(mv.action, TacAction::Discard)
+            && self.suspended == Some(player)
+            && player != self.observer
+        {
+            self.discarded_when_suspended(board, player);
+        }
+        // The suspend is passed on if the suspended player answers with one of their own
+        self.suspended = matches!(mv.action, TacAction::Suspend).then(|| player.next());
         // Previous player discard because they couldn't play anything
         if matches!(mv.action, tac_types::TacAction::Discard)
             && !board.force_discard()
             && player != self.observer
         {
-            // TODO If able to tac previous move but discard instead, we know no tac in hand
             self.discarded_no_balls_in_play(board, player);
             if !board.balls_with(player).is_empty() {
                 self.discarded_balls_in_play(board, mv.card, player);
             }
         }
 
+        // The card a Tac replays was already counted when it was played first,
+        // but the Tac was played with the same choices as that card
+        let effective = match mv.card {
+            Card::Tac if !matches!(mv.action, TacAction::Discard) => board.tac_replayed_card(),
+            card => Some(card),
+        };
+        if effective == Some(Card::Angel)
+            && !matches!(mv.action, TacAction::Discard)
+            && player != self.observer
+        {
+            self.played_angel(board, player);
+        }
+
         // We played devil so we have perfect knowledge about hand of player after us
         if matches!(mv.action, TacAction::Devil) && player == self.observer {
             let next = player.next();
-            assert_eq!(self.idx(next), 0);
+            assert_eq!(self.idx(next), NEXT);
             // Get hand of player after us
             let mut hand = board.hand(next).clone();
             // If jester was played and card we traded away was not played yet remove card from hand
-            if self.played_jester {
+            if has_traded_card == next {
                 if let Some(card) = self.traded_away {
                     hand.remove(card);
                 }
@@ -179,10 +307,10 @@ impl Knowledge {
             // Update history with hand next player
             // TODO this technically does things with knowledge not necessary, look into specializing
             self.update_with_hand(&hand, next);
-            // Make hand knowledge exact
-            self.hands[0] = EnumMap::default();
-            for c in hand.iter() {
-                self.hands[0][*c] = match self.hands[0][*c] {
+            // Make hand knowledge exact, this includes the card we traded away
+            self.hands[NEXT] = EnumMap::default();
+            for c in board.hand(next).iter() {
+                self.hands[NEXT][*c] = match self.hands[NEXT][*c] {
                     CardKnowledgeKind::Unknown => CardKnowledgeKind::Exact(1),
                     CardKnowledgeKind::Exact(x) => CardKnowledgeKind::Exact(x + 1),
                     CardKnowledgeKind::Atmost(_) => unreachable!(),
@@ -198,20 +326,33 @@ impl Knowledge {
             if self.observer == mv.played_for.prev() {
                 hand.remove(mv.card);
             }
+            // Same goes for the card we traded away if it comes back to us
+            if has_traded_card == self.observer.next() {
+                if let Some(card) = self.traded_away {
+                    hand.remove(card);
+                }
+            }
             self.update_with_hand(&hand, self.observer.next());
             // Apply rotation for hand knowledge
             self.hands.rotate_left(1);
+            self.evidence.rotate_left(1);
+            self.evidence[PREV] = EnumMap::default();
             // Our hand is already the hand from the player after us before jester
             // So we know every card in it
-            self.hands[2] = EnumMap::default();
-            for c in board.hand(self.observer).iter() {
-                self.hands[2][*c] = match self.hands[2][*c] {
+            self.hands[PREV] = EnumMap::default();
+            let mut own = board.hand(self.observer).clone();
+            // The jester we are playing doesn't get passed on
+            if mv.played_by == self.observer {
+                own.remove(mv.card);
+            }
+            for c in own.iter() {
+                self.hands[PREV][*c] = match self.hands[PREV][*c] {
                     CardKnowledgeKind::Unknown => CardKnowledgeKind::Exact(1),
                     CardKnowledgeKind::Exact(x) => CardKnowledgeKind::Exact(x + 1),
                     CardKnowledgeKind::Atmost(_) => unreachable!(),
                 };
             }
-            self.played_jester = true;
+            self.jesters_played += 1;
         }
         // Check which cards can still be in the deck
         self.sync();
@@ -241,6 +382,27 @@ impl Knowledge {
         }
     }
 
+    /// `player` moved a ball of the next player with an angel. That only helps the opponents, so
+    /// it is usually done when nothing better is left. With balls in base an opening would
+    /// have been better, which makes them less likely.
+    pub fn played_angel(&mut self, board: &Board, player: Color) {
+        if board.num_base(board.play_for(player)) > 0 {
+            let idx = self.idx(player);
+            for card in [Card::One, Card::Thirteen] {
+                self.evidence[idx][card] -= ANGEL_EVIDENCE;
+            }
+        }
+    }
+
+    /// `player` discarded instead of answering a suspend with tac, which makes a tac less likely.
+    pub fn discarded_when_suspended(&mut self, board: &Board, player: Color) {
+        let mut answers = Vec::new();
+        board.tac_moves_into(player, &mut answers);
+        if !answers.is_empty() {
+            self.evidence[self.idx(player)][Card::Tac] -= SUSPEND_TAC_EVIDENCE;
+        }
+    }
+
     pub fn discarded_balls_in_play(&mut self, board: &Board, card: Card, player: Color) {
         // Card is used to step forward
         if card.is_simple().is_some() {
@@ -273,6 +435,165 @@ impl Knowledge {
         }
     }
 
+    #[must_use]
+    pub fn observer(&self) -> Color {
+        self.observer
+    }
+
+    /// Returns what is known about the amount of `card` in the hand of `player`.
+    /// The observer knows its own hand, so nothing is stored for it.
+    #[must_use]
+    pub fn card_knowledge(&self, player: Color, card: Card) -> CardKnowledgeKind {
+        if player == self.observer {
+            return CardKnowledgeKind::Unknown;
+        }
+        self.hands[self.idx(player)][card]
+    }
+
+    /// Whether `player` announced to have an opening card this round.
+    #[must_use]
+    pub fn has_opening(&self, player: Color) -> bool {
+        player != self.observer && self.has_opening[self.idx(player)]
+    }
+
+    #[must_use]
+    pub fn traded_away(&self) -> Option<Card> {
+        self.traded_away
+    }
+
+    #[must_use]
+    pub fn got_traded(&self) -> Option<Card> {
+        self.got_traded
+    }
+
+    #[must_use]
+    pub fn jesters_played(&self) -> u8 {
+        self.jesters_played
+    }
+
+    /// The player that has to discard or answer a suspend with tac next.
+    #[must_use]
+    pub fn suspended(&self) -> Option<Color> {
+        self.suspended
+    }
+
+    /// Returns the probability that `player` holds at least one `card`.
+    /// Cards known to be in hand are certain, the other slots of the hand are filled with
+    /// the cards the observer hasn't seen yet.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn card_probability(&self, board: &Board, player: Color, card: Card) -> f64 {
+        if player == self.observer {
+            return f64::from(u8::from(board.hand(player).contains(card)));
+        }
+        let known = &self.hands[self.idx(player)];
+        match known[card] {
+            CardKnowledgeKind::Exact(x) => return f64::from(u8::from(x > 0)),
+            CardKnowledgeKind::Atmost(0) => return 0.0,
+            CardKnowledgeKind::Atmost(_) | CardKnowledgeKind::Unknown => {}
+        }
+        let exact = known
+            .values()
+            .map(|k| match k {
+                CardKnowledgeKind::Exact(x) => usize::from(*x),
+                _ => 0,
+            })
+            .sum::<usize>();
+        let slots = board.hand(player).amount().saturating_sub(exact);
+        let unseen_of = |c: Card| usize::from(self.unseen(c));
+        let copies = unseen_of(card);
+        let unseen = CARDS.iter().map(|c| unseen_of(*c)).sum::<usize>();
+        // Chance that none of the unseen copies ends up in one of the free slots
+        let none = (0..slots.min(unseen))
+            .map(|i| unseen.saturating_sub(copies + i) as f64 / (unseen - i) as f64)
+            .product::<f64>();
+        1.0 - none
+    }
+
+    /// Expected number of cards of `player` that are one of `cards`. Cards known exactly are
+    /// counted as they are, the other slots of the hand follow [`Self::hand_distribution`].
+    /// The observer counts its own hand.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn expected_cards(&self, board: &Board, player: Color, cards: &[Card]) -> f64 {
+        let hand = board.hand(player);
+        if player == self.observer {
+            return hand.iter().filter(|card| cards.contains(card)).count() as f64;
+        }
+        let known = &self.hands[self.idx(player)];
+        let exact = |card: Card| match known[card] {
+            CardKnowledgeKind::Exact(x) => usize::from(x),
+            _ => 0,
+        };
+        let slots = hand
+            .amount()
+            .saturating_sub(CARDS.iter().map(|c| exact(*c)).sum());
+        let distribution = self.hand_distribution(player);
+        cards
+            .iter()
+            .map(|card| exact(*card) as f64 + slots as f64 * distribution[*card])
+            .sum()
+    }
+
+    /// Returns the probability of each card for a card of `player` that isn't known exactly.
+    /// Every unseen copy of a card counts once, plus the evidence gathered from announcements,
+    /// trades and the cards `player` played or discarded.
+    /// The observer knows its own hand, so its distribution is empty.
+    #[must_use]
+    pub fn hand_distribution(&self, player: Color) -> EnumMap<Card, f64> {
+        let mut distribution = EnumMap::default();
+        if player == self.observer {
+            return distribution;
+        }
+        let known = &self.hands[self.idx(player)];
+        for (card, p) in &mut distribution {
+            *p = match known[card] {
+                CardKnowledgeKind::Exact(_) | CardKnowledgeKind::Atmost(0) => 0.0,
+                CardKnowledgeKind::Atmost(_) | CardKnowledgeKind::Unknown => {
+                    (f64::from(self.unseen(card))
+                        + f64::from(self.evidence[self.idx(player)][card]))
+                    .max(0.0)
+                }
+            };
+        }
+        let total = distribution.values().sum::<f64>();
+        if total > 0.0 {
+            distribution.values_mut().for_each(|p| *p /= total);
+        }
+        distribution
+    }
+
+    /// How much more likely `player` holds `card` than uniformly drawing from the unseen cards
+    /// suggests, according to [`Self::hand_distribution`].
+    #[must_use]
+    pub fn evidence_factor(&self, player: Color, card: Card) -> f64 {
+        let unseen = self.unseen(card);
+        if player == self.observer || unseen == 0 {
+            return 1.0;
+        }
+        let evidence = f64::from(self.evidence[self.idx(player)][card]);
+        (f64::from(unseen) + evidence).max(0.0) / f64::from(unseen)
+    }
+
+    /// Copies of `card` the observer hasn't seen yet, neither played nor in its own hand.
+    #[must_use]
+    pub fn unseen(&self, card: Card) -> u8 {
+        card.amount().saturating_sub(self.history[card])
+    }
+
+    fn decay_evidence(&mut self, card: Card, player: Color) {
+        let idx = self.idx(player);
+        // Announcing an opening only tells about one of them
+        let cards: &[Card] = if matches!(card, Card::One | Card::Thirteen) {
+            &[Card::One, Card::Thirteen]
+        } else {
+            &[card]
+        };
+        for card in cards {
+            self.evidence[idx][*card] *= PLAYED_DECAY;
+        }
+    }
+
     #[must_use]
     pub fn known_cards(&self, player: Color) -> Vec<(Card, u8, bool)> {
         let mut cards = Vec::new();
@@ -328,13 +649,20 @@ impl Knowledge {
     }
 
     pub fn sync(&mut self) {
+        // The card we traded away is counted as seen, but its holder still has it
+        let traded = self.traded_away.zip(
+            Some(self.traded_card_holder())
+                .filter(|holder| *holder != self.observer)
+                .map(|holder| self.idx(holder)),
+        );
         for card in &CARDS {
             if !self.possible(*card) {
-                self.hands.iter_mut().for_each(|hand| {
+                for (idx, hand) in self.hands.iter_mut().enumerate() {
                     if let CardKnowledgeKind::Unknown = hand[*card] {
-                        hand[*card] = CardKnowledgeKind::Exact(0);
+                        let holds_traded = traded == Some((*card, idx));
+                        hand[*card] = CardKnowledgeKind::Exact(u8::from(holds_traded));
                     };
-                });
+                }
             }
         }
     }
@@ -346,7 +674,16 @@ impl Knowledge {
 
     pub fn reset(&mut self) {
         self.hands.iter_mut().for_each(EnumMap::clear);
+        self.evidence.iter_mut().for_each(EnumMap::clear);
         self.history.clear();
+        self.suspended = None;
+    }
+
+    /// Returns the player currently holding the card we traded away.
+    /// Every jester passes it on to the player before the one holding it.
+    #[must_use]
+    pub fn traded_card_holder(&self) -> Color {
+        (0..self.jesters_played).fold(self.observer.partner(), |holder, _| holder.prev())
     }
 
     #[must_use]
@@ -361,14 +698,14 @@ impl std::fmt::Debug for Knowledge {
         write!(f, "Open {:?}, ", self.has_opening)?;
         write!(f, "Away {:?}, ", self.traded_away)?;
         write!(f, "Got  {:?}, ", self.got_traded)?;
-        write!(f, "Jest {:?}\n", self.played_jester)?;
+        write!(f, "Jest {:?}, ", self.jesters_played)?;
+        writeln!(f, "Susp {:?}", self.suspended)?;
         for (idx, k) in self.hands.iter().enumerate() {
-            if idx == 0 {
-                write!(f, "next: ")?;
-            } else if idx == 1 {
-                write!(f, "part: ")?;
-            } else {
-                write!(f, "prev: ")?;
+            match idx {
+                NEXT => write!(f, "next: ")?,
+                PARTNER => write!(f, "part: ")?,
+                PREV => write!(f, "prev: ")?,
+                _ => write!(f, "+{}:   ", idx + 1)?,
             }
             for (c, v) in k {
                 if matches!(
@@ -385,61 +722,547 @@ impl std::fmt::Debug for Knowledge {
     }
 }
 
+/// A contradiction between what a player knows and the actual state of the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// `observer` knows something about the hand of `player` that is wrong
+    Hand {
+        observer: Color,
+        player: Color,
+        card: Card,
+        known: CardKnowledgeKind,
+        held: u8,
+    },
+    /// `observer` saw more copies of `card` than there are in the deck
+    History {
+        observer: Color,
+        card: Card,
+        seen: u8,
+    },
+}
+
+impl std::fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hand {
+                observer,
+                player,
+                card,
+                known,
+                held,
+            } => write!(
+                f,
+                "{observer:?} thinks {player:?} holds {known:?} {card:?} but they hold {held}"
+            ),
+            Self::History {
+                observer,
+                card,
+                seen,
+            } => write!(
+                f,
+                "{observer:?} saw {seen} {card:?} but there are only {}",
+                card.amount()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Inconsistency {}
+
+/// Cross-checks the knowledge of every player against the true hands on `board`.
+/// `knowledge` has to be indexed by the color of its observer and updated with every move
+/// played on `board`.
+///
+/// Knowledge only learns about a new deal with the next move, so the hands aren't checked
+/// right after a deal.
+/// # Errors
+/// The first contradiction that was found.
+pub fn validate(board: &Board, knowledge: &[Knowledge; NUM_COLORS]) -> Result<(), Inconsistency> {
+    for (i, k) in knowledge.iter().enumerate() {
+        let observer = k.observer();
+        debug_assert_eq!(observer, Color::from(i));
+        for (card, seen) in k.history {
+            if seen > card.amount() {
+                return Err(Inconsistency::History {
+                    observer,
+                    card,
+                    seen,
+                });
+            }
+        }
+        if board.just_started() {
+            continue;
+        }
+        for player in ALL_COLORS {
+            if player == observer {
+                continue;
+            }
+            let hand = board.hand(player);
+            for card in CARDS {
+                // Cards put up for trade already count as being in the hand of the partner
+                let held = usize::from(hand.count(card))
+                    + usize::from(board.incoming_trade(player) == Some(card));
+                let held = u8::try_from(held).unwrap_or(u8::MAX);
+                let known = k.card_knowledge(player, card);
+                let wrong = match known {
+                    CardKnowledgeKind::Unknown => false,
+                    CardKnowledgeKind::Atmost(x) => held > x,
+                    CardKnowledgeKind::Exact(x) => held != x,
+                };
+                if wrong {
+                    return Err(Inconsistency::Hand {
+                        observer,
+                        player,
+                        card,
+                        known,
+                        held,
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use mcts::GameState;
-    use rand::{rngs::StdRng, seq::IteratorRandom, thread_rng, SeedableRng};
-    use tac_types::ALL_COLORS;
+    use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
 
     use super::*;
+    use crate::{
+        fuzz::{check_game, FuzzConfig},
+        rules::Ruleset,
+    };
     #[test]
     fn announce() {
+        let config = FuzzConfig {
+            redetermine_every: None,
+            ..FuzzConfig::default()
+        };
         for seed in 0..1000 {
-            println!("SEED {seed}");
+            if let Err(failure) = check_game(seed, &config) {
+                panic!("{failure}");
+            }
+        }
+    }
+
+    #[test]
+    fn declared_announce() {
+        // Random announcements are false half of the time
+        let config = FuzzConfig {
+            rules: Ruleset {
+                opening_announcement: OpeningAnnouncement::Declared,
+                ..Ruleset::default()
+            },
+            ..FuzzConfig::default()
+        };
+        for seed in 0..200 {
+            if let Err(failure) = check_game(seed, &config) {
+                panic!("{failure}");
+            }
+        }
+    }
+
+    #[test]
+    fn redetermine_respects_knowledge() {
+        for seed in 0..20 {
             let mut board = Board::new_with_seed(seed);
-            println!("{board:?}");
             let mut rng = StdRng::seed_from_u64(seed);
-            let mut know: [_; 4] =
+            let mut know: [_; NUM_COLORS] =
                 core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board));
-            for i in 0..10000 {
-                let get_moves = &board.get_moves(board.current_player());
-                let Some(mv) = get_moves.iter().choose(&mut rng) else {
-                    // Game over
+            loop {
+                for k in &know {
+                    let mut determined = board.clone();
+                    determined.redetermine(k.observer(), k, &mut rng);
+                    for player in ALL_COLORS {
+                        let hand = determined.hand(player);
+                        assert_eq!(hand.amount(), board.hand(player).amount());
+                        if player == k.observer() {
+                            continue;
+                        }
+                        for (card, amount, exact) in k.known_cards(player) {
+                            let count = usize::from(hand.count(card));
+                            // Redetermining ignores the outdated knowledge of a new deal
+                            if !exact && !board.just_started() {
+                                assert!(count <= amount as usize, "{card:?} {k:?} {board:?}");
+                            }
+                        }
+                    }
+                    if let Some(card) = k.traded_away() {
+                        let holder = k.traded_card_holder();
+                        if !board.need_trade() && board.hand(holder).iter().any(|c| *c == card) {
+                            assert!(determined.hand(holder).iter().any(|c| *c == card));
+                        }
+                    }
+                }
+                let moves = board.get_moves(board.current_player());
+                let Some(mv) = moves.iter().choose(&mut rng) else {
                     break;
                 };
-                // if seed == 5 && i == 697 {
-                // if seed == 58 && i == 844 {
-                // if seed == 7 && i == 844 {
-                // if seed == 73 && i == 100 {
-                if seed == 123 && i == 1776 {
-                    println!("{board:?}");
+                for k in &mut know {
+                    k.update_with_move(mv, &board);
                 }
-                println!("{i}: {mv}");
+                board.play(mv);
+                assert_eq!(validate(&board, &know), Ok(()), "{seed}: {mv}");
+            }
+        }
+    }
+
+    #[test]
+    fn card_probability() {
+        for seed in 0..10 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut know: [_; NUM_COLORS] =
+                core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board));
+            loop {
+                for k in &know {
+                    for player in ALL_COLORS {
+                        for card in CARDS {
+                            let p = k.card_probability(&board, player, card);
+                            assert!((0.0..=1.0).contains(&p));
+                            if k.card_knowledge(player, card) == CardKnowledgeKind::Exact(0) {
+                                assert!(p == 0.0);
+                            }
+                            if board.hand(player).contains(card) && !board.just_started() {
+                                assert!(p > 0.0, "{card:?} {player:?} {k:?} {board:?}");
+                            }
+                        }
+                    }
+                }
+                let moves = board.get_moves(board.current_player());
+                let Some(mv) = moves.iter().choose(&mut rng) else {
+                    break;
+                };
                 for k in &mut know {
                     k.update_with_move(mv, &board);
                 }
-                board.make_move(mv);
-                if seed == 7 && i == 844 {
-                    println!("{board:?}");
+                board.play(mv);
+                assert_eq!(validate(&board, &know), Ok(()), "{seed}: {mv}");
+            }
+        }
+    }
+
+    #[test]
+    fn hand_distribution() {
+        for seed in 0..10 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut know: [_; NUM_COLORS] =
+                core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board));
+            for k in &know {
+                for player in ALL_COLORS.into_iter().filter(|p| *p != k.observer()) {
+                    // Announcing an opening makes them more likely than any other card
+                    let dist = k.hand_distribution(player);
+                    if k.has_opening(player) {
+                        assert!(dist[Card::One] > dist[Card::Two]);
+                        assert!(k.evidence_factor(player, Card::One) > 1.0);
+                    }
                 }
             }
+            loop {
+                for k in &know {
+                    assert!(k
+                        .hand_distribution(k.observer())
+                        .values()
+                        .all(|p| *p == 0.0));
+                    for player in ALL_COLORS.into_iter().filter(|p| *p != k.observer()) {
+                        let dist = k.hand_distribution(player);
+                        let total = dist.values().sum::<f64>();
+                        assert!(total == 0.0 || (total - 1.0).abs() < 1e-9);
+                        for (card, p) in dist {
+                            // Only discarding instead of answering a suspend and angels lower cards
+                            let factor = k.evidence_factor(player, card);
+                            let lowered = [Card::Tac, Card::One, Card::Thirteen];
+                            assert!(factor >= 1.0 || (lowered.contains(&card) && factor >= 0.0));
+                            if matches!(
+                                k.card_knowledge(player, card),
+                                CardKnowledgeKind::Exact(_) | CardKnowledgeKind::Atmost(0)
+                            ) {
+                                assert!(p == 0.0);
+                            }
+                        }
+                    }
+                }
+                let moves = board.get_moves(board.current_player());
+                let Some(mv) = moves.iter().choose(&mut rng) else {
+                    break;
+                };
+                for k in &mut know {
+                    k.update_with_move(mv, &board);
+                }
+                board.play(mv);
+            }
+        }
+    }
+
+    #[test]
+    fn validate_detects_wrong_knowledge() {
+        let board = Board::new_with_seed(3);
+        let mut know: [_; NUM_COLORS] =
+            core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board));
+        // Knowledge of a fresh deal isn't checked until the first move is played
+        assert_eq!(validate(&board, &know), Ok(()));
+        let mut board = board;
+        let mv = board.get_moves(board.current_player()).remove(0);
+        for k in &mut know {
+            k.update_with_move(&mv, &board);
+        }
+        board.play(&mv);
+        assert_eq!(validate(&board, &know), Ok(()));
+        let card = *board.hand(Color::Blue).iter().next().unwrap();
+        know[Color::Black as usize].rule_out(card, Color::Blue);
+        assert!(matches!(
+            validate(&board, &know),
+            Err(Inconsistency::Hand {
+                observer: Color::Black,
+                player: Color::Blue,
+                known: CardKnowledgeKind::Exact(0),
+                ..
+            })
+        ));
+        know[Color::Black as usize].history[card] = card.amount() + 1;
+        assert!(matches!(
+            validate(&board, &know),
+            Err(Inconsistency::History {
+                observer: Color::Black,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn suspend_chain() {
+        let mut suspends = 0;
+        let mut answered = 0;
+        for seed in 0..200 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut know: [_; NUM_COLORS] =
+                core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board));
+            for _ in 0..300 {
+                let Some(mv) = board
+                    .get_moves(board.current_player())
+                    .into_iter()
+                    .choose(&mut rng)
+                else {
+                    break;
+                };
+                let player = mv.played_by;
+                let before = know.map(|k| k.evidence_factor(player, Card::Tac));
+                let mut answers = Vec::new();
+                board.tac_moves_into(player, &mut answers);
+                let forced = board.force_discard() && matches!(mv.action, TacAction::Discard);
+                for k in &mut know {
+                    k.update_with_move(&mv, &board);
+                }
+                board.play(&mv);
+                for (k, before) in know.iter().zip(before) {
+                    let expected = board.force_discard().then(|| board.current_player());
+                    assert_eq!(k.suspended(), expected, "{mv}");
+                    if forced && !answers.is_empty() && k.observer() != player {
+                        assert!(k.evidence_factor(player, Card::Tac) <= before);
+                        answered += 1;
+                    }
+                }
+                suspends += usize::from(matches!(mv.action, TacAction::Suspend));
+                assert_eq!(validate(&board, &know), Ok(()));
+            }
         }
+        assert!(suspends > 0);
+        assert!(answered > 0);
     }
+
+    #[test]
+    fn angel_lowers_openings() {
+        let mut angels = 0;
+        for seed in 0..200 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut know: [_; NUM_COLORS] =
+                core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board));
+            for _ in 0..300 {
+                let Some(mv) = board
+                    .get_moves(board.current_player())
+                    .into_iter()
+                    .choose(&mut rng)
+                else {
+                    break;
+                };
+                let player = mv.played_by;
+                let entering = board.num_base(board.play_for(player)) > 0;
+                let evidence = |k: &Knowledge| {
+                    (k.observer() != player).then(|| k.evidence[k.idx(player)][Card::One])
+                };
+                let before = know.map(|k| evidence(&k));
+                for k in &mut know {
+                    k.update_with_move(&mv, &board);
+                }
+                board.play(&mv);
+                let discard = matches!(mv.action, TacAction::Trade | TacAction::Discard);
+                if mv.card == Card::Angel && !discard && entering {
+                    for (k, before) in know.iter().zip(before) {
+                        if let Some(before) = before {
+                            assert!(evidence(k).unwrap() < before);
+                            angels += 1;
+                        }
+                    }
+                }
+                assert_eq!(validate(&board, &know), Ok(()));
+            }
+        }
+        assert!(angels > 0);
+    }
+
+    #[test]
+    fn tac_replaying_angel() {
+        let mut board = Board::new();
+        board.add_hand(Color::Black, Card::Angel);
+        board.add_hand(Color::Blue, Card::Tac);
+        let mut know = Knowledge::new_from_board(Color::Green, &board);
+        let play = |board: &mut Board, know: &mut Knowledge, card| {
+            let mv = board.moves_for_card(board.current_player(), card).remove(0);
+            assert_eq!(mv.played_for, mv.played_by.next());
+            know.update_with_move(&mv, board);
+            board.play(&mv);
+        };
+        play(&mut board, &mut know, Card::Angel);
+        let history = know.history;
+        let before = know.evidence_factor(Color::Blue, Card::One);
+        play(&mut board, &mut know, Card::Tac);
+        // Only the Tac is taken from the hand of Blue, the Angel was counted when Black played it
+        assert_eq!(know.history[Card::Angel], history[Card::Angel]);
+        assert_eq!(know.history[Card::Tac], history[Card::Tac] + 1);
+        // Blue helped Green with the Angel instead of entering a ball of its own
+        assert!(know.evidence_factor(Color::Blue, Card::One) < before);
+    }
+
     #[test]
     fn redetermine() {
-        let mut board = Board::new_with_seed(2);
-        let mut rng = StdRng::seed_from_u64(2);
+        let board = Board::new_with_seed(2);
         println!("{board:?}");
-        let mut know: [_; 4] =
+        let know: [_; NUM_COLORS] =
             core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board));
         // for k in know {
         //     println!("{k:?}");
         // }
         for (i, c) in ALL_COLORS.into_iter().enumerate() {
             let mut board = board.clone();
-            board.redetermine(c, &know[i]);
+            board.redetermine(c, &know[i], &mut rand::thread_rng());
             println!("REDETERMINED {c:?}\n{board:?}");
-            break;
         }
     }
+
+    #[test]
+    fn hidden_trades() {
+        let mut board = Board::new_with_seed(6);
+        let mut know: [_; NUM_COLORS] =
+            core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board));
+        let mut rng = StdRng::seed_from_u64(6);
+        let mut committed = [None; NUM_COLORS];
+        while board.need_trade() {
+            let player = board.current_player();
+            let mv = board.get_moves(player).into_iter().next().unwrap();
+            committed[player as usize] = Some(mv.card);
+            let last = board.last_trade();
+            for k in &mut know {
+                k.update_with_move(&mv, &board);
+            }
+            board.play(&mv);
+            for k in &know {
+                let partner = k.observer().partner();
+                let expected = if last {
+                    committed[partner as usize]
+                } else {
+                    None
+                };
+                assert_eq!(k.got_traded(), expected, "{:?}", k.observer());
+                if last {
+                    continue;
+                }
+                // Only our own trade stays as it is, the others are drawn again
+                let mut determined = board.clone();
+                determined.redetermine(k.observer(), k, &mut rng);
+                for player in ALL_COLORS {
+                    assert_eq!(
+                        determined.trade_committed(player),
+                        board.trade_committed(player)
+                    );
+                    assert_eq!(
+                        determined.hand(player).amount(),
+                        board.hand(player).amount()
+                    );
+                }
+                assert_eq!(
+                    determined.incoming_trade(partner),
+                    board.incoming_trade(partner)
+                );
+            }
+        }
+        assert!(know.iter().all(|k| k.got_traded().is_some()));
+    }
+    #[test]
+    fn traded_card_through_jesters() {
+        let black = Color::Black;
+        // Find a deal where black can trade away a unique card and the extra jester and tacs fit
+        let (mut board, traded) = (0..)
+            .find_map(|seed| {
+                let board = Board::new_with_seed(seed);
+                let dealt = |card| {
+                    ALL_COLORS
+                        .iter()
+                        .flat_map(|c| board.hand(*c).iter())
+                        .filter(|c| **c == card)
+                        .count()
+                };
+                let tacs = dealt(Card::Tac) + NUM_TEAMS - 1;
+                if dealt(Card::Jester) > 0 || tacs > usize::from(Card::Tac.amount()) {
+                    return None;
+                }
+                let traded = *board.hand(black).iter().find(|c| c.amount() == 1)?;
+                Some((board, traded))
+            })
+            .unwrap();
+        let mut know: [_; NUM_COLORS] =
+            core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board));
+        let mut play = |board: &mut Board, mv: TacMove| {
+            for k in &mut know {
+                k.update_with_move(&mv, board);
+            }
+            board.play(&mv);
+        };
+        for _ in ALL_COLORS {
+            let player = board.current_player();
+            let card = if player == black {
+                traded
+            } else {
+                *board.hand(player).iter().next().unwrap()
+            };
+            play(
+                &mut board,
+                TacMove::new(card, TacAction::Trade, player, player),
+            );
+        }
+        // A jester for every team brings the card black traded away back to black, the
+        // ones after the first are tacs copying it
+        let player = board.current_player();
+        board.add_hand(player, Card::Jester);
+        let mut holder = player;
+        for _ in 1..NUM_TEAMS {
+            holder = holder.next();
+            board.add_hand(holder, Card::Tac);
+        }
+        play(
+            &mut board,
+            TacMove::new(Card::Jester, TacAction::Jester, player, player),
+        );
+        for _ in 1..NUM_TEAMS {
+            play(
+                &mut board,
+                TacMove::new(Card::Tac, TacAction::Jester, player, player),
+            );
+        }
+        assert!(board.hand(black).contains(traded));
+        assert_eq!(know[black as usize].history[traded], 1);
+    }
 }
@@ -6,19 +6,129 @@
     clippy::inline_always
 )]
 use board::Board;
+use eval::EvalParams;
 use knowledge::Knowledge;
-use mcts::{policies::UCTPolicy, Evaluator, GameState, MCTS};
-use tac_types::{Color, TacMove};
+use mcts::{node::MoveInfo, policies::UCTPolicy, EvalView, Evaluator, GameState, MCTS};
+use movegen::MoveGen;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use tac_types::{Color, PackedTacMove, TacAction, TacMove, ALL_COLORS, NUM_TEAMS};
 
+pub mod arena;
 pub mod board;
+pub mod book;
+pub mod discard;
+pub mod engine;
 pub mod eval;
+pub mod explain;
+pub mod features;
+pub mod fuzz;
 pub mod history;
 pub mod knowledge;
 pub mod movegen;
+pub mod perft;
+#[cfg(test)]
+mod reference;
+pub mod rules;
+pub mod safety;
+pub mod selfplay;
 pub mod seven;
+pub mod solver;
+pub mod trade;
+pub mod tune;
+pub mod view;
 
-pub struct TacAI;
-pub struct TacEval;
+#[derive(Clone, Copy, Debug)]
+pub struct TacAI {
+    /// See [`MCTS::rollout_length`]
+    pub rollout_length: usize,
+    /// See [`MCTS::select_child_after_search`]
+    pub final_selection: FinalSelection,
+    /// See [`MCTS::determinization_log_size`]
+    pub determinization_log: usize,
+    /// See [`MCTS::eval_view`]
+    pub eval_view: EvalView,
+}
+
+impl TacAI {
+    pub const DEFAULT_ROLLOUT_LENGTH: usize = 4;
+}
+
+impl Default for TacAI {
+    fn default() -> Self {
+        Self {
+            rollout_length: Self::DEFAULT_ROLLOUT_LENGTH,
+            final_selection: FinalSelection::default(),
+            determinization_log: 0,
+            eval_view: EvalView::default(),
+        }
+    }
+}
+
+/// How the move to play is picked once the search is done.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FinalSelection {
+    /// The most visited move
+    #[default]
+    MostVisited,
+    /// The move with the best mean value among the moves with at least half the visits
+    /// of the most visited one
+    BestMean,
+    /// The move with the most visits per determinization it was legal in, among the moves
+    /// with at least half the visits of the most visited one. Moves that are only sometimes
+    /// legal aren't penalized for the playouts they couldn't get.
+    AvailabilityWeighted,
+}
+
+impl FinalSelection {
+    #[allow(clippy::cast_precision_loss)]
+    fn select<'a, M: MCTS>(self, children: &[&'a MoveInfo<M>]) -> &'a MoveInfo<M> {
+        let most_visited = children
+            .iter()
+            .max_by_key(|child| child.visits())
+            .expect("Should have at least one child");
+        let score = |child: &MoveInfo<M>| match self {
+            FinalSelection::MostVisited => child.visits() as f64,
+            FinalSelection::BestMean => child.sum_rewards() as f64 / child.visits() as f64,
+            FinalSelection::AvailabilityWeighted => {
+                child.visits() as f64 / child.availability().max(1) as f64
+            }
+        };
+        children
+            .iter()
+            .filter(|child| 2 * child.visits() >= most_visited.visits())
+            .max_by(|a, b| score(a).total_cmp(&score(b)))
+            .unwrap_or(most_visited)
+    }
+}
+
+/// Which evaluation function is used for leaf nodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvalKind {
+    /// Only counts the balls in home, see [`Board::eval_basic_normalized`].
+    Basic,
+    /// Weighs goal progress, home state and ball positions, see [`Board::eval_normalized`].
+    #[default]
+    Full,
+}
+
+/// Evaluates positions for every team, see [`Board::eval_normalized_for`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TacEval {
+    pub kind: EvalKind,
+    pub params: EvalParams,
+    /// Endgames decided within this many moves are evaluated as won or lost, `0` disables
+    /// the [`solver`]
+    pub solver_depth: usize,
+}
+
+impl TacEval {
+    /// Evaluations are fixed point numbers, a won game is worth `SCALE`.
+    pub const SCALE: i64 = 1_000;
+    /// Most positions the solver may visit for a single evaluation
+    pub const SOLVER_NODES: u64 = 200;
+}
 
 impl MCTS for TacAI {
     type State = Board;
@@ -26,19 +136,104 @@ impl MCTS for TacAI {
     type Select = UCTPolicy;
 
     fn virtual_loss(&self) -> i64 {
-        5
+        // A playout in flight counts as a lost game
+        TacEval::SCALE
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn value_scale(&self) -> f64 {
+        TacEval::SCALE as f64
+    }
+
+    fn rollout_length(&self) -> Option<usize> {
+        Some(self.rollout_length)
+    }
+
+    fn max_children_considered(&self, visits: u64) -> usize {
+        // Seven moves can have hundreds of children, so only widen with the square root of the visits
+        usize::try_from(visits.isqrt()).map_or(usize::MAX, |k| k.saturating_add(8))
+    }
+
+    fn move_heuristic(&self, state: &Board, mv: &PackedTacMove) -> f64 {
+        let mv = mv.unpack();
+        if matches!(mv.action, TacAction::Trade) {
+            let player = state.current_player();
+            let knowledge = trade::announced_knowledge(state, player);
+            return trade::trade_score(state, &knowledge, player, mv.card);
+        }
+        // Telling the partner the truth is the usual announcement
+        if let TacAction::Announce { opening } = mv.action {
+            let holds = state
+                .hand(mv.played_by)
+                .iter()
+                .any(|c| trade::is_opening(*c));
+            return if opening == holds { 1.0 } else { 0.0 };
+        }
+        if matches!(mv.action, TacAction::Discard) {
+            let knowledge = discard::seen_knowledge(state, mv.played_by);
+            return discard::discard_score(state, &knowledge, mv.played_by, mv.card);
+        }
+        // Moving balls into the goal and out of the base is usually good, giving up a card is not.
+        // A ball reaching home scores 1
+        f64::from(state.score_move_heuristic(&mv)) / 100.0
+    }
+
+    fn determinization_log_size(&self) -> usize {
+        self.determinization_log
+    }
+
+    fn eval_view(&self) -> EvalView {
+        self.eval_view
+    }
+
+    fn select_child_after_search<'a>(&self, children: &[&'a MoveInfo<Self>]) -> &'a MoveInfo<Self> {
+        self.final_selection.select(children)
+    }
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+impl TacEval {
+    /// Scales the normalized evaluation `value` of every team, indexed by [`Color::team`].
+    fn team_values(value: impl Fn(Color) -> f64) -> [i64; NUM_TEAMS] {
+        let scaled = |player: Color| (value(player) * Self::SCALE as f64).round() as i64;
+        let black = scaled(Color::Black);
+        core::array::from_fn(|team| match team {
+            0 => black,
+            // Two teams play a zero sum game, so the second evaluation is for free
+            _ if NUM_TEAMS == 2 => -black,
+            _ => scaled(ALL_COLORS[team]),
+        })
     }
 }
 
 impl Evaluator<TacAI> for TacEval {
-    type StateEval = i64;
+    /// Evaluation for every team, indexed by [`Color::team`]
+    type StateEval = [i64; NUM_TEAMS];
 
     fn eval_new(
         &self,
         state: &<TacAI as MCTS>::State,
         _handle: Option<mcts::search::SearchHandle<TacAI>>,
     ) -> Self::StateEval {
-        state.eval2()
+        if self.solver_depth > 0 && solver::is_endgame(state) {
+            if let Some(result) = solver::solve(state, self.solver_depth, Self::SOLVER_NODES) {
+                return Self::team_values(
+                    |player| {
+                        if result.is_win_for(player) {
+                            1.0
+                        } else {
+                            -1.0
+                        }
+                    },
+                );
+            }
+        }
+        match self.kind {
+            EvalKind::Basic => Self::team_values(|player| state.eval_basic_normalized_for(player)),
+            EvalKind::Full => {
+                Self::team_values(|player| state.eval_normalized_for(player, &self.params))
+            }
+        }
     }
 
     fn eval_existing(
@@ -51,15 +246,43 @@ impl Evaluator<TacAI> for TacEval {
     }
 
     fn make_relative(&self, eval: &Self::StateEval, player: &mcts::Player<TacAI>) -> i64 {
-        match player {
-            Color::Black | Color::Green => *eval,
-            Color::Blue | Color::Red => -*eval,
+        eval[player.team()]
+    }
+
+    /// The solver needs every hand, so it isn't used from the view of a single player.
+    fn eval_observed(
+        &self,
+        state: &<TacAI as MCTS>::State,
+        _observer: &Color,
+        knowledge: &Knowledge,
+    ) -> Self::StateEval {
+        match self.kind {
+            EvalKind::Basic => Self::team_values(|player| state.eval_basic_normalized_for(player)),
+            EvalKind::Full => Self::team_values(|player| {
+                state.eval_observed_normalized_for(player, &self.params, knowledge)
+            }),
         }
     }
 }
 
+thread_local! {
+    /// Reused by every move generation of the search, so playouts don't allocate unpacked moves
+    static MOVE_BUFFER: RefCell<Vec<TacMove>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Board {
+    /// Generates the legal moves of the current player into the buffer of this thread.
+    fn with_legal_moves<T>(&self, f: impl FnOnce(&[TacMove]) -> T) -> T {
+        MOVE_BUFFER.with_borrow_mut(|moves| {
+            MoveGen::new(self, self.current_player()).generate_into(moves);
+            f(moves)
+        })
+    }
+}
+
+// The search tree stores packed moves, which are a fraction of the size of a `TacMove`
 impl GameState for Board {
-    type Move = TacMove;
+    type Move = PackedTacMove;
     type Player = Color;
     type MoveList = Vec<Self::Move>;
     type Knowledge = Knowledge;
@@ -69,19 +292,28 @@ impl GameState for Board {
     }
 
     fn legal_moves(&self) -> Self::MoveList {
-        self.get_moves(self.current_player())
+        self.with_legal_moves(|moves| moves.iter().map(PackedTacMove::pack).collect())
     }
 
     fn make_move(&mut self, mv: &Self::Move) {
-        self.play(mv);
+        self.play(&mv.unpack());
+    }
+
+    fn canonical_move(&self, mv: &Self::Move) -> Self::Move {
+        PackedTacMove::pack(&mv.unpack().canonical())
     }
 
-    fn randomize_determination(&mut self, observer: Self::Player, knowledge: &Self::Knowledge) {
-        self.redetermine(observer, knowledge);
+    fn randomize_determination<R: rand::Rng>(
+        &mut self,
+        observer: Self::Player,
+        knowledge: &Self::Knowledge,
+        rng: &mut R,
+    ) {
+        self.redetermine(observer, knowledge, rng);
     }
 
     fn update_knowledge(&self, mv: &Self::Move, knowledge: &mut Self::Knowledge) {
-        knowledge.update_with_move(mv, self);
+        knowledge.update_with_move(&mv.unpack(), self);
     }
 
     fn new_knowledge(&self, observer: Self::Player) -> Self::Knowledge {
@@ -91,4 +323,20 @@ impl GameState for Board {
     fn knowledge_from_state(&self, observer: Self::Player) -> Self::Knowledge {
         Knowledge::new_from_board(observer, self)
     }
+
+    fn new_deal(&self) -> bool {
+        self.just_started()
+    }
+
+    fn update_knowledge_for_deal(&self, _observer: Self::Player, knowledge: &mut Self::Knowledge) {
+        knowledge.start_deal(self);
+    }
+
+    fn outcome(&self, player: &Self::Player) -> Option<bool> {
+        self.game_result().map(|result| result.is_win_for(*player))
+    }
+
+    fn random_move<R: rand::Rng>(&self, rng: &mut R) -> Option<Self::Move> {
+        self.with_legal_moves(|moves| moves.choose(rng).map(PackedTacMove::pack))
+    }
 }
@@ -1,78 +1,306 @@
-use itertools::Itertools;
-use tac_types::{BitBoard, Card, Color, Home, Square, TacAction, TacMove};
+use tac_types::{
+    BitBoard, Card, Color, Distance, Home, HomeSquare, Square, TacAction, TacMove, NUM_COLORS,
+};
 
 use crate::board::Board;
 
-impl Board {
+/// The player whose cards the moves of `played_by` use.
+fn hand_to_play(board: &Board, played_by: Color) -> Color {
+    if played_by == board.current_player() {
+        board.hand_to_play()
+    } else {
+        played_by
+    }
+}
+
+/// The square a move of a single ball on the ring starts from.
+fn ring_start(action: &TacAction) -> Option<Square> {
+    match *action {
+        TacAction::Step { from, .. }
+        | TacAction::StepInHome { from, .. }
+        | TacAction::Warrior { from, .. } => Some(from),
+        _ => None,
+    }
+}
+
+/// Generates the legal moves of a player into a buffer provided by the caller,
+/// so the same allocation can be reused for many positions.
+pub struct MoveGen<'a> {
+    board: &'a Board,
+    played_by: Color,
+}
+
+impl<'a> MoveGen<'a> {
+    /// After a devil, the moves of the player to move are played from the hand of the next player.
     #[must_use]
-    pub fn get_moves(&self, played_by: Color) -> Vec<TacMove> {
-        let mut moves = Vec::new();
-        let hand = self.hand(played_by);
+    pub fn new(board: &'a Board, played_by: Color) -> Self {
+        Self {
+            board,
+            played_by: hand_to_play(board, played_by),
+        }
+    }
 
-        // If player before us did winning move
-        if self.need_trade() && (self.won(Color::Black) || self.won(Color::Blue)) {
-            // No moves possible - game is over
-            return Vec::new();
+    /// Clears `moves` and fills it with every legal move.
+    pub fn generate_into(&self, moves: &mut Vec<TacMove>) {
+        moves.clear();
+        let board = self.board;
+        let played_by = self.played_by;
+        let hand = board.hand(played_by);
+
+        if board.is_terminal() {
+            return;
         }
-        if self.won(played_by.prev()) {
-            // If we have tac see if we are able to use it to prevent win
-            if self.hand(played_by).contains(Card::Tac) {
-                return self.tac_moves(played_by);
+        // If player before us did winning move see if we are able to use tac to prevent win
+        if board.won(played_by.prev()) {
+            if board.hand(played_by).contains(Card::Tac) {
+                board.tac_moves_into(played_by, moves);
             }
-            // No moves possible - game is over
-            return Vec::new();
+            return;
+        }
+        // Announcing comes before trading, whatever the hand holds
+        if board.need_announce() {
+            moves.extend([true, false].map(|opening| TacMove::announce(opening, played_by)));
+            return;
         }
         // If in trade phase trade move for every card in hand
-        if self.need_trade() {
-            for card in hand.iter().sorted().dedup() {
-                moves.push(TacMove::new(*card, TacAction::Trade, played_by, played_by));
+        if board.need_trade() {
+            for (card, _) in hand.iter_counts() {
+                moves.push(TacMove::new(card, TacAction::Trade, played_by, played_by));
             }
-            return moves;
+            return;
         }
 
         // If we are forced to discard, either respond with tac or discard any card in hand
-        if self.force_discard() {
+        if board.force_discard() {
             if hand.iter().any(|c| matches!(c, Card::Tac)) {
-                moves.extend(self.tac_moves(played_by));
+                board.tac_moves_into(played_by, moves);
             }
-            for card in hand.iter().sorted().dedup() {
-                moves.push(TacMove::new(
-                    *card,
-                    TacAction::Discard,
-                    played_by,
-                    played_by,
-                ));
+            for (card, _) in hand.iter_counts() {
+                moves.push(TacMove::new(card, TacAction::Discard, played_by, played_by));
             }
-            return moves;
+            return;
         }
 
         // Compute moves for each card in hand
-        for card in hand.iter().sorted().dedup() {
-            moves.extend(self.moves_for_card(played_by, *card));
+        for (card, _) in hand.iter_counts() {
+            board.moves_for_card_into(played_by, card, moves);
         }
 
         // We can't do anything so discard any card
         if moves.is_empty() {
-            for card in hand.iter().sorted().dedup() {
-                moves.push(TacMove::new(
-                    *card,
-                    TacAction::Discard,
-                    played_by,
-                    played_by,
-                ));
+            for (card, _) in hand.iter_counts() {
+                moves.push(TacMove::new(card, TacAction::Discard, played_by, played_by));
             }
         }
+    }
+}
+
+/// Lazily yields the legal moves of a player.
+///
+/// Moves are generated one card at a time, so only the moves of a single card
+/// are held in memory. This makes sampling a move cheap when the full list would be huge.
+pub struct MovesIter<'a> {
+    board: &'a Board,
+    played_by: Color,
+    cards: std::vec::IntoIter<Card>,
+    // Moves of the current card in reverse order, so we can pop from the back
+    pending: Vec<TacMove>,
+    yielded_any: bool,
+    discards_done: bool,
+}
 
+impl<'a> MovesIter<'a> {
+    #[must_use]
+    pub fn new(board: &'a Board, played_by: Color) -> Self {
+        let played_by = hand_to_play(board, played_by);
+        let mut pending = Vec::new();
+        let mut cards = Vec::new();
+        // Announcing, trading, forced discards and won games only have few moves, so generate them eagerly
+        let eager = board.need_announce()
+            || board.need_trade()
+            || board.won(played_by.prev())
+            || board.force_discard();
+        if eager {
+            MoveGen::new(board, played_by).generate_into(&mut pending);
+            pending.reverse();
+        } else {
+            cards = board
+                .hand(played_by)
+                .iter_counts()
+                .map(|(card, _)| card)
+                .collect();
+        }
+        Self {
+            board,
+            played_by,
+            cards: cards.into_iter(),
+            pending,
+            yielded_any: eager,
+            discards_done: eager,
+        }
+    }
+}
+
+impl Iterator for MovesIter<'_> {
+    type Item = TacMove;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(mv) = self.pending.pop() {
+                self.yielded_any = true;
+                return Some(mv);
+            }
+            if let Some(card) = self.cards.next() {
+                self.board
+                    .moves_for_card_into(self.played_by, card, &mut self.pending);
+                self.pending.reverse();
+                continue;
+            }
+            // We can't do anything so discard any card
+            if !(self.yielded_any || self.discards_done) {
+                self.discards_done = true;
+                self.pending.extend(
+                    self.board
+                        .hand(self.played_by)
+                        .iter_counts()
+                        .map(|(c, _)| {
+                            TacMove::new(c, TacAction::Discard, self.played_by, self.played_by)
+                        })
+                        .rev(),
+                );
+                continue;
+            }
+            return None;
+        }
+    }
+}
+
+impl Board {
+    #[must_use]
+    pub fn get_moves(&self, played_by: Color) -> Vec<TacMove> {
+        let mut moves = Vec::new();
+        MoveGen::new(self, played_by).generate_into(&mut moves);
         moves
     }
 
+    /// Returns an iterator that generates the legal moves of `played_by` lazily.
+    #[must_use]
+    pub fn moves_iter(&self, played_by: Color) -> MovesIter<'_> {
+        MovesIter::new(self, played_by)
+    }
+
+    /// Returns `true` if `mv` is one of the moves [`Board::get_moves`] generates for the
+    /// player to move. Instead of generating every move, only the balls touched by `mv` are
+    /// looked at, moves which depend on every ball are compared to the moves of their card.
+    #[must_use]
+    pub fn is_legal(&self, mv: &TacMove) -> bool {
+        let played_by = self.hand_to_play();
+        if self.is_terminal() || mv.played_by != played_by {
+            return false;
+        }
+        if let TacAction::Announce { opening } = mv.action {
+            return self.need_announce() && *mv == TacMove::announce(opening, played_by);
+        }
+        if self.need_announce() || !self.hand(played_by).contains(mv.card) {
+            return false;
+        }
+        // Only a tac can prevent the win of the player before us
+        if self.won(played_by.prev()) {
+            return mv.card == Card::Tac && self.tac_moves(played_by).contains(mv);
+        }
+        let own = mv.played_for == played_by;
+        if self.need_trade() {
+            return own && matches!(mv.action, TacAction::Trade);
+        }
+        match mv.action {
+            TacAction::Trade | TacAction::Announce { .. } => false,
+            // Outside of a suspend, discarding is only allowed without any other move
+            TacAction::Discard => {
+                own && (self.force_discard()
+                    || MovesIter::new(self, played_by)
+                        .next()
+                        .is_some_and(|first| matches!(first.action, TacAction::Discard)))
+            }
+            _ if self.force_discard() => {
+                mv.card == Card::Tac && self.tac_moves(played_by).contains(mv)
+            }
+            _ => self.is_legal_for_card(mv),
+        }
+    }
+
+    /// Checks a move which isn't a trade or a discard against the rules of its card.
+    fn is_legal_for_card(&self, mv: &TacMove) -> bool {
+        let played_by = mv.played_by;
+        let play_for = self.play_for(played_by);
+        let own = mv.played_for == played_by;
+        match (mv.card, &mv.action) {
+            (Card::Tac, _) => self.tac_moves(played_by).contains(mv),
+            (Card::Seven | Card::Trickster, _) => self
+                .moves_for_card(played_by, mv.card)
+                .contains(&mv.clone().canonical()),
+            (Card::Jester, TacAction::Jester) => own,
+            (Card::Devil, TacAction::Devil) => own && !self.hand(played_by.next()).is_empty(),
+            (Card::Eight, TacAction::Suspend) => {
+                own && self.can_play(play_for) && !self.hand(played_by.next()).is_empty()
+            }
+            (Card::One | Card::Thirteen, TacAction::Enter) => {
+                mv.played_for == play_for && self.num_base(play_for) > 0
+            }
+            (Card::Angel, action) => {
+                let next = self.play_for(played_by.next());
+                if mv.played_for != next {
+                    return false;
+                }
+                if self.num_base(next) > 0 {
+                    return matches!(action, TacAction::Enter);
+                }
+                let Some(from) = ring_start(action) else {
+                    return false;
+                };
+                self.balls_with(next).has(from)
+                    && [Card::One, Card::Thirteen].into_iter().any(|card| {
+                        self.moves_for_card_squares(from.bitboard(), played_by, next, card)
+                            .iter()
+                            .any(|m| m.action == *action)
+                    })
+            }
+            (card, TacAction::StepHome { .. }) => {
+                let home = *self.home(play_for);
+                mv.played_for == play_for
+                    && !home.is_empty()
+                    && !home.is_locked()
+                    && Self::home_moves_for(home, played_by, play_for, card).contains(mv)
+            }
+            (card, action) => {
+                let Some(from) = ring_start(action) else {
+                    return false;
+                };
+                mv.played_for == play_for
+                    && self.balls_with(play_for).has(from)
+                    && self
+                        .moves_for_card_squares(from.bitboard(), played_by, play_for, card)
+                        .contains(mv)
+            }
+        }
+    }
+
     #[must_use]
-    #[allow(clippy::too_many_lines)]
     pub fn moves_for_card(&self, played_by: Color, card: Card) -> Vec<TacMove> {
+        let mut moves = Vec::new();
+        self.moves_for_card_into(played_by, card, &mut moves);
+        moves
+    }
+
+    #[allow(clippy::too_many_lines)]
+    pub(crate) fn moves_for_card_into(
+        &self,
+        played_by: Color,
+        card: Card,
+        moves: &mut Vec<TacMove>,
+    ) {
         let play_for = self.play_for(played_by);
         let play_for_next = self.play_for(played_by.next());
         let can_play = self.can_play(play_for);
-        let mut moves = Vec::new();
         match card {
             Card::One | Card::Thirteen => {
                 // If we still have balls in base, we can put them on the board
@@ -85,7 +313,8 @@ impl Board {
                 // Consider special casing them so move evaluation can prune them effectively with expert knowledge
                 if (!self.home(play_for).is_empty() && !self.home(play_for).is_locked()) || can_play
                 {
-                    return self.seven_moves(played_by);
+                    self.seven_moves_into(played_by, moves);
+                    return;
                 }
             }
             Card::Eight => {
@@ -95,11 +324,13 @@ impl Board {
             }
             Card::Trickster => {
                 if can_play {
-                    return self.trickster_moves(played_by, play_for);
+                    self.trickster_moves_into(played_by, play_for, moves);
+                    return;
                 }
             }
             Card::Jester => {
-                return vec![TacMove::new(card, TacAction::Jester, played_by, played_by)];
+                moves.push(TacMove::new(card, TacAction::Jester, played_by, played_by));
+                return;
             }
             Card::Angel => {
                 // If player after us still has balls out of play
@@ -112,29 +343,37 @@ impl Board {
                     ));
                 } else {
                     let balls = self.balls_with(play_for_next);
-                    moves.extend(
-                        self.moves_for_card_squares(balls, played_by, play_for_next, Card::One)
-                            .into_iter()
-                            .map(|e| TacMove::new(Card::Angel, e.action, play_for_next, played_by)),
+                    let start = moves.len();
+                    self.moves_for_card_squares_into(
+                        balls,
+                        played_by,
+                        play_for_next,
+                        Card::One,
+                        moves,
                     );
-                    moves.extend(
-                        self.moves_for_card_squares(
-                            balls,
-                            played_by,
-                            play_for_next,
-                            Card::Thirteen,
-                        )
-                        .into_iter()
-                        .map(|e| TacMove::new(Card::Angel, e.action, play_for_next, played_by)),
+                    self.moves_for_card_squares_into(
+                        balls,
+                        played_by,
+                        play_for_next,
+                        Card::Thirteen,
+                        moves,
                     );
+                    for mv in &mut moves[start..] {
+                        mv.card = Card::Angel;
+                    }
                 }
-                return moves;
+                return;
             }
             Card::Devil => {
-                return vec![TacMove::new(card, TacAction::Devil, played_by, played_by)];
+                // The next player needs a card to be chosen
+                if !self.hand(played_by.next()).is_empty() {
+                    moves.push(TacMove::new(card, TacAction::Devil, played_by, played_by));
+                }
+                return;
             }
             Card::Tac => {
-                return self.tac_moves(played_by);
+                self.tac_moves_into(played_by, moves);
+                return;
             }
             _ => {}
         }
@@ -143,25 +382,20 @@ impl Board {
         // Uses matching on the bit patterns that correspond to states in which there are unlocked balls
         // with enough space to move the desired amount
         if !self.home(play_for).is_empty() && !self.home(play_for).is_locked() {
-            moves.extend(Self::home_moves_for(
-                *self.home(play_for),
-                played_by,
-                play_for,
-                card,
-            ));
+            Self::home_moves_for_into(*self.home(play_for), played_by, play_for, card, moves);
         }
 
         // Moves we can only do with balls on the board
 
         if can_play {
-            moves.extend(self.moves_for_card_squares(
+            self.moves_for_card_squares_into(
                 self.balls_with(play_for),
                 played_by,
                 play_for,
                 card,
-            ));
+                moves,
+            );
         }
-        moves
     }
 
     #[must_use]
@@ -172,36 +406,62 @@ impl Board {
         card: Card,
     ) -> Vec<TacMove> {
         let mut moves = Vec::new();
+        Self::home_moves_for_into(home, played_by, play_for, card, &mut moves);
+        moves
+    }
+
+    pub(crate) fn home_moves_for_into(
+        home: Home,
+        played_by: Color,
+        play_for: Color,
+        card: Card,
+        moves: &mut Vec<TacMove>,
+    ) {
         match card {
             Card::One => match home.0 {
                 0b0001 | 0b1001 | 0b1101 => moves.push(TacMove::new(
                     card,
-                    TacAction::StepHome { from: 0, to: 1 },
+                    TacAction::StepHome {
+                        from: HomeSquare::new(0),
+                        to: HomeSquare::new(1),
+                    },
                     play_for,
                     played_by,
                 )),
                 0b0010 | 0b1010 | 0b0011 | 0b1011 => moves.push(TacMove::new(
                     card,
-                    TacAction::StepHome { from: 1, to: 2 },
+                    TacAction::StepHome {
+                        from: HomeSquare::new(1),
+                        to: HomeSquare::new(2),
+                    },
                     play_for,
                     played_by,
                 )),
                 0b0100 | 0b0110 | 0b0111 => moves.push(TacMove::new(
                     card,
-                    TacAction::StepHome { from: 2, to: 3 },
+                    TacAction::StepHome {
+                        from: HomeSquare::new(2),
+                        to: HomeSquare::new(3),
+                    },
                     play_for,
                     played_by,
                 )),
                 0b0101 => {
                     moves.push(TacMove::new(
                         card,
-                        TacAction::StepHome { from: 0, to: 1 },
+                        TacAction::StepHome {
+                            from: HomeSquare::new(0),
+                            to: HomeSquare::new(1),
+                        },
                         play_for,
                         played_by,
                     ));
                     moves.push(TacMove::new(
                         card,
-                        TacAction::StepHome { from: 2, to: 3 },
+                        TacAction::StepHome {
+                            from: HomeSquare::new(2),
+                            to: HomeSquare::new(3),
+                        },
                         play_for,
                         played_by,
                     ));
@@ -212,7 +472,10 @@ impl Board {
                 0b0001 | 0b1001 => {
                     moves.push(TacMove::new(
                         card,
-                        TacAction::StepHome { from: 0, to: 2 },
+                        TacAction::StepHome {
+                            from: HomeSquare::new(0),
+                            to: HomeSquare::new(2),
+                        },
                         play_for,
                         played_by,
                     ));
@@ -220,7 +483,10 @@ impl Board {
                 0b0010 | 0b0011 => {
                     moves.push(TacMove::new(
                         card,
-                        TacAction::StepHome { from: 1, to: 3 },
+                        TacAction::StepHome {
+                            from: HomeSquare::new(1),
+                            to: HomeSquare::new(3),
+                        },
                         play_for,
                         played_by,
                     ));
@@ -231,7 +497,10 @@ impl Board {
                 if home.0 == 0b0001 {
                     moves.push(TacMove::new(
                         card,
-                        TacAction::StepHome { from: 0, to: 3 },
+                        TacAction::StepHome {
+                            from: HomeSquare::new(0),
+                            to: HomeSquare::new(3),
+                        },
                         play_for,
                         played_by,
                     ));
@@ -239,7 +508,6 @@ impl Board {
             }
             _ => {}
         }
-        moves
     }
 
     #[must_use]
@@ -251,6 +519,18 @@ impl Board {
         card: Card,
     ) -> Vec<TacMove> {
         let mut moves = Vec::new();
+        self.moves_for_card_squares_into(squares, played_by, play_for, card, &mut moves);
+        moves
+    }
+
+    pub(crate) fn moves_for_card_squares_into(
+        &self,
+        squares: BitBoard,
+        played_by: Color,
+        play_for: Color,
+        card: Card,
+        moves: &mut Vec<TacMove>,
+    ) {
         for start in squares {
             // Simple forward movement
             if let Some(amount) = card.is_simple() {
@@ -267,7 +547,7 @@ impl Board {
                 }
                 // Need to add here in case there is ball on home square
                 if start.distance_to_home(play_for) < amount
-                    && self.can_move(start, play_for.home().add(1))
+                    && self.can_move(start, play_for.home().add(Distance::ONE))
                 {
                     // TODO Compute the range of possible value to reach the home beforehand, to reduce computation
                     if let Some(goal_pos) = self.position_in_home(start, amount, play_for) {
@@ -285,49 +565,7 @@ impl Board {
             }
 
             match card {
-                Card::Four => {
-                    // Each of the four positions behind us are not occupied
-                    if (1..5).all(|i| !self.occupied(start.sub(i))) {
-                        moves.push(TacMove::new(
-                            card,
-                            TacAction::Step {
-                                from: start,
-                                to: start.add(60),
-                            },
-                            play_for,
-                            played_by,
-                        ));
-                    }
-
-                    // Minimum reverse dist to goal
-                    let min_rev_dist = 64 - start.distance_to_home(play_for) + 1;
-                    let free = self.home(play_for).free();
-
-                    // We are right infront of goal and moved in some way after entering play before
-                    if min_rev_dist == 65 && free == 4 && !self.fresh(play_for) {
-                        moves.push(TacMove::new(
-                            card,
-                            TacAction::StepInHome { from: start, to: 4 },
-                            play_for,
-                            played_by,
-                        ));
-                    } else if free > 0 // Goal needs to be free
-                    && min_rev_dist + free > 4 // Enough space to move in
-                    && (2..=4).contains(&min_rev_dist) // In range to move in home
-                    && (0..min_rev_dist - 1).all(|i| !self.occupied(play_for.home().add(i)))
-                    {
-                        let goal = 4 - min_rev_dist;
-                        moves.push(TacMove::new(
-                            card,
-                            TacAction::StepInHome {
-                                from: start,
-                                to: goal,
-                            },
-                            play_for,
-                            played_by,
-                        ));
-                    }
-                }
+                Card::Four => self.four_moves_into(start, played_by, play_for, moves),
                 Card::Warrior => {
                     moves.push(TacMove::new(
                         card,
@@ -342,18 +580,83 @@ impl Board {
                 _ => {}
             }
         }
+    }
+
+    /// Moves of the ball on `start` with a four, either four squares back on the ring or
+    /// backwards over the home square into the home.
+    #[must_use]
+    pub fn four_moves(&self, start: Square, played_by: Color, play_for: Color) -> Vec<TacMove> {
+        let mut moves = Vec::new();
+        self.four_moves_into(start, played_by, play_for, &mut moves);
         moves
     }
 
+    pub(crate) fn four_moves_into(
+        &self,
+        start: Square,
+        played_by: Color,
+        play_for: Color,
+        moves: &mut Vec<TacMove>,
+    ) {
+        let four = Distance::new(4);
+        // Each of the four positions behind us are not occupied
+        if self
+            .all_balls()
+            .is_disjoint(BitBoard::ray_from(start.sub(four), four.steps()))
+        {
+            moves.push(TacMove::new(
+                Card::Four,
+                TacAction::Step {
+                    from: start,
+                    to: start.sub(four),
+                },
+                play_for,
+                played_by,
+            ));
+        }
+
+        // Steps back onto the home square, the rest of the four is spent inside the home.
+        // A ball on the home square itself, usually our own which just entered, blocks this
+        let to_home = start.distance_to_home(play_for).reverse();
+        if to_home == Distance::ZERO || to_home >= four {
+            return;
+        }
+        let in_home = four.steps() - to_home.steps();
+        if self.home(play_for).free() >= in_home
+            && self
+                .all_balls()
+                .is_disjoint(BitBoard::ray_from(play_for.home(), to_home.steps()))
+        {
+            moves.push(TacMove::new(
+                Card::Four,
+                TacAction::StepInHome {
+                    from: start,
+                    to: HomeSquare::new(in_home - 1),
+                },
+                play_for,
+                played_by,
+            ));
+        }
+    }
+
     #[must_use]
-    #[allow(clippy::missing_panics_doc)]
     pub fn trickster_moves(&self, played_by: Color, play_for: Color) -> Vec<TacMove> {
+        let mut moves = Vec::new();
+        self.trickster_moves_into(played_by, play_for, &mut moves);
+        moves
+    }
+
+    pub(crate) fn trickster_moves_into(
+        &self,
+        played_by: Color,
+        play_for: Color,
+        moves: &mut Vec<TacMove>,
+    ) {
         // At most n choose 2 -> n * (n-1) / 2
         // This only gets called if there are balls on the board so the length can never be 0
-        let mut moves =
-            Vec::with_capacity((self.all_balls().len() * (self.all_balls().len() - 1)) / 2);
-        let mut same_switch = [false; 4];
-        let mut home_switch = [false; 4];
+        moves.reserve((self.all_balls().len() * (self.all_balls().len() - 1)) / 2);
+        let mut same_switch = [false; NUM_COLORS];
+        let mut home_switch = [false; NUM_COLORS];
         for (idx, target1) in self.all_balls().iter().enumerate() {
             let c1 = self
                 .color_on(target1)
@@ -365,7 +668,9 @@ impl Board {
                 // Check if we can prune this move in case we already have one
                 // that results in the same game state
                 if c1 == c2 {
-                    if c1.home() == target1 || c1.home() == target2 {
+                    if !BitBoard::HOMES[c1 as usize]
+                        .is_disjoint(target1.bitboard() | target2.bitboard())
+                    {
                         if home_switch[c1 as usize] {
                             // Already have one switching moves with same color on home square
                             continue;
@@ -386,7 +691,6 @@ impl Board {
                 ));
             }
         }
-        moves
     }
 
     #[must_use]
@@ -401,36 +705,99 @@ impl Board {
         }
 
         // We know there is at least another ball
-        others.rotate_right(start.0).next_square().add(start.0)
+        others
+            .rotate_right(start.0)
+            .next_square()
+            .add(Square(0).distance_to(start))
     }
 
     #[must_use]
     pub fn tac_moves(&self, played_by: Color) -> Vec<TacMove> {
         let mut moves = Vec::new();
+        self.tac_moves_into(played_by, &mut moves);
+        moves
+    }
 
-        if let Some((last_move, _)) = self.past_moves().iter().rev().find(|&(c, _)| {
-            !(matches!(c.card, Card::Tac) || (matches!(c.card, Card::Jester) && self.jester_flag()))
-        }) {
+    pub(crate) fn tac_moves_into(&self, played_by: Color, moves: &mut Vec<TacMove>) {
+        // Without chaining a Tac can't replace a move that was a Tac itself
+        if !self.rules().tac_chaining
+            && self
+                .past_moves()
+                .iter()
+                .rev()
+                .find(|(c, _)| !(matches!(c.card, Card::Jester) && self.jester_flag()))
+                .is_some_and(|(c, _)| matches!(c.card, Card::Tac))
+        {
+            return;
+        }
+        if let Some(card) = self.tac_replayed_card() {
             let mut state = self.clone();
             state.tac_undo();
-            moves.extend(
-                state
-                    .moves_for_card(played_by, last_move.card)
-                    .into_iter()
-                    .map(|m| TacMove::new(Card::Tac, m.action, m.played_for, m.played_by)),
-            );
+            let start = moves.len();
+            state.moves_for_card_into(played_by, card, moves);
+            for mv in &mut moves[start..] {
+                mv.card = Card::Tac;
+            }
         }
+    }
 
-        moves
+    /// Returns the card a Tac played now would replay. A chain of Tacs all replay the card
+    /// of the move before the chain, Jesters that let their player play again are skipped.
+    #[must_use]
+    pub fn tac_replayed_card(&self) -> Option<Card> {
+        self.past_moves()
+            .iter()
+            .rev()
+            .find(|&(c, _)| {
+                !(matches!(c.card, Card::Tac)
+                    || (matches!(c.card, Card::Jester) && self.jester_flag()))
+            })
+            .map(|(mv, _)| mv.card)
     }
 }
 
 #[cfg(test)]
 #[allow(unused_must_use)]
 mod tests {
-    use tac_types::ALL_COLORS;
+    use itertools::Itertools;
+    use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+    use tac_types::{ALL_COLORS, CARDS, NUM_COLORS, RING_SIZE};
 
     use super::*;
+    use crate::rules::Ruleset;
+
+    #[test]
+    fn is_legal_matches_generation() {
+        for seed in 0..20 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut previous = Vec::new();
+            loop {
+                let player = board.current_player();
+                let legal = board.get_moves(player);
+                // Moves of other cards, players and earlier positions are mostly illegal
+                let mut candidates = legal.clone();
+                candidates.append(&mut previous);
+                for card in CARDS {
+                    for by in ALL_COLORS {
+                        candidates.extend(board.moves_for_card(by, card));
+                    }
+                }
+                for mv in &candidates {
+                    assert_eq!(
+                        board.is_legal(mv),
+                        legal.contains(&mv.clone().canonical()),
+                        "{seed} {mv} {board:?}"
+                    );
+                }
+                let Some(mv) = legal.iter().choose(&mut rng) else {
+                    break;
+                };
+                board.play(mv);
+                previous = legal;
+            }
+        }
+    }
 
     #[test]
     fn switching_moves() {
@@ -438,8 +805,9 @@ mod tests {
         for color in ALL_COLORS {
             board.put_ball_in_play(color);
         }
+        let pairs = |balls: usize| balls * (balls - 1) / 2;
         let moves = board.trickster_moves(Color::Black, Color::Black);
-        assert_eq!(moves.len(), 6);
+        assert_eq!(moves.len(), pairs(NUM_COLORS));
         board.move_ball(Square(0), Square(4), Color::Black);
         board.put_ball_in_play(Color::Black);
         board.move_ball(Square(0), Square(8), Color::Black);
@@ -450,23 +818,23 @@ mod tests {
         // same_color_cnt * (same_color_cnt - 1) / 2 - 2
         let moves = board.trickster_moves(Color::Black, Color::Black);
         // 3 * 2 / 2 - 2 = 1
-        assert_eq!(moves.len(), 15 - 1);
+        assert_eq!(moves.len(), pairs(NUM_COLORS + 2) - 1);
         board.move_ball(Square(0), Square(12), Color::Black);
         board.put_ball_in_play(Color::Black);
         let moves = board.trickster_moves(Color::Black, Color::Black);
         // 4 * 3 / 2 - 2 = 4
-        assert_eq!(moves.len(), 21 - 4);
-        for c in [Color::Blue, Color::Green, Color::Red] {
-            board.move_ball(c.home(), c.home().add(4), c);
+        assert_eq!(moves.len(), pairs(NUM_COLORS + 3) - 4);
+        for c in ALL_COLORS.into_iter().skip(1) {
+            board.move_ball(c.home(), c.home().add(Distance::new(4)), c);
             board.put_ball_in_play(c);
-            board.move_ball(c.home(), c.home().add(8), c);
+            board.move_ball(c.home(), c.home().add(Distance::new(8)), c);
             board.put_ball_in_play(c);
-            board.move_ball(c.home(), c.home().add(12), c);
+            board.move_ball(c.home(), c.home().add(Distance::new(12)), c);
             board.put_ball_in_play(c);
         }
-        assert_eq!(board.all_balls().len(), 16);
+        assert_eq!(board.all_balls().len(), 4 * NUM_COLORS);
         let moves = board.trickster_moves(Color::Black, Color::Black);
-        assert_eq!(moves.len(), (16 * 15 / 2) - 4 * (4 * 3 / 2 - 2));
+        assert_eq!(moves.len(), pairs(4 * NUM_COLORS) - NUM_COLORS * 4);
     }
 
     #[test]
@@ -486,7 +854,7 @@ mod tests {
                 Card::Four,
                 TacAction::Step {
                     from: Square(0),
-                    to: Square(60)
+                    to: Square(0).sub(Distance::new(4))
                 },
                 Color::Black,
                 Color::Black,
@@ -515,6 +883,116 @@ mod tests {
         );
     }
 
+    /// Walks the four backwards one square at a time.
+    fn four_reference(board: &Board, start: Square, color: Color) -> Vec<TacAction> {
+        let mut actions = Vec::new();
+        if (1..=4).all(|i| !board.occupied(start.sub(Distance::new(i)))) {
+            actions.push(TacAction::Step {
+                from: start,
+                to: start.sub(Distance::new(4)),
+            });
+        }
+        let mut square = start;
+        for taken in 1..4 {
+            square = square.sub(Distance::ONE);
+            if board.occupied(square) {
+                break;
+            }
+            if square == color.home() {
+                let left = 4 - taken;
+                if (0..left).all(|i| board.home(color).is_free(HomeSquare::new(i))) {
+                    actions.push(TacAction::StepInHome {
+                        from: start,
+                        to: HomeSquare::new(left - 1),
+                    });
+                }
+                break;
+            }
+        }
+        actions
+    }
+
+    #[test]
+    #[cfg(feature = "six-players")]
+    fn six_players() {
+        let (purple, green) = (Color::Purple, Color::Green);
+        let mut board = Board::new();
+        // Balls wrap around the end of the longer ring
+        board.put_ball_in_play(purple);
+        board.move_ball(purple.home(), Square(RING_SIZE - 2), purple);
+        let step = |card, from: Square, to: Square, played_for| {
+            TacMove::new(card, TacAction::Step { from, to }, played_for, purple)
+        };
+        assert_eq!(
+            board.moves_for_card(purple, Card::Five),
+            vec![step(Card::Five, Square(RING_SIZE - 2), Square(3), purple)]
+        );
+        // Once done, moves are played for the partner three seats away
+        board.move_ball_to_goal(Square(RING_SIZE - 2), HomeSquare::new(0), purple);
+        for goal in &HomeSquare::ALL[1..] {
+            board.put_ball_in_play(purple);
+            board.move_ball_to_goal(purple.home(), *goal, purple);
+        }
+        assert_eq!(board.play_for(purple), green);
+        board.put_ball_in_play(green);
+        assert_eq!(
+            board.moves_for_card(purple, Card::Five),
+            vec![step(
+                Card::Five,
+                green.home(),
+                green.home().add(Distance::new(5)),
+                green
+            )]
+        );
+    }
+
+    #[test]
+    fn four_matches_reference() {
+        let black = Color::Black;
+        for home in 0..16u8 {
+            for start in 0..RING_SIZE {
+                // No blocker, one of ours, e.g. fresh on the home square, or an opponent
+                for blocker in (0..=RING_SIZE).filter(|b| *b != start) {
+                    for blocker_color in [black, Color::Red] {
+                        let mut board = Board::new();
+                        for pos in HomeSquare::ALL
+                            .into_iter()
+                            .filter(|p| home >> p.index() & 1 == 1)
+                        {
+                            board.set(Square(32), black);
+                            board.move_ball_to_goal(Square(32), pos, black);
+                        }
+                        board.set(Square(start), black);
+                        if blocker < RING_SIZE {
+                            board.set(Square(blocker), blocker_color);
+                        }
+                        let actions = board
+                            .four_moves(Square(start), black, black)
+                            .into_iter()
+                            .map(|mv| mv.action)
+                            .collect_vec();
+                        assert_eq!(
+                            actions,
+                            four_reference(&board, Square(start), black),
+                            "home {home:04b} start {start} blocker {blocker}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn four_blocked_by_fresh_ball() {
+        let black = Color::Black;
+        let mut board = Board::new();
+        board.put_ball_in_play(black);
+        board.move_ball(black.home(), Square(2), black);
+        assert_eq!(board.four_moves(Square(2), black, black).len(), 2);
+        board.put_ball_in_play(black);
+        assert!(board.four_moves(Square(2), black, black).is_empty());
+    }
+
     #[test]
     fn four_in_goal() {
         let mut board = Board::new();
@@ -527,10 +1005,10 @@ mod tests {
                 .clone(),
             black,
         );
-        assert!(board.home(black).is_free(0));
-        assert!(board.home(black).is_free(1));
-        assert!(!board.home(black).is_free(2));
-        assert!(board.home(black).is_free(3));
+        assert!(board.home(black).is_free(HomeSquare::new(0)));
+        assert!(board.home(black).is_free(HomeSquare::new(1)));
+        assert!(!board.home(black).is_free(HomeSquare::new(2)));
+        assert!(board.home(black).is_free(HomeSquare::new(3)));
         board.put_ball_in_play(black);
         board.move_ball(Square(0), Square(1), black);
         assert_eq!(
@@ -546,10 +1024,10 @@ mod tests {
                 .clone(),
             black,
         );
-        assert!(!board.home(black).is_free(0));
-        assert!(board.home(black).is_free(1));
-        assert!(!board.home(black).is_free(2));
-        assert!(board.home(black).is_free(3));
+        assert!(!board.home(black).is_free(HomeSquare::new(0)));
+        assert!(board.home(black).is_free(HomeSquare::new(1)));
+        assert!(!board.home(black).is_free(HomeSquare::new(2)));
+        assert!(board.home(black).is_free(HomeSquare::new(3)));
     }
 
     #[test]
@@ -564,7 +1042,7 @@ mod tests {
             moves[0],
             TacMove::new(
                 Card::Warrior,
-                TacAction::Step {
+                TacAction::Warrior {
                     from: Color::Black.home(),
                     to: Color::Red.home()
                 },
@@ -580,7 +1058,7 @@ mod tests {
             moves[0],
             TacMove::new(
                 Card::Warrior,
-                TacAction::Step {
+                TacAction::Warrior {
                     from: Color::Red.home(),
                     to: Color::Red.home()
                 },
@@ -590,6 +1068,109 @@ mod tests {
         );
     }
 
+    #[test]
+    fn angel() {
+        let (black, blue) = (Color::Black, Color::Blue);
+        let mut board = Board::new();
+        // The angel enters a ball of the next player
+        let enter_blue = TacMove::new(Card::Angel, TacAction::Enter, blue, black);
+        assert_eq!(
+            board.moves_for_card(black, Card::Angel),
+            vec![enter_blue.clone()]
+        );
+        board.apply_action(enter_blue.action.clone(), enter_blue.played_for);
+        assert_eq!(board.num_base(blue), 3);
+        // Without balls in base it moves them like a one or thirteen
+        for offset in [16, 20, 24] {
+            board.move_ball(blue.home(), blue.home().add(Distance::new(offset)), blue);
+            board.put_ball_in_play(blue);
+        }
+        assert_eq!(board.num_base(blue), 0);
+        let moves = board.moves_for_card(black, Card::Angel);
+        assert!(!moves.is_empty());
+        for mv in moves {
+            assert_eq!(
+                (mv.card, mv.played_by, mv.played_for),
+                (Card::Angel, black, blue)
+            );
+            let TacAction::Step { from, to } = mv.action else {
+                panic!("{mv}");
+            };
+            assert!([1, 13].contains(&from.distance_to(to).steps()), "{mv}");
+        }
+
+        // Playing for the partner still helps the next player
+        let mut board = Board::new();
+        for goal in HomeSquare::ALL {
+            board.put_ball_in_play(black);
+            board.move_ball_to_goal(black.home(), goal, black);
+        }
+        assert_eq!(board.play_for(black), black.partner());
+        assert_eq!(board.moves_for_card(black, Card::Angel), vec![enter_blue]);
+        // Unless the next player is done as well, then it helps their partner
+        for goal in HomeSquare::ALL {
+            board.put_ball_in_play(blue);
+            board.move_ball_to_goal(blue.home(), goal, blue);
+        }
+        assert_eq!(
+            board.moves_for_card(black, Card::Angel),
+            vec![TacMove::new(
+                Card::Angel,
+                TacAction::Enter,
+                blue.partner(),
+                black
+            )]
+        );
+    }
+
+    #[test]
+    fn devil() {
+        let mut devils = 0;
+        for seed in 0..100 {
+            let mut board = Board::new_with_seed(seed);
+            let mut rng = StdRng::seed_from_u64(seed);
+            while let Some(mv) = board
+                .get_moves(board.current_player())
+                .into_iter()
+                .choose(&mut rng)
+            {
+                board.play(&mv);
+                if !matches!(mv.action, TacAction::Devil) {
+                    continue;
+                }
+                // The devil can itself be the move chosen after another devil
+                let player = mv.played_by;
+                // The devil player chooses the move of the next player from their hand
+                let next = player.next();
+                assert!(board.devil_flag());
+                assert_eq!(board.current_player(), player);
+                assert_eq!(board.hand_to_play(), next);
+                let moves = board.get_moves(player);
+                assert!(!moves.is_empty());
+                assert_eq!(board.moves_iter(player).collect::<Vec<_>>(), moves);
+                for mv in &moves {
+                    assert_eq!(mv.played_by, next);
+                    assert!(board.hand(next).contains(mv.card));
+                }
+                let mv = moves.into_iter().choose(&mut rng).unwrap();
+                let cards = board.hand(next).amount();
+                board.play(&mv);
+                // A jester passes the hands on and a new deal refills them
+                if !(board.jester_flag() || board.just_started()) {
+                    assert_eq!(board.hand(next).amount(), cards - 1);
+                    // Another devil passes the choice on, otherwise play continues after them
+                    if matches!(mv.action, TacAction::Devil) {
+                        assert_eq!(board.current_player(), next);
+                    } else {
+                        assert_eq!(board.current_player(), next.next());
+                    }
+                }
+                devils += 1;
+            }
+        }
+        assert!(devils > 0);
+    }
+
     #[test]
     fn tac() {
         let mut board = Board::new();
@@ -600,27 +1181,118 @@ mod tests {
         let mv = TacMove::new(Card::One, TacAction::Enter, Color::Black, Color::Black);
         board.play(&mv);
         assert_eq!(board.color_on(Color::Black.home()).unwrap(), Color::Black);
+        // Every Tac takes back the move before it, so every other move of the chain counts
+        for (idx, color) in ALL_COLORS.into_iter().enumerate().skip(1) {
+            assert_eq!(board.current_player(), color);
+            let moves = board.moves_for_card(color, Card::Tac);
+            assert_eq!(moves.len(), 1);
+            board.play(&moves[0]);
+            for (before, c) in ALL_COLORS[..=idx].iter().enumerate() {
+                let played = (idx - before) % 2 == 0;
+                assert_eq!(board.color_on(c.home()), played.then_some(*c));
+            }
+        }
+        assert_eq!(board.current_player(), Color::Black);
+    }
+
+    #[test]
+    fn tac_chain_across_jester() {
+        let mut board = Board::new();
+        board.add_hand(Color::Black, Card::One);
+        // After the jester Red plays from the hand of the player after it
+        board.add_hand(Color::Red.next(), Card::Tac);
+        board.add_hand(Color::Blue, Card::Jester);
+        board.add_hand(Color::Green, Card::Tac);
+        board.add_hand(Color::Red, Card::Tac);
+        board.play(&TacMove::new(
+            Card::One,
+            TacAction::Enter,
+            Color::Black,
+            Color::Black,
+        ));
+        board.play(&TacMove::new(
+            Card::Jester,
+            TacAction::Jester,
+            Color::Blue,
+            Color::Blue,
+        ));
+        // The Jester is skipped, Blue replaces the Enter with its own
         assert_eq!(board.current_player(), Color::Blue);
-        let moves = board.moves_for_card(board.current_player(), Card::Tac);
+        let moves = board.moves_for_card(Color::Blue, Card::Tac);
         assert_eq!(moves.len(), 1);
         board.play(&moves[0]);
-        assert_eq!(board.current_player(), Color::Green);
         assert_eq!(board.color_on(Color::Black.home()), None);
-        assert_eq!(board.color_on(Color::Blue.home()).unwrap(), Color::Blue);
-        let moves = board.moves_for_card(board.current_player(), Card::Tac);
+        assert_eq!(board.color_on(Color::Blue.home()), Some(Color::Blue));
+        // Every Tac brings back the one before the replaced Tac
+        let moves = board.moves_for_card(Color::Green, Card::Tac);
         assert_eq!(moves.len(), 1);
         board.play(&moves[0]);
-        assert_eq!(board.current_player(), Color::Red);
-        assert_eq!(board.color_on(Color::Black.home()).unwrap(), Color::Black);
+        assert_eq!(board.color_on(Color::Black.home()), Some(Color::Black));
         assert_eq!(board.color_on(Color::Blue.home()), None);
-        assert_eq!(board.color_on(Color::Green.home()).unwrap(), Color::Green);
-        let moves = board.moves_for_card(board.current_player(), Card::Tac);
+        assert_eq!(board.color_on(Color::Green.home()), Some(Color::Green));
+        let moves = board.moves_for_card(Color::Red, Card::Tac);
         assert_eq!(moves.len(), 1);
         board.play(&moves[0]);
-        assert_eq!(board.current_player(), Color::Black);
         assert_eq!(board.color_on(Color::Black.home()), None);
-        assert_eq!(board.color_on(Color::Blue.home()).unwrap(), Color::Blue);
+        assert_eq!(board.color_on(Color::Blue.home()), Some(Color::Blue));
         assert_eq!(board.color_on(Color::Green.home()), None);
-        assert_eq!(board.color_on(Color::Red.home()).unwrap(), Color::Red);
+        assert_eq!(board.color_on(Color::Red.home()), Some(Color::Red));
+    }
+
+    #[test]
+    fn tac_without_chaining() {
+        let rules = Ruleset {
+            tac_chaining: false,
+            ..Ruleset::default()
+        };
+        let mut board = Board::new_with_rules(0, rules);
+        ALL_COLORS
+            .iter()
+            .for_each(|c| board.add_hand(*c, Card::Tac));
+        board.add_hand(Color::Black, Card::One);
+        let mv = TacMove::new(Card::One, TacAction::Enter, Color::Black, Color::Black);
+        board.play(&mv);
+        let moves = board.moves_for_card(board.current_player(), Card::Tac);
+        assert_eq!(moves.len(), 1);
+        board.play(&moves[0]);
+        assert!(board
+            .moves_for_card(board.current_player(), Card::Tac)
+            .is_empty());
+    }
+
+    #[test]
+    fn moves_iter_matches_get_moves() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for seed in 0..20 {
+            let mut board = Board::new_with_seed(seed);
+            for _ in 0..300 {
+                let player = board.current_player();
+                let moves = board.get_moves(player);
+                assert_eq!(board.moves_iter(player).collect::<Vec<_>>(), moves);
+                let Some(mv) = moves.into_iter().choose(&mut rng) else {
+                    break;
+                };
+                board.play(&mv);
+            }
+        }
+    }
+
+    #[test]
+    fn generated_moves_are_canonical() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for seed in 0..20 {
+            let mut board = Board::new_with_seed(seed);
+            for _ in 0..300 {
+                let moves = board.get_moves(board.current_player());
+                for (idx, mv) in moves.iter().enumerate() {
+                    assert_eq!(mv.clone().canonical(), *mv);
+                    assert!(!moves[..idx].contains(mv), "{mv}");
+                }
+                let Some(mv) = moves.into_iter().choose(&mut rng) else {
+                    break;
+                };
+                board.play(&mv);
+            }
+        }
     }
 }
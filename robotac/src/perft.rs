@@ -0,0 +1,378 @@
+//! Counting move paths to verify move generation, like perft in chess engines.
+//!
+//! Cards are dealt from the seed of the board, so the counts of a position never change
+//! unless move generation does. [`POSITIONS`] lists positions with known counts.
+
+use tac_types::TacMove;
+
+use crate::board::Board;
+
+/// A position reached by playing `moves` on the board dealt with `seed`.
+pub struct PerftPosition {
+    pub name: &'static str,
+    pub seed: u64,
+    /// Moves in the notation of [`tac_types::notation`]
+    pub moves: &'static [&'static str],
+    /// Number of move paths for depths starting at 1
+    pub counts: &'static [u64],
+}
+
+impl PerftPosition {
+    /// # Panics
+    /// If one of the moves can't be parsed.
+    #[must_use]
+    pub fn board(&self) -> Board {
+        let mut board = Board::new_with_seed(self.seed);
+        for mv in self.moves {
+            board.play(&mv.parse().expect("Perft positions contain valid moves"));
+        }
+        board
+    }
+}
+
+#[cfg(not(feature = "six-players"))]
+pub const POSITIONS: &[PerftPosition] = &[
+    PerftPosition {
+        name: "start",
+        seed: 0,
+        moves: &[],
+        counts: &[4, 16, 80, 320, 560, 945],
+    },
+    PerftPosition {
+        name: "devil",
+        seed: 1,
+        moves: &[
+            "13:TRADE@K",
+            "TAC:TRADE@U",
+            "13:TRADE@G",
+            "WAR:TRADE@R",
+            "13:ENTER@K",
+            "8:DISCARD@U",
+            "13:ENTER@G",
+            "TAC:ENTER@R",
+            "12:S0-12@K",
+            "4:DISCARD@U",
+            "8:DISCARD@G",
+            "3:S48-51@R",
+            "2:S12-14@K",
+            "6:DISCARD@U",
+            "2:DISCARD@G",
+            "6:S51-57@R",
+            "3:S14-17@K",
+            "6:DISCARD@U",
+            "TAC:DISCARD@G",
+            "10:S57-3@R",
+            "13:ENTER@K",
+            "WAR:DISCARD@U",
+            "3:DISCARD@G",
+            "10:S3-13@R",
+            "6:TRADE@U",
+            "8:TRADE@G",
+            "10:TRADE@R",
+            "3:TRADE@K",
+            "DEV:DEVIL@U",
+            "TAC:DEVIL@G",
+        ],
+        counts: &[2, 22, 88, 264, 432, 3_792],
+    },
+    PerftPosition {
+        name: "seven",
+        seed: 2,
+        moves: &[
+            "1:TRADE@K",
+            "4:TRADE@U",
+            "JES:TRADE@G",
+            "1:TRADE@R",
+            "JES:JESTER@K",
+            "1:ENTER@K",
+            "1:ENTER@U",
+            "13:ENTER@G",
+            "4:DISCARD@R",
+            "7:S0-7/@K",
+            "8:SUSPEND@U",
+            "5:DISCARD@G",
+            "8:DISCARD@R",
+            "5:S7-12@K",
+            "2:S16-18@U",
+            "4:S32-28@G",
+            "6:DISCARD@R",
+            "TAC:S12-18@K",
+            "12:DISCARD@U",
+            "8:I28-3@G",
+            "2:DISCARD@R",
+            "3:S18-21@K",
+            "9:DISCARD@U",
+            "4:DISCARD@G",
+            "8:TRADE@U",
+            "2:TRADE@G",
+            "6:TRADE@R",
+            "7:TRADE@K",
+            "5:DISCARD@U",
+            "TRI:DISCARD@G",
+            "13:ENTER@R",
+            "3:S21-24@K",
+            "3:DISCARD@U",
+            "9:DISCARD@G",
+            "1:S48-49@R",
+            "5:S24-29@K",
+            "6:DISCARD@U",
+            "7:DISCARD@G",
+            "8:S49-57@R",
+            "9:S29-38@K",
+            "TAC:DISCARD@U",
+            "12:DISCARD@G",
+            "8:S57-1@R",
+            "2:S38-40@K",
+            "4:DISCARD@U",
+            "9:DISCARD@G",
+            "5:S1-6@R",
+            "1:S40-41@K",
+            "2:TRADE@G",
+            "5:TRADE@R",
+            "2:TRADE@K",
+            "TAC:TRADE@U",
+            "ANG:ENTER@G>R",
+            "13:S48-61@R",
+            "7:S41-48/@K",
+            "9:DISCARD@U",
+            "12:DISCARD@G",
+            "6:S6-12@R",
+            "2:S48-50@K",
+            "3:DISCARD@U",
+            "10:DISCARD@G",
+            "1:ENTER@R",
+            "3:S50-53@K",
+            "6:DISCARD@U",
+            "10:DISCARD@G",
+            "TAC:S61-7@R",
+            "6:S53-59@K",
+            "5:DISCARD@U",
+            "2:DISCARD@G",
+            "9:S12-21@R",
+            "10:S59-5@K",
+            "5:DISCARD@U",
+            "10:TRADE@R",
+            "8:TRADE@K",
+            "7:TRADE@U",
+            "12:TRADE@G",
+        ],
+        counts: &[48, 146, 296, 296, 13_542, 33_982],
+    },
+];
+
+/// The same kind of positions for six players.
+#[cfg(feature = "six-players")]
+pub const POSITIONS: &[PerftPosition] = &[
+    PerftPosition {
+        name: "start",
+        seed: 0,
+        moves: &[],
+        counts: &[4, 20, 80, 320, 1_600, 4_800],
+    },
+    PerftPosition {
+        name: "devil",
+        seed: 1,
+        moves: &[
+            "13:TRADE@K",
+            "TAC:TRADE@U",
+            "TAC:TRADE@G",
+            "4:TRADE@R",
+            "6:TRADE@Y",
+            "8:TRADE@P",
+            "13:ENTER@K",
+            "6:DISCARD@U",
+            "13:ENTER@G",
+            "13:ENTER@R",
+            "TAC:ENTER@Y",
+            "1:ENTER@P",
+            "13:S0-13@K",
+            "TRI:DISCARD@U",
+            "12:S32-44@G",
+            "3:DISCARD@R",
+            "3:S64-67@Y",
+            "6:S80-86@P",
+            "4:S13-9@K",
+            "3:DISCARD@U",
+            "13:ENTER@G",
+            "7:DISCARD@R",
+            "2:S67-69@Y",
+            "3:S86-89@P",
+            "3:S9-12@K",
+            "6:DISCARD@U",
+            "8:SUSPEND@G",
+            "10:DISCARD@R",
+            "8:SUSPEND@Y",
+            "TAC:SUSPEND@P",
+            "2:DISCARD@K",
+            "6:DISCARD@U",
+            "8:S44-52@G",
+            "10:DISCARD@R",
+            "2:S69-71@Y",
+            "WAR:W89-12@P",
+            "6:TRADE@U",
+            "8:TRADE@G",
+            "4:TRADE@R",
+            "8:TRADE@Y",
+            "ANG:TRADE@P",
+            "13:TRADE@K",
+            "6:DISCARD@U",
+            "12:S52-64@G",
+            "13:ENTER@R",
+            "6:S71-77@Y",
+            "8:S12-20@P",
+            "2:DISCARD@K",
+            "8:DISCARD@U",
+            "TAC:S64-72@G",
+            "10:S48-58@R",
+            "1:ENTER@Y",
+            "7:S20-27/@P",
+            "4:DISCARD@K",
+            "2:DISCARD@U",
+            "ANG:ENTER@G>R",
+            "4:S48-44@R",
+            "7:S64-68/S77-80@Y",
+            "7:S27-34/@P",
+            "4:DISCARD@K",
+            "TAC:DISCARD@U",
+            "12:DISCARD@G",
+            "10:S44-54@R",
+            "3:S68-71@Y",
+            "1:ENTER@P",
+            "12:DISCARD@K",
+            "12:TRADE@G",
+            "3:TRADE@R",
+            "8:TRADE@Y",
+            "7:TRADE@P",
+            "1:TRADE@K",
+            "5:TRADE@U",
+            "7:S72-79/@G",
+            "5:S58-63@R",
+            "7:S71-78/@Y",
+            "TRI:X63-80@P",
+            "13:ENTER@K",
+            "JES:JESTER@U",
+            "DEV:DEVIL@U",
+        ],
+        counts: &[2, 10, 30, 134, 536, 1_072],
+    },
+    PerftPosition {
+        name: "seven",
+        seed: 1,
+        moves: &[
+            "13:TRADE@K",
+            "TAC:TRADE@U",
+            "TAC:TRADE@G",
+            "4:TRADE@R",
+            "6:TRADE@Y",
+            "8:TRADE@P",
+            "13:ENTER@K",
+            "6:DISCARD@U",
+            "13:ENTER@G",
+            "13:ENTER@R",
+            "TAC:ENTER@Y",
+            "1:ENTER@P",
+            "13:S0-13@K",
+            "TRI:DISCARD@U",
+            "12:S32-44@G",
+            "3:DISCARD@R",
+            "3:S64-67@Y",
+            "6:S80-86@P",
+            "4:S13-9@K",
+            "3:DISCARD@U",
+            "13:ENTER@G",
+            "7:DISCARD@R",
+            "2:S67-69@Y",
+            "3:S86-89@P",
+            "3:S9-12@K",
+            "6:DISCARD@U",
+            "8:SUSPEND@G",
+            "10:DISCARD@R",
+            "8:SUSPEND@Y",
+            "TAC:SUSPEND@P",
+            "2:DISCARD@K",
+            "6:DISCARD@U",
+            "8:S44-52@G",
+            "10:DISCARD@R",
+            "2:S69-71@Y",
+            "WAR:W89-12@P",
+            "6:TRADE@U",
+            "8:TRADE@G",
+            "4:TRADE@R",
+            "8:TRADE@Y",
+            "ANG:TRADE@P",
+            "13:TRADE@K",
+            "6:DISCARD@U",
+            "12:S52-64@G",
+            "13:ENTER@R",
+        ],
+        counts: &[5, 25, 75, 225, 1_010, 1_770],
+    },
+];
+
+impl Board {
+    /// Counts the sequences of `depth` legal moves starting from this position.
+    /// Sequences ending early because the game is over aren't counted.
+    #[must_use]
+    pub fn perft(&self, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.get_moves(self.current_player());
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .iter()
+            .map(|mv| {
+                let mut board = self.clone();
+                board.play(mv);
+                board.perft(depth - 1)
+            })
+            .sum()
+    }
+
+    /// Like [`Self::perft`], split up by the first move.
+    #[must_use]
+    pub fn perft_divide(&self, depth: usize) -> Vec<(TacMove, u64)> {
+        self.get_moves(self.current_player())
+            .into_iter()
+            .map(|mv| {
+                let mut board = self.clone();
+                board.play(&mv);
+                let count = board.perft(depth.saturating_sub(1));
+                (mv, count)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        for position in POSITIONS {
+            let board = position.board();
+            for (depth, count) in position.counts.iter().enumerate() {
+                assert_eq!(
+                    board.perft(depth + 1),
+                    *count,
+                    "{} depth {}",
+                    position.name,
+                    depth + 1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn divide() {
+        let board = POSITIONS[0].board();
+        let divided = board.perft_divide(3);
+        assert_eq!(
+            divided.iter().map(|(_, count)| count).sum::<u64>(),
+            board.perft(3)
+        );
+    }
+}
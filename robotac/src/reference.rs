@@ -0,0 +1,282 @@
+//! Slow reference implementation of how moves change the balls, used to check [`Board::play`].
+//! Every square is a plain array entry, balls walk one square at a time and instead of undoing
+//! moves for a Tac the position before every move is remembered.
+
+use tac_types::{
+    Card, Color, Distance, HomeSquare, Square, TacAction, TacMove, ALL_COLORS, HOME_SIZE,
+    NUM_COLORS, RING_SIZE,
+};
+
+use crate::{board::Board, rules::Ruleset};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Balls {
+    ring: [Option<Color>; RING_SIZE as usize],
+    homes: [[bool; HOME_SIZE as usize]; NUM_COLORS],
+    base: [u8; NUM_COLORS],
+}
+
+impl Balls {
+    pub(crate) fn from_board(board: &Board) -> Self {
+        Self {
+            ring: core::array::from_fn(|sq| board.color_on(Square(u8::try_from(sq).unwrap()))),
+            homes: ALL_COLORS.map(|c| HomeSquare::ALL.map(|pos| !board.home(c).is_free(pos))),
+            base: ALL_COLORS.map(|c| board.num_base(c)),
+        }
+    }
+
+    fn capture(&mut self, square: Square) {
+        if let Some(color) = self.ring[square.0 as usize].take() {
+            self.base[color as usize] += 1;
+        }
+    }
+
+    fn lift(&mut self, square: Square) -> Color {
+        self.ring[square.0 as usize]
+            .take()
+            .expect("Moved balls have to be on the board")
+    }
+
+    fn step(&mut self, from: Square, to: Square) {
+        let color = self.lift(from);
+        self.capture(to);
+        self.ring[to.0 as usize] = Some(color);
+    }
+
+    fn apply(&mut self, action: &TacAction, player: Color, rules: Ruleset) {
+        match *action {
+            TacAction::Step { from, to } => self.step(from, to),
+            TacAction::Warrior { from, to } => {
+                if from == to {
+                    self.capture(from);
+                } else {
+                    self.step(from, to);
+                }
+            }
+            TacAction::Enter => {
+                self.capture(player.home());
+                self.ring[player.home().0 as usize] = Some(player);
+                self.base[player as usize] -= 1;
+            }
+            TacAction::StepInHome { from, to } => {
+                self.lift(from);
+                self.homes[player as usize][to.index() as usize] = true;
+            }
+            TacAction::StepHome { from, to } => {
+                self.homes[player as usize][from.index() as usize] = false;
+                self.homes[player as usize][to.index() as usize] = true;
+            }
+            TacAction::Trickster { target1, target2 } => {
+                self.ring.swap(target1.0 as usize, target2.0 as usize);
+            }
+            TacAction::SevenSteps { ref steps } => self.seven(steps, player, rules),
+            TacAction::Suspend
+            | TacAction::Jester
+            | TacAction::Devil
+            | TacAction::Discard
+            | TacAction::Trade
+            | TacAction::Announce { .. } => {}
+        }
+    }
+
+    fn seven(&mut self, steps: &[TacAction], player: Color, rules: Ruleset) {
+        // Balls inside the home never pass each other, lift them all before putting them down
+        for step in steps {
+            if let TacAction::StepHome { from, .. } = step {
+                self.homes[player as usize][from.index() as usize] = false;
+            }
+        }
+        for step in steps {
+            if let TacAction::StepHome { to, .. } = step {
+                self.homes[player as usize][to.index() as usize] = true;
+            }
+        }
+        let mut pending = steps
+            .iter()
+            .filter_map(|step| match *step {
+                TacAction::Step { from, to } => Some((from, to, None)),
+                TacAction::StepInHome { from, to } => Some((from, player.home(), Some(to))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let path = |from: Square, to: Square| {
+            (1..=from.distance_to(to).steps()).map(move |i| from.add(Distance::new(i)))
+        };
+        while !pending.is_empty() {
+            // A ball has to get out of the way before the balls behind it pass its square
+            let idx = (0..pending.len())
+                .find(|&idx| {
+                    let (from, to, _) = pending[idx];
+                    path(from, to).all(|sq| pending.iter().all(|(other, _, _)| *other != sq))
+                })
+                .expect("Seven steps can't block each other in a circle");
+            let (from, to, goal) = pending.remove(idx);
+            self.lift(from);
+            for square in path(from, to) {
+                let passing = square != to || goal.is_some();
+                let jumps_own = !rules.seven_captures_own && passing;
+                if !(jumps_own && self.ring[square.0 as usize] == Some(player)) {
+                    self.capture(square);
+                }
+            }
+            match goal {
+                Some(goal) => self.homes[player as usize][goal.index() as usize] = true,
+                None => self.ring[to.0 as usize] = Some(player),
+            }
+        }
+    }
+}
+
+/// Follows the balls of a game by replaying the same moves as a [`Board`].
+pub(crate) struct Reference {
+    balls: Balls,
+    /// Every move played together with the balls before it
+    history: Vec<(TacMove, Balls)>,
+    rules: Ruleset,
+}
+
+impl Reference {
+    pub(crate) fn new(board: &Board) -> Self {
+        Self {
+            balls: Balls::from_board(board),
+            history: Vec::new(),
+            rules: *board.rules(),
+        }
+    }
+
+    pub(crate) fn balls(&self) -> &Balls {
+        &self.balls
+    }
+
+    pub(crate) fn play(&mut self, mv: &TacMove) {
+        if matches!(mv.action, TacAction::Trade | TacAction::Announce { .. }) {
+            return;
+        }
+        let before = self.balls.clone();
+        if mv.card == Card::Tac && !matches!(mv.action, TacAction::Discard | TacAction::Jester) {
+            // A Tac plays on the balls as they were before the move it replaces
+            let (_, replaced) = self
+                .history
+                .iter()
+                .rev()
+                .find(|(past, _)| !matches!(past.action, TacAction::Jester))
+                .expect("A Tac needs a move to replace");
+            self.balls = replaced.clone();
+        }
+        self.balls.apply(&mv.action, mv.played_for, self.rules);
+        self.history.push((mv.clone(), before));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+    use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+
+    use super::*;
+    use crate::rules::OpeningAnnouncement;
+
+    fn compare_random_games(rules: Ruleset, seeds: std::ops::Range<u64>) {
+        compare_games(rules, seeds, false);
+    }
+
+    /// Returns the longest chain of Tacs replacing each other.
+    fn compare_games(rules: Ruleset, seeds: std::ops::Range<u64>, prefer_tac: bool) -> usize {
+        let mut longest_chain = 0;
+        for seed in seeds {
+            let mut board = Board::new_with_rules(seed, rules);
+            let mut reference = Reference::new(&board);
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut played = Vec::new();
+            loop {
+                let moves = board.get_moves(board.current_player());
+                let tacs = moves
+                    .iter()
+                    .filter(|mv| mv.card == Card::Tac && !matches!(mv.action, TacAction::Discard))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                let Some(mv) = (if prefer_tac && !tacs.is_empty() {
+                    tacs.into_iter().choose(&mut rng)
+                } else {
+                    moves.into_iter().choose(&mut rng)
+                }) else {
+                    break;
+                };
+                // Undoing an action on its own has to give back the same balls,
+                // a Tac needs the replaced move undone first so it's only covered by playing it.
+                // Trades and announcements are never undone
+                if mv.card != Card::Tac
+                    && !matches!(mv.action, TacAction::Trade | TacAction::Announce { .. })
+                {
+                    let mut undone = board.clone();
+                    let captured = undone.apply_action(mv.action.clone(), mv.played_for);
+                    undone.undo_action(mv.action.clone(), mv.played_for, captured);
+                    assert_eq!(
+                        Balls::from_board(&undone),
+                        Balls::from_board(&board),
+                        "seed {seed} undoing {mv} after {}",
+                        played.iter().join(", ")
+                    );
+                }
+                board.play(&mv);
+                reference.play(&mv);
+                played.push(mv);
+                assert_eq!(
+                    &Balls::from_board(&board),
+                    reference.balls(),
+                    "seed {seed} after {}",
+                    played.iter().join(", ")
+                );
+                let chain = board
+                    .past_moves()
+                    .iter()
+                    .rev()
+                    .filter(|(mv, _)| !matches!(mv.action, TacAction::Jester))
+                    .take_while(|(mv, _)| {
+                        mv.card == Card::Tac && !matches!(mv.action, TacAction::Discard)
+                    })
+                    .count();
+                longest_chain = longest_chain.max(chain);
+            }
+        }
+        longest_chain
+    }
+
+    #[test]
+    fn matches_board() {
+        compare_random_games(Ruleset::default(), 0..200);
+    }
+
+    #[test]
+    fn matches_board_seven_jumping_own() {
+        let rules = Ruleset {
+            seven_captures_own: false,
+            ..Ruleset::default()
+        };
+        compare_random_games(rules, 0..200);
+    }
+
+    #[test]
+    fn matches_board_without_tac_chaining() {
+        let rules = Ruleset {
+            tac_chaining: false,
+            ..Ruleset::default()
+        };
+        compare_random_games(rules, 0..50);
+    }
+
+    #[test]
+    fn matches_board_with_declared_announcements() {
+        let rules = Ruleset {
+            opening_announcement: OpeningAnnouncement::Declared,
+            ..Ruleset::default()
+        };
+        compare_random_games(rules, 0..50);
+    }
+
+    #[test]
+    fn matches_board_tac_chains() {
+        let longest_chain = compare_games(Ruleset::default(), 0..500, true);
+        assert!(longest_chain >= 3);
+    }
+}
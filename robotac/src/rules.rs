@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// What players tell the others about their openings after a new deal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OpeningAnnouncement {
+    /// Every player announces whether they were dealt a One or Thirteen before trading
+    #[default]
+    Dealt,
+    /// Nobody announces anything
+    Silent,
+    /// Every player announces with a move before trading, see [`tac_types::TacAction::Announce`].
+    /// Announcements don't have to be true
+    Declared,
+}
+
+/// Optional rules that differ between house rules.
+/// The default matches the rules the engine has always played by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ruleset {
+    /// A seven captures our own balls it passes over. Otherwise they are jumped,
+    /// only the last square of a step captures them.
+    pub seven_captures_own: bool,
+    /// A Tac can be answered with another Tac.
+    pub tac_chaining: bool,
+    pub opening_announcement: OpeningAnnouncement,
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self {
+            seven_captures_own: true,
+            tac_chaining: true,
+            opening_announcement: OpeningAnnouncement::default(),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Tactical check of the move picked by a search.
+//!
+//! Random playouts rarely find the single reply that refutes a move, like a tac taking back a
+//! capture or a capture of a ball that just moved into reach. Before a move is played, all
+//! immediate replies of the next opponent are tried in a few determinizations, and a move that
+//! hands them a ball or the game is swapped for one the search liked about as much that doesn't.
+
+use mcts::manager::Manager;
+use rand::Rng;
+use tac_types::{Color, TacMove};
+
+use crate::{board::Board, knowledge::Knowledge, TacAI};
+
+/// Determinizations the replies to every candidate are tried in.
+pub const SAMPLES: usize = 8;
+
+/// Moves with at least this share of the visits of the searched move may replace it.
+pub const SIMILAR_VISITS: f64 = 0.5;
+
+/// How often the best immediate reply of an opponent wins the game or captures balls of the team
+/// that played a move, summed over determinizations. Fewer lost games always count as less risk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Risk {
+    pub games: usize,
+    pub balls: usize,
+}
+
+fn same_team(a: Color, b: Color) -> bool {
+    a == b || a == b.partner()
+}
+
+/// Balls of the team of `player` that aren't in their bases.
+fn balls_out(board: &Board, player: Color) -> u8 {
+    8 - board.num_base(player) - board.num_base(player.partner())
+}
+
+/// Samples `samples` boards with hands the player to move considers possible.
+pub fn determinizations<R: Rng>(
+    board: &Board,
+    knowledge: &Knowledge,
+    samples: usize,
+    rng: &mut R,
+) -> Vec<Board> {
+    let player = board.current_player();
+    (0..samples.max(1))
+        .map(|_| {
+            let mut determined = board.clone();
+            determined.redetermine(player, knowledge, rng);
+            determined
+        })
+        .collect()
+}
+
+/// Returns what the best reply of the next player to `mv` costs the team playing it, compared to
+/// before the move. Replies of the partner aren't considered, neither are moves after the reply.
+/// `mv` has to be legal in all `determinizations`, which holds for the moves of the real hand.
+#[must_use]
+pub fn reply_risk(determinizations: &[Board], mv: &TacMove) -> Risk {
+    let mut risk = Risk::default();
+    for board in determinizations {
+        let player = board.current_player();
+        let before = balls_out(board, player);
+        let mut after = board.clone();
+        after.play(mv);
+        let opponent = after.current_player();
+        if after.is_terminal() || same_team(player, opponent) {
+            continue;
+        }
+        let mut lost_balls = 0;
+        for reply in after.get_moves(opponent) {
+            let mut answered = after.clone();
+            answered.play(&reply);
+            if answered.won(opponent) {
+                risk.games += 1;
+                lost_balls = 0;
+                break;
+            }
+            lost_balls = lost_balls.max(before.saturating_sub(balls_out(&answered, player)));
+        }
+        risk.balls += usize::from(lost_balls);
+    }
+    risk
+}
+
+/// Returns the candidate whose best reply costs the least in `determinizations`, the earliest
+/// one if several are equally safe. The first candidate should be the move the search picked.
+#[must_use]
+pub fn safest_move(determinizations: &[Board], candidates: &[TacMove]) -> Option<TacMove> {
+    candidates
+        .iter()
+        .min_by_key(|mv| reply_risk(determinizations, mv))
+        .cloned()
+}
+
+/// Returns `best` followed by the legal moves of the search with at least [`SIMILAR_VISITS`]
+/// of its visits, most visited first.
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn similar_moves(mcts: &Manager<TacAI>, board: &Board, best: &TacMove) -> Vec<TacMove> {
+    let legal = board.get_moves(board.current_player());
+    let stats = mcts.root_move_stats();
+    let best_visits = stats
+        .iter()
+        .find(|(mv, _)| mv.unpack() == *best)
+        .map_or(0, |(_, stats)| stats.visits);
+    let mut moves = vec![best.clone()];
+    moves.extend(
+        stats
+            .iter()
+            .filter(|(_, stats)| stats.visits as f64 >= SIMILAR_VISITS * best_visits as f64)
+            .map(|(mv, _)| mv.unpack())
+            .filter(|mv| mv != best && legal.contains(mv)),
+    );
+    moves
+}
+
+#[cfg(test)]
+#[allow(unused_must_use)]
+mod tests {
+    use tac_types::{Card, Distance, Square, TacAction, ALL_COLORS};
+
+    use super::*;
+    use crate::arena::AgentConfig;
+
+    /// Black can move its ball three squares, where a Blue ball can reach it with a five, or two.
+    fn exposed() -> (Board, TacMove, TacMove) {
+        let (black, blue) = (Color::Black, Color::Blue);
+        let mut board = Board::new();
+        while board.need_trade() {
+            let trade = board.get_moves(board.current_player())[0].clone();
+            board.play(&trade);
+        }
+        for color in ALL_COLORS {
+            board.clear_hand(color);
+        }
+        board.add_hand(black, Card::Two);
+        board.add_hand(black, Card::Three);
+        board.add_hand(blue, Card::Five);
+        for color in ALL_COLORS.into_iter().skip(2) {
+            board.add_hand(color, Card::One);
+        }
+        board.put_ball_in_play(black);
+        board.put_ball_in_play(blue);
+        board.move_ball(blue.home(), black.home().sub(Distance::new(2)), blue);
+        let step = |card, to| {
+            TacMove::new(
+                card,
+                TacAction::Step {
+                    from: Square(0),
+                    to: Square(to),
+                },
+                black,
+                black,
+            )
+        };
+        (board, step(Card::Three, 3), step(Card::Two, 2))
+    }
+
+    #[test]
+    fn avoids_captures() {
+        let (board, exposed, safe) = exposed();
+        let determinizations = [board];
+        assert_eq!(
+            reply_risk(&determinizations, &exposed),
+            Risk { games: 0, balls: 1 }
+        );
+        assert_eq!(reply_risk(&determinizations, &safe), Risk::default());
+        assert_eq!(
+            safest_move(&determinizations, &[exposed.clone(), safe.clone()]),
+            Some(safe.clone())
+        );
+        // Equally safe moves keep the order of the search
+        assert_eq!(
+            safest_move(&determinizations, &[safe.clone(), exposed]),
+            Some(safe)
+        );
+        assert_eq!(safest_move(&determinizations, &[]), None);
+        assert!(Risk { games: 1, balls: 0 } > Risk { games: 0, balls: 4 });
+    }
+
+    #[test]
+    fn tac_replies() {
+        // A tac takes back the step of Black and plays it for Blue instead
+        let (mut board, three, two) = exposed();
+        board.clear_hand(Color::Blue);
+        board.add_hand(Color::Blue, Card::Tac);
+        let determinizations = [board];
+        // Taking back the two puts the ball of Black back where the two of Blue ends
+        assert_eq!(
+            reply_risk(&determinizations, &two),
+            Risk { games: 0, balls: 1 }
+        );
+        assert_eq!(reply_risk(&determinizations, &three), Risk::default());
+        assert_eq!(
+            safest_move(&determinizations, &[two, three.clone()]),
+            Some(three)
+        );
+    }
+
+    #[test]
+    fn checked_search() {
+        let board = Board::new_with_seed(0);
+        let agent = AgentConfig {
+            playouts: 200,
+            loss_check: true,
+            ..AgentConfig::new("checked")
+        };
+        let mv = agent.choose_move(&board).unwrap();
+        assert!(board.get_moves(board.current_player()).contains(&mv));
+
+        let mut mcts = agent.manager(&board);
+        mcts.playout_n(200);
+        let best = mcts.best_move().unwrap().unpack();
+        let similar = similar_moves(&mcts, &board, &best);
+        assert_eq!(similar[0], best);
+        assert!(similar.iter().skip(1).all(|mv| *mv != best));
+
+        // A seeded search tries the same replies and keeps its move
+        let seeded = AgentConfig {
+            seed: Some(0),
+            ..agent
+        };
+        let searched = seeded.search(&board).map(|(mv, _)| mv);
+        assert!(searched.is_some());
+        assert_eq!(seeded.search(&board).map(|(mv, _)| mv), searched);
+    }
+}
@@ -0,0 +1,148 @@
+//! Self-play games for training policies and evaluations offline.
+//!
+//! Games are written as JSON lines, one [`Sample`] per position in which the player to move
+//! had to choose between more than one move:
+//!
+//! ```text
+//! {"seed":0,"ply":12,"player":"Blue","features":[0.0,...],"policy":[[<TacMove>,0.75],...],"result":-1.0}
+//! ```
+
+use std::io::{self, Write};
+
+use serde::{Deserialize, Serialize};
+use tac_types::{Color, PackedTacMove, TacMove};
+
+use crate::{arena::AgentConfig, board::Board};
+
+/// A position of a self-play game with the search result and the outcome of the game.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    /// Seed the game was dealt with
+    pub seed: u64,
+    /// Number of moves played before this position
+    pub ply: u32,
+    pub player: Color,
+    /// Position from the view of `player`, see [`Board::to_features`]
+    pub features: Vec<f32>,
+    /// Moves searched by `player` with their share of the visits at the root
+    pub policy: Vec<(TacMove, f32)>,
+    /// `1` if the team of `player` won the game, `-1` if it lost and `0` if the game was cut off
+    pub result: f32,
+}
+
+/// Plays games of an agent against itself and records the positions.
+pub struct SelfPlay {
+    pub agent: AgentConfig,
+    /// Games still running after this many moves are cut off
+    pub max_plies: usize,
+}
+
+impl SelfPlay {
+    #[must_use]
+    pub fn new(agent: AgentConfig) -> Self {
+        Self {
+            agent,
+            max_plies: 2_000,
+        }
+    }
+
+    /// Plays the game dealt with `seed` and returns a sample for every position with a choice.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    pub fn play_game(&self, seed: u64) -> Vec<Sample> {
+        let mut board = Board::new_with_seed(seed);
+        let mut samples = Vec::new();
+        for _ in 0..self.max_plies {
+            if board.is_terminal() {
+                break;
+            }
+            let player = board.current_player();
+            let moves = board.get_moves(player);
+            let mv = if moves.len() > 1 {
+                let mut mcts = self.agent.manager(&board);
+                if self.agent.threads > 1 {
+                    mcts.playout_n_parallel(self.agent.playouts, self.agent.threads);
+                } else {
+                    mcts.playout_n(self.agent.playouts);
+                }
+                let stats = mcts.root_move_stats();
+                let visits = stats.iter().map(|(_, s)| s.visits).sum::<u64>().max(1);
+                samples.push(Sample {
+                    seed,
+                    ply: board.move_count,
+                    player,
+                    features: board.to_features(player).to_vec(),
+                    policy: stats
+                        .iter()
+                        .map(|(mv, s)| (mv.unpack(), (s.visits as f64 / visits as f64) as f32))
+                        .collect(),
+                    result: 0.0,
+                });
+                mcts.best_move().map(PackedTacMove::unpack)
+            } else {
+                moves.into_iter().next()
+            };
+            let Some(mv) = mv else {
+                break;
+            };
+            board.play(&mv);
+        }
+        if let Some(result) = board.game_result() {
+            for sample in &mut samples {
+                sample.result = if result.is_win_for(sample.player) {
+                    1.0
+                } else {
+                    -1.0
+                };
+            }
+        }
+        samples
+    }
+
+    /// Plays `games` games starting with `seed` and writes their samples to `writer`.
+    /// Returns the number of samples written.
+    /// # Errors
+    /// If writing fails.
+    pub fn write_games<W: Write>(&self, seed: u64, games: u64, mut writer: W) -> io::Result<usize> {
+        let mut written = 0;
+        for game in 0..games {
+            for sample in self.play_game(seed + game) {
+                serde_json::to_writer(&mut writer, &sample)?;
+                writer.write_all(b"\n")?;
+                written += 1;
+            }
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::*;
+    use crate::features::NUM_FEATURES;
+
+    #[test]
+    fn write_game() {
+        let mut agent = AgentConfig::new("selfplay");
+        agent.playouts = 20;
+        let mut selfplay = SelfPlay::new(agent);
+        selfplay.max_plies = 30;
+        let mut out = Vec::new();
+        let written = selfplay.write_games(4, 1, &mut out).unwrap();
+        let lines = String::from_utf8(out).unwrap();
+        let samples = lines
+            .lines()
+            .map(|line| serde_json::from_str::<Sample>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert!(written > 0);
+        assert_eq!(samples.len(), written);
+        for sample in samples {
+            assert_eq!(sample.features.len(), NUM_FEATURES);
+            let total = sample.policy.iter().map(|(_, p)| p).sum::<f32>();
+            assert!((total - 1.0).abs() < 1e-4);
+            assert_eq!(sample.result, 0.0);
+        }
+    }
+}
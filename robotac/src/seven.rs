@@ -1,5 +1,10 @@
+use std::collections::HashSet;
+
 use itertools::Itertools;
-use tac_types::{BitBoard, Card, Color, Home, Square, TacAction, TacMove};
+use tac_types::{
+    BitBoard, Card, Color, Distance, Home, HomeSquare, Square, TacAction, TacMove, ALL_COLORS,
+    NUM_COLORS,
+};
 
 use crate::board::Board;
 
@@ -10,7 +15,7 @@ fn balls_reach_home(
     player: Color,
 ) -> impl Iterator<Item = (Square, u8)> {
     balls.iter().filter_map(move |b| {
-        let dist = b.distance_to_home(player);
+        let dist = b.distance_to_home(player).steps();
         (dist <= budget).then_some((b, dist))
     })
 }
@@ -23,7 +28,10 @@ fn moves_for_budget(
 ) -> impl Iterator<Item = (TacAction, Square, u8)> {
     balls_reach_home(balls, budget - (goal + 1), player).map(move |(b, dist_home)| {
         (
-            TacAction::StepInHome { from: b, to: goal },
+            TacAction::StepInHome {
+                from: b,
+                to: HomeSquare::new(goal),
+            },
             b,
             budget - (dist_home + goal + 1),
         )
@@ -31,12 +39,44 @@ fn moves_for_budget(
 }
 
 impl Board {
-    #[allow(clippy::too_many_lines)]
+    #[cfg(test)]
     pub(crate) fn seven_moves(&self, player: Color) -> Vec<TacMove> {
+        let mut moves = Vec::new();
+        self.seven_moves_into(player, &mut moves);
+        moves
+    }
+
+    /// Generates all seven moves of `player`, keeping only one move for each resulting position.
+    pub(crate) fn seven_moves_into(&self, player: Color, moves: &mut Vec<TacMove>) {
+        let start = moves.len();
+        self.seven_combinations_into(player, moves);
+        let mut generated = moves.split_off(start);
+        self.dedup_seven_moves(&mut generated);
+        moves.extend(generated.into_iter().map(TacMove::canonical));
+    }
+
+    /// Removes seven moves which lead to the same position as an earlier move.
+    /// Different splits of the steps can end up with identical balls, e.g. when passing over
+    /// one of our own balls captures it anyway.
+    fn dedup_seven_moves(&self, moves: &mut Vec<TacMove>) {
+        let mut board = self.clone();
+        let mut seen = HashSet::with_capacity(moves.len());
+        moves.retain(|mv| seen.insert(board.seven_result(mv)));
+    }
+
+    /// Balls, homes and bases of all colors after playing the seven `mv`.
+    fn seven_result(&mut self, mv: &TacMove) -> [(BitBoard, Home, u8); NUM_COLORS] {
+        let captured = self.apply_action(mv.action.clone(), mv.played_for);
+        let result = ALL_COLORS.map(|c| (self.balls_with(c), *self.home(c), self.num_base(c)));
+        self.undo_action(mv.action.clone(), mv.played_for, captured);
+        result
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn seven_combinations_into(&self, player: Color, moves: &mut Vec<TacMove>) {
         // TODO Some thoughts about generating seven moves
         // This still needs to take into account moves that go from ring to home
         let play_for = self.play_for(player);
-        let mut moves = Vec::new();
         let num_balls = self.balls_with(play_for).len();
         let home = *self.home(play_for);
         let balls_bb = self.balls_with(play_for);
@@ -50,7 +90,10 @@ impl Board {
                     .into_iter()
                     .map(|hm| {
                         hm.into_iter()
-                            .map(|(from, to)| TacAction::StepHome { from, to })
+                            .map(|(from, to)| TacAction::StepHome {
+                                from: HomeSquare::new(from),
+                                to: HomeSquare::new(to),
+                            })
                             .collect_vec()
                     })
                     .collect_vec()
@@ -68,7 +111,7 @@ impl Board {
                         player,
                     )
                 }));
-                return moves;
+                return;
             }
 
             let board_budget = 7 - home_budget;
@@ -179,7 +222,7 @@ impl Board {
                         if remaining_budget != 0 {
                             res.push(TacAction::Step {
                                 from: balls[0],
-                                to: balls[0].add(remaining_budget),
+                                to: balls[0].add(Distance::new(remaining_budget)),
                             });
                         }
                         combinations.push(res);
@@ -192,13 +235,13 @@ impl Board {
                             if i != 0 {
                                 res.push(TacAction::Step {
                                     from: balls[0],
-                                    to: balls[0].add(i),
+                                    to: balls[0].add(Distance::new(i)),
                                 });
                             }
                             if j != 0 {
                                 res.push(TacAction::Step {
                                     from: balls[1],
-                                    to: balls[1].add(j),
+                                    to: balls[1].add(Distance::new(j)),
                                 });
                             }
                             combinations.push(res);
@@ -212,19 +255,19 @@ impl Board {
                                 if i != 0 {
                                     res.push(TacAction::Step {
                                         from: balls[0],
-                                        to: balls[0].add(i),
+                                        to: balls[0].add(Distance::new(i)),
                                     });
                                 }
                                 if j != 0 {
                                     res.push(TacAction::Step {
                                         from: balls[1],
-                                        to: balls[1].add(j),
+                                        to: balls[1].add(Distance::new(j)),
                                     });
                                 }
                                 if k != 0 {
                                     res.push(TacAction::Step {
                                         from: balls[2],
-                                        to: balls[2].add(k),
+                                        to: balls[2].add(Distance::new(k)),
                                     });
                                 }
                                 combinations.push(res);
@@ -240,25 +283,25 @@ impl Board {
                                     if i != 0 {
                                         res.push(TacAction::Step {
                                             from: balls[0],
-                                            to: balls[0].add(i),
+                                            to: balls[0].add(Distance::new(i)),
                                         });
                                     }
                                     if j != 0 {
                                         res.push(TacAction::Step {
                                             from: balls[1],
-                                            to: balls[1].add(j),
+                                            to: balls[1].add(Distance::new(j)),
                                         });
                                     }
                                     if k != 0 {
                                         res.push(TacAction::Step {
                                             from: balls[2],
-                                            to: balls[2].add(k),
+                                            to: balls[2].add(Distance::new(k)),
                                         });
                                     }
                                     if l != 0 {
                                         res.push(TacAction::Step {
                                             from: balls[3],
-                                            to: balls[3].add(l),
+                                            to: balls[3].add(Distance::new(l)),
                                         });
                                     }
                                     combinations.push(res);
@@ -279,7 +322,6 @@ impl Board {
                 )
             }));
         }
-        moves
     }
 }
 
@@ -433,4 +475,47 @@ mod tests {
 
         assert_eq!(moves.len(), 120);
     }
+
+    #[test]
+    fn seven_moves_unique() {
+        let mut board = Board::new();
+        let player = Color::Black;
+        let check = |board: &Board| {
+            let mut all = Vec::new();
+            board.seven_combinations_into(player, &mut all);
+            let moves = board.seven_moves(player);
+            let mut scratch = board.clone();
+            let results = moves
+                .iter()
+                .map(|mv| scratch.seven_result(mv))
+                .collect::<HashSet<_>>();
+            // Every position is reached by exactly one move
+            assert_eq!(results.len(), moves.len());
+            assert!(all
+                .iter()
+                .all(|mv| results.contains(&scratch.seven_result(mv))));
+            all.len() - moves.len()
+        };
+
+        board.put_ball_in_play(player);
+        board.move_ball(Square(0), Square(7), player);
+        board.put_ball_in_play(player);
+        board.move_ball(Square(0), Square(3), player);
+        assert_eq!(check(&board), 0);
+
+        board.put_ball_in_play(player);
+        board.move_ball(Square(0), Square(14), player);
+        assert_eq!(check(&board), 0);
+
+        // Moving in home wastes budget, so stepping onto our own ball and passing over it
+        // end up in the same position
+        let mut board = Board::new();
+        board.put_ball_in_play(player);
+        board.move_ball_to_goal(Square(0), HomeSquare::new(1), player);
+        board.put_ball_in_play(player);
+        board.move_ball(Square(0), Square(18), player);
+        board.put_ball_in_play(player);
+        board.move_ball(Square(0), Square(21), player);
+        assert!(check(&board) > 0);
+    }
 }
@@ -0,0 +1,171 @@
+//! Exact search for endgames.
+//!
+//! Once a team only has a few balls left outside the homes, random playouts mostly add noise. The
+//! solver instead searches all moves up to a fixed depth and only returns a result if the
+//! game is decided within it for every answer of the opponents. Hidden cards are handled by
+//! solving a number of determinizations and only trusting results that hold in all of them.
+
+use rand::Rng;
+use tac_types::{TacMove, ALL_COLORS, HOME_SIZE, NUM_TEAMS};
+
+use crate::{
+    board::{Board, GameResult},
+    knowledge::Knowledge,
+};
+
+/// Positions where a team has at most this many balls outside of their homes count as endgames.
+pub const ENDGAME_BALLS: u8 = 2;
+
+/// Returns whether a team has at most [`ENDGAME_BALLS`] balls left to bring home.
+#[must_use]
+pub fn is_endgame(board: &Board) -> bool {
+    ALL_COLORS[..NUM_TEAMS].iter().any(|color| {
+        let in_home = board.home(*color).amount() + board.home(color.partner()).amount();
+        2 * HOME_SIZE - in_home <= ENDGAME_BALLS
+    })
+}
+
+/// Depth first search with a limit on the number of positions.
+struct Solver {
+    nodes_left: u64,
+}
+
+impl Solver {
+    /// Returns the result if the team to move plays for the win, `None` if that isn't decided
+    /// within `depth` moves or the node budget ran out.
+    fn search(&mut self, board: &Board, depth: usize) -> Option<GameResult> {
+        let to_move = board.current_player();
+        if let Some(result) = board.game_result() {
+            return Some(result);
+        }
+        if depth == 0 {
+            return None;
+        }
+        let mut undecided = false;
+        let mut lost = None;
+        for mv in board.get_moves(to_move) {
+            match self.child(board, &mv, depth) {
+                Some(result) if result.is_win_for(to_move) => return Some(result),
+                Some(result) => lost = Some(result),
+                None => undecided = true,
+            }
+            if self.nodes_left == 0 {
+                return None;
+            }
+        }
+        lost.filter(|_| !undecided)
+    }
+
+    /// Result of playing `mv`.
+    fn child(&mut self, board: &Board, mv: &TacMove, depth: usize) -> Option<GameResult> {
+        self.nodes_left = self.nodes_left.checked_sub(1)?;
+        let mut after = board.clone();
+        after.play(mv);
+        self.search(&after, depth - 1)
+    }
+}
+
+/// Solves the position with all hands as they are on `board`.
+/// Returns the result if the team to move plays for the win, or `None` if the game isn't
+/// decided within `depth` moves or more than `max_nodes` positions would have to be searched.
+#[must_use]
+pub fn solve(board: &Board, depth: usize, max_nodes: u64) -> Option<GameResult> {
+    Solver {
+        nodes_left: max_nodes,
+    }
+    .search(board, depth)
+}
+
+/// Returns a move that wins within `depth` moves in `samples` determinizations of the hands
+/// `knowledge` doesn't know about, or `None` if there is no such move.
+/// The node budget `max_nodes` applies to every determinization separately.
+pub fn winning_move<R: Rng>(
+    board: &Board,
+    knowledge: &Knowledge,
+    depth: usize,
+    max_nodes: u64,
+    samples: usize,
+    rng: &mut R,
+) -> Option<TacMove> {
+    let player = board.current_player();
+    let determinizations = (0..samples.max(1))
+        .map(|_| {
+            let mut determined = board.clone();
+            determined.redetermine(player, knowledge, rng);
+            determined
+        })
+        .collect::<Vec<_>>();
+    board.get_moves(player).into_iter().find(|mv| {
+        determinizations.iter().all(|determined| {
+            let mut solver = Solver {
+                nodes_left: max_nodes,
+            };
+            // Moves of the real hand are legal in every determinization as it is kept as is
+            depth > 0
+                && solver
+                    .child(determined, mv, depth)
+                    .is_some_and(|result| result.is_win_for(player))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, seq::IteratorRandom, SeedableRng};
+
+    use super::*;
+
+    /// Plays random games and returns the positions right before a team won.
+    fn final_positions() -> Vec<Board> {
+        (0..10)
+            .filter_map(|seed| {
+                let mut board = Board::new_with_seed(seed);
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut previous = board.clone();
+                while let Some(mv) = board
+                    .get_moves(board.current_player())
+                    .into_iter()
+                    .choose(&mut rng)
+                {
+                    previous = board.clone();
+                    board.play(&mv);
+                }
+                board.is_terminal().then_some(previous)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn solves_won_positions() {
+        let positions = final_positions();
+        assert!(!positions.is_empty());
+        for board in positions {
+            assert!(is_endgame(&board));
+            // The mover won with the last move
+            assert_eq!(
+                solve(&board, 1, 10_000),
+                Some(GameResult::from_winner(board.current_player()))
+            );
+            assert_eq!(solve(&board, 0, 10_000), None);
+            // Without nodes left nothing gets solved
+            assert_eq!(solve(&board, 1, 0), None);
+
+            // Determinizations could hand the next player a tac to take the win back
+            let mut board = board.clone();
+            board.clear_hand(board.current_player().next());
+            let knowledge = Knowledge::new_from_board(board.current_player(), &board);
+            let mut rng = StdRng::seed_from_u64(0);
+            let mv = winning_move(&board, &knowledge, 1, 10_000, 4, &mut rng).unwrap();
+            let mut after = board.clone();
+            after.play(&mv);
+            assert!(after.won(board.current_player()));
+        }
+    }
+
+    #[test]
+    fn start_is_no_endgame() {
+        let board = Board::new_with_seed(0);
+        assert!(!is_endgame(&board));
+        assert_eq!(solve(&board, 2, 10_000), None);
+    }
+}
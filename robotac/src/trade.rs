@@ -0,0 +1,101 @@
+use itertools::Itertools;
+use tac_types::{Card, Color};
+
+use crate::{board::Board, knowledge::Knowledge};
+
+/// How much a card is worth to the player holding it, independent of the position.
+pub(crate) fn card_value(card: Card) -> f64 {
+    match card {
+        Card::Tac => 3.0,
+        Card::Jester | Card::Devil | Card::Trickster | Card::Seven => 2.0,
+        Card::One | Card::Thirteen | Card::Four | Card::Warrior | Card::Angel => 1.5,
+        _ => 1.0,
+    }
+}
+
+pub(crate) fn is_opening(card: Card) -> bool {
+    matches!(card, Card::One | Card::Thirteen)
+}
+
+/// Scores giving `card` to the partner of `player`, higher is better.
+/// Strong cards are kept, openings go to a partner that announced to have none.
+#[must_use]
+pub fn trade_score(board: &Board, knowledge: &Knowledge, player: Color, card: Card) -> f64 {
+    let partner = player.partner();
+    let hand = board.hand(player);
+    // Whatever we give away is lost for us
+    let mut score = -card_value(card);
+    if is_opening(card) {
+        // Without an opening our partner can't get balls out of the base
+        if !knowledge.has_opening(partner) && board.num_base(board.play_for(partner)) > 0 {
+            score += 4.0;
+        }
+        // Keep our only opening if we need it ourselves
+        let openings = hand.iter().filter(|c| is_opening(**c)).count();
+        if openings == 1 && board.balls_with(player).is_empty() && board.num_base(player) > 0 {
+            score -= 2.0;
+        }
+    }
+    // A second copy of a card is worth less to us
+    if hand.count(card) > 1 {
+        score += 0.5;
+    }
+    score
+}
+
+/// Knowledge of `player` that only contains the announcements of the current round,
+/// which is all that matters for trading.
+#[must_use]
+pub fn announced_knowledge(board: &Board, player: Color) -> Knowledge {
+    let mut knowledge = Knowledge::new(player);
+    knowledge.set_openings_from_board(board);
+    knowledge
+}
+
+impl Board {
+    /// Returns the cards `player` can trade together with their score, best first.
+    #[must_use]
+    pub fn best_trades(&self, player: Color, knowledge: &Knowledge) -> Vec<(Card, f64)> {
+        self.hand(player)
+            .iter()
+            .unique()
+            .map(|card| (*card, trade_score(self, knowledge, player, *card)))
+            .sorted_by(|(_, a), (_, b)| b.total_cmp(a))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_trades() {
+        let mut tested_opening = false;
+        for seed in 0..50 {
+            let board = Board::new_with_seed(seed);
+            let player = board.current_player();
+            let knowledge = announced_knowledge(&board, player);
+            let trades = board.best_trades(player, &knowledge);
+            assert_eq!(
+                trades.len(),
+                board.hand(player).iter().unique().count(),
+                "Every card is a candidate"
+            );
+            let (best, _) = trades[0];
+            if trades.iter().any(|(card, _)| *card != Card::Tac) {
+                assert_ne!(best, Card::Tac);
+            }
+            let openings = board
+                .hand(player)
+                .iter()
+                .filter(|c| is_opening(**c))
+                .count();
+            if openings > 1 && !board.openings()[player.partner() as usize] {
+                assert!(is_opening(best), "{seed} {trades:?}");
+                tested_opening = true;
+            }
+        }
+        assert!(tested_opening);
+    }
+}
@@ -0,0 +1,309 @@
+//! Automatic tuning of [`EvalParams`] with SPSA (simultaneous perturbation stochastic
+//! approximation), the method used by most chess engines to tune their evaluation.
+//!
+//! Every iteration perturbs all weights at once in a random direction, plays a match between
+//! the weights moved one way and the weights moved the other way and moves the weights towards
+//! the winner, by more the clearer the result. The steps shrink over time, so the weights settle
+//! even though every match result is noisy.
+
+use std::{io, path::Path};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{arena::MatchResult, eval::EvalParams};
+
+/// Number of weights changed by tuning, see [`EvalParams::tunable`].
+pub const NUM_TUNABLE: usize = 15;
+
+/// Names of the weights changed by tuning in the order of [`EvalParams::tunable`].
+/// The score of a win isn't tuned, it only sets the scale of the others.
+pub const TUNABLE: [&str; NUM_TUNABLE] = [
+    "in_home",
+    "home_free",
+    "home_clean",
+    "near_goal",
+    "seven_range",
+    "four_range",
+    "in_play",
+    "premium_card",
+    "opening_card",
+    "discard_liability",
+    "playing_for_partner",
+    "finish_soon",
+    "blocking",
+    "blocked",
+    "suspend_threat",
+];
+
+impl EvalParams {
+    /// The weights changed by tuning, named by [`TUNABLE`].
+    #[must_use]
+    pub fn tunable(&self) -> [i64; NUM_TUNABLE] {
+        let mut params = *self;
+        params.tunable_mut().map(|w| *w)
+    }
+
+    /// Replaces the weights returned by [`Self::tunable`], keeping the score of a win.
+    #[must_use]
+    pub fn with_tunable(mut self, values: [i64; NUM_TUNABLE]) -> Self {
+        for (weight, value) in self.tunable_mut().into_iter().zip(values) {
+            *weight = value;
+        }
+        self
+    }
+
+    fn tunable_mut(&mut self) -> [&mut i64; NUM_TUNABLE] {
+        [
+            &mut self.in_home,
+            &mut self.home_free,
+            &mut self.home_clean,
+            &mut self.near_goal,
+            &mut self.seven_range,
+            &mut self.four_range,
+            &mut self.in_play,
+            &mut self.premium_card,
+            &mut self.opening_card,
+            &mut self.discard_liability,
+            &mut self.playing_for_partner,
+            &mut self.finish_soon,
+            &mut self.blocking,
+            &mut self.blocked,
+            &mut self.suspend_threat,
+        ]
+    }
+}
+
+/// Settings of [`Spsa`]. The step sizes of iteration `k` are
+/// `perturbation / (k + 1)^gamma` and `learning_rate / (k + 1 + stability)^alpha`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpsaConfig {
+    /// How far weights are moved for a match, relative to their starting value
+    pub perturbation: f64,
+    /// Smallest absolute perturbation, so weights that start at zero are tuned too
+    pub min_perturbation: f64,
+    /// How far weights move towards the winner of a match, in perturbations per point of
+    /// the match score above one half
+    pub learning_rate: f64,
+    /// Iterations over which the learning rate decays slower at the start
+    pub stability: f64,
+    pub alpha: f64,
+    pub gamma: f64,
+    /// Seed of the random directions of the perturbations
+    pub seed: u64,
+}
+
+impl Default for SpsaConfig {
+    fn default() -> Self {
+        Self {
+            perturbation: 0.2,
+            min_perturbation: 1.0,
+            learning_rate: 2.0,
+            stability: 10.0,
+            alpha: 0.602,
+            gamma: 0.101,
+            seed: 0,
+        }
+    }
+}
+
+/// One iteration of [`Spsa::step`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpsaIteration {
+    pub iteration: u64,
+    /// Weights moved in the direction of the perturbation, they played first in the match
+    pub plus: EvalParams,
+    /// Weights moved against the direction of the perturbation
+    pub minus: EvalParams,
+    /// Result of the match from the view of [`Self::plus`]
+    pub result: MatchResult,
+    /// Weights after the update
+    pub params: EvalParams,
+}
+
+/// Tunes [`EvalParams`] with matches played by a caller supplied function.
+pub struct Spsa {
+    pub config: SpsaConfig,
+    /// Number of finished iterations, setting it resumes tuning with smaller steps
+    pub iteration: u64,
+    start: EvalParams,
+    /// Weights as real numbers, matches are played with the rounded values
+    theta: [f64; NUM_TUNABLE],
+    /// Perturbation of every weight in the first iteration
+    scale: [f64; NUM_TUNABLE],
+    rng: StdRng,
+}
+
+impl Spsa {
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(start: EvalParams, config: SpsaConfig) -> Self {
+        let theta = start.tunable().map(|w| w as f64);
+        let scale = theta.map(|w| (w.abs() * config.perturbation).max(config.min_perturbation));
+        Self {
+            config,
+            iteration: 0,
+            start,
+            theta,
+            scale,
+            rng: StdRng::seed_from_u64(config.seed),
+        }
+    }
+
+    /// The current estimate of the best weights, rounded and clamped to be non negative.
+    #[must_use]
+    pub fn params(&self) -> EvalParams {
+        Self::round(self.start, self.theta)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn round(start: EvalParams, theta: [f64; NUM_TUNABLE]) -> EvalParams {
+        start.with_tunable(theta.map(|w| w.round().max(0.0) as i64))
+    }
+
+    /// Plays one match with `play` and updates the weights with its result.
+    /// `play` gets the iteration, so it can deal different games every time, and both weights.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn step(
+        &mut self,
+        play: impl FnOnce(u64, &EvalParams, &EvalParams) -> MatchResult,
+    ) -> SpsaIteration {
+        let k = self.iteration as f64;
+        let perturbation = 1.0 / (k + 1.0).powf(self.config.gamma);
+        let learning_rate =
+            self.config.learning_rate / (k + 1.0 + self.config.stability).powf(self.config.alpha);
+        let direction: [f64; NUM_TUNABLE] =
+            core::array::from_fn(|_| if self.rng.gen() { 1.0 } else { -1.0 });
+        let shifted = |sign: f64| {
+            core::array::from_fn(|i| {
+                self.theta[i] + sign * direction[i] * self.scale[i] * perturbation
+            })
+        };
+        let plus = Self::round(self.start, shifted(1.0));
+        let minus = Self::round(self.start, shifted(-1.0));
+        let result = play(self.iteration, &plus, &minus);
+        let gradient = if result.games() > 0 {
+            result.score() - 0.5
+        } else {
+            0.0
+        };
+        for ((theta, direction), scale) in self.theta.iter_mut().zip(direction).zip(self.scale) {
+            // Weights are rounded to zero anyway, this keeps them from drifting far below
+            *theta = (*theta + learning_rate * gradient * direction * scale).max(-0.5);
+        }
+        self.iteration += 1;
+        SpsaIteration {
+            iteration: self.iteration,
+            plus,
+            minus,
+            result,
+            params: self.params(),
+        }
+    }
+
+    /// Runs `iterations` iterations of [`Self::step`], calling `on_iteration` after each.
+    /// With a `checkpoint` the current weights are written to it in ron format after every
+    /// iteration, so tuning can be interrupted and resumed from them.
+    /// # Errors
+    /// If the checkpoint can't be written.
+    pub fn run(
+        &mut self,
+        iterations: u64,
+        mut play: impl FnMut(u64, &EvalParams, &EvalParams) -> MatchResult,
+        checkpoint: Option<&Path>,
+        mut on_iteration: impl FnMut(&SpsaIteration),
+    ) -> io::Result<EvalParams> {
+        for _ in 0..iterations {
+            let iteration = self.step(&mut play);
+            if let Some(path) = checkpoint {
+                let params = ron::ser::to_string_pretty(
+                    &iteration.params,
+                    ron::ser::PrettyConfig::default(),
+                )
+                .expect("Weights can be serialized");
+                std::fs::write(path, params)?;
+            }
+            on_iteration(&iteration);
+        }
+        Ok(self.params())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tunable_weights() {
+        let params = EvalParams::default();
+        assert_eq!(params.with_tunable(params.tunable()), params);
+        let values = core::array::from_fn(|i| i64::try_from(i).unwrap());
+        let changed = params.with_tunable(values);
+        assert_eq!(changed.tunable(), values);
+        assert_eq!(changed.win, params.win);
+        // The names follow the serialized fields
+        let serialized = ron::to_string(&changed).unwrap();
+        for (name, value) in TUNABLE.iter().zip(values) {
+            assert!(serialized.contains(&format!("{name}:{value}")), "{name}");
+        }
+    }
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    fn converges_to_better_weights() {
+        // Matches are won by the weights closer to the target, with some noise
+        let target =
+            EvalParams::default().with_tunable([150, 2, 2, 15, 10, 3, 3, 6, 8, 4, 30, 20, 4, 5, 3]);
+        let distance = |params: &EvalParams| {
+            params
+                .tunable()
+                .iter()
+                .zip(target.tunable())
+                .map(|(w, t)| (w - t).abs() as f64 / t as f64)
+                .sum::<f64>()
+        };
+        let mut noise = StdRng::seed_from_u64(1);
+        let play = |_, plus: &EvalParams, minus: &EvalParams| {
+            let mut result = MatchResult::default();
+            let p = if distance(plus) < distance(minus) {
+                0.7
+            } else {
+                0.3
+            };
+            for _ in 0..20 {
+                if noise.gen_bool(p) {
+                    result.wins += 1;
+                } else {
+                    result.losses += 1;
+                }
+            }
+            result
+        };
+        let start = EvalParams::default();
+        let mut spsa = Spsa::new(start, SpsaConfig::default());
+        let mut iterations = 0;
+        let tuned = spsa.run(300, play, None, |_| iterations += 1).unwrap();
+        assert_eq!(iterations, 300);
+        assert_eq!(spsa.iteration, 300);
+        assert!(distance(&tuned) < distance(&start), "{tuned:?}");
+        assert!(tuned.in_home > start.in_home);
+        assert!(tuned.near_goal > start.near_goal);
+    }
+
+    #[test]
+    fn checkpoints() {
+        let path = std::env::temp_dir().join(format!("robotac-tune-{}.ron", std::process::id()));
+        let mut spsa = Spsa::new(EvalParams::default(), SpsaConfig::default());
+        let draw = |_, _: &EvalParams, _: &EvalParams| MatchResult {
+            draws: 2,
+            ..MatchResult::default()
+        };
+        let tuned = spsa.run(3, draw, Some(&path), |_| {}).unwrap();
+        // Drawn matches don't change the weights
+        assert_eq!(tuned, EvalParams::default());
+        let saved: EvalParams = ron::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved, tuned);
+    }
+}
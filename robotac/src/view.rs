@@ -0,0 +1,176 @@
+//! Summaries of positions as JSON for tools that don't link against this crate, like web
+//! interfaces or bots written in other languages.
+//!
+//! A view only contains what an observer can see, hands of the others are left out:
+//!
+//! ```text
+//! {"move_count":3,"current_player":"Green","hand_to_play":"Green","result":null,
+//!  "balls":[{"square":0,"color":"Black"}],"players":[{"color":"Black","base":3,...}],...}
+//! ```
+
+use serde::{Deserialize, Serialize};
+use tac_types::{Card, Color, HomeSquare, Square, TacMove, ALL_COLORS};
+
+use crate::board::{Board, GameResult};
+
+/// A ball on the ring.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BallView {
+    pub square: Square,
+    pub color: Color,
+}
+
+/// What is known about one player.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerView {
+    pub color: Color,
+    /// Balls that haven't entered the ring yet
+    pub base: u8,
+    /// Occupied squares of the home, from the entrance to the end
+    pub home: [bool; 4],
+    /// The ball on the start square hasn't moved since it entered
+    pub fresh: bool,
+    pub hand_size: usize,
+    /// Only shown to the player and to observers that see everything
+    pub hand: Option<Vec<Card>>,
+}
+
+/// Public flags of the position.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlagsView {
+    pub need_trade: bool,
+    pub need_announce: bool,
+    pub force_discard: bool,
+    pub jester: bool,
+    pub devil: bool,
+}
+
+/// Summary of a position from the view of an observer, see [`Board::view`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoardView {
+    pub move_count: u32,
+    pub current_player: Color,
+    /// Differs from the current player after a devil, when the move is chosen from the next
+    /// player's hand
+    pub hand_to_play: Color,
+    pub result: Option<GameResult>,
+    pub balls: Vec<BallView>,
+    pub players: Vec<PlayerView>,
+    /// Cards left in the deck for the next deals
+    pub deck_size: usize,
+    /// Cards played or discarded in the current deal, in order
+    pub discarded: Vec<Card>,
+    pub flags: FlagsView,
+}
+
+/// A legal move in move notation and in its structured form.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MoveView {
+    pub notation: String,
+    #[serde(flatten)]
+    pub mv: TacMove,
+}
+
+impl Board {
+    /// Summarizes the position as seen by `observer`, `None` sees every hand.
+    #[must_use]
+    pub fn view(&self, observer: Option<Color>) -> BoardView {
+        let balls = self
+            .all_balls()
+            .iter()
+            .map(|square| BallView {
+                square,
+                color: self
+                    .color_on(square)
+                    .expect("Squares of balls are occupied"),
+            })
+            .collect();
+        let players = ALL_COLORS
+            .into_iter()
+            .map(|color| PlayerView {
+                color,
+                base: self.num_base(color),
+                home: HomeSquare::ALL.map(|pos| !self.home(color).is_free(pos)),
+                fresh: self.fresh(color),
+                hand_size: self.hand(color).iter().count(),
+                hand: observer
+                    .is_none_or(|observer| observer == color)
+                    .then(|| self.hand(color).iter().copied().collect()),
+            })
+            .collect();
+        BoardView {
+            move_count: self.move_count,
+            current_player: self.current_player(),
+            hand_to_play: self.hand_to_play(),
+            result: self.game_result(),
+            balls,
+            players,
+            deck_size: self.deck().size(),
+            discarded: self.discarded().to_vec(),
+            flags: FlagsView {
+                need_trade: self.need_trade(),
+                need_announce: self.need_announce(),
+                force_discard: self.force_discard(),
+                jester: self.jester_flag(),
+                devil: self.devil_flag(),
+            },
+        }
+    }
+
+    /// [`Board::view`] as JSON.
+    /// # Panics
+    /// If the view can't be serialized, which doesn't happen.
+    #[must_use]
+    pub fn to_json_view(&self, observer: Option<Color>) -> String {
+        serde_json::to_string(&self.view(observer)).expect("Views can be serialized")
+    }
+
+    /// The legal moves of the player to move as a JSON list of [`MoveView`].
+    /// # Panics
+    /// If the moves can't be serialized, which doesn't happen.
+    #[must_use]
+    pub fn legal_moves_json(&self) -> String {
+        let moves = self
+            .get_moves(self.current_player())
+            .into_iter()
+            .map(|mv| MoveView {
+                notation: mv.to_string(),
+                mv,
+            })
+            .collect::<Vec<_>>();
+        serde_json::to_string(&moves).expect("Moves can be serialized")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hidden_hands() {
+        let board = Board::new_random_state(3, crate::rules::Ruleset::default(), 10..=20);
+        let view: BoardView = serde_json::from_str(&board.to_json_view(Some(Color::Blue))).unwrap();
+        assert_eq!(view, board.view(Some(Color::Blue)));
+        for player in &view.players {
+            assert_eq!(player.hand.is_some(), player.color == Color::Blue);
+            assert_eq!(player.hand_size, board.hand(player.color).iter().count());
+        }
+        let balls = ALL_COLORS
+            .iter()
+            .map(|c| board.balls_with(*c).len())
+            .sum::<usize>();
+        assert_eq!(view.balls.len(), balls);
+        let everything = board.view(None);
+        assert!(everything.players.iter().all(|p| p.hand.is_some()));
+
+        let moves: Vec<MoveView> = serde_json::from_str(&board.legal_moves_json()).unwrap();
+        let legal = board.get_moves(board.current_player());
+        assert_eq!(
+            moves.iter().map(|m| &m.mv).collect::<Vec<_>>(),
+            legal.iter().collect::<Vec<_>>()
+        );
+        for mv in moves {
+            assert_eq!(mv.notation.parse::<TacMove>(), Ok(mv.mv));
+        }
+    }
+}
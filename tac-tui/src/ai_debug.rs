@@ -3,7 +3,7 @@ use ratatui::{
     crossterm::event::Event,
     widgets::{Block, Paragraph, Widget},
 };
-use robotac::TacAI;
+use robotac::{board::Board, explain, TacAI, TacEval};
 
 use crate::app::Message;
 
@@ -14,11 +14,28 @@ impl AiDebugView {
         None
     }
 
-    pub fn draw(&self, ai: &Manager<TacAI>) -> impl Widget + '_ {
+    pub fn draw(&self, ai: &Manager<TacAI>, board: &Board) -> impl Widget + '_ {
         let mut string = String::new();
-        for s in ai.stats() {
-            string.push_str(&format!("{:?}\n", s));
+        for (mv, stats) in ai.root_move_stats() {
+            string.push_str(&format!(
+                "{:>6} {:>7.2} {:>6} {}\n",
+                stats.visits,
+                stats.mean_action_value / TacEval::SCALE as f64,
+                stats.availability_count,
+                mv
+            ));
         }
-        Paragraph::new(string).block(Block::bordered().title("Debug state"))
+        let candidates = explain::candidates(board, ai, 3);
+        if !candidates.is_empty() {
+            string.push_str("\nBest moves\n");
+        }
+        for candidate in candidates {
+            string.push_str(&format!(
+                "{:>6} {:>7.2} {}: {}\n",
+                candidate.visits, candidate.mean_value, candidate.mv, candidate.description
+            ));
+        }
+        let title = format!("AI state - {}", ai.tree().memory_stats());
+        Paragraph::new(string).block(Block::bordered().title(title))
     }
 }
@@ -1,41 +1,167 @@
 use std::{
     fs::File,
     io::{self, Write},
-    time::Duration,
+    path::Path,
+    time::{Duration, Instant},
 };
 
-use mcts::{manager::Manager, policies::UCTPolicy};
+use mcts::manager::Manager;
+use rand::{rngs::StdRng, SeedableRng};
 use ratatui::{
-    crossterm::event::{self, Event, KeyCode},
-    layout::{Constraint, Layout, Rect},
+    crossterm::{
+        event::{self, Event, KeyCode, MouseButton, MouseEvent, MouseEventKind},
+        terminal,
+    },
+    layout::{Constraint, Layout, Position, Rect},
+    widgets::{Block, Paragraph},
     DefaultTerminal, Frame,
 };
-use robotac::{board::Board, history::History, TacAI, TacEval};
-use tac_types::TacMove;
+use robotac::{
+    arena::{AgentConfig, Difficulty},
+    board::{Board, UndoStack},
+    history::{unix_time, Annotation, History, PlayerInfo, Replay, SearchStats},
+    knowledge::Knowledge,
+    TacAI,
+};
+use tac_types::{Color, PackedTacMove, TacMove, ALL_COLORS, NUM_COLORS};
 
 use crate::{
     ai_debug::AiDebugView,
-    board::BoardView,
+    board::{self, BoardView},
+    branch::Branch,
     debug::DebugView,
     history::{LoadHistory, SaveHistory},
+    hotseat::{draw_hands, draw_pass_device, Visibility},
+    jump_input::JumpInput,
+    knowledge::{knowledge_after, KnowledgeView},
+    move_entry::MoveEntry,
     moves::MoveList,
     seed_input::SeedInput,
+    session::Session,
 };
 
+/// Number of threads searching in the background once analysis starts
+const ANALYSIS_THREADS: usize = 2;
+/// Time between updates of the search statistics shown in the move list
+const ANALYSIS_REFRESH: Duration = Duration::from_millis(250);
+
+/// Which panel is shown below the move list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DebugPanel {
+    Board,
+    Ai,
+    Knowledge,
+}
+
+impl DebugPanel {
+    fn toggle(self, panel: DebugPanel) -> Self {
+        if self == panel {
+            DebugPanel::Board
+        } else {
+            panel
+        }
+    }
+}
+
+/// Searching the current position in the background, the statistics are shown in the move list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Analysis {
+    threads: usize,
+    paused: bool,
+}
+
+impl Default for Analysis {
+    fn default() -> Self {
+        Self {
+            threads: ANALYSIS_THREADS,
+            paused: false,
+        }
+    }
+}
+
+impl Analysis {
+    fn max_threads() -> usize {
+        std::thread::available_parallelism().map_or(1, std::num::NonZero::get)
+    }
+
+    fn more_threads(&mut self) {
+        self.threads = (self.threads + 1).min(Self::max_threads());
+    }
+
+    fn fewer_threads(&mut self) {
+        self.threads = self.threads.saturating_sub(1).max(1);
+    }
+}
+
+/// Where the panels are drawn in the terminal.
+struct Areas {
+    board: Rect,
+    hands: Rect,
+    seats: Rect,
+    score: Rect,
+    moves: Rect,
+    debug: Rect,
+}
+
+impl Areas {
+    fn new(area: Rect) -> Self {
+        let horizontal =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]);
+        let vertical = Layout::vertical([Constraint::Percentage(40), Constraint::Percentage(60)]);
+        let [left, right] = horizontal.areas(area);
+        let [board, hands, seats, score] = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(6),
+            Constraint::Length(4),
+            Constraint::Length(5),
+        ])
+        .areas(left);
+        let [moves, debug] = vertical.areas(right);
+        Self {
+            board,
+            hands,
+            seats,
+            score,
+            moves,
+            debug,
+        }
+    }
+}
+
+/// Who picks the moves of a player.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Seat {
+    Human,
+    Ai,
+}
+
+impl Seat {
+    fn toggle(self) -> Self {
+        match self {
+            Seat::Human => Seat::Ai,
+            Seat::Ai => Seat::Human,
+        }
+    }
+}
+
 enum Mode {
     Moves,
+    BoardEntry,
     SeedEdit,
     SaveHistory,
     LoadHistory,
+    JumpTo,
 }
 
 impl Mode {
     fn need_input(&self) -> bool {
         match self {
             Mode::Moves => false,
+            Mode::BoardEntry => false,
             Mode::SeedEdit => true,
             Mode::SaveHistory => true,
             Mode::LoadHistory => false,
+            Mode::JumpTo => true,
         }
     }
 }
@@ -43,24 +169,57 @@ impl Mode {
 pub enum Message {
     Quit,
     MakeMove(TacMove),
+    Undo,
     Reset(Option<u64>),
+    NextDeal,
     SaveHistory(String),
     LoadHistory(String),
+    JumpTo(usize),
+    StepReplay(bool),
+    Branch,
+    MainLine,
 }
 
 pub struct App {
     board: Board,
+    /// Earlier boards of the main line or the branch, to take moves back
+    undo: UndoStack,
     history: History,
     mode: Mode,
     ai: Manager<TacAI>,
     board_view: BoardView,
     move_list: MoveList,
+    move_entry: MoveEntry,
     debug: DebugView,
     ai_debug: AiDebugView,
+    knowledge_view: KnowledgeView,
+    debug_panel: DebugPanel,
+    /// What every player knows about the hands of the others
+    knowledge: [Knowledge; NUM_COLORS],
     seed_input: SeedInput,
     save_history: SaveHistory,
     load_history: LoadHistory,
-    previous_seed: u64,
+    jump_input: JumpInput,
+    /// Loaded history that is being stepped through
+    replay: Option<Replay>,
+    /// Exploratory moves that aren't added to the history
+    branch: Option<Branch>,
+    /// Deals played so far, a new seed starts a new session
+    session: Session,
+    analysis: Option<Analysis>,
+    /// When the move list last got the statistics of the analysis
+    last_refresh: Instant,
+    seats: [Seat; NUM_COLORS],
+    last_ai_move: Option<TacMove>,
+    /// How strong the AI seats play
+    difficulty: Difficulty,
+    /// Random choices of the AI seats around the search, seeded with the deal so they can be
+    /// replayed
+    rng: StdRng,
+    /// Humans share the terminal and only see their own hand
+    hot_seat: bool,
+    /// The human who confirmed to be in front of the terminal in hot seat mode
+    device_holder: Option<Color>,
 }
 
 impl Default for App {
@@ -71,36 +230,92 @@ impl Default for App {
 
 impl App {
     pub fn new() -> Self {
-        let previous_seed = 0;
-        let board = Board::new_with_seed(previous_seed);
-        let ai = Manager::new(board.clone(), TacAI, UCTPolicy(0.7), TacEval);
+        let session = Session::new(0);
+        let board = Board::new_with_seed(session.seed());
+        let difficulty = Difficulty::default();
+        let ai = Self::new_ai(difficulty, &board);
+        let rng = StdRng::seed_from_u64(session.seed());
         let move_list = MoveList::new(&board);
+        let move_entry = MoveEntry::new(&board);
         Self {
+            knowledge: core::array::from_fn(|i| Knowledge::new_from_board(Color::from(i), &board)),
             board,
+            undo: UndoStack::default(),
             history: History::new(0),
             mode: Mode::Moves,
             ai,
             board_view: BoardView::default(),
             move_list,
+            move_entry,
             debug: DebugView,
             ai_debug: AiDebugView,
+            knowledge_view: KnowledgeView::default(),
+            debug_panel: DebugPanel::Board,
             seed_input: SeedInput::default(),
             save_history: SaveHistory::default(),
             load_history: LoadHistory::default(),
-            previous_seed,
+            jump_input: JumpInput::default(),
+            replay: None,
+            branch: None,
+            session,
+            analysis: None,
+            last_refresh: Instant::now(),
+            seats: [Seat::Human; NUM_COLORS],
+            last_ai_move: None,
+            difficulty,
+            rng,
+            hot_seat: false,
+            device_holder: None,
         }
     }
 
+    fn agent(difficulty: Difficulty) -> AgentConfig {
+        AgentConfig {
+            name: "AI".to_string(),
+            ..difficulty.agent()
+        }
+    }
+
+    fn new_ai(difficulty: Difficulty, board: &Board) -> Manager<TacAI> {
+        Self::agent(difficulty).manager(board)
+    }
+
+    /// Starts a new search for the current board with what the players know at this point.
+    fn restart_ai(&mut self) {
+        self.ai = Self::new_ai(self.difficulty, &self.board);
+        self.ai.set_knowledge(self.knowledge);
+    }
+
     pub fn new_board(&mut self, seed: u64) {
         self.board = Board::new_with_seed(seed);
+        self.undo.clear();
         self.history = History::new(seed);
+        self.replay = None;
+        self.branch = None;
+        self.knowledge = knowledge_after(&self.history, 0);
+        self.ai = Self::new_ai(self.difficulty, &self.board);
+        self.rng = StdRng::seed_from_u64(seed);
         self.on_state_change();
     }
 
     pub fn load_history(&mut self, history: &History) {
-        self.board = history.board_with_history();
+        let mut replay = Replay::new(history.clone());
+        replay.goto(replay.len());
         self.history = history.clone();
-        self.on_state_change();
+        self.replay = Some(replay);
+        self.show_replay_position();
+    }
+
+    /// Shows the board of the current replay position.
+    fn show_replay_position(&mut self) {
+        if let Some(replay) = &self.replay {
+            self.branch = None;
+            self.board = replay.board().clone();
+            self.undo.clear();
+            self.knowledge = knowledge_after(&self.history, replay.position());
+            self.restart_ai();
+            self.on_state_change();
+        }
     }
 
     pub fn run(mut self, mut terminal: DefaultTerminal) -> io::Result<()> {
@@ -109,47 +324,297 @@ impl App {
             if let Some(message) = self.update() {
                 match message {
                     Message::Quit => break,
-                    Message::MakeMove(mv) => {
-                        self.board.play(&mv);
-                        self.history.moves.push(mv);
-                        self.on_state_change();
+                    Message::MakeMove(mv) => self.make_move(mv),
+                    Message::Undo if self.branch.is_some() => self.undo_branch_move(),
+                    Message::Undo if self.replay.is_some() => {
+                        if self.replay.as_mut().is_some_and(Replay::step_backward) {
+                            self.show_replay_position();
+                        }
+                    }
+                    Message::Undo => {
+                        if self.undo.undo(&mut self.board) {
+                            self.history.pop();
+                            self.knowledge =
+                                knowledge_after(&self.history, self.history.moves.len());
+                            self.restart_ai();
+                            self.on_state_change();
+                        }
                     }
                     Message::Reset(seed) => {
-                        let seed = seed.unwrap_or(self.previous_seed);
-                        self.new_board(seed);
+                        if let Some(seed) = seed {
+                            self.session = Session::new(seed);
+                        }
+                        self.new_board(self.session.seed());
                         self.mode = Mode::Moves;
-                        self.previous_seed = seed;
+                    }
+                    Message::Branch => {
+                        if self.branch.is_none() {
+                            let start = self
+                                .replay
+                                .as_ref()
+                                .map_or(self.history.moves.len(), Replay::position);
+                            self.branch = Some(Branch::new(&self.board, self.knowledge, start));
+                        }
+                    }
+                    Message::MainLine => self.leave_branch(),
+                    Message::NextDeal => {
+                        let seed = self.session.next_deal();
+                        self.new_board(seed);
                     }
                     Message::SaveHistory(s) => {
+                        self.history.players = ALL_COLORS.map(|c| self.player_info(c));
+                        // The analysis and the knowledge belong to the last position, which isn't
+                        // shown while replaying
+                        let at_end = self.replay.is_none() && self.branch.is_none();
+                        if at_end {
+                            self.history.knowledge = Some(self.knowledge);
+                        }
                         let _ = Self::write_history_to_file(&self.history, &s);
+                        let tree = format!("histories/{}.tree", s);
+                        if at_end {
+                            let _ = self.ai.save(tree);
+                        } else {
+                            let _ = std::fs::remove_file(tree);
+                        }
                         self.mode = Mode::Moves
                     }
+                    Message::JumpTo(position) => {
+                        self.mode = Mode::Moves;
+                        if let Some(replay) = &mut self.replay {
+                            replay.goto(position);
+                            self.show_replay_position();
+                        }
+                    }
+                    Message::StepReplay(forward) => {
+                        if let Some(replay) = &mut self.replay {
+                            let moved = if forward {
+                                replay.step_forward()
+                            } else {
+                                replay.step_backward()
+                            };
+                            if moved {
+                                self.show_replay_position();
+                            }
+                        }
+                    }
                     Message::LoadHistory(s) => {
                         self.mode = Mode::Moves;
                         if let Ok(content) = std::fs::read_to_string(format!("histories/{}", s)) {
-                            if let Ok(history) = ron::de::from_str::<History>(&content) {
+                            if let Ok(history) = History::from_ron(&content) {
                                 self.load_history(&history);
+                                let tree = Path::new("histories").join(&s).with_extension("tree");
+                                let _ = self.ai.load(tree);
+                                self.update_analysis();
                             }
                         }
                     }
                 }
+            } else if let Some((mv, search)) = self.ai_move() {
+                self.last_ai_move = Some(mv.clone());
+                self.make_move_annotated(
+                    mv,
+                    Annotation {
+                        search,
+                        ..Annotation::default()
+                    },
+                );
+            } else if self.last_refresh.elapsed() >= ANALYSIS_REFRESH {
+                self.refresh_analysis();
             }
         }
         Ok(())
     }
 
+    fn make_move(&mut self, mv: TacMove) {
+        self.make_move_annotated(mv, Annotation::default());
+    }
+
+    fn make_move_annotated(&mut self, mv: TacMove, annotation: Annotation) {
+        // Playing a move while replaying branches off and drops the remaining moves
+        if self.branch.is_none() {
+            if let Some(replay) = self.replay.take() {
+                self.history.truncate(replay.position());
+            }
+        }
+        for k in &mut self.knowledge {
+            k.update_with_move(&mv, &self.board);
+        }
+        self.undo.push(&self.board);
+        self.board.play(&mv);
+        self.ai.advance(&PackedTacMove::pack(&mv));
+        if let Some(branch) = &mut self.branch {
+            branch.push(mv);
+        } else {
+            self.history.push(
+                mv,
+                Annotation {
+                    time: unix_time(),
+                    ..annotation
+                },
+            );
+        }
+        self.on_state_change();
+    }
+
+    /// Takes back the last move of the branch, the main line can't be undone from a branch.
+    fn undo_branch_move(&mut self) {
+        let Some(branch) = &mut self.branch else {
+            return;
+        };
+        if branch.pop().is_some() && self.undo.undo(&mut self.board) {
+            self.knowledge = branch.knowledge();
+            self.restart_ai();
+            self.on_state_change();
+        }
+    }
+
+    /// Drops the moves of the branch and returns to where it left the main line.
+    fn leave_branch(&mut self) {
+        if let Some(branch) = self.branch.take() {
+            self.board = branch.main_line().clone();
+            self.undo.truncate(self.undo.len() - branch.moves_played());
+            self.knowledge = branch.main_line_knowledge();
+            self.restart_ai();
+            self.on_state_change();
+        }
+    }
+
+    /// Searches a move if the player to move is controlled by the AI.
+    fn ai_move(&mut self) -> Option<(TacMove, Option<SearchStats>)> {
+        let player = self.board.current_player();
+        if self.seats[player as usize] != Seat::Ai
+            || !matches!(self.mode, Mode::Moves | Mode::BoardEntry)
+            || self.replay.is_some()
+            || self.branch.is_some()
+            || self.board.is_terminal()
+        {
+            return None;
+        }
+        if let Some(mv) = self.ai.forced_move() {
+            return Some((mv.unpack(), None));
+        }
+        let agent = Self::agent(self.difficulty);
+        self.ai.playout_n_parallel(agent.playouts, agent.threads);
+        let best = self.ai.best_move()?;
+        let best = agent.checked_move(
+            &self.ai,
+            &self.board,
+            &self.knowledge[player as usize],
+            best.unpack(),
+            &mut self.rng,
+        );
+        let mv = agent.blunder(&self.board, best, &mut self.rng);
+        let stats = SearchStats::of(&self.ai, PackedTacMove::pack(&mv));
+        Some((mv, stats))
+    }
+
+    /// Who controls `color`, the AI is described by the agent it searches with.
+    fn player_info(&self, color: Color) -> PlayerInfo {
+        match self.seats[color as usize] {
+            Seat::Human => PlayerInfo {
+                name: "Human".to_string(),
+                agent: None,
+            },
+            Seat::Ai => PlayerInfo {
+                name: "AI".to_string(),
+                agent: Some(Self::agent(self.difficulty)),
+            },
+        }
+    }
+
     fn write_history_to_file(history: &History, name: &str) -> std::io::Result<()> {
         let mut file = File::create(format!("histories/{}.hist", name))?;
-        let ron = ron::ser::to_string_pretty(history, ron::ser::PrettyConfig::default()).unwrap();
-        let _ = file.write_all(&ron.into_bytes());
-        Ok(())
+        file.write_all(history.to_ron().as_bytes())
+    }
+
+    /// Which hidden information may be shown to whoever is in front of the terminal.
+    fn visibility(&self) -> Visibility {
+        if self.hot_seat {
+            Visibility::Player(self.device_holder.unwrap_or(self.board.current_player()))
+        } else {
+            Visibility::Everything
+        }
+    }
+
+    /// Whether the moves of the player to move may be shown, they and the search give away
+    /// their hand.
+    fn shows_moves(&self) -> bool {
+        self.visibility().shows_hand(self.board.current_player())
+    }
+
+    /// Handles clicks on the board and the move list. Clicking a square enters a move on the
+    /// board, clicking a move selects it and clicking it again plays it.
+    fn click(&mut self, mouse: MouseEvent) -> Option<Message> {
+        if self.mode.need_input() || self.awaiting_pass() {
+            return None;
+        }
+        let (width, height) = terminal::size().ok()?;
+        let areas = Areas::new(Rect::new(0, 0, width, height));
+        let position = Position::new(mouse.column, mouse.row);
+        if areas.board.contains(position) {
+            if let MouseEventKind::Down(MouseButton::Left) = mouse.kind {
+                let target = board::target_at(areas.board, position)?;
+                self.mode = Mode::BoardEntry;
+                self.move_entry.click(target);
+            }
+            return None;
+        }
+        if !areas.moves.contains(position) || !self.shows_moves() {
+            return None;
+        }
+        // The first line is the border with the title
+        let line = usize::from((mouse.row - areas.moves.y).checked_sub(1)?);
+        let key = match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                return match self.mode {
+                    Mode::BoardEntry => self.move_entry.click_line(line),
+                    _ => {
+                        self.mode = Mode::Moves;
+                        self.move_list.click_line(line)
+                    }
+                };
+            }
+            MouseEventKind::ScrollDown => KeyCode::Char('j'),
+            MouseEventKind::ScrollUp => KeyCode::Char('k'),
+            _ => return None,
+        };
+        let event = Event::Key(key.into());
+        match self.mode {
+            Mode::BoardEntry => self.move_entry.update(&event),
+            _ => self.move_list.update(&event),
+        }
+    }
+
+    /// Whether the human to move has to take over the terminal before anything is shown.
+    fn awaiting_pass(&self) -> bool {
+        let player = self.board.current_player();
+        self.hot_seat
+            && self.replay.is_none()
+            && !self.board.is_terminal()
+            && self.seats[player as usize] == Seat::Human
+            && self.device_holder != Some(player)
     }
 
     pub fn update(&mut self) -> Option<Message> {
         if event::poll(Duration::from_millis(10)).ok()? {
             let event = event::read().ok()?;
+            if let Event::Mouse(mouse) = event {
+                return self.click(mouse);
+            }
             let mut pass_down = false;
             if let Event::Key(key_ev) = event {
+                if self.awaiting_pass() {
+                    match key_ev.code {
+                        KeyCode::Enter => self.device_holder = Some(self.board.current_player()),
+                        KeyCode::Char('q') => return Some(Message::Quit),
+                        _ => {}
+                    }
+                    return None;
+                }
+                // Keys typed into the filter of the move list aren't shortcuts
+                if matches!(self.mode, Mode::Moves) && self.move_list.editing_filter() {
+                    return self.move_list.update(&event);
+                }
                 if matches!(key_ev.code, KeyCode::Esc) {
                     self.mode = Mode::Moves;
                     return None;
@@ -159,11 +624,66 @@ impl App {
                     match key_ev.code {
                         KeyCode::Char('q') => return Some(Message::Quit),
                         KeyCode::Char('m') => self.mode = Mode::Moves,
+                        KeyCode::Char('b') => self.mode = Mode::BoardEntry,
                         KeyCode::Char('n') => self.mode = Mode::SeedEdit,
                         KeyCode::Char('r') => return Some(Message::Reset(None)),
+                        KeyCode::Char('d') => return Some(Message::NextDeal),
+                        KeyCode::Char('D') => {
+                            self.difficulty = self.difficulty.next();
+                            self.restart_ai();
+                            self.on_state_change();
+                        }
+                        KeyCode::Char('h') => {
+                            self.hot_seat = !self.hot_seat;
+                            self.device_holder = None;
+                        }
+                        KeyCode::Char('u') => return Some(Message::Undo),
+                        KeyCode::Char('w') => return Some(Message::Branch),
+                        KeyCode::Char('W') => return Some(Message::MainLine),
                         KeyCode::Char('s') => self.mode = Mode::SaveHistory,
                         KeyCode::Char('l') => self.mode = Mode::LoadHistory,
-                        KeyCode::Char('p') => self.ai.playout_n(1000),
+                        KeyCode::Char('g') if self.replay.is_some() => self.mode = Mode::JumpTo,
+                        KeyCode::Char('[') => return Some(Message::StepReplay(false)),
+                        KeyCode::Char(']') => return Some(Message::StepReplay(true)),
+                        KeyCode::Char('p') => {
+                            match &mut self.analysis {
+                                Some(analysis) => analysis.paused = !analysis.paused,
+                                None => self.analysis = Some(Analysis::default()),
+                            }
+                            self.update_analysis();
+                        }
+                        KeyCode::Char('+') => {
+                            if let Some(analysis) = &mut self.analysis {
+                                analysis.more_threads();
+                            }
+                            self.update_analysis();
+                        }
+                        KeyCode::Char('-') => {
+                            if let Some(analysis) = &mut self.analysis {
+                                analysis.fewer_threads();
+                            }
+                            self.update_analysis();
+                        }
+                        KeyCode::Char('a') => {
+                            self.debug_panel = self.debug_panel.toggle(DebugPanel::Ai)
+                        }
+                        KeyCode::Char('K') => {
+                            self.debug_panel = self.debug_panel.toggle(DebugPanel::Knowledge)
+                        }
+                        KeyCode::Char('o') if self.debug_panel == DebugPanel::Knowledge => {
+                            return self.knowledge_view.update(&event);
+                        }
+                        KeyCode::Char(c @ '1'..='4') => {
+                            let seat = &mut self.seats[c as usize - '1' as usize];
+                            *seat = seat.toggle();
+                        }
+                        KeyCode::Char('P') => {
+                            self.analysis = match self.analysis {
+                                Some(_) => None,
+                                None => Some(Analysis::default()),
+                            };
+                            self.update_analysis();
+                        }
                         _ => {
                             pass_down = true;
                         }
@@ -175,9 +695,11 @@ impl App {
             if pass_down {
                 return match self.mode {
                     Mode::Moves => self.move_list.update(&event),
+                    Mode::BoardEntry => self.move_entry.update(&event),
                     Mode::SeedEdit => self.seed_input.update(&event),
                     Mode::SaveHistory => self.save_history.update(&event),
                     Mode::LoadHistory => self.load_history.update(&event),
+                    Mode::JumpTo => self.jump_input.update(&event),
                 };
             }
         }
@@ -185,20 +707,86 @@ impl App {
     }
 
     fn on_state_change(&mut self) {
+        // Loaded games and explored branches aren't part of the session
+        if self.replay.is_none()
+            && self.branch.is_none()
+            && self.history.seed == self.session.seed()
+        {
+            self.session.on_state_change(&self.board);
+        }
+        self.update_analysis();
         self.board_view.on_state_change(&self.board);
         self.move_list.on_state_change(&self.board);
+        self.move_entry.on_state_change(&self.board);
+        self.refresh_analysis();
+    }
+
+    /// Starts or stops the background search to match the analysis settings.
+    fn update_analysis(&mut self) {
+        match self.analysis {
+            Some(analysis) if !analysis.paused => self.ai.start_pondering(analysis.threads),
+            _ => self.ai.stop_pondering(),
+        }
+        self.refresh_analysis();
+    }
+
+    /// Shows the current statistics of the search in the move list.
+    fn refresh_analysis(&mut self) {
+        let status = self.analysis.map(|analysis| {
+            let playouts = self.ai.stats().iter().map(|s| s.visits).sum::<u64>();
+            if analysis.paused {
+                format!("analysis paused, {playouts} playouts")
+            } else {
+                format!(
+                    "analysing with {} threads, {playouts} playouts",
+                    analysis.threads
+                )
+            }
+        });
+        self.move_list.on_search_update(&self.ai, status);
+        self.last_refresh = Instant::now();
     }
 
     fn draw(&self, frame: &mut Frame) {
-        let horizontal =
-            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]);
-        let vertical = Layout::vertical([Constraint::Percentage(40), Constraint::Percentage(60)]);
-        let [board, right] = horizontal.areas(frame.area());
-        let [moves, debug] = vertical.areas(right);
-        frame.render_widget(self.board_view.draw(), board);
-        frame.render_widget(self.move_list.draw(), moves);
-        frame.render_widget(self.debug.draw(&self.board), debug);
-        // frame.render_widget(self.ai_debug.draw(&self.ai), debug);
+        if self.awaiting_pass() {
+            frame.render_widget(draw_pass_device(self.board.current_player()), frame.area());
+            return;
+        }
+        let visibility = self.visibility();
+        let shows_moves = self.shows_moves();
+        let Areas {
+            board,
+            hands,
+            seats,
+            score,
+            moves,
+            debug,
+        } = Areas::new(frame.area());
+        let entry = matches!(self.mode, Mode::BoardEntry).then_some(&self.move_entry);
+        frame.render_widget(self.board_view.draw(entry), board);
+        frame.render_widget(draw_hands(&self.board, visibility), hands);
+        frame.render_widget(self.draw_seats(), seats);
+        frame.render_widget(self.session.draw(), score);
+        if !shows_moves {
+            let title = format!("Moves - {:?} to move", self.board.current_player());
+            frame.render_widget(Block::bordered().title(title), moves);
+        } else if entry.is_some() {
+            frame.render_widget(self.move_entry.draw(), moves);
+        } else {
+            frame.render_widget(self.move_list.draw(), moves);
+        }
+        match self.debug_panel {
+            DebugPanel::Board => {
+                frame.render_widget(self.debug.draw(&self.board, visibility), debug);
+            }
+            DebugPanel::Ai if !shows_moves => {
+                frame.render_widget(Block::bordered().title("AI state - hidden"), debug);
+            }
+            DebugPanel::Ai => frame.render_widget(self.ai_debug.draw(&self.ai, &self.board), debug),
+            DebugPanel::Knowledge => {
+                frame.render_widget(self.knowledge_view.draw(&self.knowledge, visibility), debug);
+            }
+        }
         match self.mode {
             Mode::SeedEdit => {
                 let area = Rect {
@@ -209,6 +797,15 @@ impl App {
                 };
                 frame.render_widget(self.seed_input.draw(), area);
             }
+            Mode::JumpTo => {
+                let area = Rect {
+                    x: frame.area().width / 2 - 10,
+                    y: frame.area().height / 2 - 1,
+                    width: 30,
+                    height: 3,
+                };
+                frame.render_widget(self.jump_input.draw(), area);
+            }
             Mode::SaveHistory => {
                 let area = Rect {
                     x: frame.area().width / 2 - 10,
@@ -231,4 +828,24 @@ impl App {
         }
         if matches!(self.mode, Mode::SeedEdit) {}
     }
+
+    fn draw_seats(&self) -> Paragraph<'_> {
+        let seats = ALL_COLORS
+            .iter()
+            .zip(self.seats)
+            .enumerate()
+            .map(|(idx, (color, seat))| format!("{} {color:?}: {seat:?}", idx + 1))
+            .collect::<Vec<_>>()
+            .join("  ");
+        let last = match (&self.branch, &self.replay, &self.last_ai_move) {
+            (Some(branch), _, _) => branch.breadcrumb(),
+            (None, Some(replay), _) => {
+                format!("Replay at move {}/{}", replay.position(), replay.len())
+            }
+            (None, None, Some(mv)) => format!("AI played {mv}"),
+            (None, None, None) => String::new(),
+        };
+        let title = format!("Seats - AI {:?}", self.difficulty);
+        Paragraph::new(format!("{seats}\n{last}")).block(Block::bordered().title(title))
+    }
 }
@@ -2,16 +2,23 @@ use std::f64::consts::TAU;
 
 use ratatui::{
     crossterm::event::Event,
-    style::Color,
+    layout::{Margin, Position, Rect},
+    style::{Color, Style},
     symbols::Marker,
+    text::Line,
     widgets::{
         canvas::{Canvas, Rectangle, Shape},
         Block, Widget,
     },
 };
-use tac_types::{Home, Square, ALL_COLORS};
+use tac_types::{Home, HomeSquare, Square, ALL_COLORS, NUM_COLORS, RING_SIZE, SQUARES_PER_COLOR};
 
-use crate::app::Message;
+const RING: usize = RING_SIZE as usize;
+
+use crate::{
+    app::Message,
+    move_entry::{MoveEntry, Target},
+};
 
 const CANVAS_SIZE: f64 = 256.0;
 const CANVAS_PADDING: f64 = 32.0;
@@ -28,7 +35,7 @@ struct ColoredPoints<'a> {
     pub points: &'a [BoardPoint],
 }
 
-impl<'a> Shape for ColoredPoints<'a> {
+impl Shape for ColoredPoints<'_> {
     fn draw(&self, painter: &mut ratatui::widgets::canvas::Painter) {
         for BoardPoint { x, y, color } in self.points {
             if let Some((x, y)) = painter.get_point(*x, *y) {
@@ -39,9 +46,9 @@ impl<'a> Shape for ColoredPoints<'a> {
 }
 
 pub struct BoardView {
-    points: [BoardPoint; 64],
-    outside: [u8; 4],
-    homes: [Home; 4],
+    points: [BoardPoint; RING],
+    outside: [u8; NUM_COLORS],
+    homes: [Home; NUM_COLORS],
 }
 
 impl Default for BoardView {
@@ -52,9 +59,9 @@ impl Default for BoardView {
 
 impl BoardView {
     pub fn new() -> Self {
-        let mut points = [BoardPoint::default(); 64];
-        (0..64).for_each(|i| {
-            let angle = i as f64 / 64.0 * TAU;
+        let mut points = [BoardPoint::default(); RING];
+        (0..RING).for_each(|i| {
+            let angle = i as f64 / RING as f64 * TAU;
             let (x, y) = (angle.cos() * CANVAS_SIZE, angle.sin() * CANVAS_SIZE);
             points[i] = BoardPoint {
                 x,
@@ -64,8 +71,8 @@ impl BoardView {
         });
         Self {
             points,
-            outside: [4; 4],
-            homes: [Home::default(); 4],
+            outside: [4; NUM_COLORS],
+            homes: [Home::default(); NUM_COLORS],
         }
     }
 
@@ -74,7 +81,7 @@ impl BoardView {
     }
     pub fn on_state_change(&mut self, board: &robotac::board::Board) {
         for (idx, p) in self.points.iter_mut().enumerate() {
-            // This is a valid casting because `points` has a fixed size of 64
+            // This is a valid casting because `points` has one entry per square of the ring
             let idx = idx as u8;
             if let Some(c) = board.color_on(Square(idx)) {
                 p.color = term_color(c);
@@ -88,7 +95,8 @@ impl BoardView {
         }
     }
 
-    pub fn draw(&self) -> impl Widget + '_ {
+    /// Draws the board, highlighting the cursor and selected positions if a move is being entered.
+    pub fn draw<'a>(&'a self, entry: Option<&'a MoveEntry>) -> impl Widget + 'a {
         // diameter + padding
         let size = CANVAS_SIZE + CANVAS_PADDING;
         let bounds = [-size, size];
@@ -110,8 +118,8 @@ impl BoardView {
                 });
 
                 let resolution = 4;
-                for i in 0..64 / resolution {
-                    let angle = (i * resolution) as f64 / 64.0 * TAU;
+                for i in 0..RING / resolution {
+                    let angle = (i * resolution) as f64 / RING as f64 * TAU;
                     let (x, y) = (
                         angle.cos() * (CANVAS_SIZE + 16.0),
                         angle.sin() * (CANVAS_SIZE + 16.0),
@@ -135,8 +143,9 @@ impl BoardView {
                 }
 
                 for (idx, home) in self.homes.iter().enumerate() {
-                    let angle = (idx * 16) as f64 / 64.0 * TAU;
-                    for p in 1..=4 {
+                    let angle = (idx * usize::from(SQUARES_PER_COLOR)) as f64 / RING as f64 * TAU;
+                    for pos in HomeSquare::ALL {
+                        let p = pos.index() + 1;
                         let (x, y) = (
                             angle.cos() * (CANVAS_SIZE - 32.0 * p as f64),
                             angle.sin() * (CANVAS_SIZE - 32.0 * p as f64),
@@ -144,7 +153,7 @@ impl BoardView {
                         ctx.draw(&make_rectangle(
                             x,
                             y,
-                            if home.is_free(p - 1) {
+                            if home.is_free(pos) {
                                 Color::Rgb(255, 255, 255)
                             } else {
                                 term_color(ALL_COLORS[idx])
@@ -153,12 +162,22 @@ impl BoardView {
                     }
                 }
                 let dist = CANVAS_SIZE;
-                let idx_pos = [
+                let corners = [
                     (dist - CANVAS_PADDING, -dist),
                     (dist - CANVAS_PADDING, dist),
                     (-dist, dist),
                     (-dist, -dist),
                 ];
+                // There are no free corners for six colors, so the bases go between the homes
+                let between: [(f64, f64); NUM_COLORS] = core::array::from_fn(|idx| {
+                    let angle = (idx as f64 + 0.5) / NUM_COLORS as f64 * TAU;
+                    (angle.cos() * (dist - 64.0), angle.sin() * (dist - 64.0))
+                });
+                let idx_pos: &[(f64, f64)] = if NUM_COLORS == corners.len() {
+                    &corners
+                } else {
+                    &between
+                };
                 for (idx, amount) in self.outside.iter().enumerate() {
                     let (x, y) = idx_pos[idx];
                     (0..*amount).for_each(|i| {
@@ -169,17 +188,71 @@ impl BoardView {
                         ));
                     });
                 }
+
+                if let Some(entry) = entry {
+                    for target in entry.selection() {
+                        let (x, y) = target_position(*target);
+                        ctx.print(x, y, Line::styled("o", Style::new().fg(Color::Yellow)));
+                    }
+                    let (x, y) = target_position(entry.cursor());
+                    ctx.print(x, y, Line::styled("+", Style::new().fg(Color::Magenta)));
+                }
             })
             .x_bounds(bounds)
             .y_bounds(bounds)
     }
 }
 
-fn term_color(tac_color: tac_types::Color) -> Color {
-    match tac_color {
-        tac_types::Color::Black => Color::Black,
-        tac_types::Color::Blue => Color::Blue,
-        tac_types::Color::Green => Color::Green,
-        tac_types::Color::Red => Color::Red,
+/// Returns the position on the board drawn at the terminal cell `position`, if there is one
+/// close enough. `area` is where the board is drawn, including the border.
+pub fn target_at(area: Rect, position: Position) -> Option<Target> {
+    let inner = area.inner(Margin::new(1, 1));
+    if !inner.contains(position) || inner.width < 2 || inner.height < 2 {
+        return None;
     }
+    // Inverse of the mapping from canvas coordinates to cells of the canvas
+    let size = CANVAS_SIZE + CANVAS_PADDING;
+    let cell_width = 2.0 * size / f64::from(inner.width - 1);
+    let cell_height = 2.0 * size / f64::from(inner.height - 1);
+    let x = -size + f64::from(position.x - inner.x) * cell_width;
+    let y = size - f64::from(position.y - inner.y) * cell_height;
+    let ring = (0..RING_SIZE).map(|sq| Target::Ring(Square(sq)));
+    let homes = ALL_COLORS
+        .into_iter()
+        .flat_map(|color| HomeSquare::ALL.map(|pos| Target::Home(color, pos)));
+    let (target, distance) = ring
+        .chain(homes)
+        .map(|target| {
+            let (tx, ty) = target_position(target);
+            (target, (tx - x).hypot(ty - y))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    // Squares on the ring are about 25 apart, clicks next to the board don't select anything
+    (distance <= cell_width.max(cell_height).max(16.0)).then_some(target)
+}
+
+/// Returns the canvas coordinates of a position on the board.
+fn target_position(target: Target) -> (f64, f64) {
+    let (idx, radius) = match target {
+        Target::Ring(sq) => (sq.0, CANVAS_SIZE),
+        Target::Home(color, pos) => (
+            color.home().0,
+            CANVAS_SIZE - 32.0 * f64::from(pos.index() + 1),
+        ),
+    };
+    let angle = f64::from(idx) / RING as f64 * TAU;
+    (angle.cos() * radius, angle.sin() * radius)
+}
+
+fn term_color(tac_color: tac_types::Color) -> Color {
+    // Indexed by color, the last two are only used with six colors
+    const TERM_COLORS: [Color; 6] = [
+        Color::Black,
+        Color::Blue,
+        Color::Green,
+        Color::Red,
+        Color::Yellow,
+        Color::Magenta,
+    ];
+    TERM_COLORS[tac_color as usize]
 }
@@ -0,0 +1,76 @@
+use itertools::Itertools;
+use robotac::{board::Board, knowledge::Knowledge};
+use tac_types::{TacMove, NUM_COLORS};
+
+/// Moves of the breadcrumb shown before the older ones are elided
+const BREADCRUMB_MOVES: usize = 6;
+
+/// Exploratory moves played from a position of the game without being added to its history.
+pub struct Branch {
+    /// Board where the branch left the main line
+    board: Board,
+    /// What every player knew where the branch left the main line
+    knowledge: [Knowledge; NUM_COLORS],
+    /// Moves played on the branch, in order
+    moves: Vec<TacMove>,
+    /// Moves of the main line before the branch
+    start: usize,
+}
+
+impl Branch {
+    pub fn new(board: &Board, knowledge: [Knowledge; NUM_COLORS], start: usize) -> Self {
+        Self {
+            board: board.clone(),
+            knowledge,
+            moves: Vec::new(),
+            start,
+        }
+    }
+
+    /// Board of the main line the branch started from.
+    pub fn main_line(&self) -> &Board {
+        &self.board
+    }
+
+    /// What every player knew on the main line.
+    pub fn main_line_knowledge(&self) -> [Knowledge; NUM_COLORS] {
+        self.knowledge
+    }
+
+    pub fn push(&mut self, mv: TacMove) {
+        self.moves.push(mv);
+    }
+
+    pub fn pop(&mut self) -> Option<TacMove> {
+        self.moves.pop()
+    }
+
+    /// Returns the amount of moves played on the branch.
+    pub fn moves_played(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// Returns the knowledge of every player after the moves of the branch have been played.
+    pub fn knowledge(&self) -> [Knowledge; NUM_COLORS] {
+        let mut board = self.board.clone();
+        let mut knowledge = self.knowledge;
+        for mv in &self.moves {
+            for k in &mut knowledge {
+                k.update_with_move(mv, &board);
+            }
+            board.play(mv);
+        }
+        knowledge
+    }
+
+    /// Where the branch left the main line and the last moves played on it.
+    pub fn breadcrumb(&self) -> String {
+        let skipped = self.moves.len().saturating_sub(BREADCRUMB_MOVES);
+        let moves = self.moves[skipped..].iter().join(" > ");
+        match (skipped, self.moves.is_empty()) {
+            (_, true) => format!("What-if from move {}", self.start),
+            (0, false) => format!("What-if from move {} > {moves}", self.start),
+            _ => format!("What-if from move {} > ... > {moves}", self.start),
+        }
+    }
+}
@@ -4,7 +4,7 @@ use ratatui::{
 };
 use robotac::board::Board;
 
-use crate::app::Message;
+use crate::{app::Message, hotseat::Visibility};
 
 pub struct DebugView;
 
@@ -13,7 +13,16 @@ impl DebugView {
         None
     }
 
-    pub fn draw(&self, board: &Board) -> impl Widget + '_ {
-        Paragraph::new(format!("{:?}", board)).block(Block::bordered().title("Debug state"))
+    pub fn draw(&self, board: &Board, visibility: Visibility) -> impl Widget + '_ {
+        let title = match board.game_result() {
+            Some(result) => format!("Debug state - {result:?} won"),
+            None => "Debug state".to_string(),
+        };
+        // The debug output contains every hand
+        let content = match visibility {
+            Visibility::Everything => format!("{:?}", board),
+            Visibility::Player(_) => "Hidden in hot seat mode".to_string(),
+        };
+        Paragraph::new(content).block(Block::bordered().title(title))
     }
 }
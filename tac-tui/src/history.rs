@@ -53,7 +53,7 @@ impl LoadHistory {
                         .filter(|s| {
                             if let Ok(s) = s.as_ref() {
                                 let path = s.path();
-                                !path.is_dir()
+                                !path.is_dir() && path.extension().is_some_and(|ext| ext == "hist")
                             } else {
                                 false
                             }
@@ -70,7 +70,7 @@ impl LoadHistory {
                         .filter_map(|s| {
                             let s = s.ok()?;
                             let path = s.path();
-                            if !path.is_dir() {
+                            if !path.is_dir() && path.extension().is_some_and(|ext| ext == "hist") {
                                 Some(s)
                             } else {
                                 None
@@ -91,7 +91,7 @@ impl LoadHistory {
             .filter_map(|s| {
                 let s = s.ok()?;
                 let path = s.path();
-                if !path.is_dir() {
+                if !path.is_dir() && path.extension().is_some_and(|ext| ext == "hist") {
                     Some(s.file_name().to_str().unwrap().to_owned())
                 } else {
                     None
@@ -0,0 +1,52 @@
+use itertools::Itertools;
+use ratatui::widgets::{Block, Paragraph, Widget};
+use robotac::board::Board;
+use tac_types::{Color, ALL_COLORS};
+
+use crate::popup::Popup;
+
+/// Which hidden information the views are allowed to show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visibility {
+    /// Everything is shown, e.g. when playing alone against the AI
+    Everything,
+    /// Only what this player knows, used when several humans share the terminal
+    Player(Color),
+}
+
+impl Visibility {
+    pub fn shows_hand(self, player: Color) -> bool {
+        match self {
+            Visibility::Everything => true,
+            Visibility::Player(viewer) => viewer == player,
+        }
+    }
+}
+
+/// Lists the cards of every player whose hand is visible.
+pub fn draw_hands(board: &Board, visibility: Visibility) -> impl Widget + '_ {
+    let hands = ALL_COLORS
+        .iter()
+        .map(|color| {
+            let hand = board.hand(*color);
+            if visibility.shows_hand(*color) {
+                format!(
+                    "{color:?}: {}",
+                    hand.iter().map(|card| format!("{card:?}")).join(" ")
+                )
+            } else {
+                format!("{color:?}: {} cards", hand.amount())
+            }
+        })
+        .join("\n");
+    Paragraph::new(hands).block(Block::bordered().title("Hands"))
+}
+
+/// Covers the whole screen until `player` confirms that nobody else is watching.
+pub fn draw_pass_device(player: Color) -> impl Widget {
+    Popup::default()
+        .title("Hot seat".to_string())
+        .content(format!(
+            "Pass the device to {player:?}.\n\nPress Enter once only {player:?} can see the screen."
+        ))
+}
@@ -0,0 +1,40 @@
+use crate::{app::Message, popup::Popup};
+use ratatui::{
+    crossterm::event::{Event, KeyCode},
+    prelude::*,
+};
+
+#[derive(Default)]
+pub struct JumpInput {
+    input: String,
+}
+
+impl JumpInput {
+    pub fn update(&mut self, event: &Event) -> Option<Message> {
+        if let Event::Key(key) = event {
+            match key.code {
+                KeyCode::Char(c) => {
+                    if c.is_ascii_digit() {
+                        self.input.push(c)
+                    }
+                }
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Enter => {
+                    let position = self.input.parse::<usize>().ok();
+                    self.input.clear();
+                    return position.map(Message::JumpTo);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    pub fn draw(&self) -> impl Widget + '_ {
+        Popup::default()
+            .title("Jump to move".to_string())
+            .content(self.input.clone())
+    }
+}
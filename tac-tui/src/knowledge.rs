@@ -0,0 +1,99 @@
+use ratatui::{
+    crossterm::event::{Event, KeyCode},
+    layout::Constraint,
+    style::{Style, Stylize},
+    widgets::{Block, Row, Table, Widget},
+};
+use robotac::{
+    history::History,
+    knowledge::{CardKnowledgeKind, Knowledge},
+};
+use tac_types::{Color, ALL_COLORS, CARDS, NUM_COLORS};
+
+use crate::{app::Message, hotseat::Visibility};
+
+/// Returns the knowledge of every player after the first `moves` moves of `history` have been played.
+/// The knowledge saved with the history is used at its end.
+pub fn knowledge_after(history: &History, moves: usize) -> [Knowledge; NUM_COLORS] {
+    let mut history = history.clone();
+    history.truncate(moves);
+    history.knowledge_after_moves()
+}
+
+/// Shows what one player knows about the hands of the others.
+#[derive(Default)]
+pub struct KnowledgeView {
+    observer: usize,
+}
+
+impl KnowledgeView {
+    pub fn update(&mut self, event: &Event) -> Option<Message> {
+        if let Event::Key(key) = event {
+            if key.code == KeyCode::Char('o') {
+                self.observer = (self.observer + 1) % ALL_COLORS.len();
+            }
+        }
+        None
+    }
+
+    /// Shows the knowledge of the selected observer, or of the viewer if hands are hidden.
+    pub fn draw(
+        &self,
+        knowledge: &[Knowledge; NUM_COLORS],
+        visibility: Visibility,
+    ) -> impl Widget + '_ {
+        let observer = match visibility {
+            Visibility::Everything => self.observer,
+            Visibility::Player(viewer) => viewer as usize,
+        };
+        let knowledge = knowledge[observer];
+        let observer = knowledge.observer();
+        let others: [Color; NUM_COLORS - 1] =
+            core::array::from_fn(|i| Color::from((observer as usize + i + 1) % NUM_COLORS));
+        let header = Row::new(["Card".to_string(), "Seen".to_string()].into_iter().chain(
+            others.iter().map(|c| {
+                let opening = if knowledge.has_opening(*c) {
+                    ""
+                } else {
+                    " (no open)"
+                };
+                format!("{c:?}{opening}")
+            }),
+        ))
+        .style(Style::new().bold());
+        let rows = CARDS.iter().map(|card| {
+            let seen = format!("{}/{}", knowledge.history[*card], card.amount());
+            Row::new(
+                [format!("{card:?}"), seen]
+                    .into_iter()
+                    .chain(
+                        others
+                            .iter()
+                            .map(|c| match knowledge.card_knowledge(*c, *card) {
+                                CardKnowledgeKind::Unknown => "?".to_string(),
+                                CardKnowledgeKind::Atmost(x) => format!("<= {x}"),
+                                CardKnowledgeKind::Exact(x) => format!("{x}"),
+                            }),
+                    ),
+            )
+        });
+        let title = format!(
+            "Knowledge of {observer:?} - traded {:?}, got {:?}, jesters {}",
+            knowledge.traded_away(),
+            knowledge.got_traded(),
+            knowledge.jesters_played()
+        );
+        Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Length(6),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(header)
+        .block(Block::bordered().title(title))
+    }
+}
@@ -1,16 +1,31 @@
+use std::io::stdout;
+
 use app::App;
+use ratatui::crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+};
 
 pub mod ai_debug;
 pub mod app;
 pub mod board;
+pub mod branch;
 pub mod debug;
 pub mod history;
+pub mod hotseat;
+pub mod jump_input;
+pub mod knowledge;
+pub mod move_entry;
 pub mod moves;
 pub mod popup;
 pub mod seed_input;
+pub mod session;
 
 fn main() {
     let terminal = ratatui::init();
+    // Clicks select squares on the board and moves in the list
+    let _ = execute!(stdout(), EnableMouseCapture);
     let _ = App::new().run(terminal);
+    let _ = execute!(stdout(), DisableMouseCapture);
     ratatui::restore();
 }
@@ -0,0 +1,230 @@
+use itertools::Itertools;
+use ratatui::{
+    crossterm::event::{Event, KeyCode},
+    text::Line,
+    widgets::{Block, Borders, List, Widget},
+};
+use robotac::board::Board;
+use tac_types::{
+    Card, Color, Distance, HomeSquare, Square, TacAction, TacMove, ALL_COLORS, HOME_SIZE,
+};
+
+use crate::app::Message;
+
+/// A position on the board that can be selected while entering a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Ring(Square),
+    Home(Color, HomeSquare),
+}
+
+impl Target {
+    /// Collects all positions a move touches, in the order they appear in the action.
+    fn of_move(mv: &TacMove) -> Vec<Target> {
+        let mut targets = Vec::new();
+        Self::of_action(&mv.action, mv.played_for, &mut targets);
+        targets
+    }
+
+    fn of_action(action: &TacAction, color: Color, targets: &mut Vec<Target>) {
+        match action {
+            TacAction::Step { from, to } | TacAction::Warrior { from, to } => {
+                targets.extend([Target::Ring(*from), Target::Ring(*to)]);
+            }
+            TacAction::StepHome { from, to } => {
+                targets.extend([Target::Home(color, *from), Target::Home(color, *to)]);
+            }
+            TacAction::StepInHome { from, to } => {
+                targets.extend([Target::Ring(*from), Target::Home(color, *to)]);
+            }
+            TacAction::Trickster { target1, target2 } => {
+                targets.extend([Target::Ring(*target1), Target::Ring(*target2)]);
+            }
+            TacAction::Enter => targets.push(Target::Ring(color.home())),
+            TacAction::SevenSteps { steps } => {
+                for step in steps {
+                    Self::of_action(step, color, targets);
+                }
+            }
+            TacAction::Suspend
+            | TacAction::Jester
+            | TacAction::Devil
+            | TacAction::Discard
+            | TacAction::Trade
+            | TacAction::Announce { .. } => {}
+        }
+    }
+}
+
+/// Builds a move by picking a card and the squares it should affect on the board.
+/// Only legal moves matching every selected square remain available.
+pub struct MoveEntry {
+    moves: Vec<TacMove>,
+    cards: Vec<Card>,
+    card: Option<usize>,
+    cursor: Target,
+    selection: Vec<Target>,
+    selected_move: usize,
+}
+
+impl MoveEntry {
+    pub fn new(board: &Board) -> Self {
+        let moves = board.get_moves(board.current_player());
+        let cards = moves.iter().map(|mv| mv.card).unique().collect_vec();
+        Self {
+            moves,
+            cards,
+            card: None,
+            cursor: Target::Ring(board.current_player().home()),
+            selection: Vec::new(),
+            selected_move: 0,
+        }
+    }
+
+    pub fn on_state_change(&mut self, board: &Board) {
+        *self = MoveEntry::new(board);
+    }
+
+    pub fn cursor(&self) -> Target {
+        self.cursor
+    }
+
+    pub fn selection(&self) -> &[Target] {
+        &self.selection
+    }
+
+    /// Legal moves with the selected card that touch all selected positions.
+    pub fn matching(&self) -> impl Iterator<Item = &TacMove> {
+        let card = self.card.map(|idx| self.cards[idx]);
+        self.moves.iter().filter(move |mv| {
+            if card.is_some_and(|card| card != mv.card) {
+                return false;
+            }
+            let targets = Target::of_move(mv);
+            self.selection.iter().all(|t| targets.contains(t))
+        })
+    }
+
+    fn move_cursor(&mut self, amount: i8) {
+        self.cursor = match self.cursor {
+            Target::Ring(sq) => Target::Ring(if amount >= 0 {
+                sq.add(Distance::new(amount.unsigned_abs()))
+            } else {
+                sq.sub(Distance::new(amount.unsigned_abs()))
+            }),
+            Target::Home(color, pos) => {
+                let pos = pos.index().saturating_add_signed(amount).min(HOME_SIZE - 1);
+                Target::Home(color, HomeSquare::new(pos))
+            }
+        };
+    }
+
+    /// Moves the cursor from the entrance of a home into it and back out.
+    fn toggle_home(&mut self, enter: bool) {
+        self.cursor = match self.cursor {
+            Target::Ring(sq) if enter => ALL_COLORS
+                .into_iter()
+                .find(|c| c.home() == sq)
+                .map_or(self.cursor, |c| Target::Home(c, HomeSquare::new(0))),
+            Target::Home(color, _) if !enter => Target::Ring(color.home()),
+            target => target,
+        };
+    }
+
+    fn reset_selected_move(&mut self) {
+        self.selected_move = 0;
+    }
+
+    /// Moves the cursor to a clicked position and selects it, or unselects it if it already was.
+    pub fn click(&mut self, target: Target) {
+        self.cursor = target;
+        self.toggle_selection();
+    }
+
+    /// Selects the move shown on `line` of the list, or plays it if it already was selected.
+    pub fn click_line(&mut self, line: usize) -> Option<Message> {
+        let idx = line + self.selected_move.saturating_sub(8);
+        if idx == self.selected_move {
+            return self.matching().nth(idx).cloned().map(Message::MakeMove);
+        }
+        if idx < self.matching().count() {
+            self.selected_move = idx;
+        }
+        None
+    }
+
+    fn toggle_selection(&mut self) {
+        if let Some(pos) = self.selection.iter().position(|t| *t == self.cursor) {
+            self.selection.remove(pos);
+        } else {
+            self.selection.push(self.cursor);
+        }
+        self.reset_selected_move();
+    }
+
+    pub fn update(&mut self, event: &Event) -> Option<Message> {
+        if let Event::Key(key) = event {
+            match key.code {
+                KeyCode::Right => self.move_cursor(1),
+                KeyCode::Left => self.move_cursor(-1),
+                KeyCode::PageUp => self.move_cursor(4),
+                KeyCode::PageDown => self.move_cursor(-4),
+                KeyCode::Down => self.toggle_home(true),
+                KeyCode::Up => self.toggle_home(false),
+                KeyCode::Tab => {
+                    self.card = match self.card {
+                        None if !self.cards.is_empty() => Some(0),
+                        Some(idx) if idx + 1 < self.cards.len() => Some(idx + 1),
+                        _ => None,
+                    };
+                    self.reset_selected_move();
+                }
+                KeyCode::Char(' ') => self.toggle_selection(),
+                KeyCode::Backspace => {
+                    self.selection.pop();
+                    self.reset_selected_move();
+                }
+                KeyCode::Char('j') => {
+                    let count = self.matching().count();
+                    self.selected_move = (self.selected_move + 1).min(count.saturating_sub(1));
+                }
+                KeyCode::Char('k') => {
+                    self.selected_move = self.selected_move.saturating_sub(1);
+                }
+                KeyCode::Enter => {
+                    if let Some(mv) = self.matching().nth(self.selected_move) {
+                        return Some(Message::MakeMove(mv.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    pub fn draw(&self) -> impl Widget + '_ {
+        let card = self.card.map_or("any card".to_string(), |idx| {
+            format!("{:?}", self.cards[idx])
+        });
+        let block = Block::new().borders(Borders::ALL).title(
+            Line::raw(format!(
+                "Board entry - {card}, cursor {:?}, selected {:?}",
+                self.cursor, self.selection
+            ))
+            .left_aligned(),
+        );
+        let items = self
+            .matching()
+            .enumerate()
+            .skip(self.selected_move.saturating_sub(8))
+            .map(|(idx, mv)| {
+                format!(
+                    "{}{} {}",
+                    if idx == self.selected_move { '>' } else { ' ' },
+                    idx,
+                    mv
+                )
+            });
+        List::new(items).block(block)
+    }
+}
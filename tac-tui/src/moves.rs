@@ -1,64 +1,313 @@
+use mcts::{manager::Manager, node::ComputedStats};
 use ratatui::{
     crossterm::event::{Event, KeyCode},
     text::Line,
     widgets::{Block, Borders, List, Widget},
 };
-use tac_types::TacMove;
+use robotac::{TacAI, TacEval};
+use tac_types::{Card, PackedTacMove, TacAction, TacMove};
 
 use crate::app::Message;
 
+/// Name of the kind of `action` used by the `a:` filter.
+fn action_kind(action: &TacAction) -> &'static str {
+    match action {
+        TacAction::Step { .. } => "step",
+        TacAction::StepHome { .. } => "home",
+        TacAction::StepInHome { .. } => "inhome",
+        TacAction::Trickster { .. } => "trickster",
+        TacAction::Enter => "enter",
+        TacAction::Suspend => "suspend",
+        TacAction::Jester => "jester",
+        TacAction::Devil => "devil",
+        TacAction::Warrior { .. } => "warrior",
+        TacAction::Discard => "discard",
+        TacAction::Trade => "trade",
+        TacAction::SevenSteps { .. } => "seven",
+        TacAction::Announce { .. } => "announce",
+    }
+}
+
+/// Whether `action` moves a ball to `target`, ring squares are numbers and home squares `h<n>`.
+fn reaches(action: &TacAction, target: &str) -> bool {
+    match action {
+        TacAction::Step { to, .. } | TacAction::Warrior { to, .. } => to.0.to_string() == target,
+        TacAction::StepHome { to, .. } | TacAction::StepInHome { to, .. } => {
+            format!("h{to}") == target
+        }
+        TacAction::Trickster { target1, target2 } => [target1, target2]
+            .iter()
+            .any(|sq| sq.0.to_string() == target),
+        TacAction::SevenSteps { steps } => steps.iter().any(|step| reaches(step, target)),
+        _ => false,
+    }
+}
+
+/// Moves shown by the move list, typed as terms separated by spaces that all have to match.
+///
+/// - `c:<card>` the card in move notation, e.g. `c:7` or `c:tac`
+/// - `a:<kind>` the kind of action, e.g. `a:enter` or `a:seven`
+/// - `t:<square>` a ball ends up on the ring square or the home square `h<n>`
+/// - anything else has to be part of the move notation
+#[derive(Clone, Debug, Default)]
+struct MoveFilter {
+    input: String,
+}
+
+impl MoveFilter {
+    fn matches(&self, mv: &TacMove) -> bool {
+        let notation = mv.to_string().to_lowercase();
+        self.input.to_lowercase().split_whitespace().all(|term| {
+            if let Some(card) = term.strip_prefix("c:") {
+                notation.split(':').next() == Some(card)
+            } else if let Some(kind) = term.strip_prefix("a:") {
+                action_kind(&mv.action) == kind
+            } else if let Some(target) = term.strip_prefix("t:") {
+                reaches(&mv.action, target)
+            } else {
+                notation.contains(term)
+            }
+        })
+    }
+}
+
+/// Line of the move list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Row {
+    Move(usize),
+    /// Header of the seven moves played with `card`
+    Sevens {
+        card: Card,
+        count: usize,
+    },
+}
+
 pub struct MoveList {
     moves: Vec<TacMove>,
+    /// Search statistics of every move, `None` if it hasn't been searched
+    stats: Vec<Option<ComputedStats>>,
+    /// Shown in the title while the position is being analysed
+    status: Option<String>,
+    /// Selected row, see [`MoveList::rows`]
     selected: usize,
+    filter: MoveFilter,
+    /// Keys are typed into the filter instead of selecting moves
+    editing_filter: bool,
+    /// Cards whose seven moves are listed below their header
+    expanded: Vec<Card>,
 }
 
 impl MoveList {
     pub fn new(board: &robotac::board::Board) -> Self {
         Self {
             moves: board.get_moves(board.current_player()),
+            stats: Vec::new(),
+            status: None,
             selected: 0,
+            filter: MoveFilter::default(),
+            editing_filter: false,
+            expanded: Vec::new(),
         }
     }
+
+    /// Whether keys have to be passed to the move list because the filter is being typed.
+    pub fn editing_filter(&self) -> bool {
+        self.editing_filter
+    }
+
+    /// Moves that match the filter, seven moves are grouped by card below a header that
+    /// takes the place of the first of them.
+    fn rows(&self) -> Vec<Row> {
+        let shown = |mv: &TacMove| self.filter.matches(mv);
+        let mut rows = Vec::new();
+        let mut grouped = Vec::new();
+        for (idx, mv) in self.moves.iter().enumerate() {
+            if !shown(mv) {
+                continue;
+            }
+            if !matches!(mv.action, TacAction::SevenSteps { .. }) {
+                rows.push(Row::Move(idx));
+                continue;
+            }
+            if grouped.contains(&mv.card) {
+                continue;
+            }
+            grouped.push(mv.card);
+            let sevens = self
+                .moves
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| {
+                    other.card == mv.card
+                        && matches!(other.action, TacAction::SevenSteps { .. })
+                        && shown(other)
+                })
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+            rows.push(Row::Sevens {
+                card: mv.card,
+                count: sevens.len(),
+            });
+            if self.expanded.contains(&mv.card) {
+                rows.extend(sevens.into_iter().map(Row::Move));
+            }
+        }
+        rows
+    }
+
+    fn update_filter(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char(c) => self.filter.input.push(c),
+            KeyCode::Backspace => {
+                self.filter.input.pop();
+            }
+            KeyCode::Enter => self.editing_filter = false,
+            KeyCode::Esc => {
+                self.filter.input.clear();
+                self.editing_filter = false;
+            }
+            _ => {}
+        }
+        self.selected = 0;
+    }
+
     pub fn update(&mut self, event: &Event) -> Option<Message> {
         if let Event::Key(key) = event {
+            if self.editing_filter {
+                self.update_filter(key.code);
+                return None;
+            }
+            let rows = self.rows();
             match key.code {
                 KeyCode::Right | KeyCode::Char('j') => {
-                    self.selected = (self.selected + 1).min(self.moves.len() - 1);
+                    self.selected = (self.selected + 1).min(rows.len().saturating_sub(1));
                 }
                 KeyCode::Left | KeyCode::Char('k') => {
                     self.selected = self.selected.saturating_sub(1);
                 }
-                KeyCode::Enter => {
-                    if let Some(mv) = self.moves.get(self.selected) {
-                        return Some(Message::MakeMove(mv.clone()));
+                KeyCode::Char('/') => self.editing_filter = true,
+                KeyCode::Enter | KeyCode::Char(' ') => match rows.get(self.selected) {
+                    Some(Row::Move(idx)) if key.code == KeyCode::Enter => {
+                        return Some(Message::MakeMove(self.moves[*idx].clone()));
                     }
-                }
+                    Some(Row::Sevens { card, .. }) => {
+                        if let Some(pos) = self.expanded.iter().position(|c| c == card) {
+                            self.expanded.remove(pos);
+                        } else {
+                            self.expanded.push(*card);
+                        }
+                    }
+                    _ => {}
+                },
                 _ => {}
             }
         }
         None
     }
+
+    /// Selects the row shown on `line` of the list. Clicking the selected row again plays its
+    /// move or expands its group, like pressing enter.
+    pub fn click_line(&mut self, line: usize) -> Option<Message> {
+        let row = line + self.selected.saturating_sub(8);
+        if row == self.selected {
+            return self.update(&Event::Key(KeyCode::Enter.into()));
+        }
+        if row < self.rows().len() {
+            self.selected = row;
+        }
+        None
+    }
+
+    /// Shows the moves of the new position, the filter and the expanded groups are kept.
     pub fn on_state_change(&mut self, board: &robotac::board::Board) {
-        *self = MoveList::new(board);
+        *self = MoveList {
+            filter: std::mem::take(&mut self.filter),
+            editing_filter: self.editing_filter,
+            expanded: std::mem::take(&mut self.expanded),
+            ..MoveList::new(board)
+        };
+    }
+
+    /// Takes the statistics of the moves from the current search of `ai`.
+    pub fn on_search_update(&mut self, ai: &Manager<TacAI>, status: Option<String>) {
+        let stats = ai.root_move_stats();
+        self.stats = self
+            .moves
+            .iter()
+            .map(|mv| {
+                let packed = PackedTacMove::pack(mv);
+                stats
+                    .iter()
+                    .find_map(|(searched, stats)| (*searched == packed).then_some(*stats))
+            })
+            .collect();
+        self.status = status;
+    }
+
+    fn draw_move(&self, idx: usize, selected: bool, indent: &str) -> String {
+        let stats = match self.stats.get(idx).copied().flatten() {
+            Some(stats) => format!(
+                "{:>7} {:>6.2} ",
+                stats.visits,
+                stats.mean_action_value / TacEval::SCALE as f64
+            ),
+            None if self.stats.is_empty() => String::new(),
+            None => format!("{:>15}", ""),
+        };
+        format!(
+            "{}{indent}{} {}{}",
+            if selected { '>' } else { ' ' },
+            idx,
+            stats,
+            self.moves[idx]
+        )
     }
 
     pub fn draw(&self) -> impl Widget + '_ {
+        let rows = self.rows();
+        let mut title = match &self.status {
+            Some(status) => format!("Moves - {status}"),
+            None => "Moves".to_string(),
+        };
+        if self.editing_filter || !self.filter.input.is_empty() {
+            let cursor = if self.editing_filter { "_" } else { "" };
+            let matching = self.moves.iter().filter(|mv| self.filter.matches(mv));
+            title = format!(
+                "{title} - filter: {}{cursor} ({} of {})",
+                self.filter.input,
+                matching.count(),
+                self.moves.len()
+            );
+        }
         let block = Block::new()
             .borders(Borders::ALL)
-            .title(Line::raw("Moves").left_aligned());
-        let items = self
-            .moves
+            .title(Line::raw(title).left_aligned());
+        let items = rows
             .iter()
             .enumerate()
-            .skip(self.selected.saturating_sub(8))
-            .map(|(idx, e)| {
-                format!(
-                    "{}{} {}",
-                    if idx == self.selected { '>' } else { ' ' },
-                    idx,
-                    e
-                )
-            });
+            .map(|(row, entry)| {
+                let selected = row == self.selected;
+                match *entry {
+                    Row::Move(idx) => {
+                        // Seven moves are listed below their header
+                        let grouped =
+                            matches!(self.moves[idx].action, TacAction::SevenSteps { .. });
+                        self.draw_move(idx, selected, if grouped { "  " } else { "" })
+                    }
+                    Row::Sevens { card, count } => {
+                        let (marker, hint) = if self.expanded.contains(&card) {
+                            ('v', "")
+                        } else {
+                            ('>', ", enter to expand")
+                        };
+                        format!(
+                            "{}{marker} {count} seven moves with {card:?}{hint}",
+                            if selected { '>' } else { ' ' },
+                        )
+                    }
+                }
+            })
+            .skip(self.selected.saturating_sub(8));
         List::new(items).block(block).highlight_symbol(">")
     }
 }
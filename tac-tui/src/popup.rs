@@ -12,7 +12,7 @@ pub struct Popup<'a> {
     style: Style,
 }
 
-impl<'a> Popup<'a> {
+impl Popup<'_> {
     pub fn title(self, title: String) -> Self {
         Self {
             title: Line::from(title),
@@ -0,0 +1,110 @@
+use ratatui::widgets::{Block, Paragraph, Widget};
+use robotac::board::{Board, GameResult, ALL_RESULTS};
+use tac_types::{ALL_COLORS, NUM_TEAMS};
+
+/// Deals a team has to win to take the rubber
+const RUBBER_WINS: usize = 3;
+
+fn team_name(team: GameResult) -> String {
+    // The results are in the order of the teams, see `ALL_RESULTS`
+    let first = ALL_COLORS[team as usize];
+    format!("{first:?}/{:?}", first.partner())
+}
+
+/// One game of a rubber.
+#[derive(Clone, Copy, Debug)]
+struct Deal {
+    seed: u64,
+    /// `None` while the deal is being played or if it was abandoned
+    result: Option<GameResult>,
+}
+
+/// Several deals played in a row, every deal with its own seed.
+/// A rubber is won by the first team winning [`RUBBER_WINS`] deals.
+pub struct Session {
+    /// Deals of the current rubber, the last one is being played
+    deals: Vec<Deal>,
+    /// Rubbers won by each team in the order of [`ALL_RESULTS`]
+    rubbers: [usize; NUM_TEAMS],
+}
+
+impl Session {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            deals: vec![Deal { seed, result: None }],
+            rubbers: [0; NUM_TEAMS],
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.current().seed
+    }
+
+    fn current(&self) -> &Deal {
+        self.deals.last().expect("A session always has a deal")
+    }
+
+    /// Deals of the current rubber won by `team`.
+    fn wins(&self, team: GameResult) -> usize {
+        self.deals
+            .iter()
+            .filter(|deal| deal.result == Some(team))
+            .count()
+    }
+
+    /// The team that won the current rubber, if any.
+    pub fn rubber_winner(&self) -> Option<GameResult> {
+        ALL_RESULTS
+            .into_iter()
+            .find(|team| self.wins(*team) >= RUBBER_WINS)
+    }
+
+    /// Records the result of the current deal, a move that gets undone takes its result back.
+    pub fn on_state_change(&mut self, board: &Board) {
+        if let Some(deal) = self.deals.last_mut() {
+            deal.result = board.game_result();
+        }
+    }
+
+    /// Starts the next deal and returns its seed. A finished rubber is counted
+    /// and a new one starts with the next deal.
+    pub fn next_deal(&mut self) -> u64 {
+        let seed = self.seed().wrapping_add(1);
+        if let Some(winner) = self.rubber_winner() {
+            self.rubbers[ALL_RESULTS.iter().position(|team| *team == winner).unwrap()] += 1;
+            self.deals.clear();
+        }
+        self.deals.push(Deal { seed, result: None });
+        seed
+    }
+
+    pub fn draw(&self) -> impl Widget + '_ {
+        let score = ALL_RESULTS
+            .map(|team| format!("{} {}", team_name(team), self.wins(team)))
+            .join(" - ");
+        let deals = self
+            .deals
+            .iter()
+            .enumerate()
+            .map(|(idx, deal)| {
+                let result = match deal.result {
+                    Some(team) => team_name(team),
+                    None if idx + 1 == self.deals.len() => "playing".to_string(),
+                    None => "abandoned".to_string(),
+                };
+                format!("{}: {result}", idx + 1)
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        let status = match self.rubber_winner() {
+            Some(team) => format!("{} win the rubber, d for the next one", team_name(team)),
+            None => format!("seed {}, d for the next deal", self.seed()),
+        };
+        let title = format!(
+            "Rubber {} - rubbers won {}",
+            self.rubbers.iter().sum::<usize>() + 1,
+            self.rubbers.map(|won| won.to_string()).join(":")
+        );
+        Paragraph::new(format!("{score}\n{deals}\n{status}")).block(Block::bordered().title(title))
+    }
+}
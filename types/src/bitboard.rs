@@ -2,14 +2,87 @@ use std::ops::{
     BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Sub, SubAssign,
 };
 
-use crate::square::Square;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
-pub struct BitBoard(pub u64);
+use crate::{
+    color::{ALL_COLORS, NUM_COLORS},
+    square::{Distance, Square, RING_SIZE},
+};
+
+/// One bit per square of the ring.
+#[cfg(not(feature = "six-players"))]
+pub type Bits = u64;
+/// One bit per square of the ring, the bits above the ring are always zero.
+#[cfg(feature = "six-players")]
+pub type Bits = u128;
+
+/// Bits that belong to a square of the ring.
+const RING_MASK: Bits = Bits::MAX >> (Bits::BITS - RING_SIZE as u32);
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "six-players", serde(from = "[u64; 2]", into = "[u64; 2]"))]
+pub struct BitBoard(pub Bits);
+
+// Not every format supports 128 bit integers, so wide bitboards are stored as two words
+#[cfg(feature = "six-players")]
+impl From<BitBoard> for [u64; 2] {
+    fn from(bitboard: BitBoard) -> Self {
+        [bitboard.0 as u64, (bitboard.0 >> u64::BITS) as u64]
+    }
+}
+
+#[cfg(feature = "six-players")]
+impl From<[u64; 2]> for BitBoard {
+    fn from([low, high]: [u64; 2]) -> Self {
+        Self(Bits::from(high) << u64::BITS | Bits::from(low))
+    }
+}
 
 impl BitBoard {
     pub const EMPTY: Self = Self(0);
     pub const ONE: Self = Self(1);
+    /// Home square of every color, indexed by the color.
+    pub const HOMES: [Self; NUM_COLORS] = {
+        let mut homes = [Self::EMPTY; NUM_COLORS];
+        let mut idx = 0;
+        while idx < NUM_COLORS {
+            homes[idx] = ALL_COLORS[idx].home().bitboard();
+            idx += 1;
+        }
+        homes
+    };
+    /// Home squares of all colors.
+    pub const ALL_HOMES: Self = {
+        let mut all = Self::EMPTY;
+        let mut idx = 0;
+        while idx < NUM_COLORS {
+            all = Self(all.0 | Self::HOMES[idx].0);
+            idx += 1;
+        }
+        all
+    };
+
+    /// Returns `len` consecutive squares starting at `square` and going forward,
+    /// wrapping around the ring.
+    #[must_use]
+    pub const fn ray_from(square: Square, len: u8) -> Self {
+        let ray = if len >= RING_SIZE {
+            RING_MASK
+        } else {
+            (1 << len) - 1
+        };
+        Self(ray).rotate_left(square.0)
+    }
+
+    /// Returns the squares passed when moving forward from `start` to `end`, excluding both.
+    /// If `start` and `end` are the same this is every other square of the ring.
+    #[must_use]
+    pub const fn between(start: Square, end: Square) -> Self {
+        let len = (end.0 + RING_SIZE - start.0 - 1) % RING_SIZE;
+        Self::ray_from(start.add(Distance::ONE), len)
+    }
 
     #[must_use]
     pub const fn is_empty(self) -> bool {
@@ -65,14 +138,23 @@ impl BitBoard {
         Self(self.0 - 1)
     }
 
+    /// Moves every square `n` squares backwards around the ring.
     #[must_use]
     pub const fn rotate_right(self, n: u8) -> Self {
-        Self(self.0.rotate_right(n as u32))
+        self.rotate_left(RING_SIZE - n % RING_SIZE)
     }
 
+    /// Moves every square `n` squares forward around the ring.
     #[must_use]
     pub const fn rotate_left(self, n: u8) -> Self {
-        Self(self.0.rotate_left(n as u32))
+        if RING_SIZE as u32 == Bits::BITS {
+            return Self(self.0.rotate_left(n as u32));
+        }
+        let n = n % RING_SIZE;
+        if n == 0 {
+            return self;
+        }
+        Self((self.0 << n | self.0 >> (RING_SIZE - n)) & RING_MASK)
     }
 }
 
@@ -159,6 +241,79 @@ impl Not for BitBoard {
     type Output = Self;
 
     fn not(self) -> Self::Output {
-        Self(!self.0)
+        Self(!self.0 & RING_MASK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ALL_COLORS;
+
+    #[test]
+    fn ray_from() {
+        assert_eq!(BitBoard::ray_from(Square(3), 0), BitBoard::EMPTY);
+        assert_eq!(
+            BitBoard::ray_from(Square(3), 2),
+            Square(3).bitboard() | Square(4).bitboard()
+        );
+        let last = Square(RING_SIZE - 1);
+        assert_eq!(
+            BitBoard::ray_from(last.sub(Distance::ONE), 3),
+            last.sub(Distance::ONE).bitboard() | last.bitboard() | Square(0).bitboard()
+        );
+        assert_eq!(BitBoard::ray_from(Square(10), RING_SIZE), !BitBoard::EMPTY);
+    }
+
+    #[test]
+    fn between() {
+        assert_eq!(BitBoard::between(Square(5), Square(6)), BitBoard::EMPTY);
+        assert_eq!(
+            BitBoard::between(Square(5), Square(8)),
+            Square(6).bitboard() | Square(7).bitboard()
+        );
+        assert_eq!(
+            BitBoard::between(Square(RING_SIZE - 1), Square(2)),
+            Square(0).bitboard() | Square(1).bitboard()
+        );
+        assert_eq!(
+            BitBoard::between(Square(7), Square(7)),
+            !Square(7).bitboard()
+        );
+        for start in 0..RING_SIZE {
+            for end in 0..RING_SIZE {
+                let (start, end) = (Square(start), Square(end));
+                let expected = (1..start.distance_to(end).steps())
+                    .fold(BitBoard::EMPTY, |acc, i| {
+                        acc | start.add(Distance::new(i)).bitboard()
+                    });
+                if start != end {
+                    assert_eq!(BitBoard::between(start, end), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotations() {
+        let last = Square(RING_SIZE - 1);
+        assert_eq!(last.bitboard().rotate_left(1), Square(0).bitboard());
+        assert_eq!(Square(0).bitboard().rotate_right(1), last.bitboard());
+        assert_eq!(last.bitboard().rotate_left(RING_SIZE), last.bitboard());
+        assert_eq!((!BitBoard::EMPTY).len(), RING_SIZE as usize);
+        for n in 0..RING_SIZE {
+            let rotated = BitBoard::ALL_HOMES.rotate_left(n);
+            assert_eq!(rotated.rotate_right(n), BitBoard::ALL_HOMES);
+            assert_eq!(rotated.len(), BitBoard::ALL_HOMES.len());
+        }
+    }
+
+    #[test]
+    fn homes() {
+        for color in ALL_COLORS {
+            assert_eq!(BitBoard::HOMES[color as usize], color.home().bitboard());
+            assert!(BitBoard::ALL_HOMES.has(color.home()));
+        }
+        assert_eq!(BitBoard::ALL_HOMES.len(), NUM_COLORS);
     }
 }
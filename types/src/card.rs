@@ -1,6 +1,8 @@
 use enum_map::Enum;
 use serde::{Deserialize, Serialize};
 
+use crate::Distance;
+
 pub const NUM_CARDS: usize = 18;
 pub const CARDS: [Card; NUM_CARDS] = [
     Card::One,
@@ -102,20 +104,22 @@ impl Card {
         }
     }
 
+    /// Returns the steps of cards which only move forward.
     #[must_use]
-    pub fn is_simple(self) -> Option<u8> {
-        match self {
-            Card::One => Some(1),
-            Card::Two => Some(2),
-            Card::Three => Some(3),
-            Card::Five => Some(5),
-            Card::Six => Some(6),
-            Card::Eight => Some(8),
-            Card::Nine => Some(9),
-            Card::Ten => Some(10),
-            Card::Twelve => Some(12),
-            Card::Thirteen => Some(13),
-            _ => None,
-        }
+    pub fn is_simple(self) -> Option<Distance> {
+        let steps = match self {
+            Card::One => 1,
+            Card::Two => 2,
+            Card::Three => 3,
+            Card::Five => 5,
+            Card::Six => 6,
+            Card::Eight => 8,
+            Card::Nine => 9,
+            Card::Ten => 10,
+            Card::Twelve => 12,
+            Card::Thirteen => 13,
+            _ => return None,
+        };
+        Some(Distance::new(steps))
     }
 }
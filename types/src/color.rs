@@ -1,14 +1,39 @@
 use serde::{Deserialize, Serialize};
 
-use crate::Square;
+use crate::{BitBoard, Distance, Square};
 
-pub const ALL_COLORS: [Color; 4] = [Color::Black, Color::Blue, Color::Green, Color::Red];
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+/// Number of players, every player has their own color.
+#[cfg(not(feature = "six-players"))]
+pub const NUM_COLORS: usize = 4;
+/// Number of players, every player has their own color.
+#[cfg(feature = "six-players")]
+pub const NUM_COLORS: usize = 6;
+/// Number of teams, partners sit opposite of each other.
+pub const NUM_TEAMS: usize = NUM_COLORS / 2;
+/// Distance on the ring between the homes of two neighbouring colors.
+pub const SQUARES_PER_COLOR: u8 = 16;
+
+#[cfg(not(feature = "six-players"))]
+pub const ALL_COLORS: [Color; NUM_COLORS] = [Color::Black, Color::Blue, Color::Green, Color::Red];
+#[cfg(feature = "six-players")]
+pub const ALL_COLORS: [Color; NUM_COLORS] = [
+    Color::Black,
+    Color::Blue,
+    Color::Green,
+    Color::Red,
+    Color::Yellow,
+    Color::Purple,
+];
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Color {
     Black,
     Blue,
     Green,
     Red,
+    #[cfg(feature = "six-players")]
+    Yellow,
+    #[cfg(feature = "six-players")]
+    Purple,
 }
 
 impl From<Color> for usize {
@@ -19,44 +44,35 @@ impl From<Color> for usize {
 
 impl From<usize> for Color {
     fn from(value: usize) -> Self {
-        unsafe { std::mem::transmute(value as u8) }
+        ALL_COLORS[value]
     }
 }
 
 impl Color {
     #[must_use]
     pub const fn next(self) -> Self {
-        match self {
-            Color::Black => Color::Blue,
-            Color::Blue => Color::Green,
-            Color::Green => Color::Red,
-            Color::Red => Color::Black,
-        }
+        ALL_COLORS[(self as usize + 1) % NUM_COLORS]
     }
 
     #[must_use]
     pub const fn prev(self) -> Self {
-        match self {
-            Color::Black => Color::Red,
-            Color::Blue => Color::Black,
-            Color::Green => Color::Blue,
-            Color::Red => Color::Green,
-        }
+        ALL_COLORS[(self as usize + NUM_COLORS - 1) % NUM_COLORS]
     }
 
     #[must_use]
     pub const fn partner(self) -> Self {
-        self.next().next()
+        ALL_COLORS[(self as usize + NUM_TEAMS) % NUM_COLORS]
+    }
+
+    /// Index of the team of this color, the first [`NUM_TEAMS`] colors are in different teams.
+    #[must_use]
+    pub const fn team(self) -> usize {
+        self as usize % NUM_TEAMS
     }
 
     #[must_use]
     pub const fn home(self) -> Square {
-        match self {
-            Color::Black => Square(0),
-            Color::Blue => Square(16),
-            Color::Green => Square(32),
-            Color::Red => Square(48),
-        }
+        Square(self as u8 * SQUARES_PER_COLOR)
     }
 
     #[must_use]
@@ -72,15 +88,114 @@ impl Color {
     }
 }
 
+/// Moves every color the same number of seats further in playing order.
+/// Partners and neighbours stay partners and neighbours, so positions that only differ by
+/// such a rotation are strategically the same.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ColorPermutation(u8);
+
+impl ColorPermutation {
+    pub const IDENTITY: Self = Self(0);
+
+    #[must_use]
+    pub const fn rotation(seats: usize) -> Self {
+        Self((seats % NUM_COLORS) as u8)
+    }
+
+    /// The rotation that turns `from` into `to`.
+    #[must_use]
+    pub fn between(from: Color, to: Color) -> Self {
+        Self::rotation(to as usize + NUM_COLORS - from as usize)
+    }
+
+    #[must_use]
+    pub const fn seats(self) -> usize {
+        self.0 as usize
+    }
+
+    /// The rotation that undoes this one.
+    #[must_use]
+    pub const fn inverse(self) -> Self {
+        Self::rotation(NUM_COLORS - self.0 as usize)
+    }
+
+    #[must_use]
+    pub fn color(self, color: Color) -> Color {
+        Color::from((color as usize + self.seats()) % NUM_COLORS)
+    }
+
+    #[must_use]
+    pub const fn square(self, square: Square) -> Square {
+        square.add(Distance::new(self.0 * SQUARES_PER_COLOR))
+    }
+
+    #[must_use]
+    pub const fn bitboard(self, bitboard: BitBoard) -> BitBoard {
+        bitboard.rotate_left(self.0 * SQUARES_PER_COLOR)
+    }
+
+    /// Moves the entry of every color to the entry of its new color.
+    #[must_use]
+    pub fn array<T>(self, values: [T; NUM_COLORS]) -> [T; NUM_COLORS] {
+        let mut values = values;
+        values.rotate_right(self.seats());
+        values
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Distance;
+
     #[test]
     fn between() {
         for color in ALL_COLORS {
             assert_eq!(color.between(color.next()), 0);
-            assert_eq!(color.between(color.prev()), 2);
-            assert_eq!(color.between(color.partner()), 1);
+            assert_eq!(color.between(color.prev()), NUM_COLORS - 2);
+            assert_eq!(color.between(color.partner()), NUM_TEAMS - 1);
+            assert_eq!(color.partner().partner(), color);
+            assert_eq!(color.partner().team(), color.team());
+            assert_ne!(color.next().team(), color.team());
+            assert_eq!(Color::from(color as usize), color);
+        }
+    }
+
+    #[test]
+    fn homes() {
+        for color in ALL_COLORS {
+            let quarter = Distance::new(SQUARES_PER_COLOR);
+            assert_eq!(color.next().home(), color.home().add(quarter));
+            assert_eq!(color.home().distance_to_home(color), Distance::RING);
+            assert_eq!(
+                color.home().sub(Distance::ONE).distance_to_home(color),
+                Distance::ONE
+            );
+            assert_eq!(color.home().distance_to_home(color.next()), quarter);
+        }
+    }
+
+    #[test]
+    fn permutations() {
+        for seats in 0..NUM_COLORS {
+            let perm = ColorPermutation::rotation(seats);
+            let values = perm.array(ALL_COLORS);
+            for color in ALL_COLORS {
+                let rotated = perm.color(color);
+                assert_eq!(ColorPermutation::between(color, rotated), perm);
+                assert_eq!(perm.inverse().color(rotated), color);
+                assert_eq!(perm.square(color.home()), rotated.home());
+                assert_eq!(
+                    perm.bitboard(color.home().bitboard()),
+                    rotated.home().bitboard()
+                );
+                assert_eq!(values[rotated as usize], color);
+                assert_eq!(perm.color(color.partner()), rotated.partner());
+            }
         }
+        assert_eq!(
+            ColorPermutation::rotation(NUM_COLORS + 1),
+            ColorPermutation::rotation(1)
+        );
     }
 }
@@ -1,5 +1,6 @@
 use crate::{Card, NUM_CARDS};
 use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
 
 const DECK: [(Card, u8); NUM_CARDS] = {
     #[allow(clippy::enum_glob_use)]
@@ -26,7 +27,15 @@ const DECK: [(Card, u8); NUM_CARDS] = {
     ]
 };
 
-#[derive(Debug, Clone)]
+/// Cards handed out by each deal of a deck, five per player and six in the last deal.
+#[cfg(not(feature = "six-players"))]
+const DEALS: [usize; 5] = [20, 20, 20, 20, 24];
+/// Cards handed out by each deal of a deck, five per player in the first deal and four
+/// afterwards. The last two cards of the deck are never dealt.
+#[cfg(feature = "six-players")]
+const DEALS: [usize; 4] = [30, 24, 24, 24];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Deck {
     cards: [(Card, u8); NUM_CARDS],
     times_dealt: u8,
@@ -49,10 +58,10 @@ impl Deck {
 
     #[allow(clippy::missing_panics_doc)]
     pub fn deal<R: Rng>(&mut self, rng: &mut R) -> Vec<Card> {
-        if self.times_dealt == 5 {
+        if usize::from(self.times_dealt) == DEALS.len() {
             *self = Self::default();
         }
-        let deal_amount = if self.times_dealt == 4 { 24 } else { 20 };
+        let deal_amount = DEALS[usize::from(self.times_dealt)];
         let mut cards = Vec::new();
         (0..deal_amount).for_each(|_| {
             let card = self.draw_one(rng);
@@ -63,12 +72,14 @@ impl Deck {
         cards
     }
 
+    /// Removes a specific card, e.g. one that is known to be in a hand.
     pub fn take(&mut self, card: Card) {
         let amount = &mut self.cards[card as usize].1;
         debug_assert!(*amount > 0);
         *amount -= 1;
     }
 
+    /// Returns a card taken from the deck, e.g. from a hand that gets dealt again.
     pub fn put_back(&mut self, card: Card) {
         self.cards[card as usize].1 += 1;
         debug_assert!(self.cards[card as usize].1 <= card.amount());
@@ -85,8 +96,83 @@ impl Deck {
         *card
     }
 
+    /// Returns how many cards of this type are left in the deck.
+    #[must_use]
+    pub fn remaining(&self, card: Card) -> u8 {
+        self.cards[card as usize].1
+    }
+
+    /// Returns every card left in the deck, copies of a card follow each other.
+    pub fn cards(&self) -> impl Iterator<Item = Card> + '_ {
+        self.cards
+            .iter()
+            .flat_map(|(card, amount)| std::iter::repeat_n(*card, *amount as usize))
+    }
+
+    /// Returns how many cards are left in the deck.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.cards.iter().map(|(_, amount)| *amount as usize).sum()
+    }
+
+    /// Returns the probability that the next card drawn from the deck is `card`.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn probability_of(&self, card: Card) -> f64 {
+        match self.size() {
+            0 => 0.0,
+            size => f64::from(self.remaining(card)) / size as f64,
+        }
+    }
+
+    /// Returns how many cards the last deal handed out, 0 before the first one.
+    #[must_use]
+    pub fn last_deal_size(&self) -> usize {
+        match self.times_dealt {
+            0 => 0,
+            dealt => DEALS[usize::from(dealt) - 1],
+        }
+    }
+
     #[must_use]
     pub fn fresh(&self) -> bool {
         self.times_dealt == 1
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::CARDS;
+
+    #[test]
+    fn probabilities() {
+        let mut deck = Deck::new();
+        let total: f64 = CARDS.iter().map(|card| deck.probability_of(*card)).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert_eq!(deck.remaining(Card::Tac), Card::Tac.amount());
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let size = deck.size();
+        let hand = deck.deal(&mut rng);
+        assert_eq!(deck.size(), size - hand.len());
+        for card in CARDS {
+            let dealt = hand.iter().filter(|c| **c == card).count();
+            assert_eq!(
+                deck.remaining(card) as usize + dealt,
+                card.amount() as usize
+            );
+        }
+        assert_eq!(deck.cards().count(), deck.size());
+        let card = deck.cards().next().unwrap();
+        deck.take(card);
+        assert!(deck.cards().filter(|c| *c == card).count() < card.amount() as usize);
+        deck.put_back(card);
+        while deck.size() > 0 {
+            deck.draw_one(&mut rng);
+        }
+        assert!(CARDS.iter().all(|card| deck.probability_of(*card) == 0.0));
+    }
+}
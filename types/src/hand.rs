@@ -1,46 +1,133 @@
-use crate::Card;
-use smallvec::SmallVec;
+use crate::{Card, CARDS};
+use enum_map::EnumMap;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
-pub struct Hand(pub SmallVec<Card, 6>);
+/// Cards of a player, stored as the number of copies of each card.
+/// Iterating yields the cards sorted, copies of a card follow each other.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<Card>", into = "Vec<Card>")]
+pub struct Hand {
+    counts: EnumMap<Card, u8>,
+    amount: u8,
+}
 
 impl Hand {
     #[must_use]
     pub fn new(cards: Vec<Card>) -> Self {
-        Self(cards.into())
+        let mut hand = Self::default();
+        for card in cards {
+            hand.push(card);
+        }
+        hand
     }
 
     #[must_use]
     pub fn amount(&self) -> usize {
-        self.0.len()
+        usize::from(self.amount)
     }
 
     #[must_use]
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.amount == 0
     }
 
     pub fn push(&mut self, card: Card) {
-        self.0.push(card);
+        self.counts[card] += 1;
+        self.amount += 1;
     }
 
-    /// # Panics
-    /// If card is not in hand
+    /// Removes one copy of `card`, returns `false` if there is none.
     pub fn remove(&mut self, card: Card) -> bool {
-        let Some(pos) = self.0.iter().position(|x| *x == card) else {
+        if self.counts[card] == 0 {
             return false;
-        };
-        // .unwrap_or_else(|| panic!("We require the card to be in hand {card:?} {self:?}")),
-        self.0.swap_remove(pos);
+        }
+        self.counts[card] -= 1;
+        self.amount -= 1;
         true
     }
 
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
     #[must_use]
     pub fn contains(&self, card: Card) -> bool {
-        self.0.iter().any(|c| *c == card)
+        self.counts[card] > 0
+    }
+
+    /// Returns how many copies of `card` are in the hand.
+    #[must_use]
+    pub fn count(&self, card: Card) -> u8 {
+        self.counts[card]
+    }
+
+    /// Returns every card in the hand once together with the number of copies.
+    #[must_use]
+    pub fn iter_counts(&self) -> impl DoubleEndedIterator<Item = (Card, u8)> + '_ {
+        self.counts
+            .iter()
+            .filter(|(_, count)| **count > 0)
+            .map(|(card, count)| (card, *count))
     }
 
     pub fn iter(&self) -> impl Iterator<Item = &Card> + '_ {
-        self.0.iter()
+        let cards: &'static [Card] = &CARDS;
+        cards
+            .iter()
+            .flat_map(|card| std::iter::repeat_n(card, usize::from(self.counts[*card])))
+    }
+}
+
+impl From<Vec<Card>> for Hand {
+    fn from(cards: Vec<Card>) -> Self {
+        Self::new(cards)
+    }
+}
+
+impl From<Hand> for Vec<Card> {
+    fn from(hand: Hand) -> Self {
+        hand.iter().copied().collect()
+    }
+}
+
+impl FromIterator<Card> for Hand {
+    fn from_iter<T: IntoIterator<Item = Card>>(iter: T) -> Self {
+        let mut hand = Self::default();
+        for card in iter {
+            hand.push(card);
+        }
+        hand
+    }
+}
+
+impl std::fmt::Debug for Hand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiset() {
+        let mut hand = Hand::new(vec![Card::Tac, Card::One, Card::Tac]);
+        assert_eq!(hand.amount(), 3);
+        assert_eq!(hand.count(Card::Tac), 2);
+        assert_eq!(
+            hand.iter().copied().collect::<Vec<_>>(),
+            vec![Card::One, Card::Tac, Card::Tac]
+        );
+        assert_eq!(
+            hand.iter_counts().collect::<Vec<_>>(),
+            vec![(Card::One, 1), (Card::Tac, 2)]
+        );
+        assert!(hand.remove(Card::Tac));
+        assert!(!hand.remove(Card::Two));
+        assert_eq!(hand.count(Card::Tac), 1);
+        assert!(hand.contains(Card::One));
+        hand.clear();
+        assert!(hand.is_empty());
     }
 }
@@ -1,6 +1,71 @@
 use std::fmt::Display;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash)]
+use serde::{Deserialize, Serialize};
+
+/// Number of squares in a home.
+pub const HOME_SIZE: u8 = 4;
+
+/// A square inside a home, `0` is the square closest to the ring.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Serialize, Deserialize,
+)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct HomeSquare(u8);
+
+impl HomeSquare {
+    pub const ALL: [Self; HOME_SIZE as usize] = [Self(0), Self(1), Self(2), Self(3)];
+
+    /// # Panics
+    /// If `index` is outside of the home.
+    #[must_use]
+    pub const fn new(index: u8) -> Self {
+        assert!(index < HOME_SIZE, "Home squares are below 4");
+        Self(index)
+    }
+
+    /// Returns `None` if `index` is outside of the home.
+    #[must_use]
+    pub const fn try_new(index: u8) -> Option<Self> {
+        if index < HOME_SIZE {
+            Some(Self(index))
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub const fn index(self) -> u8 {
+        self.0
+    }
+
+    const fn bit(self) -> u8 {
+        1 << self.0
+    }
+}
+
+impl TryFrom<u8> for HomeSquare {
+    type Error = String;
+
+    fn try_from(index: u8) -> Result<Self, Self::Error> {
+        Self::try_new(index).ok_or_else(|| format!("{index} is outside of the home"))
+    }
+}
+
+impl From<HomeSquare> for u8 {
+    fn from(square: HomeSquare) -> Self {
+        square.0
+    }
+}
+
+impl Display for HomeSquare {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Serialize, Deserialize,
+)]
 pub struct Home(pub u8);
 
 impl Home {
@@ -12,28 +77,29 @@ impl Home {
         self.0 == Self::EMPTY.0
     }
 
-    pub fn xor(&mut self, pos: u8) {
-        self.0 ^= 1 << pos;
+    pub fn xor(&mut self, pos: HomeSquare) {
+        self.0 ^= pos.bit();
     }
 
-    pub fn set(&mut self, pos: u8) {
+    pub fn set(&mut self, pos: HomeSquare) {
         debug_assert!(self.is_free(pos));
         self.xor(pos);
     }
 
-    pub fn unset(&mut self, pos: u8) {
+    pub fn unset(&mut self, pos: HomeSquare) {
         debug_assert!(!self.is_free(pos));
         self.xor(pos);
     }
 
+    /// Number of free squares in front of the first ball, [`HOME_SIZE`] if the home is empty.
     #[must_use]
     pub const fn free(self) -> u8 {
         (self.0 | 0b10000).trailing_zeros() as u8
     }
 
     #[must_use]
-    pub const fn is_free(self, pos: u8) -> bool {
-        (self.0 & (1 << pos)) == 0
+    pub const fn is_free(self, pos: HomeSquare) -> bool {
+        (self.0 & pos.bit()) == 0
     }
 
     #[must_use]
@@ -52,15 +118,15 @@ impl Home {
     }
 
     #[must_use]
-    pub const fn get_single_unlocked(self) -> Option<u8> {
+    pub const fn get_single_unlocked(self) -> Option<HomeSquare> {
         if !self.is_locked() && !self.is_empty() {
-            return Some(self.free());
+            return Some(HomeSquare::new(self.free()));
         }
         None
     }
 
     #[must_use]
-    pub fn get_all_unlocked(self) -> Vec<u8> {
+    pub fn get_all_unlocked(self) -> Vec<HomeSquare> {
         let mut home = self;
         let mut res = Vec::new();
         while let Some(unlocked) = home.get_single_unlocked() {
@@ -83,27 +149,40 @@ mod tests {
 
     #[test]
     fn get_single_unlocked() {
+        let [h0, h1, h2, h3] = HomeSquare::ALL;
         let mut home = Home::EMPTY;
 
         assert_eq!(home.get_single_unlocked(), None);
-        home.xor(0);
-        assert_eq!(home.get_single_unlocked(), Some(0));
-        home.xor(3);
-        assert_eq!(home.get_single_unlocked(), Some(0));
-        home.xor(0);
+        home.xor(h0);
+        assert_eq!(home.get_single_unlocked(), Some(h0));
+        home.xor(h3);
+        assert_eq!(home.get_single_unlocked(), Some(h0));
+        home.xor(h0);
         assert_eq!(home.get_single_unlocked(), None);
-        home.xor(2);
+        home.xor(h2);
         assert_eq!(home.get_single_unlocked(), None);
-        home.xor(1);
-        home.xor(2);
-        assert_eq!(home.get_single_unlocked(), Some(1));
+        home.xor(h1);
+        home.xor(h2);
+        assert_eq!(home.get_single_unlocked(), Some(h1));
     }
 
     #[test]
     fn get_all_unlocked() {
         let mut home = Home::EMPTY;
-        home.xor(2);
-        home.xor(0);
-        assert_eq!(home.get_all_unlocked(), vec![0, 2]);
+        home.xor(HomeSquare::new(2));
+        home.xor(HomeSquare::new(0));
+        assert_eq!(
+            home.get_all_unlocked(),
+            vec![HomeSquare::new(0), HomeSquare::new(2)]
+        );
+    }
+
+    #[test]
+    fn home_square() {
+        assert_eq!(HomeSquare::try_new(3).map(HomeSquare::index), Some(3));
+        assert_eq!(HomeSquare::try_new(HOME_SIZE), None);
+        // Serialized as the index, which is checked when deserializing
+        assert_eq!(u8::from(HomeSquare::new(2)), 2);
+        assert!(HomeSquare::try_from(4).is_err());
     }
 }
@@ -1,7 +1,7 @@
 #![warn(clippy::pedantic)]
 // NOTE We allow this here because all truncations are values
-// returned from functions that depend on the number of bits on a u64
-//  which can never exceed 64 which fits into u8
+// returned from functions that depend on the number of bits of a bitboard
+//  which can never exceed 128 which fits into u8
 #![allow(clippy::cast_possible_truncation, clippy::inline_always)]
 pub mod bitboard;
 pub mod card;
@@ -9,6 +9,7 @@ pub mod color;
 pub mod deck;
 pub mod hand;
 pub mod home;
+pub mod notation;
 pub mod square;
 pub mod tacmove;
 
@@ -18,5 +19,6 @@ pub use color::*;
 pub use deck::*;
 pub use hand::*;
 pub use home::*;
+pub use notation::*;
 pub use square::*;
 pub use tacmove::*;
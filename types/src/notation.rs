@@ -0,0 +1,365 @@
+//! Compact text notation for moves.
+//!
+//! A move is written as `<card>:<action>@<played by>`, followed by `><played for>` if the
+//! move is played for the partner, e.g. `7:S3-5/S10-12@K` or `TAC:ENTER@U>R`.
+//!
+//! - Cards are their value (`1` to `13`) or the first three letters of their name
+//!   (`TRI`, `JES`, `ANG`, `DEV`, `WAR`, `TAC`)
+//! - Colors are `K` for Black, `U` for Blue, `G` for Green and `R` for Red, with six players
+//!   also `Y` for Yellow and `P` for Purple
+//! - Steps on the ring are `S<from>-<to>`, steps in home `H<from>-<to>` and steps from the
+//!   ring into home `I<from>-<to>`. Switching balls is `X<square>-<square>` and a warrior
+//!   move `W<from>-<to>`
+//! - The steps of a seven are separated by `/`, a seven with a single step ends with `/`
+//! - Announcements are `ANNOUNCE+` for an opening and `ANNOUNCE-` for none
+//! - All other actions are their name in capitals, e.g. `ENTER`, `DISCARD` or `TRADE`
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{Card, Color, HomeSquare, Square, TacAction, TacMove, CARDS, HOME_SIZE, RING_SIZE};
+
+/// Text that isn't a move in the notation of this module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseMoveError(pub String);
+
+impl Display for ParseMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid move notation: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseMoveError {}
+
+fn card_token(card: Card) -> &'static str {
+    match card {
+        Card::One => "1",
+        Card::Two => "2",
+        Card::Three => "3",
+        Card::Four => "4",
+        Card::Five => "5",
+        Card::Six => "6",
+        Card::Seven => "7",
+        Card::Eight => "8",
+        Card::Nine => "9",
+        Card::Ten => "10",
+        Card::Twelve => "12",
+        Card::Thirteen => "13",
+        Card::Trickster => "TRI",
+        Card::Jester => "JES",
+        Card::Angel => "ANG",
+        Card::Devil => "DEV",
+        Card::Warrior => "WAR",
+        Card::Tac => "TAC",
+    }
+}
+
+fn color_token(color: Color) -> char {
+    match color {
+        Color::Black => 'K',
+        Color::Blue => 'U',
+        Color::Green => 'G',
+        Color::Red => 'R',
+        #[cfg(feature = "six-players")]
+        Color::Yellow => 'Y',
+        #[cfg(feature = "six-players")]
+        Color::Purple => 'P',
+    }
+}
+
+fn parse_card(s: &str) -> Result<Card, ParseMoveError> {
+    CARDS
+        .into_iter()
+        .find(|card| card_token(*card) == s)
+        .ok_or_else(|| ParseMoveError(format!("unknown card `{s}`")))
+}
+
+fn parse_color(s: &str) -> Result<Color, ParseMoveError> {
+    match s {
+        "K" => Ok(Color::Black),
+        "U" => Ok(Color::Blue),
+        "G" => Ok(Color::Green),
+        "R" => Ok(Color::Red),
+        #[cfg(feature = "six-players")]
+        "Y" => Ok(Color::Yellow),
+        #[cfg(feature = "six-players")]
+        "P" => Ok(Color::Purple),
+        _ => Err(ParseMoveError(format!("unknown color `{s}`"))),
+    }
+}
+
+fn parse_number(s: &str, max: u8) -> Result<u8, ParseMoveError> {
+    s.parse::<u8>()
+        .ok()
+        .filter(|n| *n < max)
+        .ok_or_else(|| ParseMoveError(format!("`{s}` isn't a number below {max}")))
+}
+
+/// Parses `<from>-<to>` where both are below the given bounds.
+fn parse_pair(s: &str, max_from: u8, max_to: u8) -> Result<(u8, u8), ParseMoveError> {
+    let (from, to) = s
+        .split_once('-')
+        .ok_or_else(|| ParseMoveError(format!("expected `<from>-<to>` in `{s}`")))?;
+    Ok((parse_number(from, max_from)?, parse_number(to, max_to)?))
+}
+
+fn parse_step(s: &str) -> Result<TacAction, ParseMoveError> {
+    match s.split_at_checked(1) {
+        Some(("S", rest)) => {
+            let (from, to) = parse_pair(rest, RING_SIZE, RING_SIZE)?;
+            Ok(TacAction::Step {
+                from: Square(from),
+                to: Square(to),
+            })
+        }
+        Some(("H", rest)) => {
+            let (from, to) = parse_pair(rest, HOME_SIZE, HOME_SIZE)?;
+            Ok(TacAction::StepHome {
+                from: HomeSquare::new(from),
+                to: HomeSquare::new(to),
+            })
+        }
+        Some(("I", rest)) => {
+            let (from, to) = parse_pair(rest, RING_SIZE, HOME_SIZE)?;
+            Ok(TacAction::StepInHome {
+                from: Square(from),
+                to: HomeSquare::new(to),
+            })
+        }
+        _ => Err(ParseMoveError(format!("unknown step `{s}`"))),
+    }
+}
+
+impl Display for TacAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TacAction::Step { from, to } => write!(f, "S{}-{}", from.0, to.0),
+            TacAction::StepHome { from, to } => write!(f, "H{from}-{to}"),
+            TacAction::StepInHome { from, to } => write!(f, "I{}-{to}", from.0),
+            TacAction::Trickster { target1, target2 } => {
+                write!(f, "X{}-{}", target1.0, target2.0)
+            }
+            TacAction::Warrior { from, to } => write!(f, "W{}-{}", from.0, to.0),
+            TacAction::Enter => write!(f, "ENTER"),
+            TacAction::Suspend => write!(f, "SUSPEND"),
+            TacAction::Jester => write!(f, "JESTER"),
+            TacAction::Devil => write!(f, "DEVIL"),
+            TacAction::Discard => write!(f, "DISCARD"),
+            TacAction::Trade => write!(f, "TRADE"),
+            TacAction::Announce { opening: true } => write!(f, "ANNOUNCE+"),
+            TacAction::Announce { opening: false } => write!(f, "ANNOUNCE-"),
+            TacAction::SevenSteps { steps } => {
+                for (idx, step) in steps.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, "/")?;
+                    }
+                    write!(f, "{step}")?;
+                }
+                // Otherwise a single step couldn't be told apart from a simple move
+                if steps.len() <= 1 {
+                    write!(f, "/")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for TacAction {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains('/') {
+            let steps = s
+                .split('/')
+                .filter(|step| !step.is_empty())
+                .map(parse_step)
+                .collect::<Result<_, _>>()?;
+            return Ok(TacAction::SevenSteps { steps });
+        }
+        match s {
+            "ENTER" => return Ok(TacAction::Enter),
+            "SUSPEND" => return Ok(TacAction::Suspend),
+            "JESTER" => return Ok(TacAction::Jester),
+            "DEVIL" => return Ok(TacAction::Devil),
+            "DISCARD" => return Ok(TacAction::Discard),
+            "TRADE" => return Ok(TacAction::Trade),
+            "ANNOUNCE+" => return Ok(TacAction::Announce { opening: true }),
+            "ANNOUNCE-" => return Ok(TacAction::Announce { opening: false }),
+            _ => {}
+        }
+        match s.split_at_checked(1) {
+            Some(("X", rest)) => {
+                let (target1, target2) = parse_pair(rest, RING_SIZE, RING_SIZE)?;
+                Ok(TacAction::Trickster {
+                    target1: Square(target1),
+                    target2: Square(target2),
+                })
+            }
+            Some(("W", rest)) => {
+                let (from, to) = parse_pair(rest, RING_SIZE, RING_SIZE)?;
+                Ok(TacAction::Warrior {
+                    from: Square(from),
+                    to: Square(to),
+                })
+            }
+            _ => parse_step(s).map_err(|_| ParseMoveError(format!("unknown action `{s}`"))),
+        }
+    }
+}
+
+impl Display for TacMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}@{}",
+            card_token(self.card),
+            self.action,
+            color_token(self.played_by)
+        )?;
+        if self.played_for != self.played_by {
+            write!(f, ">{}", color_token(self.played_for))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for TacMove {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (card, rest) = s
+            .split_once(':')
+            .ok_or_else(|| ParseMoveError(format!("expected `<card>:` in `{s}`")))?;
+        let (action, colors) = rest
+            .rsplit_once('@')
+            .ok_or_else(|| ParseMoveError(format!("expected `@<color>` in `{s}`")))?;
+        let (played_by, played_for) = if let Some((by, played_for)) = colors.split_once('>') {
+            (parse_color(by)?, parse_color(played_for)?)
+        } else {
+            let by = parse_color(colors)?;
+            (by, by)
+        };
+        Ok(TacMove::new(
+            parse_card(card)?,
+            action.parse()?,
+            played_for,
+            played_by,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ALL_COLORS;
+
+    #[test]
+    fn roundtrip() {
+        let actions = [
+            TacAction::Step {
+                from: Square(60),
+                to: Square(3),
+            },
+            TacAction::StepHome {
+                from: HomeSquare::new(0),
+                to: HomeSquare::new(3),
+            },
+            TacAction::StepInHome {
+                from: Square(62),
+                to: HomeSquare::new(2),
+            },
+            TacAction::Trickster {
+                target1: Square(5),
+                target2: Square(63),
+            },
+            TacAction::Enter,
+            TacAction::Suspend,
+            TacAction::Jester,
+            TacAction::Devil,
+            TacAction::Warrior {
+                from: Square(12),
+                to: Square(40),
+            },
+            TacAction::Discard,
+            TacAction::Trade,
+            TacAction::SevenSteps {
+                steps: vec![TacAction::Step {
+                    from: Square(20),
+                    to: Square(27),
+                }],
+            },
+            TacAction::SevenSteps {
+                steps: vec![
+                    TacAction::StepHome {
+                        from: HomeSquare::new(1),
+                        to: HomeSquare::new(2),
+                    },
+                    TacAction::Step {
+                        from: Square(61),
+                        to: Square(1),
+                    },
+                    TacAction::StepInHome {
+                        from: Square(63),
+                        to: HomeSquare::new(0),
+                    },
+                ],
+            },
+            TacAction::Announce { opening: true },
+            TacAction::Announce { opening: false },
+        ];
+        for card in CARDS {
+            for action in &actions {
+                for played_by in ALL_COLORS {
+                    for played_for in [played_by, played_by.partner()] {
+                        let mv = TacMove::new(card, action.clone(), played_for, played_by);
+                        assert_eq!(mv.to_string().parse::<TacMove>(), Ok(mv));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn notation() {
+        let mv = TacMove::new(
+            Card::Seven,
+            TacAction::SevenSteps {
+                steps: vec![
+                    TacAction::Step {
+                        from: Square(3),
+                        to: Square(5),
+                    },
+                    TacAction::Step {
+                        from: Square(10),
+                        to: Square(15),
+                    },
+                ],
+            },
+            Color::Black,
+            Color::Black,
+        );
+        assert_eq!(mv.to_string(), "7:S3-5/S10-15@K");
+        let mv = TacMove::new(Card::Tac, TacAction::Enter, Color::Red, Color::Blue);
+        assert_eq!(mv.to_string(), "TAC:ENTER@U>R");
+        assert_eq!(" TAC:ENTER@U>R\n".parse(), Ok(mv));
+    }
+
+    #[test]
+    fn invalid() {
+        for s in [
+            "",
+            "7",
+            "7:S3-5",
+            "11:S3-5@K",
+            "7:S3-5/@X",
+            &format!("1:S3-{RING_SIZE}@K"),
+            "1:H3-4@K",
+            "1:S3@K",
+            "1:ENTRY@K",
+            "TRI:X1-2/@K",
+        ] {
+            assert!(s.parse::<TacMove>().is_err(), "{s}");
+        }
+    }
+}
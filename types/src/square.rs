@@ -1,6 +1,15 @@
+use std::fmt::Display;
+
 use serde::{Deserialize, Serialize};
 
-use crate::{bitboard::BitBoard, color::Color};
+use crate::{
+    bitboard::BitBoard,
+    color::{Color, NUM_COLORS, SQUARES_PER_COLOR},
+};
+
+/// Number of squares on the ring.
+#[allow(clippy::cast_possible_truncation)]
+pub const RING_SIZE: u8 = SQUARES_PER_COLOR * NUM_COLORS as u8;
 
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Serialize, Deserialize,
@@ -9,46 +18,43 @@ pub struct Square(pub u8);
 
 impl Square {
     const MIN: Square = Square(0);
-    const MAX: Square = Square(63);
+    const MAX: Square = Square(RING_SIZE - 1);
 
     #[must_use]
     pub const fn bitboard(self) -> BitBoard {
         BitBoard(1 << self.0)
     }
 
-    // Wrap square around range 0 to 63.
-    // NOTE This only works if square value is 127 at most
+    // Wrap square around the ring.
+    // NOTE This only works if square value is less than two times the ring size
     #[must_use]
     pub const fn make_valid(self) -> Self {
-        Self(self.0 & 63)
+        Self(self.0 % RING_SIZE)
     }
 
     #[must_use]
-    pub const fn add(self, amount: u8) -> Self {
-        Self(self.0 + amount).make_valid()
+    pub const fn add(self, amount: Distance) -> Self {
+        Self(self.0 + amount.0).make_valid()
     }
 
     #[must_use]
-    pub const fn sub(self, amount: u8) -> Self {
-        Self(self.0 + 64 - amount).make_valid()
+    pub const fn sub(self, amount: Distance) -> Self {
+        Self(self.0 + RING_SIZE - amount.0).make_valid()
     }
 
+    /// Steps forward until the home square of `color` is passed, between 1 and `RING_SIZE`.
     #[must_use]
-    pub const fn distance_to_home(self, color: Color) -> u8 {
-        64 - (match color {
-            Color::Black => self.0,
-            Color::Blue => self.0 + 48,
-            Color::Green => self.0 + 32,
-            Color::Red => self.0 + 16,
-        } & 63)
+    pub const fn distance_to_home(self, color: Color) -> Distance {
+        Distance(RING_SIZE - (self.0 + RING_SIZE - color.home().0) % RING_SIZE)
     }
 
+    /// Steps forward to reach `other`, between 0 and `RING_SIZE - 1`.
     #[must_use]
-    pub fn distance_to(self, other: Square) -> u8 {
-        if self <= other {
-            other.0 - self.0
+    pub const fn distance_to(self, other: Square) -> Distance {
+        if self.0 <= other.0 {
+            Distance(other.0 - self.0)
         } else {
-            64 - (self.0 - other.0)
+            Distance(RING_SIZE - (self.0 - other.0))
         }
     }
 
@@ -62,3 +68,90 @@ impl Square {
         self == Self::MAX
     }
 }
+
+/// Number of squares moved on the ring, at most one full round.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Serialize, Deserialize,
+)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct Distance(u8);
+
+impl Distance {
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(1);
+    /// One full round around the ring.
+    pub const RING: Self = Self(RING_SIZE);
+
+    /// # Panics
+    /// If `steps` is more than one round around the ring.
+    #[must_use]
+    pub const fn new(steps: u8) -> Self {
+        assert!(steps <= RING_SIZE, "Distances are at most one round");
+        Self(steps)
+    }
+
+    /// Returns `None` if `steps` is more than one round around the ring.
+    #[must_use]
+    pub const fn try_new(steps: u8) -> Option<Self> {
+        if steps <= RING_SIZE {
+            Some(Self(steps))
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub const fn steps(self) -> u8 {
+        self.0
+    }
+
+    /// Distance when going the other way around the ring to the same square.
+    #[must_use]
+    pub const fn reverse(self) -> Self {
+        Self(RING_SIZE - self.0)
+    }
+}
+
+impl TryFrom<u8> for Distance {
+    type Error = String;
+
+    fn try_from(steps: u8) -> Result<Self, Self::Error> {
+        Self::try_new(steps).ok_or_else(|| format!("{steps} steps are more than one round"))
+    }
+}
+
+impl From<Distance> for u8 {
+    fn from(distance: Distance) -> Self {
+        distance.0
+    }
+}
+
+impl Display for Distance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        let late = Square(RING_SIZE - 4);
+        assert_eq!(late.add(Distance::new(6)), Square(2));
+        assert_eq!(Square(2).sub(Distance::new(6)), late);
+        assert_eq!(Square(5).add(Distance::RING), Square(5));
+        assert_eq!(late.distance_to(Square(2)), Distance::new(6));
+        assert_eq!(Square(2).distance_to(Square(2)), Distance::ZERO);
+        assert_eq!(Distance::new(4).reverse(), Distance::new(RING_SIZE - 4));
+        assert!(Distance::try_new(RING_SIZE + 1).is_none());
+        assert!(Distance::try_from(RING_SIZE).is_ok());
+        for start in 0..RING_SIZE {
+            for end in 0..RING_SIZE {
+                let (start, end) = (Square(start), Square(end));
+                assert_eq!(start.add(start.distance_to(end)), end);
+            }
+        }
+    }
+}
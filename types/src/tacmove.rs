@@ -1,90 +1,457 @@
 use std::fmt::Display;
 
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use smallvec::SmallVec;
 
-use crate::{square::Square, Card, Color};
+use crate::{
+    square::{Distance, Square, RING_SIZE},
+    Card, Color, ColorPermutation, HomeSquare, CARDS, NUM_CARDS, NUM_COLORS,
+};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TacAction {
-    Step { from: Square, to: Square },
-    // TODO HomeSquare type
-    StepHome { from: u8, to: u8 },
-    StepInHome { from: Square, to: u8 },
-    Trickster { target1: Square, target2: Square },
+    Step {
+        from: Square,
+        to: Square,
+    },
+    StepHome {
+        from: HomeSquare,
+        to: HomeSquare,
+    },
+    StepInHome {
+        from: Square,
+        to: HomeSquare,
+    },
+    Trickster {
+        target1: Square,
+        target2: Square,
+    },
     Enter,
     Suspend,
     Jester,
     Devil,
-    Warrior { from: Square, to: Square },
+    Warrior {
+        from: Square,
+        to: Square,
+    },
     Discard,
     Trade,
-    SevenSteps { steps: Vec<TacAction> },
+    SevenSteps {
+        #[serde(
+            serialize_with = "serialize_steps",
+            deserialize_with = "deserialize_steps"
+        )]
+        steps: Vec<TacAction>,
+    },
+    /// Tells the others whether the player can open, before trading.
+    /// Announcements don't have to be true, the card of the move has no meaning.
+    Announce {
+        opening: bool,
+    },
 }
 
-impl Display for TacAction {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl TacAction {
+    /// Brings actions that only differ in representation into the same form.
+    /// See [`TacMove::canonicalize`].
+    pub fn canonicalize(&mut self) {
         match self {
-            TacAction::Step { from, to } => {
-                write!(f, "Step {} {}", from.0, to.0)?;
-            }
-            TacAction::StepHome { from, to } => {
-                write!(f, "Home {from} {to}")?;
+            TacAction::Trickster { target1, target2 } => {
+                if target2 < target1 {
+                    std::mem::swap(target1, target2);
+                }
             }
-            TacAction::StepInHome { from, to } => {
-                write!(f, "In home {} {}", from.0, to)?;
+            TacAction::SevenSteps { steps } => {
+                steps.sort_by_key(|step| match *step {
+                    TacAction::StepHome { from, .. } => (0, from.index()),
+                    TacAction::StepInHome { from, .. } => (1, from.0),
+                    TacAction::Step { from, .. } => (2, from.0),
+                    _ => unreachable!("Seven steps only contain steps"),
+                });
             }
-            TacAction::Trickster { target1, target2 } => {
-                write!(f, "Switch {} {}", target1.0, target2.0)?;
+            _ => {}
+        }
+    }
+
+    /// Returns the action with the colors rotated by `perm`, in canonical form.
+    /// Home squares are relative to the owner and stay the same.
+    #[must_use]
+    pub fn permuted(&self, perm: ColorPermutation) -> Self {
+        let square = |sq| perm.square(sq);
+        let mut action = match *self {
+            TacAction::Step { from, to } => TacAction::Step {
+                from: square(from),
+                to: square(to),
+            },
+            TacAction::StepInHome { from, to } => TacAction::StepInHome {
+                from: square(from),
+                to,
+            },
+            TacAction::Trickster { target1, target2 } => TacAction::Trickster {
+                target1: square(target1),
+                target2: square(target2),
+            },
+            TacAction::Warrior { from, to } => TacAction::Warrior {
+                from: square(from),
+                to: square(to),
+            },
+            TacAction::SevenSteps { ref steps } => TacAction::SevenSteps {
+                steps: steps.iter().map(|step| step.permuted(perm)).collect(),
+            },
+            ref action => action.clone(),
+        };
+        action.canonicalize();
+        action
+    }
+}
+
+/// Seven steps are stored in canonical order in move notation, e.g. `"H0-1/S5-6/S20-23"`,
+/// which is a lot shorter than a list of actions and doesn't change with the order of steps.
+fn serialize_steps<S: Serializer>(steps: &[TacAction], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seven = TacAction::SevenSteps {
+        steps: steps.to_vec(),
+    };
+    seven.canonicalize();
+    serializer.collect_str(&seven)
+}
+
+/// Reads steps in move notation or as a list of actions, which is how they used to be stored.
+fn deserialize_steps<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<TacAction>, D::Error> {
+    struct StepsVisitor;
+
+    impl<'de> de::Visitor<'de> for StepsVisitor {
+        type Value = Vec<TacAction>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "seven steps in move notation or a list of steps")
+        }
+
+        fn visit_str<E: de::Error>(self, notation: &str) -> Result<Self::Value, E> {
+            match notation.parse().map_err(E::custom)? {
+                TacAction::SevenSteps { steps } => Ok(steps),
+                action => Err(E::custom(format!("expected seven steps, got `{action}`"))),
             }
-            TacAction::Warrior { from, to } => {
-                write!(f, "Warrior {} {}", from.0, to.0)?;
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut steps = Vec::new();
+            while let Some(step) = seq.next_element::<TacAction>()? {
+                if !matches!(
+                    step,
+                    TacAction::Step { .. }
+                        | TacAction::StepHome { .. }
+                        | TacAction::StepInHome { .. }
+                ) {
+                    return Err(de::Error::custom(format!(
+                        "seven steps can't contain `{step}`"
+                    )));
+                }
+                steps.push(step);
             }
-            TacAction::SevenSteps { steps } => {
-                for (idx, s) in steps.iter().enumerate() {
-                    if idx == steps.len() - 1 {
-                        write!(f, "{s}")?;
-                    } else {
-                        write!(f, "{s} | ")?;
-                    }
+            Ok(steps)
+        }
+    }
+
+    let mut seven = TacAction::SevenSteps {
+        steps: deserializer.deserialize_any(StepsVisitor)?,
+    };
+    seven.canonicalize();
+    let TacAction::SevenSteps { steps } = seven else {
+        unreachable!("Canonicalizing keeps the kind of action")
+    };
+    Ok(steps)
+}
+
+/// A [`TacMove`] packed into 64 bits.
+///
+/// Layout from the least significant bit:
+/// - [`COLOR_BITS`] played for, [`COLOR_BITS`] played by
+/// - 5 bits card
+/// - 4 bits action kind
+/// - Seven steps: 3 bits step count followed by [`STEP_BITS`] per step, see [`pack_step`]
+/// - Other actions: [`SQUARE_BITS`] for the first and [`SQUARE_BITS`] for the second square
+///
+/// A seven moves at most four balls, so all steps fit into the remaining bits.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PackedTacMove(u64);
+
+/// Bits needed to store any color.
+const COLOR_BITS: u32 = usize::BITS - (NUM_COLORS - 1).leading_zeros();
+/// Bits needed to store any square of the ring.
+const SQUARE_BITS: u32 = u8::BITS - (RING_SIZE - 1).leading_zeros();
+const COLOR_MASK: u64 = (1 << COLOR_BITS) - 1;
+const SQUARE_MASK: u64 = (1 << SQUARE_BITS) - 1;
+const CARD_SHIFT: u32 = 2 * COLOR_BITS;
+const ACTION_SHIFT: u32 = CARD_SHIFT + 5;
+const PAYLOAD_SHIFT: u32 = ACTION_SHIFT + 4;
+#[cfg(not(feature = "six-players"))]
+const STEP_BITS: u32 = 12;
+#[cfg(feature = "six-players")]
+const STEP_BITS: u32 = 11;
+const STEP_MASK: u64 = (1 << STEP_BITS) - 1;
+const MAX_SEVEN_STEPS: usize = 4;
+
+impl PackedTacMove {
+    #[must_use]
+    /// # Panics
+    /// If a seven moves more than four balls
+    pub fn pack(mv: &TacMove) -> Self {
+        let mut bits = mv.played_for as u64
+            | (mv.played_by as u64) << COLOR_BITS
+            | (mv.card as u64) << CARD_SHIFT
+            | u64::from(action_kind(&mv.action)) << ACTION_SHIFT;
+        let (first, second) = match mv.action {
+            TacAction::Step { from, to } | TacAction::Warrior { from, to } => (from.0, to.0),
+            TacAction::StepHome { from, to } => (from.index(), to.index()),
+            TacAction::StepInHome { from, to } => (from.0, to.index()),
+            TacAction::Trickster { target1, target2 } => (target1.0, target2.0),
+            TacAction::Announce { opening } => (u8::from(opening), 0),
+            TacAction::SevenSteps { ref steps } => {
+                assert!(
+                    steps.len() <= MAX_SEVEN_STEPS,
+                    "A seven can't move more than four balls"
+                );
+                bits |= (steps.len() as u64) << PAYLOAD_SHIFT;
+                for (i, step) in steps.iter().enumerate() {
+                    bits |=
+                        u64::from(pack_step(step)) << (PAYLOAD_SHIFT + 3 + STEP_BITS * i as u32);
                 }
+                (0, 0)
             }
-            _ => {
-                write!(f, "{self:?}")?;
+            _ => (0, 0),
+        };
+        bits |=
+            u64::from(first) << PAYLOAD_SHIFT | u64::from(second) << (PAYLOAD_SHIFT + SQUARE_BITS);
+        Self(bits)
+    }
+
+    #[must_use]
+    pub fn unpack(self) -> TacMove {
+        let bits = self.0;
+        let played_for = Color::from((bits & COLOR_MASK) as usize);
+        let played_by = Color::from((bits >> COLOR_BITS & COLOR_MASK) as usize);
+        let card = CARDS[(bits >> CARD_SHIFT & 0b1_1111) as usize];
+        let first = (bits >> PAYLOAD_SHIFT & SQUARE_MASK) as u8;
+        let second = (bits >> (PAYLOAD_SHIFT + SQUARE_BITS) & SQUARE_MASK) as u8;
+        let action = match bits >> ACTION_SHIFT & 0b1111 {
+            0 => TacAction::Step {
+                from: Square(first),
+                to: Square(second),
+            },
+            1 => TacAction::StepHome {
+                from: HomeSquare::new(first),
+                to: HomeSquare::new(second),
+            },
+            2 => TacAction::StepInHome {
+                from: Square(first),
+                to: HomeSquare::new(second),
+            },
+            3 => TacAction::Trickster {
+                target1: Square(first),
+                target2: Square(second),
+            },
+            4 => TacAction::Enter,
+            5 => TacAction::Suspend,
+            6 => TacAction::Jester,
+            7 => TacAction::Devil,
+            8 => TacAction::Warrior {
+                from: Square(first),
+                to: Square(second),
+            },
+            9 => TacAction::Discard,
+            10 => TacAction::Trade,
+            11 => {
+                let count = (bits >> PAYLOAD_SHIFT & 0b111) as usize;
+                let steps = (0..count)
+                    .map(|i| {
+                        unpack_step(
+                            (bits >> (PAYLOAD_SHIFT + 3 + STEP_BITS * i as u32) & STEP_MASK) as u16,
+                        )
+                        .unwrap_or_else(|| unreachable!("Packed steps are valid"))
+                    })
+                    .collect();
+                TacAction::SevenSteps { steps }
             }
+            12 => TacAction::Announce {
+                opening: first != 0,
+            },
+            kind => unreachable!("Invalid action kind {kind}"),
         };
-        Ok(())
-    }
-}
-
-pub enum PackedTacMove {
-    // pub card: Card,
-    // 5 bits
-    // pub action: TacAction,
-    // 16 bits
-    // from, to -> 12 bits
-    // 13 variants -> 4 bits
-    // total 12
-    // pub played_for: Color,
-    // 2 bits
-    // ---
-    // 5 + 16 + 2 => 23 -> u32
-    // PROBLEM -> Seven
-    // Seven needs 4 * (12 + 2)
-    // 12 -> from, to
-    // 2 -> color
-    // -> 56 bits
-    // half the size of unpacked
-    // IDEA
-    // Instead of storing square positions just store move amount
-    // At most 7 -> 3 bits
-    // For 4 moves that makes 12 bits
-    // For each move 1 bit if move is for partner
-    // For each move 1 bit if move goes in home
-    // -> 12 + 4 + 4 -> 20 bits
-    // This requires us to sort the moves by position and location
-    Normal(u32),
-    Seven(u32),
+        TacMove::new(card, action, played_for, played_by)
+    }
+
+    #[must_use]
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the packed move with the given bits or `None` if they aren't the result of [`Self::pack`].
+    #[must_use]
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        let first = (bits >> PAYLOAD_SHIFT & SQUARE_MASK) as u8;
+        let second = (bits >> (PAYLOAD_SHIFT + SQUARE_BITS) & SQUARE_MASK) as u8;
+        let home = |pos| HomeSquare::try_new(pos).is_some();
+        let ring = |square| square < RING_SIZE;
+        let colors = [bits & COLOR_MASK, bits >> COLOR_BITS & COLOR_MASK];
+        let valid = (bits >> CARD_SHIFT & 0b1_1111) < NUM_CARDS as u64
+            && colors.iter().all(|color| *color < NUM_COLORS as u64)
+            && match bits >> ACTION_SHIFT & 0b1111 {
+                0 | 3 | 8 => ring(first) && ring(second),
+                1 => home(first) && home(second),
+                2 => ring(first) && home(second),
+                11 => {
+                    let count = (bits >> PAYLOAD_SHIFT & 0b111) as usize;
+                    count <= MAX_SEVEN_STEPS
+                        && (0..count).all(|i| {
+                            let step =
+                                bits >> (PAYLOAD_SHIFT + 3 + STEP_BITS * i as u32) & STEP_MASK;
+                            unpack_step(step as u16).is_some()
+                        })
+                }
+                12 => first <= 1,
+                kind => kind <= 11,
+            };
+        // Bits that aren't used by the action have to be zero
+        (valid && Self::pack(&Self(bits).unpack()).0 == bits).then_some(Self(bits))
+    }
+}
+
+impl From<PackedTacMove> for u64 {
+    fn from(mv: PackedTacMove) -> Self {
+        mv.bits()
+    }
+}
+
+impl TryFrom<u64> for PackedTacMove {
+    type Error = String;
+
+    fn try_from(bits: u64) -> Result<Self, Self::Error> {
+        Self::from_bits(bits).ok_or_else(|| format!("{bits:#x} is not a packed move"))
+    }
+}
+
+fn action_kind(action: &TacAction) -> u8 {
+    match action {
+        TacAction::Step { .. } => 0,
+        TacAction::StepHome { .. } => 1,
+        TacAction::StepInHome { .. } => 2,
+        TacAction::Trickster { .. } => 3,
+        TacAction::Enter => 4,
+        TacAction::Suspend => 5,
+        TacAction::Jester => 6,
+        TacAction::Devil => 7,
+        TacAction::Warrior { .. } => 8,
+        TacAction::Discard => 9,
+        TacAction::Trade => 10,
+        TacAction::SevenSteps { .. } => 11,
+        TacAction::Announce { .. } => 12,
+    }
+}
+
+/// Packs a single step of a seven into [`STEP_BITS`].
+/// 2 bits kind, 6 bits start and 4 bits for either the distance on the ring or the home position.
+#[cfg(not(feature = "six-players"))]
+fn pack_step(step: &TacAction) -> u16 {
+    let (kind, first, second) = match *step {
+        TacAction::Step { from, to } => {
+            let distance = from.distance_to(to).steps();
+            debug_assert!(distance < 16);
+            (0, from.0, distance)
+        }
+        TacAction::StepHome { from, to } => (1, from.index(), to.index()),
+        TacAction::StepInHome { from, to } => (2, from.0, to.index()),
+        _ => unreachable!("Seven steps only contain steps"),
+    };
+    kind | u16::from(first) << 2 | u16::from(second) << 8
+}
+
+/// Reads a step packed by [`pack_step`], `None` if the bits aren't a step.
+#[cfg(not(feature = "six-players"))]
+fn unpack_step(bits: u16) -> Option<TacAction> {
+    let first = (bits >> 2 & 0b11_1111) as u8;
+    let second = (bits >> 8 & 0b1111) as u8;
+    match bits & 0b11 {
+        0 => Some(TacAction::Step {
+            from: Square(first),
+            to: Square(first).add(Distance::new(second)),
+        }),
+        1 => Some(TacAction::StepHome {
+            from: HomeSquare::try_new(first)?,
+            to: HomeSquare::try_new(second)?,
+        }),
+        2 => Some(TacAction::StepInHome {
+            from: Square(first),
+            to: HomeSquare::try_new(second)?,
+        }),
+        _ => None,
+    }
+}
+
+/// Packs a single step of a seven into [`STEP_BITS`].
+/// 7 bits start followed by 4 bits code, a start square on the ring needs one bit more
+/// than with four players, so the kind shares its bits with the second value. Codes below 8
+/// are the distance of a step on the ring, 8 and above a step in home and 12 and above a step
+/// into home, plus the home position.
+#[cfg(feature = "six-players")]
+fn pack_step(step: &TacAction) -> u16 {
+    let (first, code) = match *step {
+        TacAction::Step { from, to } => {
+            let distance = from.distance_to(to).steps();
+            debug_assert!((1..8).contains(&distance));
+            (from.0, distance)
+        }
+        TacAction::StepHome { from, to } => (from.index(), 8 + to.index()),
+        TacAction::StepInHome { from, to } => (from.0, 12 + to.index()),
+        _ => unreachable!("Seven steps only contain steps"),
+    };
+    u16::from(first) | u16::from(code) << SQUARE_BITS
+}
+
+/// Reads a step packed by [`pack_step`], `None` if the bits aren't a step.
+#[cfg(feature = "six-players")]
+fn unpack_step(bits: u16) -> Option<TacAction> {
+    let first = (u64::from(bits) & SQUARE_MASK) as u8;
+    let code = (bits >> SQUARE_BITS) as u8;
+    match code {
+        1..8 if first < RING_SIZE => Some(TacAction::Step {
+            from: Square(first),
+            to: Square(first).add(Distance::new(code)),
+        }),
+        8..12 => Some(TacAction::StepHome {
+            from: HomeSquare::try_new(first)?,
+            to: HomeSquare::new(code - 8),
+        }),
+        12..16 if first < RING_SIZE => Some(TacAction::StepInHome {
+            from: Square(first),
+            to: HomeSquare::new(code - 12),
+        }),
+        _ => None,
+    }
+}
+
+impl From<&TacMove> for PackedTacMove {
+    fn from(mv: &TacMove) -> Self {
+        Self::pack(mv)
+    }
+}
+
+impl From<PackedTacMove> for TacMove {
+    fn from(mv: PackedTacMove) -> Self {
+        mv.unpack()
+    }
+}
+
+impl std::fmt::Debug for PackedTacMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.unpack())
+    }
+}
+
+impl Display for PackedTacMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.unpack())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -95,16 +462,6 @@ pub struct TacMove {
     pub played_by: Color,
 }
 
-impl Display for TacMove {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{:?} {} {:?} {:?}",
-            self.card, self.action, self.played_for, self.played_by
-        )
-    }
-}
-
 impl TacMove {
     #[must_use]
     pub fn new(card: Card, action: TacAction, played_for: Color, played_by: Color) -> Self {
@@ -115,19 +472,393 @@ impl TacMove {
             played_by,
         }
     }
+
+    /// Announcement of `player` whether they can open.
+    /// The card is unused and always [`Card::One`], so equal announcements compare equal.
+    #[must_use]
+    pub fn announce(opening: bool, player: Color) -> Self {
+        Self::new(Card::One, TacAction::Announce { opening }, player, player)
+    }
+
+    /// Brings the move into the form move generation uses. Moves that have the same effect
+    /// compare equal afterwards, like seven steps in another order or swapped Trickster targets.
+    pub fn canonicalize(&mut self) {
+        self.action.canonicalize();
+    }
+
+    #[must_use]
+    pub fn canonical(mut self) -> Self {
+        self.canonicalize();
+        self
+    }
+
+    /// Returns the move with the colors rotated by `perm`, see [`ColorPermutation`].
+    #[must_use]
+    pub fn permuted(&self, perm: ColorPermutation) -> Self {
+        Self::new(
+            self.card,
+            self.action.permuted(perm),
+            perm.color(self.played_for),
+            perm.color(self.played_by),
+        )
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PackedTacMoveResult {
     Capture(Color),
-    // Square -> 6 bits
-    // Color -> 2 bits
-    // (6 + 2) * 7 -> u64
+    // Square -> 6 bits (7 with six players)
+    // Color -> 2 bits (3 with six players)
+    // (6 + 2) * 7 -> 56 bits, followed by 3 bits for the amount of captures
     // half the size of unpacked
-    SevenCaptures(u64),
+    SevenCaptures(PackedCaptures),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Captures of a seven packed by [`PackedTacMoveResult::pack`].
+#[cfg(not(feature = "six-players"))]
+pub type PackedCaptures = u64;
+/// Captures of a seven packed by [`PackedTacMoveResult::pack`], with six players a capture
+/// takes 10 bits so seven of them don't fit into 64 bits.
+#[cfg(feature = "six-players")]
+pub type PackedCaptures = u128;
+
+const CAPTURE_BITS: u32 = SQUARE_BITS + COLOR_BITS;
+/// A seven moves seven steps, so it captures at most seven balls.
+const MAX_CAPTURES: u32 = 7;
+
+impl PackedTacMoveResult {
+    #[must_use]
+    pub fn pack(result: &TacMoveResult) -> Self {
+        match result {
+            TacMoveResult::Capture(color) => Self::Capture(*color),
+            TacMoveResult::SevenCaptures(captures) => {
+                let bits = captures
+                    .iter()
+                    .enumerate()
+                    .fold(0, |acc, (i, (sq, color))| {
+                        let capture =
+                            PackedCaptures::from(sq.0) | (*color as PackedCaptures) << SQUARE_BITS;
+                        acc | capture << (CAPTURE_BITS * i as u32)
+                    });
+                Self::SevenCaptures(
+                    bits | (captures.len() as PackedCaptures) << (CAPTURE_BITS * MAX_CAPTURES),
+                )
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn unpack(self) -> TacMoveResult {
+        match self {
+            Self::Capture(color) => TacMoveResult::Capture(color),
+            Self::SevenCaptures(bits) => {
+                let count = (bits >> (CAPTURE_BITS * MAX_CAPTURES) & 0b111) as usize;
+                TacMoveResult::SevenCaptures(
+                    (0..count)
+                        .map(|i| {
+                            let capture = bits >> (CAPTURE_BITS * i as u32);
+                            (
+                                Square((capture & PackedCaptures::from(SQUARE_MASK)) as u8),
+                                Color::from(
+                                    (capture >> SQUARE_BITS & PackedCaptures::from(COLOR_MASK))
+                                        as usize,
+                                ),
+                            )
+                        })
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TacMoveResult {
     Capture(Color),
     SevenCaptures(SmallVec<(Square, Color), 7>),
 }
+
+impl TacMoveResult {
+    /// Returns the result with the colors rotated by `perm`, see [`ColorPermutation`].
+    #[must_use]
+    pub fn permuted(&self, perm: ColorPermutation) -> Self {
+        match self {
+            Self::Capture(color) => Self::Capture(perm.color(*color)),
+            Self::SevenCaptures(captures) => Self::SevenCaptures(
+                captures
+                    .iter()
+                    .map(|(sq, color)| (perm.square(*sq), perm.color(*color)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_move_roundtrip() {
+        let actions = [
+            TacAction::Step {
+                from: Square(60),
+                to: Square(3),
+            },
+            TacAction::StepHome {
+                from: HomeSquare::new(0),
+                to: HomeSquare::new(3),
+            },
+            TacAction::StepInHome {
+                from: Square(62),
+                to: HomeSquare::new(2),
+            },
+            TacAction::Trickster {
+                target1: Square(5),
+                target2: Square(63),
+            },
+            TacAction::Enter,
+            TacAction::Suspend,
+            TacAction::Jester,
+            TacAction::Devil,
+            TacAction::Warrior {
+                from: Square(12),
+                to: Square(40),
+            },
+            TacAction::Discard,
+            TacAction::Trade,
+            TacAction::SevenSteps {
+                steps: vec![
+                    TacAction::StepHome {
+                        from: HomeSquare::new(1),
+                        to: HomeSquare::new(2),
+                    },
+                    TacAction::Step {
+                        from: Square(RING_SIZE - 3),
+                        to: Square(1),
+                    },
+                    TacAction::StepInHome {
+                        from: Square(RING_SIZE - 1),
+                        to: HomeSquare::new(0),
+                    },
+                    TacAction::Step {
+                        from: Square(20),
+                        to: Square(21),
+                    },
+                ],
+            },
+            TacAction::Announce { opening: true },
+            TacAction::Announce { opening: false },
+        ];
+        for card in CARDS {
+            for action in &actions {
+                let mv = TacMove::new(card, action.clone(), Color::Red, Color::Blue);
+                let packed = PackedTacMove::pack(&mv);
+                assert_eq!(packed.unpack(), mv);
+                assert_eq!(PackedTacMove::from_bits(packed.bits()), Some(packed));
+            }
+        }
+        for bits in [
+            u64::MAX,
+            0b1_1111 << CARD_SHIFT,
+            13 << ACTION_SHIFT,
+            12 << ACTION_SHIFT | 2 << PAYLOAD_SHIFT,
+            1 << 63,
+        ] {
+            assert!(PackedTacMove::from_bits(bits).is_none(), "{bits:#x}");
+        }
+    }
+
+    #[test]
+    fn canonical_moves() {
+        let trickster = |target1, target2| {
+            TacMove::new(
+                Card::Trickster,
+                TacAction::Trickster {
+                    target1: Square(target1),
+                    target2: Square(target2),
+                },
+                Color::Black,
+                Color::Black,
+            )
+        };
+        assert_eq!(trickster(40, 3).canonical(), trickster(3, 40));
+        assert_eq!(trickster(3, 40).canonical(), trickster(3, 40));
+
+        let seven = |steps: &[TacAction]| {
+            TacMove::new(
+                Card::Seven,
+                TacAction::SevenSteps {
+                    steps: steps.to_vec(),
+                },
+                Color::Red,
+                Color::Red,
+            )
+        };
+        let ring = TacAction::Step {
+            from: Square(20),
+            to: Square(23),
+        };
+        let other_ring = TacAction::Step {
+            from: Square(5),
+            to: Square(6),
+        };
+        let rear = TacAction::StepHome {
+            from: HomeSquare::new(0),
+            to: HomeSquare::new(1),
+        };
+        let front = TacAction::StepHome {
+            from: HomeSquare::new(2),
+            to: HomeSquare::new(3),
+        };
+        let canonical = seven(&[
+            rear.clone(),
+            front.clone(),
+            other_ring.clone(),
+            ring.clone(),
+        ]);
+        assert_eq!(
+            seven(&[ring, rear, other_ring, front]).canonical(),
+            canonical
+        );
+        assert_eq!(canonical.clone().canonical(), canonical);
+    }
+
+    #[test]
+    fn compact_seven_steps() {
+        let steps = vec![
+            TacAction::Step {
+                from: Square(20),
+                to: Square(23),
+            },
+            TacAction::StepHome {
+                from: HomeSquare::new(0),
+                to: HomeSquare::new(1),
+            },
+            TacAction::StepInHome {
+                from: Square(62),
+                to: HomeSquare::new(2),
+            },
+        ];
+        let mv = TacMove::new(
+            Card::Seven,
+            TacAction::SevenSteps { steps },
+            Color::Blue,
+            Color::Blue,
+        );
+        let serialized = ron::to_string(&mv).unwrap();
+        assert!(
+            serialized.contains(r#"steps:"H0-1/I62-2/S20-23""#),
+            "{serialized}"
+        );
+        assert_eq!(
+            ron::from_str::<TacMove>(&serialized).unwrap(),
+            mv.clone().canonical()
+        );
+
+        let old = "(card:Seven,action:SevenSteps(steps:[Step(from:(20),to:(23)),\
+            StepHome(from:0,to:1),StepInHome(from:(62),to:2)]),played_for:Blue,played_by:Blue)";
+        assert_eq!(ron::from_str::<TacMove>(old).unwrap(), mv.canonical());
+
+        let single =
+            "(card:Seven,action:SevenSteps(steps:\"S20-27/\"),played_for:Red,played_by:Red)";
+        assert_eq!(
+            ron::from_str::<TacMove>(single).unwrap().action,
+            TacAction::SevenSteps {
+                steps: vec![TacAction::Step {
+                    from: Square(20),
+                    to: Square(27),
+                }]
+            }
+        );
+        for invalid in ["\"ENTER\"", "\"X1-2/\"", "[Discard]"] {
+            let mv = format!(
+                "(card:Seven,action:SevenSteps(steps:{invalid}),played_for:Red,played_by:Red)"
+            );
+            assert!(ron::from_str::<TacMove>(&mv).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn packed_result_roundtrip() {
+        let results = [
+            TacMoveResult::Capture(Color::Green),
+            TacMoveResult::SevenCaptures(SmallVec::new()),
+            TacMoveResult::SevenCaptures(
+                [
+                    (Square(63), Color::Red),
+                    (Square(0), Color::Black),
+                    (Square(17), Color::Blue),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+        ];
+        for result in results {
+            assert_eq!(PackedTacMoveResult::pack(&result).unpack(), result);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "six-players")]
+    fn six_player_steps() {
+        // One bit more per start square still fits four steps into a packed move
+        assert_eq!(STEP_BITS, 11);
+        assert!(PAYLOAD_SHIFT + 3 + STEP_BITS * MAX_SEVEN_STEPS as u32 <= u64::BITS);
+        let mut steps = Vec::new();
+        for from in 0..RING_SIZE {
+            for distance in 1..8 {
+                steps.push(TacAction::Step {
+                    from: Square(from),
+                    to: Square(from).add(Distance::new(distance)),
+                });
+            }
+            for to in HomeSquare::ALL {
+                steps.push(TacAction::StepInHome {
+                    from: Square(from),
+                    to,
+                });
+            }
+        }
+        for from in HomeSquare::ALL {
+            for to in HomeSquare::ALL.into_iter().filter(|to| *to > from) {
+                steps.push(TacAction::StepHome { from, to });
+            }
+        }
+        for step in &steps {
+            assert_eq!(unpack_step(pack_step(step)).as_ref(), Some(step));
+        }
+        for chunk in steps.chunks(MAX_SEVEN_STEPS).rev().take(20) {
+            let mv = TacMove::new(
+                Card::Seven,
+                TacAction::SevenSteps {
+                    steps: chunk.to_vec(),
+                },
+                Color::Purple,
+                Color::Yellow,
+            );
+            assert_eq!(PackedTacMove::pack(&mv).unpack(), mv);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "six-players")]
+    fn six_player_captures() {
+        let captures = (0..MAX_CAPTURES as u8)
+            .map(|i| {
+                (
+                    Square(RING_SIZE - 1 - i),
+                    crate::ALL_COLORS[usize::from(i) % NUM_COLORS],
+                )
+            })
+            .collect();
+        let result = TacMoveResult::SevenCaptures(captures);
+        let packed = PackedTacMoveResult::pack(&result);
+        // Seven captures of the outer squares need more than 64 bits
+        let PackedTacMoveResult::SevenCaptures(bits) = packed else {
+            panic!("{packed:?}");
+        };
+        assert!(bits > u128::from(u64::MAX));
+        assert_eq!(packed.unpack(), result);
+    }
+}